@@ -0,0 +1,727 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Derive macros for the `atomic` crate.
+//!
+//! These are re-exported by `atomic::derive` when its `derive` feature is
+//! enabled, and shouldn't be used from this crate directly.
+
+#![warn(rust_2018_idioms)]
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    braced, parenthesized, parse_macro_input, Attribute, Data, DeriveInput, Error, Field, Fields,
+    Ident, LitInt, Token, Visibility,
+};
+
+// The integer reprs which have a matching `Atomic` type everywhere.
+const REPRS: &[&str] = &["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64"];
+
+/// Derives `NoUninit` and `CheckedBitPattern` for a fieldless enum with an
+/// integer repr, and generates an `Atomic{Name}` wrapper for it.
+///
+/// See `atomic::derive::Atomicize`.
+#[proc_macro_derive(Atomicize)]
+pub fn derive_atomicize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match atomicize(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Derives an `Atomic{Name}` twin of a struct, with an `Atomic` for each of
+/// its fields.
+///
+/// See `atomic::derive::AtomicFields`.
+#[proc_macro_derive(AtomicFields, attributes(atomic))]
+pub fn derive_atomic_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match atomic_fields(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn repr(input: &DeriveInput) -> Result<Ident, Error> {
+    let mut repr = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                if REPRS.iter().any(|r| ident == r) {
+                    repr = Some(ident.clone());
+                }
+            }
+            Ok(())
+        })?;
+    }
+    repr.ok_or_else(|| {
+        Error::new(
+            Span::call_site(),
+            "`Atomicize` requires an integer repr such as `#[repr(u8)]`",
+        )
+    })
+}
+
+fn atomicize(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "`Atomicize` can only be derived for enums",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`Atomicize` can't be derived for generic enums",
+        ));
+    }
+    if data.variants.is_empty() {
+        return Err(Error::new(
+            Span::call_site(),
+            "`Atomicize` can't be derived for empty enums",
+        ));
+    }
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "`Atomicize` can only be derived for fieldless enums",
+            ));
+        }
+    }
+    let repr = repr(input)?;
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let atomic_name = format_ident!("Atomic{}", name);
+    let variants = data.variants.iter().map(|v| &v.ident);
+    let doc = format!("An atomic `{}`, generated by `#[derive(Atomicize)]`.", name);
+
+    Ok(quote! {
+        #[automatically_derived]
+        unsafe impl ::atomic::bytemuck::NoUninit for #name {}
+
+        #[automatically_derived]
+        unsafe impl ::atomic::bytemuck::CheckedBitPattern for #name {
+            type Bits = #repr;
+
+            #[inline]
+            fn is_valid_bit_pattern(bits: &#repr) -> bool {
+                false #(|| *bits == #name::#variants as #repr)*
+            }
+        }
+
+        #[doc = #doc]
+        #[repr(transparent)]
+        #vis struct #atomic_name(::atomic::Atomic<#repr>);
+
+        #[automatically_derived]
+        impl #atomic_name {
+            /// Creates a new atomic enum.
+            #[inline]
+            pub const fn new(v: #name) -> Self {
+                Self(::atomic::Atomic::new(v as #repr))
+            }
+
+            /// Consumes the atomic enum and returns the contained value.
+            #[inline]
+            pub fn into_inner(self) -> #name {
+                Self::from_bits(self.0.into_inner())
+            }
+
+            /// Loads a value from the atomic enum.
+            ///
+            /// See `Atomic::load`.
+            #[inline]
+            pub fn load(&self, order: ::atomic::Ordering) -> #name {
+                Self::from_bits(self.0.load(order))
+            }
+
+            /// Stores a value into the atomic enum.
+            ///
+            /// See `Atomic::store`.
+            #[inline]
+            pub fn store(&self, val: #name, order: ::atomic::Ordering) {
+                self.0.store(val as #repr, order)
+            }
+
+            /// Stores a value into the atomic enum, returning the old value.
+            ///
+            /// See `Atomic::swap`.
+            #[inline]
+            pub fn swap(&self, val: #name, order: ::atomic::Ordering) -> #name {
+                Self::from_bits(self.0.swap(val as #repr, order))
+            }
+
+            /// Stores a value into the atomic enum if the current value is the
+            /// same as the `current` value.
+            ///
+            /// See `Atomic::compare_exchange`.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: #name,
+                new: #name,
+                success: ::atomic::Ordering,
+                failure: ::atomic::Ordering,
+            ) -> ::core::result::Result<#name, #name> {
+                match self.0.compare_exchange(current as #repr, new as #repr, success, failure) {
+                    ::core::result::Result::Ok(bits) => ::core::result::Result::Ok(Self::from_bits(bits)),
+                    ::core::result::Result::Err(bits) => ::core::result::Result::Err(Self::from_bits(bits)),
+                }
+            }
+
+            #[inline]
+            fn from_bits(bits: #repr) -> #name {
+                debug_assert!(
+                    <#name as ::atomic::bytemuck::CheckedBitPattern>::is_valid_bit_pattern(&bits),
+                    "invalid discriminant in an atomic enum"
+                );
+                // Only valid discriminants are ever stored.
+                unsafe { ::core::mem::transmute::<#repr, #name>(bits) }
+            }
+        }
+
+        #[automatically_derived]
+        impl ::core::convert::From<#name> for #atomic_name {
+            #[inline]
+            fn from(v: #name) -> Self {
+                Self::new(v)
+            }
+        }
+    })
+}
+
+// The options of a field of a `#[derive(AtomicFields)]` struct.
+struct FieldOptions {
+    skip: bool,
+    load: Ident,
+    store: Ident,
+}
+
+fn field_options(field: &Field) -> Result<FieldOptions, Error> {
+    let mut options = FieldOptions {
+        skip: false,
+        load: Ident::new("Acquire", Span::call_site()),
+        store: Ident::new("Release", Span::call_site()),
+    };
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("atomic")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("load") {
+                let order: Ident = meta.value()?.parse()?;
+                if !["Relaxed", "Acquire", "SeqCst"].iter().any(|o| order == o) {
+                    return Err(Error::new_spanned(
+                        order,
+                        "the load ordering must be `Relaxed`, `Acquire` or `SeqCst`",
+                    ));
+                }
+                options.load = order;
+            } else if meta.path.is_ident("store") {
+                let order: Ident = meta.value()?.parse()?;
+                if !["Relaxed", "Release", "SeqCst"].iter().any(|o| order == o) {
+                    return Err(Error::new_spanned(
+                        order,
+                        "the store ordering must be `Relaxed`, `Release` or `SeqCst`",
+                    ));
+                }
+                options.store = order;
+            } else {
+                return Err(meta.error("expected `skip`, `load` or `store`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn atomic_fields(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "`AtomicFields` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "`AtomicFields` can only be derived for structs",
+            ))
+        }
+    };
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let atomic_name = format_ident!("Atomic{}", name);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = &input.generics;
+    let doc = format!(
+        "An atomic twin of `{}`, generated by `#[derive(AtomicFields)]`.",
+        name
+    );
+
+    let mut defs = Vec::new();
+    let mut news = Vec::new();
+    let mut loads = Vec::new();
+    let mut stores = Vec::new();
+    let mut into_inners = Vec::new();
+    let mut accessors = Vec::new();
+    for field in fields {
+        let options = field_options(field)?;
+        let field_vis = &field.vis;
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        if options.skip {
+            defs.push(quote!(#field_vis #ident: #ty));
+            news.push(quote!(#ident: v.#ident));
+            loads.push(quote!(#ident: ::core::clone::Clone::clone(&self.#ident)));
+            into_inners.push(quote!(#ident: self.#ident));
+            continue;
+        }
+        let (load, store) = (&options.load, &options.store);
+        defs.push(quote!(#field_vis #ident: ::atomic::Atomic<#ty>));
+        news.push(quote!(#ident: ::atomic::Atomic::new(v.#ident)));
+        loads.push(quote!(#ident: self.#ident.load(::atomic::Ordering::#load)));
+        stores.push(quote!(self.#ident.store(v.#ident, ::atomic::Ordering::#store);));
+        into_inners.push(quote!(#ident: self.#ident.into_inner()));
+
+        let setter = format_ident!("set_{}", ident);
+        let get_doc = format!("Loads `{}` with `{}` ordering.", ident, load);
+        let set_doc = format!("Stores `{}` with `{}` ordering.", ident, store);
+        accessors.push(quote! {
+            #[doc = #get_doc]
+            #[inline]
+            #field_vis fn #ident(&self) -> #ty {
+                self.#ident.load(::atomic::Ordering::#load)
+            }
+
+            #[doc = #set_doc]
+            #[inline]
+            #field_vis fn #setter(&self, val: #ty) {
+                self.#ident.store(val, ::atomic::Ordering::#store)
+            }
+        });
+    }
+
+    Ok(quote! {
+        #[doc = #doc]
+        #vis struct #atomic_name #generics #where_clause {
+            #(#defs,)*
+        }
+
+        #[automatically_derived]
+        impl #impl_generics #atomic_name #ty_generics #where_clause {
+            /// Creates the atomic twin with the fields of `v`.
+            #[inline]
+            pub fn new(v: #name #ty_generics) -> Self {
+                Self { #(#news,)* }
+            }
+
+            /// Loads every field, one at a time.
+            ///
+            /// This isn't atomic across fields: stores which happen
+            /// concurrently may be partially visible in the result.
+            #[inline]
+            pub fn snapshot(&self) -> #name #ty_generics {
+                #name { #(#loads,)* }
+            }
+
+            /// Stores every field of `v`, one at a time.
+            ///
+            /// This isn't atomic across fields: concurrent loads may see some
+            /// of the new fields with some of the old ones. Skipped fields
+            /// aren't changed.
+            #[inline]
+            pub fn store(&self, v: #name #ty_generics) {
+                #(#stores)*
+            }
+
+            /// Consumes the atomic twin and returns its fields.
+            #[inline]
+            pub fn into_inner(self) -> #name #ty_generics {
+                #name { #(#into_inners,)* }
+            }
+
+            #(#accessors)*
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::core::convert::From<#name #ty_generics> for #atomic_name #ty_generics #where_clause {
+            #[inline]
+            fn from(v: #name #ty_generics) -> Self {
+                Self::new(v)
+            }
+        }
+    })
+}
+
+// A field of an `atomic_bitfield!`: `name: start..end`.
+struct BitField {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    start: LitInt,
+    end: LitInt,
+}
+
+impl Parse for BitField {
+    fn parse(input: ParseStream<'_>) -> Result<Self, Error> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let start = input.parse()?;
+        input.parse::<Token![..]>()?;
+        let end = input.parse()?;
+        Ok(BitField {
+            attrs,
+            vis,
+            ident,
+            start,
+            end,
+        })
+    }
+}
+
+// `struct Name(u32) { fields }`, with attributes and visibility.
+struct BitFieldStruct {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    ty: Ident,
+    fields: Punctuated<BitField, Token![,]>,
+}
+
+impl Parse for BitFieldStruct {
+    fn parse(input: ParseStream<'_>) -> Result<Self, Error> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let ident = input.parse()?;
+        let ty;
+        parenthesized!(ty in input);
+        let ty = ty.parse()?;
+        let fields;
+        braced!(fields in input);
+        let fields = fields.parse_terminated(BitField::parse, Token![,])?;
+        Ok(BitFieldStruct {
+            attrs,
+            vis,
+            ident,
+            ty,
+            fields,
+        })
+    }
+}
+
+// The integer types which `AtomicArithmetic` can be derived over.
+const INTEGERS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// Derives `AtomicArithmetic` for a `#[repr(transparent)]` newtype of a
+/// primitive integer.
+///
+/// See `atomic::derive::AtomicArithmetic`.
+#[proc_macro_derive(AtomicArithmetic)]
+pub fn derive_atomic_arithmetic(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match atomic_arithmetic(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn atomic_arithmetic(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let field = match &input.data {
+        Data::Struct(data) if data.fields.len() == 1 => data.fields.iter().next().unwrap(),
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "`AtomicArithmetic` can only be derived for structs with a single field",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`AtomicArithmetic` can't be derived for generic structs",
+        ));
+    }
+    let mut transparent = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("transparent") {
+                transparent = true;
+            }
+            Ok(())
+        })?;
+    }
+    if !transparent {
+        return Err(Error::new(
+            Span::call_site(),
+            "`AtomicArithmetic` requires `#[repr(transparent)]`",
+        ));
+    }
+    let ty = &field.ty;
+    let is_integer = match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path
+            .path
+            .get_ident()
+            .is_some_and(|ident| INTEGERS.iter().any(|i| ident == i)),
+        _ => false,
+    };
+    if !is_integer {
+        return Err(Error::new_spanned(
+            ty,
+            "`AtomicArithmetic` requires a field of a primitive integer type",
+        ));
+    }
+
+    let name = &input.ident;
+    Ok(quote! {
+        #[automatically_derived]
+        unsafe impl ::atomic::AtomicArithmetic for #name {
+            type Repr = #ty;
+        }
+    })
+}
+
+/// Defines a struct of bit fields packed into a single atomic integer.
+///
+/// See `atomic::atomic_bitfield`.
+#[proc_macro]
+pub fn atomic_bitfield(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as BitFieldStruct);
+    match bitfield(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn bitfield(input: &BitFieldStruct) -> Result<proc_macro2::TokenStream, Error> {
+    let ty = &input.ty;
+    let bits = match ty.to_string().as_str() {
+        "u8" => 8,
+        "u16" => 16,
+        "u32" => 32,
+        "u64" => 64,
+        _ => {
+            return Err(Error::new_spanned(
+                ty,
+                "the bit fields must be stored in `u8`, `u16`, `u32` or `u64`",
+            ))
+        }
+    };
+
+    let mut ranges: Vec<(u32, u32, &Ident)> = Vec::new();
+    let mut methods = Vec::new();
+    for field in &input.fields {
+        let start: u32 = field.start.base10_parse()?;
+        let end: u32 = field.end.base10_parse()?;
+        if start >= end {
+            return Err(Error::new_spanned(
+                &field.end,
+                format!("the bit field `{}` is empty", field.ident),
+            ));
+        }
+        if end > bits {
+            return Err(Error::new_spanned(
+                &field.end,
+                format!("the bit field `{}` doesn't fit in a `{}`", field.ident, ty),
+            ));
+        }
+        if let Some((_, _, other)) = ranges.iter().find(|&&(s, e, _)| start < e && s < end) {
+            return Err(Error::new_spanned(
+                &field.ident,
+                format!(
+                    "the bit field `{}` overlaps the bit field `{}`",
+                    field.ident, other
+                ),
+            ));
+        }
+        ranges.push((start, end, &field.ident));
+
+        let attrs = &field.attrs;
+        let vis = &field.vis;
+        let ident = &field.ident;
+        let width = end - start;
+        // Computed here so that the generated code doesn't need to handle
+        // fields which are as wide as the integer.
+        let max = if width == 64 {
+            u64::MAX
+        } else {
+            (1u64 << width) - 1
+        };
+        let max = syn::LitInt::new(&format!("{}{}", max, ty), Span::call_site());
+        let shift = start;
+        let get = format_ident!("get_{}", ident);
+        let with = format_ident!("with_{}", ident);
+        let load = format_ident!("load_{}", ident);
+        let store = format_ident!("store_{}", ident);
+        let fetch_update = format_ident!("fetch_update_{}", ident);
+        let get_doc = format!("Extracts the `{}` field from a value of the word.", ident);
+        let with_doc = format!(
+            "Returns `word` with the `{}` field replaced by `val`.\n\n\
+             # Panics\n\n\
+             Panics if `val` doesn't fit in {} bits.",
+            ident, width
+        );
+        let load_doc = format!("Loads the `{}` field.", ident);
+        let store_doc = format!(
+            "Stores `val` into the `{}` field, leaving the other fields unchanged.\n\n\
+             This is a compare-and-swap loop over the whole word. `order` is the \
+             ordering of the final, successful compare-and-swap.\n\n\
+             # Panics\n\n\
+             Panics if `val` doesn't fit in {} bits.",
+            ident, width
+        );
+        let fetch_update_doc = format!(
+            "Updates the `{}` field with `f`, leaving the other fields unchanged.\n\n\
+             Returns the previous value of the field. See `Atomic::fetch_update`.\n\n\
+             # Panics\n\n\
+             Panics if `f` returns a value which doesn't fit in {} bits.",
+            ident, width
+        );
+        // The documentation of the field goes on its load method.
+        let load_attrs = if attrs.is_empty() {
+            quote!()
+        } else {
+            quote!(#(#attrs)* #[doc = ""])
+        };
+        methods.push(quote! {
+            #[doc = #get_doc]
+            #[inline]
+            #vis const fn #get(word: #ty) -> #ty {
+                (word >> #shift) & #max
+            }
+
+            #[doc = #with_doc]
+            #[inline]
+            #[track_caller]
+            #vis const fn #with(word: #ty, val: #ty) -> #ty {
+                assert!(val <= #max, "the value doesn't fit in the bit field");
+                (word & !(#max << #shift)) | (val << #shift)
+            }
+
+            #load_attrs
+            #[doc = #load_doc]
+            #[inline]
+            #vis fn #load(&self, order: ::atomic::Ordering) -> #ty {
+                Self::#get(self.0.load(order))
+            }
+
+            #[doc = #store_doc]
+            #[inline]
+            #[track_caller]
+            #vis fn #store(&self, val: #ty, order: ::atomic::Ordering) {
+                let _ = Self::#with(0, val);
+                let fetch_order = match order {
+                    ::atomic::Ordering::Release => ::atomic::Ordering::Relaxed,
+                    ::atomic::Ordering::AcqRel => ::atomic::Ordering::Acquire,
+                    order => order,
+                };
+                let _ = self
+                    .0
+                    .fetch_update(order, fetch_order, |word| ::core::option::Option::Some(Self::#with(word, val)));
+            }
+
+            #[doc = #fetch_update_doc]
+            #[inline]
+            #[track_caller]
+            #vis fn #fetch_update<F>(
+                &self,
+                set_order: ::atomic::Ordering,
+                fetch_order: ::atomic::Ordering,
+                mut f: F,
+            ) -> ::core::result::Result<#ty, #ty>
+            where
+                F: ::core::ops::FnMut(#ty) -> ::core::option::Option<#ty>,
+            {
+                match self.0.fetch_update(set_order, fetch_order, |word| {
+                    f(Self::#get(word)).map(|val| Self::#with(word, val))
+                }) {
+                    ::core::result::Result::Ok(word) => ::core::result::Result::Ok(Self::#get(word)),
+                    ::core::result::Result::Err(word) => ::core::result::Result::Err(Self::#get(word)),
+                }
+            }
+        });
+    }
+
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let ident = &input.ident;
+    Ok(quote! {
+        #(#attrs)*
+        #[repr(transparent)]
+        #vis struct #ident(::atomic::Atomic<#ty>);
+
+        #[allow(dead_code)]
+        impl #ident {
+            /// Creates a new bit field struct from the whole word.
+            #[inline]
+            pub const fn new(word: #ty) -> Self {
+                Self(::atomic::Atomic::new(word))
+            }
+
+            /// Consumes the bit field struct and returns the whole word.
+            #[inline]
+            pub fn into_inner(self) -> #ty {
+                self.0.into_inner()
+            }
+
+            /// Returns the underlying `Atomic` holding the whole word.
+            #[inline]
+            pub fn as_atomic(&self) -> &::atomic::Atomic<#ty> {
+                &self.0
+            }
+
+            /// Loads the whole word.
+            ///
+            /// This is a consistent snapshot of all of the fields.
+            #[inline]
+            pub fn load(&self, order: ::atomic::Ordering) -> #ty {
+                self.0.load(order)
+            }
+
+            /// Stores the whole word.
+            #[inline]
+            pub fn store(&self, word: #ty, order: ::atomic::Ordering) {
+                self.0.store(word, order)
+            }
+
+            /// Stores the whole word if it is the same as `current`.
+            ///
+            /// See `Atomic::compare_exchange`.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: #ty,
+                new: #ty,
+                success: ::atomic::Ordering,
+                failure: ::atomic::Ordering,
+            ) -> ::core::result::Result<#ty, #ty> {
+                self.0.compare_exchange(current, new, success, failure)
+            }
+
+            #(#methods)*
+        }
+    })
+}
@@ -0,0 +1,73 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(atomic_diagnostic_namespace)");
+    // Set with `RUSTFLAGS="--cfg atomic_loom"` to run the loom tests.
+    println!("cargo:rustc-check-cfg=cfg(atomic_loom)");
+
+    println!("cargo:rustc-check-cfg=cfg(atomic_asm_128)");
+    println!("cargo:rustc-check-cfg=cfg(atomic_cmpxchg16b_dynamic)");
+    // Set with `RUSTFLAGS="--cfg atomic_no_cmpxchg16b"` to behave as if the
+    // CPU didn't support `cmpxchg16b`, to test the fallback.
+    println!("cargo:rustc-check-cfg=cfg(atomic_no_cmpxchg16b)");
+
+    // 16-byte atomics are implemented with inline assembly on x86_64 with the
+    // `cmpxchg16b` target feature and on little-endian AArch64, unless
+    // `core::sync::atomic::AtomicU128` is used instead, which requires the
+    // `nightly` feature and a target with 128-bit atomics. Miri doesn't
+    // support inline assembly.
+    //
+    // If the target feature isn't enabled at compile time on x86_64, the
+    // instruction is detected at runtime instead, which requires `std`. The
+    // fallback is used on CPUs without it, so the feature can only be detected
+    // when there is one. libatomic already does the same detection itself.
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let target_features = env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default();
+    let target_has_atomic = env::var("CARGO_CFG_TARGET_HAS_ATOMIC").unwrap_or_default();
+    let nightly_128 = env::var_os("CARGO_FEATURE_NIGHTLY").is_some()
+        && target_has_atomic.split(',').any(|w| w == "128");
+    if !nightly_128 && env::var_os("CARGO_CFG_MIRI").is_none() {
+        if target_arch == "x86_64" {
+            if target_features.split(',').any(|f| f == "cmpxchg16b") {
+                println!("cargo:rustc-cfg=atomic_asm_128");
+            } else if env::var_os("CARGO_FEATURE_STD").is_some()
+                && env::var_os("CARGO_FEATURE_FALLBACK").is_some()
+                && env::var_os("CARGO_FEATURE_LIBATOMIC").is_none()
+            {
+                println!("cargo:rustc-cfg=atomic_cmpxchg16b_dynamic");
+            }
+        } else if target_arch == "aarch64"
+            && env::var("CARGO_CFG_TARGET_ENDIAN").as_deref() == Ok("little")
+        {
+            println!("cargo:rustc-cfg=atomic_asm_128");
+        }
+    }
+
+    // #[diagnostic::on_unimplemented] was stabilized in Rust 1.78.
+    if rustc_minor_version().is_some_and(|v| v >= 78) {
+        println!("cargo:rustc-cfg=atomic_diagnostic_namespace");
+    }
+
+    // The tests for the C interop features build C code, which needs to know
+    // the target. The C API test also loads a shared library which calls back
+    // into the test executable, so the executable's symbols need to be
+    // visible to it.
+    let cabi = env::var_os("CARGO_FEATURE_CABI").is_some();
+    let libatomic = env::var_os("CARGO_FEATURE_LIBATOMIC").is_some();
+    if (cabi || libatomic) && env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("linux") {
+        if let Ok(target) = env::var("TARGET") {
+            println!("cargo:rustc-env=ATOMIC_TEST_TARGET={}", target);
+        }
+        if cabi {
+            println!("cargo:rustc-link-arg-tests=-Wl,--export-dynamic");
+        }
+    }
+}
+
+fn rustc_minor_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC")?;
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    version.split('.').nth(1)?.parse().ok()
+}
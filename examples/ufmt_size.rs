@@ -0,0 +1,35 @@
+// Formats atomics with `ufmt`, to check that this doesn't use `core::fmt`,
+// which would defeat the purpose of `ufmt` on small targets. Built as a
+// library, the LLVM IR only contains the code used here:
+//
+//     cargo rustc --release --example ufmt_size --features ufmt -- --emit=llvm-ir
+//
+// The `.ll` file in `target/release/examples` must not mention `core::fmt`.
+#![no_std]
+
+use atomic::Atomic;
+use ufmt::{uWrite, uwrite};
+
+pub struct Buf {
+    pub bytes: [u8; 32],
+    pub len: usize,
+}
+
+impl uWrite for Buf {
+    type Error = ();
+
+    fn write_str(&mut self, s: &str) -> Result<(), ()> {
+        let end = self.len + s.len();
+        self.bytes
+            .get_mut(self.len..end)
+            .ok_or(())?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub fn format_atomics(buf: &mut Buf, a: &Atomic<u32>, b: &Atomic<i16>, c: &Atomic<bool>) {
+    let _ = uwrite!(buf, "{} {:?} {}", a, b, c);
+}
@@ -0,0 +1,114 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Implementations of `abi_stable::StableAbi` for the atomics of the primitive
+// types, so that they can be shared across a dynamic library boundary.
+//
+// They are only implemented where the type is lock-free: every binary has its
+// own copy of the lock table used by the fallback, so an `Atomic` which isn't
+// lock-free would be guarded by different locks on each side.
+
+use ::abi_stable::abi_stability::{GetStaticEquivalent_, StableAbi};
+use ::abi_stable::reexports::False;
+use ::abi_stable::type_layout::TypeLayout;
+
+use crate::Atomic;
+
+// The layout reported for `Atomic<T>`, which is a `#[repr(transparent)]`
+// wrapper around an `UnsafeCell<T>`. It has the same name, so that it isn't
+// compatible with a plain `T` or a standard library atomic.
+mod layout {
+    use core::cell::UnsafeCell;
+
+    #[repr(transparent)]
+    #[derive(::abi_stable::StableAbi)]
+    pub struct Atomic<T>(UnsafeCell<T>);
+}
+
+macro_rules! stable_abi {
+    ($($width:literal: $($t:ty)*;)*) => ($($(
+        #[cfg(target_has_atomic = $width)]
+        unsafe impl GetStaticEquivalent_ for Atomic<$t> {
+            type StaticEquivalent = Self;
+        }
+
+        #[cfg(target_has_atomic = $width)]
+        unsafe impl StableAbi for Atomic<$t> {
+            type IsNonZeroType = False;
+
+            const LAYOUT: &'static TypeLayout = <layout::Atomic<$t> as StableAbi>::LAYOUT;
+        }
+    )*)*);
+}
+stable_abi! {
+    "8": bool u8 i8;
+    "16": u16 i16;
+    "32": u32 i32;
+    "64": u64 i64;
+    "ptr": usize isize;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Atomic;
+    use abi_stable::abi_stability::check_layout_compatibility;
+    use abi_stable::StableAbi;
+    use core::sync::atomic::AtomicU64;
+
+    #[repr(C)]
+    #[derive(StableAbi)]
+    struct Shared {
+        counter: Atomic<u64>,
+        ready: Atomic<bool>,
+    }
+
+    // The same struct as seen by another binary, built against the same or a
+    // changed definition of it.
+    mod same {
+        use crate::Atomic;
+        use abi_stable::StableAbi;
+
+        #[repr(C)]
+        #[derive(StableAbi)]
+        pub struct Shared {
+            counter: Atomic<u64>,
+            ready: Atomic<bool>,
+        }
+    }
+
+    mod changed {
+        use crate::Atomic;
+        use abi_stable::StableAbi;
+
+        #[repr(C)]
+        #[derive(StableAbi)]
+        pub struct Shared {
+            counter: Atomic<u32>,
+            ready: Atomic<bool>,
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let shared = <Shared as StableAbi>::LAYOUT;
+        let same = <same::Shared as StableAbi>::LAYOUT;
+        let changed = <changed::Shared as StableAbi>::LAYOUT;
+        assert!(check_layout_compatibility(shared, same).is_ok());
+        assert!(check_layout_compatibility(shared, changed).is_err());
+
+        // An `Atomic<T>` is only compatible with itself.
+        let atomic = <Atomic<u64> as StableAbi>::LAYOUT;
+        assert_eq!(atomic.size(), 8);
+        assert!(check_layout_compatibility(atomic, <u64 as StableAbi>::LAYOUT).is_err());
+        assert!(check_layout_compatibility(atomic, <AtomicU64 as StableAbi>::LAYOUT).is_err());
+        assert!(check_layout_compatibility(
+            <Atomic<usize> as StableAbi>::LAYOUT,
+            <Atomic<isize> as StableAbi>::LAYOUT
+        )
+        .is_err());
+    }
+}
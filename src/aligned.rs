@@ -0,0 +1,177 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+
+use bytemuck::NoUninit;
+
+// Used to reject wrappers whose size doesn't match their alignment, which
+// would contain padding bytes.
+struct AssertSize<T, const N: usize>(T);
+
+impl<T, const N: usize> AssertSize<T, N> {
+    // Use the macro from `core` so the error is the same with `std`.
+    const OK: () = if mem::size_of::<T>() != N {
+        core::panic!("the size of the wrapped type must be equal to the alignment")
+    };
+}
+
+macro_rules! aligned {
+    ($($name:ident $align:literal)*) => ($(
+        #[doc = concat!("A wrapper which aligns a ", stringify!($align), "-byte value to ")]
+        #[doc = concat!(stringify!($align), " bytes.")]
+        ///
+        /// Native atomic instructions require the value to be aligned to its
+        /// size, so wrapping a value with a smaller alignment allows `Atomic`
+        /// to use them. This is usually created through `static_atomic!`.
+        ///
+        /// Using `new` or `default` with a type of a different size fails to
+        /// compile.
+        #[repr(C, align($align))]
+        #[derive(Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct $name<T>(T);
+
+        impl<T> $name<T> {
+            /// Wraps a value.
+            #[inline]
+            pub const fn new(v: T) -> $name<T> {
+                #[allow(clippy::let_unit_value)]
+                let () = AssertSize::<T, $align>::OK;
+                $name(v)
+            }
+
+            /// Returns the wrapped value.
+            #[inline]
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        impl<T: Default> Default for $name<T> {
+            // Not derived, so that the size is checked by `new`.
+            #[inline]
+            fn default() -> $name<T> {
+                $name::new(T::default())
+            }
+        }
+
+        impl<T> Deref for $name<T> {
+            type Target = T;
+
+            #[inline]
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $name<T> {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+
+        impl<T: fmt::Debug> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        // The wrapper can only be constructed through `new`, which ensures that
+        // the size of `T` matches the alignment so there is no padding. Every
+        // other constructor, such as `Default`, must go through `new` as well.
+        unsafe impl<T: NoUninit> NoUninit for $name<T> {}
+
+        impl<T> AlignTo<T> for AlignSelector<$align> {
+            type Output = $name<T>;
+        }
+    )*);
+}
+
+/// Selects the aligned wrapper for a given size. Used by `static_atomic!`.
+#[doc(hidden)]
+pub struct AlignSelector<const SIZE: usize>;
+
+/// Maps a size to the aligned wrapper for that size. Used by `static_atomic!`.
+#[doc(hidden)]
+pub trait AlignTo<T> {
+    type Output;
+}
+
+aligned! {
+    Align1 1
+    Align2 2
+    Align4 4
+    Align8 8
+    Align16 16
+}
+
+/// Declares statics containing `Atomic`s which are aligned for native atomic
+/// instructions.
+///
+/// A type such as `[u8; 8]` only has an alignment of 1, so `Atomic<[u8; 8]>`
+/// can't use 8-byte atomic instructions and falls back to a lock. This macro
+/// wraps the value in one of the `Align*` wrappers so that its alignment
+/// matches its size, which makes the resulting `Atomic` lock-free whenever the
+/// target supports atomics of that size. Only types with a size of 1, 2, 4, 8
+/// or 16 bytes are accepted.
+///
+/// Arrays of atomics are declared with `[Atomic<T>; N]` as the type, in which
+/// case the initializer is used for every element. Attributes and visibility
+/// are passed through.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{static_atomic, Atomic, Ordering};
+///
+/// static_atomic! {
+///     /// Protocol state: version, flags and epoch.
+///     pub static STATE: [u8; 8] = [1, 0, 0, 0, 0, 0, 0, 0];
+///     static SLOTS: [Atomic<[u16; 2]>; 4] = [0; 2];
+/// }
+///
+/// assert!(atomic::Atomic::<atomic::Align8<[u8; 8]>>::is_lock_free());
+/// assert_eq!(STATE.load(Ordering::Relaxed)[0], 1);
+/// SLOTS[1].store(atomic::Align4::new([1, 2]), Ordering::Relaxed);
+/// assert_eq!(*SLOTS[1].load(Ordering::Relaxed), [1, 2]);
+/// ```
+///
+/// Types whose size isn't a supported power of two are rejected:
+///
+/// ```compile_fail
+/// atomic::static_atomic!(static FOO: [u8; 12] = [0; 12];);
+/// ```
+#[macro_export]
+macro_rules! static_atomic {
+    (@aligned $ty:ty) => {
+        <$crate::AlignSelector<{ ::core::mem::size_of::<$ty>() }> as $crate::AlignTo<$ty>>::Output
+    };
+    () => {};
+    (
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: [Atomic<$ty:ty>; $n:expr] = $init:expr;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis static $name: [$crate::Atomic<$crate::static_atomic!(@aligned $ty)>; $n] =
+            $crate::Atomic::new_array(<$crate::static_atomic!(@aligned $ty)>::new($init));
+        $crate::static_atomic!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: $ty:ty = $init:expr;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis static $name: $crate::Atomic<$crate::static_atomic!(@aligned $ty)> =
+            $crate::Atomic::new(<$crate::static_atomic!(@aligned $ty)>::new($init));
+        $crate::static_atomic!($($rest)*);
+    };
+}
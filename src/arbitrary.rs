@@ -0,0 +1,67 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::Atomic;
+
+/// Generates an arbitrary `T` and wraps it in a new `Atomic`.
+///
+/// This requires the `arbitrary` feature.
+impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Atomic<T> {
+    #[inline]
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        T::arbitrary(u).map(Atomic::new)
+    }
+
+    #[inline]
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        T::arbitrary_take_rest(u).map(Atomic::new)
+    }
+
+    #[inline]
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        T::size_hint(depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Atomic, Ordering};
+    use arbitrary::{Arbitrary, Unstructured};
+
+    #[derive(Arbitrary, Debug)]
+    struct Counters {
+        hits: Atomic<u32>,
+        enabled: Atomic<bool>,
+        pair: Atomic<[u8; 2]>,
+        plain: u16,
+    }
+
+    #[test]
+    fn derive() {
+        assert_eq!(Counters::size_hint(0), (4 + 1 + 2 + 2, Some(4 + 1 + 2 + 2)));
+        assert_eq!(
+            <Atomic<u64> as Arbitrary>::size_hint(0),
+            <u64 as Arbitrary>::size_hint(0)
+        );
+
+        let data: [u8; 4096] = core::array::from_fn(|i| (i * 7 + i / 13) as u8);
+        let mut u = Unstructured::new(&data);
+        let mut seen_enabled = [false; 2];
+        while !u.is_empty() {
+            let c = Counters::arbitrary(&mut u).unwrap();
+            c.hits.fetch_add(1, Ordering::Relaxed);
+            seen_enabled[c.enabled.load(Ordering::Relaxed) as usize] = true;
+            let _ = (c.pair.load(Ordering::Relaxed), c.plain);
+        }
+        assert_eq!(seen_enabled, [true, true]);
+
+        let a = Atomic::<u16>::arbitrary_take_rest(Unstructured::new(&[1, 2])).unwrap();
+        assert_eq!(a.into_inner(), u16::from_le_bytes([1, 2]));
+    }
+}
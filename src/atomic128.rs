@@ -0,0 +1,335 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// 16-byte atomics implemented with inline assembly, for targets where
+// `core::sync::atomic::AtomicU128` can't be used because it requires the
+// `nightly` feature. See `build.rs` for when `atomic_asm_128` is set.
+//
+// On x86_64 this requires the `cmpxchg16b` target feature, for example with
+// `-C target-feature=+cmpxchg16b` or `-C target-cpu=native`. Without it,
+// `atomic_cmpxchg16b_dynamic` is set instead, and the size dispatch only uses
+// these types if `detect` finds the instruction at runtime. The result never
+// changes while the program runs, so all the operations on an `Atomic` agree
+// on whether they use the fallback lock.
+//
+// On AArch64 this uses `caspal` if the `lse` target feature is enabled, and
+// an `ldaxp`/`stlxp` loop otherwise.
+//
+// Every operation is a compare-and-swap loop, which is sequentially
+// consistent, so the orderings are ignored. Loads are performed with a
+// compare-and-swap as well, so they write to the memory. This is fine since
+// an `Atomic` is always in an `UnsafeCell`.
+
+use core::arch::asm;
+use core::cell::UnsafeCell;
+use core::sync::atomic::Ordering;
+
+// Returns `true` if the CPU supports `cmpxchg16b`. The standard library caches
+// the result of the detection, so this is only an atomic load and a branch
+// after the first call.
+#[cfg(atomic_cmpxchg16b_dynamic)]
+#[inline]
+pub(crate) fn detect() -> bool {
+    !cfg!(atomic_no_cmpxchg16b) && std::is_x86_feature_detected!("cmpxchg16b")
+}
+
+// Compares the value at `dst` with `old` and replaces it with `new` if they
+// are equal. Returns the previous value and whether it was replaced.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+unsafe fn cas(dst: *mut u128, old: u128, new: u128) -> (u128, bool) {
+    let (prev_lo, prev_hi): (u64, u64);
+    let ok: u8;
+    // rbx is reserved by LLVM and can't be an operand, so the low half of the
+    // new value is swapped into it around the instruction.
+    asm!(
+        "xchg {new_lo}, rbx",
+        "lock cmpxchg16b xmmword ptr [{dst}]",
+        "sete {ok}",
+        "mov rbx, {new_lo}",
+        dst = in(reg) dst,
+        new_lo = inout(reg) new as u64 => _,
+        ok = out(reg_byte) ok,
+        in("rcx") (new >> 64) as u64,
+        inout("rax") old as u64 => prev_lo,
+        inout("rdx") (old >> 64) as u64 => prev_hi,
+        options(nostack),
+    );
+    ((prev_hi as u128) << 64 | prev_lo as u128, ok != 0)
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "lse"))]
+#[inline]
+unsafe fn cas(dst: *mut u128, old: u128, new: u128) -> (u128, bool) {
+    let (prev_lo, prev_hi): (u64, u64);
+    // The halves of each value must be in a pair of consecutive registers,
+    // starting with an even one.
+    asm!(
+        "caspal x4, x5, x6, x7, [{dst}]",
+        dst = in(reg) dst,
+        inout("x4") old as u64 => prev_lo,
+        inout("x5") (old >> 64) as u64 => prev_hi,
+        in("x6") new as u64,
+        in("x7") (new >> 64) as u64,
+        options(nostack),
+    );
+    let prev = (prev_hi as u128) << 64 | prev_lo as u128;
+    (prev, prev == old)
+}
+
+#[cfg(all(target_arch = "aarch64", not(target_feature = "lse")))]
+#[inline]
+unsafe fn cas(dst: *mut u128, old: u128, new: u128) -> (u128, bool) {
+    let (prev_lo, prev_hi): (u64, u64);
+    // The loaded pair is only known to be atomic once it is stored back, so
+    // the previous value is stored again if it doesn't match.
+    asm!(
+        "2:",
+        "ldaxp {prev_lo}, {prev_hi}, [{dst}]",
+        "cmp {prev_lo}, {old_lo}",
+        "ccmp {prev_hi}, {old_hi}, #0, eq",
+        "csel {tmp_lo}, {new_lo}, {prev_lo}, eq",
+        "csel {tmp_hi}, {new_hi}, {prev_hi}, eq",
+        "stlxp {r:w}, {tmp_lo}, {tmp_hi}, [{dst}]",
+        "cbnz {r:w}, 2b",
+        dst = in(reg) dst,
+        old_lo = in(reg) old as u64,
+        old_hi = in(reg) (old >> 64) as u64,
+        new_lo = in(reg) new as u64,
+        new_hi = in(reg) (new >> 64) as u64,
+        prev_lo = out(reg) prev_lo,
+        prev_hi = out(reg) prev_hi,
+        tmp_lo = out(reg) _,
+        tmp_hi = out(reg) _,
+        r = out(reg) _,
+        options(nostack),
+    );
+    let prev = (prev_hi as u128) << 64 | prev_lo as u128;
+    (prev, prev == old)
+}
+
+// Replaces the value at `dst` with `f` applied to it, returning the previous
+// value.
+#[inline]
+unsafe fn update(dst: *mut u128, mut f: impl FnMut(u128) -> u128) -> u128 {
+    // The first exchange only reads the current value, unless it happens to
+    // be zero.
+    let mut prev = 0;
+    loop {
+        match cas(dst, prev, f(prev)) {
+            (p, true) => return p,
+            (p, false) => prev = p,
+        }
+    }
+}
+
+macro_rules! atomic128 {
+    ($name:ident, $int:ty) => {
+        #[repr(C, align(16))]
+        pub(crate) struct $name(UnsafeCell<$int>);
+
+        // All accesses are atomic.
+        unsafe impl Sync for $name {}
+
+        // The size dispatch only uses the signed type for `fetch_min` and
+        // `fetch_max`.
+        #[allow(dead_code)]
+        impl $name {
+            #[inline]
+            fn as_ptr(&self) -> *mut u128 {
+                self.0.get() as *mut u128
+            }
+
+            #[inline]
+            pub(crate) fn load(&self, _: Ordering) -> $int {
+                unsafe { cas(self.as_ptr(), 0, 0).0 as $int }
+            }
+
+            #[inline]
+            pub(crate) fn store(&self, val: $int, order: Ordering) {
+                self.swap(val, order);
+            }
+
+            #[inline]
+            pub(crate) fn swap(&self, val: $int, _: Ordering) -> $int {
+                unsafe { update(self.as_ptr(), |_| val as u128) as $int }
+            }
+
+            #[inline]
+            pub(crate) fn compare_exchange(
+                &self,
+                current: $int,
+                new: $int,
+                _: Ordering,
+                _: Ordering,
+            ) -> Result<$int, $int> {
+                match unsafe { cas(self.as_ptr(), current as u128, new as u128) } {
+                    (prev, true) => Ok(prev as $int),
+                    (prev, false) => Err(prev as $int),
+                }
+            }
+
+            #[inline]
+            pub(crate) fn compare_exchange_weak(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.compare_exchange(current, new, success, failure)
+            }
+
+            #[inline]
+            fn fetch_update(&self, f: impl Fn($int) -> $int) -> $int {
+                unsafe { update(self.as_ptr(), |x| f(x as $int) as u128) as $int }
+            }
+
+            #[inline]
+            pub(crate) fn fetch_add(&self, val: $int, _: Ordering) -> $int {
+                self.fetch_update(|x| x.wrapping_add(val))
+            }
+
+            #[inline]
+            pub(crate) fn fetch_sub(&self, val: $int, _: Ordering) -> $int {
+                self.fetch_update(|x| x.wrapping_sub(val))
+            }
+
+            #[inline]
+            pub(crate) fn fetch_and(&self, val: $int, _: Ordering) -> $int {
+                self.fetch_update(|x| x & val)
+            }
+
+            #[inline]
+            pub(crate) fn fetch_or(&self, val: $int, _: Ordering) -> $int {
+                self.fetch_update(|x| x | val)
+            }
+
+            #[inline]
+            pub(crate) fn fetch_xor(&self, val: $int, _: Ordering) -> $int {
+                self.fetch_update(|x| x ^ val)
+            }
+
+            #[inline]
+            pub(crate) fn fetch_min(&self, val: $int, _: Ordering) -> $int {
+                self.fetch_update(|x| x.min(val))
+            }
+
+            #[inline]
+            pub(crate) fn fetch_max(&self, val: $int, _: Ordering) -> $int {
+                self.fetch_update(|x| x.max(val))
+            }
+        }
+    };
+}
+
+atomic128!(AtomicU128, u128);
+atomic128!(AtomicI128, i128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::Ordering::SeqCst;
+
+    #[test]
+    fn operations() {
+        #[cfg(atomic_cmpxchg16b_dynamic)]
+        if !std::is_x86_feature_detected!("cmpxchg16b") {
+            return;
+        }
+
+        let a = AtomicU128(UnsafeCell::new(0));
+        assert_eq!(a.load(SeqCst), 0);
+        a.store(u64::MAX as u128, SeqCst);
+        assert_eq!(a.fetch_add(1, SeqCst), u64::MAX as u128);
+        assert_eq!(a.swap(u128::MAX, SeqCst), 1 << 64);
+        assert_eq!(a.compare_exchange(0, 1, SeqCst, SeqCst), Err(u128::MAX));
+        assert_eq!(
+            a.compare_exchange(u128::MAX, 1, SeqCst, SeqCst),
+            Ok(u128::MAX)
+        );
+        assert_eq!(a.fetch_sub(2, SeqCst), 1);
+        assert_eq!(a.fetch_and(1 << 100 | 1, SeqCst), u128::MAX);
+        assert_eq!(a.fetch_or(2, SeqCst), 1 << 100 | 1);
+        assert_eq!(a.fetch_xor(1 << 100, SeqCst), 1 << 100 | 3);
+        assert_eq!(a.fetch_max(1 << 64, SeqCst), 3);
+        assert_eq!(a.fetch_min(5, SeqCst), 1 << 64);
+        assert_eq!(a.load(SeqCst), 5);
+
+        let i = AtomicI128(UnsafeCell::new(-1));
+        assert_eq!(i.fetch_min(i128::MIN, SeqCst), -1);
+        assert_eq!(i.fetch_max(0, SeqCst), i128::MIN);
+        assert_eq!(i.fetch_sub(1, SeqCst), 0);
+        assert_eq!(i.load(SeqCst), -1);
+    }
+
+    // Every stored value has equal halves, so a load which read the halves of
+    // two different values would show up.
+    #[test]
+    fn torn_loads() {
+        #[cfg(atomic_cmpxchg16b_dynamic)]
+        if !std::is_x86_feature_detected!("cmpxchg16b") {
+            return;
+        }
+
+        use core::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+        fn splat(i: u64) -> u128 {
+            (i as u128) << 64 | i as u128
+        }
+
+        let a = AtomicU128(UnsafeCell::new(0));
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for t in 0..2 {
+                let (a, done) = (&a, &done);
+                s.spawn(move || {
+                    let mut i = t;
+                    while !done.load(Relaxed) {
+                        if i % 4 < 2 {
+                            a.store(splat(i), SeqCst);
+                        } else {
+                            let _ = a.compare_exchange(splat(i - 2), splat(i), SeqCst, SeqCst);
+                        }
+                        i = i.wrapping_add(2);
+                    }
+                });
+            }
+            for _ in 0..100_000 {
+                let v = a.load(SeqCst);
+                assert_eq!(v >> 64, v & u64::MAX as u128, "torn load: {:#x}", v);
+            }
+            done.store(true, Relaxed);
+        });
+    }
+
+    // The size dispatch uses the instruction exactly when it was detected.
+    #[cfg(atomic_cmpxchg16b_dynamic)]
+    #[test]
+    fn dispatch() {
+        use crate::{Atomic, Backing};
+
+        let a = Atomic::new(u64::MAX as u128);
+        assert_eq!(a.fetch_add(1, SeqCst), u64::MAX as u128);
+        assert_eq!(a.load(SeqCst), 1 << 64);
+        match a.backing() {
+            Backing::Native128 => assert!(detect()),
+            Backing::Locked { .. } => assert!(!detect()),
+            b => panic!("unexpected backing {:?}", b),
+        }
+        if cfg!(atomic_no_cmpxchg16b) {
+            assert!(!detect());
+        }
+        assert_eq!(Atomic::<u128>::is_lock_free_runtime(), detect());
+        assert_eq!(
+            crate::AtomicStampedPtr::<u8>::is_lock_free_runtime(),
+            detect()
+        );
+        // Only the guarantee at compile time doesn't depend on the CPU.
+        assert!(!Atomic::<u128>::is_lock_free());
+    }
+}
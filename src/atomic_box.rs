@@ -0,0 +1,303 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[inline]
+fn into_ptr<T>(v: Option<Box<T>>) -> *mut T {
+    match v {
+        Some(b) => Box::into_raw(b),
+        None => ptr::null_mut(),
+    }
+}
+
+#[inline]
+unsafe fn from_ptr<T>(p: *mut T) -> Option<Box<T>> {
+    if p.is_null() {
+        None
+    } else {
+        Some(Box::from_raw(p))
+    }
+}
+
+/// An atomic optional `Box<T>`, which owns the value it contains.
+///
+/// This is the equivalent of an `Atomic<Option<Box<T>>>`. Values are moved in
+/// and out of it as whole `Box`es, so ownership of the value is always with
+/// exactly one place: `swap` and `take` hand the previous value back to the
+/// caller, `store` drops it, and dropping the `AtomicBox` drops the value it
+/// contains. It is backed by an `AtomicPtr<T>`, with `None` represented by a
+/// null pointer, so it is always lock-free.
+///
+/// `swap`, `take` and `store` always use `AcqRel` ordering, since the thread
+/// which receives a `Box` reads and drops the value it contains, which must
+/// not race with the thread which wrote it.
+///
+/// There is no safe way to read the value while it is shared: another thread
+/// could replace and drop it while it is being read. `load_ref` is `unsafe`
+/// for this reason, and is only sound if values are never dropped while
+/// readers may exist.
+///
+/// This requires the `alloc` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicBox, Ordering};
+///
+/// struct Config {
+///     verbose: bool,
+/// }
+///
+/// let config = AtomicBox::new(Some(Box::new(Config { verbose: false })));
+/// let old = config.swap(Some(Box::new(Config { verbose: true })));
+/// assert!(!old.unwrap().verbose);
+/// assert!(config.is_some(Ordering::Relaxed));
+/// assert!(config.take().unwrap().verbose);
+/// ```
+pub struct AtomicBox<T> {
+    p: AtomicPtr<T>,
+    marker: PhantomData<Option<Box<T>>>,
+}
+
+// Like `Atomic<T>`, values are only moved in and out, so `T: Sync` isn't
+// needed for `AtomicBox<T>` to be `Sync`.
+unsafe impl<T: Send> Send for AtomicBox<T> {}
+unsafe impl<T: Send> Sync for AtomicBox<T> {}
+
+impl<T> AtomicBox<T> {
+    /// Creates a new `AtomicBox`.
+    #[inline]
+    pub fn new(v: Option<Box<T>>) -> AtomicBox<T> {
+        AtomicBox {
+            p: AtomicPtr::new(into_ptr(v)),
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates a new, empty `AtomicBox`.
+    #[inline]
+    pub const fn empty() -> AtomicBox<T> {
+        AtomicBox {
+            p: AtomicPtr::new(ptr::null_mut()),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a mutable reference to the contained value.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.p.get_mut().as_mut() }
+    }
+
+    /// Consumes the `AtomicBox` and returns the contained value.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<Box<T>> {
+        unsafe { from_ptr(core::mem::replace(self.p.get_mut(), ptr::null_mut())) }
+    }
+
+    /// Stores a value into the `AtomicBox`, returning the previous value.
+    ///
+    /// This is an `AcqRel` operation: the new value is published to the
+    /// thread which later takes it out, and the writes to the previous value
+    /// are visible to the calling thread.
+    #[inline]
+    pub fn swap(&self, val: Option<Box<T>>) -> Option<Box<T>> {
+        unsafe { from_ptr(self.p.swap(into_ptr(val), Ordering::AcqRel)) }
+    }
+
+    /// Takes the value out of the `AtomicBox`, leaving it empty.
+    #[inline]
+    pub fn take(&self) -> Option<Box<T>> {
+        self.swap(None)
+    }
+
+    /// Stores a value into the `AtomicBox`, dropping the previous value.
+    ///
+    /// The previous value is dropped after it was replaced, on the calling
+    /// thread.
+    #[inline]
+    pub fn store(&self, val: Box<T>) {
+        drop(self.swap(Some(val)));
+    }
+
+    /// Returns whether the `AtomicBox` contains a value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn is_some(&self, order: Ordering) -> bool {
+        !self.p.load(order).is_null()
+    }
+
+    /// Returns a reference to the contained value.
+    ///
+    /// # Safety
+    ///
+    /// The value must not be dropped while the returned reference is alive.
+    /// Any other thread may replace the value with `swap`, `take` or `store`,
+    /// so this requires a scheme which defers dropping the previous values
+    /// until all readers are done, or values which are never dropped. `T` must
+    /// be `Sync` if the reference is used while the `AtomicBox` is shared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub unsafe fn load_ref(&self, order: Ordering) -> Option<&T> {
+        self.p.load(order).as_ref()
+    }
+}
+
+impl<T> Drop for AtomicBox<T> {
+    #[inline]
+    fn drop(&mut self) {
+        drop(unsafe { from_ptr(*self.p.get_mut()) });
+    }
+}
+
+impl<T> Default for AtomicBox<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> From<Box<T>> for AtomicBox<T> {
+    #[inline]
+    fn from(v: Box<T>) -> Self {
+        Self::new(Some(v))
+    }
+}
+
+impl<T> From<Option<Box<T>>> for AtomicBox<T> {
+    #[inline]
+    fn from(v: Option<Box<T>>) -> Self {
+        Self::new(v)
+    }
+}
+
+impl<T> fmt::Debug for AtomicBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The value can't be read safely, only whether there is one.
+        f.debug_struct("AtomicBox")
+            .field("is_some", &self.is_some(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use core::sync::atomic::AtomicUsize;
+
+    // Counts the values which are alive, to detect leaks and double drops.
+    struct Counted<'a>(&'a AtomicUsize, usize);
+
+    impl<'a> Counted<'a> {
+        fn new(live: &'a AtomicUsize, id: usize) -> Box<Counted<'a>> {
+            live.fetch_add(1, Relaxed);
+            Box::new(Counted(live, id))
+        }
+    }
+
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            assert!(self.0.fetch_sub(1, Relaxed) > 0);
+        }
+    }
+
+    #[test]
+    fn operations() {
+        let live = AtomicUsize::new(0);
+        let mut a = AtomicBox::default();
+        assert!(a.take().is_none());
+        a.store(Counted::new(&live, 1));
+        a.store(Counted::new(&live, 2));
+        assert_eq!(live.load(SeqCst), 1);
+        assert_eq!(a.get_mut().unwrap().1, 2);
+        assert_eq!(unsafe { a.load_ref(SeqCst) }.unwrap().1, 2);
+        let old = a.swap(Some(Counted::new(&live, 3))).unwrap();
+        assert_eq!(old.1, 2);
+        drop(old);
+        assert_eq!(format!("{:?}", a), "AtomicBox { is_some: true }");
+        assert_eq!(a.into_inner().unwrap().1, 3);
+        assert_eq!(live.load(SeqCst), 0);
+
+        let a = AtomicBox::from(Counted::new(&live, 4));
+        drop(a);
+        assert_eq!(live.load(SeqCst), 0);
+    }
+
+    #[test]
+    fn swap_storm() {
+        const THREADS: usize = 4;
+        const SWAPS: usize = if cfg!(miri) { 50 } else { 10_000 };
+
+        let live = AtomicUsize::new(0);
+        let slot = AtomicBox::new(Some(Counted::new(&live, 0)));
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let (slot, live) = (&slot, &live);
+                s.spawn(move || {
+                    for i in 0..SWAPS {
+                        let new = Counted::new(live, t * SWAPS + i);
+                        match i % 3 {
+                            0 => drop(slot.swap(Some(new))),
+                            1 => slot.store(new),
+                            _ => {
+                                drop(new);
+                                drop(slot.take());
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        assert!(live.load(SeqCst) <= 1);
+        drop(slot);
+        assert_eq!(live.load(SeqCst), 0);
+    }
+
+    // The value is written by one thread and read and dropped by another, with
+    // no other synchronization than the `AtomicBox`, which Miri checks for
+    // data races.
+    #[test]
+    fn handoff() {
+        let rounds = if cfg!(miri) { 20 } else { 10_000 };
+
+        let slot = AtomicBox::empty();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..rounds {
+                    let mut v = Box::new([0; 4]);
+                    v.fill(i);
+                    slot.store(v);
+                }
+            });
+            s.spawn(|| {
+                let mut last = 0;
+                while last != rounds - 1 {
+                    if let Some(mut v) = slot.take() {
+                        assert!(v.iter().all(|&x| x == v[0]));
+                        assert!(v[0] >= last);
+                        last = v[0];
+                        // Writing to the value must not race either.
+                        v.fill(usize::MAX);
+                    }
+                    std::thread::yield_now();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+    NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+
+use crate::Atomic;
+
+/// Types which can be stored in an `AtomicOption`.
+///
+/// An `Option<T>` usually contains padding bytes, so it can't be stored in an
+/// `Atomic` directly. This trait maps `Some` values and `None` to a `Repr`
+/// type without padding instead:
+///
+/// - The `NonZero` integers use `Option<Self>`, where `None` is zero.
+/// - `bool` and `char` use an integer with an invalid value for `None`.
+/// - The other integers and floats use an integer twice as wide, with a flag
+///   bit above the value.
+///
+/// `AtomicOption<T>` is lock-free whenever `Atomic<T::Repr>` is. For example,
+/// `AtomicOption<u32>` is lock-free on 64-bit targets, while
+/// `AtomicOption<u64>` needs 128-bit atomics and otherwise uses the fallback.
+///
+/// This can also be implemented for other types. `into_repr` must never
+/// return a value with the same bytes as `NONE`.
+pub trait OptionRepr: Copy {
+    /// The representation of an `Option<Self>`.
+    type Repr: NoUninit + Send;
+
+    /// The representation of `None`.
+    const NONE: Self::Repr;
+
+    /// Returns the representation of `Some(self)`.
+    fn into_repr(self) -> Self::Repr;
+
+    /// Converts a representation back to an `Option<Self>`.
+    fn from_repr(repr: Self::Repr) -> Option<Self>;
+}
+
+macro_rules! option_repr_non_zero {
+    ($($t:ty)*) => ($(
+        impl OptionRepr for $t {
+            type Repr = Option<$t>;
+            const NONE: Option<$t> = None;
+
+            #[inline]
+            fn into_repr(self) -> Option<$t> {
+                Some(self)
+            }
+
+            #[inline]
+            fn from_repr(repr: Option<$t>) -> Option<$t> {
+                repr
+            }
+        }
+    )*);
+}
+option_repr_non_zero! {
+    NonZeroU8 NonZeroU16 NonZeroU32 NonZeroU64 NonZeroU128 NonZeroUsize
+    NonZeroI8 NonZeroI16 NonZeroI32 NonZeroI64 NonZeroI128 NonZeroIsize
+}
+
+macro_rules! option_repr_widen {
+    ($($t:ty, $bits:ty => $wide:ty;)*) => ($(
+        impl OptionRepr for $t {
+            type Repr = $wide;
+            const NONE: $wide = 0;
+
+            #[inline]
+            fn into_repr(self) -> $wide {
+                const FLAG: $wide = 1 << <$bits>::BITS;
+                bytemuck::cast::<$t, $bits>(self) as $wide | FLAG
+            }
+
+            #[inline]
+            fn from_repr(repr: $wide) -> Option<$t> {
+                if repr == 0 {
+                    None
+                } else {
+                    Some(bytemuck::cast::<$bits, $t>(repr as $bits))
+                }
+            }
+        }
+    )*);
+}
+option_repr_widen! {
+    u8, u8 => u16;
+    i8, u8 => u16;
+    u16, u16 => u32;
+    i16, u16 => u32;
+    u32, u32 => u64;
+    i32, u32 => u64;
+    f32, u32 => u64;
+    u64, u64 => u128;
+    i64, u64 => u128;
+    f64, u64 => u128;
+}
+#[cfg(target_pointer_width = "16")]
+option_repr_widen! {
+    usize, u16 => u32;
+    isize, u16 => u32;
+}
+#[cfg(target_pointer_width = "32")]
+option_repr_widen! {
+    usize, u32 => u64;
+    isize, u32 => u64;
+}
+#[cfg(target_pointer_width = "64")]
+option_repr_widen! {
+    usize, u64 => u128;
+    isize, u64 => u128;
+}
+
+impl OptionRepr for bool {
+    type Repr = u8;
+    const NONE: u8 = 2;
+
+    #[inline]
+    fn into_repr(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    fn from_repr(repr: u8) -> Option<bool> {
+        match repr {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl OptionRepr for char {
+    type Repr = u32;
+    // Just above the largest code point.
+    const NONE: u32 = 0x11_0000;
+
+    #[inline]
+    fn into_repr(self) -> u32 {
+        self as u32
+    }
+
+    #[inline]
+    fn from_repr(repr: u32) -> Option<char> {
+        char::from_u32(repr)
+    }
+}
+
+/// An atomic `Option<T>`, which can be used as a single-slot mailbox.
+///
+/// Every operation is a single atomic operation on the representation of the
+/// `Option`, see `OptionRepr`.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicOption, Ordering};
+///
+/// let mailbox = AtomicOption::<u32>::empty();
+/// assert_eq!(mailbox.try_insert(1, Ordering::Release), Ok(()));
+/// assert_eq!(mailbox.try_insert(2, Ordering::Release), Err(2));
+/// assert_eq!(mailbox.take(Ordering::Acquire), Some(1));
+/// assert_eq!(mailbox.take(Ordering::Acquire), None);
+/// ```
+#[repr(transparent)]
+pub struct AtomicOption<T: OptionRepr>(Atomic<T::Repr>);
+
+impl<T: OptionRepr> AtomicOption<T> {
+    /// Creates a new, empty `AtomicOption`.
+    #[inline]
+    pub const fn empty() -> AtomicOption<T> {
+        AtomicOption(Atomic::new(T::NONE))
+    }
+
+    /// Creates a new `AtomicOption`.
+    #[inline]
+    pub fn new(v: Option<T>) -> AtomicOption<T> {
+        AtomicOption(Atomic::new(repr(v)))
+    }
+
+    /// Checks if operations on an `AtomicOption<T>` are lock-free on the
+    /// current target.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        Atomic::<T::Repr>::is_lock_free()
+    }
+
+    /// Consumes the `AtomicOption` and returns the contained value.
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        T::from_repr(self.0.into_inner())
+    }
+
+    /// Loads the value without taking it.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Option<T> {
+        T::from_repr(self.0.load(order))
+    }
+
+    /// Stores a value, returning the previously stored value.
+    ///
+    /// See `Atomic::swap`.
+    #[inline]
+    pub fn insert(&self, val: T, order: Ordering) -> Option<T> {
+        T::from_repr(self.0.swap(val.into_repr(), order))
+    }
+
+    /// Takes the value, leaving the `AtomicOption` empty.
+    ///
+    /// See `Atomic::swap`.
+    #[inline]
+    pub fn take(&self, order: Ordering) -> Option<T> {
+        T::from_repr(self.0.swap(T::NONE, order))
+    }
+
+    /// Stores a value if the `AtomicOption` is empty, or returns it back if
+    /// the `AtomicOption` is full.
+    ///
+    /// `order` is the ordering of the operation if it succeeds. If it fails,
+    /// the ordering is the same without the `Release` part.
+    #[inline]
+    pub fn try_insert(&self, val: T, order: Ordering) -> Result<(), T> {
+        let failure = match order {
+            Ordering::Release => Ordering::Relaxed,
+            Ordering::AcqRel => Ordering::Acquire,
+            order => order,
+        };
+        match self
+            .0
+            .compare_exchange(T::NONE, val.into_repr(), order, failure)
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(val),
+        }
+    }
+}
+
+#[inline]
+fn repr<T: OptionRepr>(v: Option<T>) -> T::Repr {
+    match v {
+        Some(v) => v.into_repr(),
+        None => T::NONE,
+    }
+}
+
+impl<T: OptionRepr> Default for AtomicOption<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: OptionRepr> From<Option<T>> for AtomicOption<T> {
+    #[inline]
+    fn from(v: Option<T>) -> Self {
+        Self::new(v)
+    }
+}
+
+impl<T: OptionRepr + fmt::Debug> fmt::Debug for AtomicOption<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicOption")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+
+    fn round_trip<T: OptionRepr + PartialEq + fmt::Debug>(values: &[T]) {
+        let a = AtomicOption::<T>::default();
+        assert_eq!(a.load(SeqCst), None);
+        for &v in values {
+            assert_eq!(a.try_insert(v, SeqCst), Ok(()));
+            assert_eq!(a.try_insert(v, SeqCst), Err(v));
+            assert_eq!(a.insert(v, SeqCst), Some(v));
+            assert_eq!(a.take(SeqCst), Some(v));
+            assert_eq!(a.take(SeqCst), None);
+        }
+        assert_eq!(
+            AtomicOption::new(Some(values[0])).into_inner(),
+            Some(values[0])
+        );
+    }
+
+    #[test]
+    fn representations() {
+        round_trip(&[0u8, 1, u8::MAX]);
+        round_trip(&[0i8, -1, i8::MIN]);
+        round_trip(&[0u16, u16::MAX]);
+        round_trip(&[0i32, -1, i32::MAX]);
+        round_trip(&[0u64, u64::MAX]);
+        round_trip(&[0usize, usize::MAX]);
+        round_trip(&[0.0f32, -0.0, f32::INFINITY]);
+        round_trip(&[0.0f64, -1.5]);
+        round_trip(&[false, true]);
+        round_trip(&['\0', 'a', char::MAX]);
+        round_trip(&[NonZeroU32::new(1).unwrap(), NonZeroU32::MAX]);
+        round_trip(&[NonZeroI64::new(-1).unwrap()]);
+    }
+
+    #[test]
+    fn lock_free() {
+        assert_eq!(
+            AtomicOption::<u8>::is_lock_free(),
+            cfg!(target_has_atomic = "16")
+        );
+        assert_eq!(
+            AtomicOption::<u32>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(
+            AtomicOption::<char>::is_lock_free(),
+            cfg!(target_has_atomic = "32")
+        );
+        assert_eq!(
+            AtomicOption::<NonZeroU64>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(
+            AtomicOption::<u64>::is_lock_free(),
+            Atomic::<u128>::is_lock_free()
+        );
+    }
+
+    #[test]
+    fn spsc_handoff() {
+        const VALUES: u32 = if cfg!(miri) { 100 } else { 100_000 };
+
+        fn handoff<T: OptionRepr + Send>(f: impl Fn(u32) -> T + Sync, g: impl Fn(T) -> u32) {
+            let mailbox = AtomicOption::<T>::empty();
+            let mut received = std::vec::Vec::new();
+            std::thread::scope(|s| {
+                s.spawn(|| {
+                    for i in 0..VALUES {
+                        let mut v = f(i);
+                        while let Err(back) = mailbox.try_insert(v, Release) {
+                            v = back;
+                            std::thread::yield_now();
+                        }
+                    }
+                });
+                while received.len() < VALUES as usize {
+                    match mailbox.take(Acquire) {
+                        Some(v) => received.push(g(v)),
+                        None => std::thread::yield_now(),
+                    }
+                }
+            });
+            // Every value was received exactly once, in order.
+            assert!(received.iter().copied().eq(0..VALUES));
+            assert_eq!(mailbox.take(SeqCst).map(g), None);
+        }
+
+        // Lock-free on 64-bit targets.
+        handoff(|i| i, |v| v);
+        // Uses the fallback without 128-bit atomics.
+        handoff(|i| i as u64, |v| v as u32);
+        handoff(|i| NonZeroU32::new(i + 1).unwrap(), |v| v.get() - 1);
+    }
+}
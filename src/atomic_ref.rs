@@ -0,0 +1,318 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+// The references which are loaded are dereferenced, so every load must
+// synchronize with the store which published the reference, whatever ordering
+// the caller asked for. Orderings are only ever strengthened.
+#[inline]
+fn with_acquire(order: Ordering) -> Ordering {
+    match order {
+        Ordering::Relaxed => Ordering::Acquire,
+        Ordering::Release => Ordering::AcqRel,
+        order => order,
+    }
+}
+
+#[inline]
+fn with_release(order: Ordering) -> Ordering {
+    match order {
+        Ordering::Relaxed => Ordering::Release,
+        Ordering::Acquire => Ordering::AcqRel,
+        order => order,
+    }
+}
+
+#[inline]
+const fn to_ptr<T>(v: Option<&T>) -> *mut T {
+    match v {
+        Some(r) => r as *const T as *mut T,
+        None => ptr::null_mut(),
+    }
+}
+
+/// An atomic optional reference, which can be used to publish a reference to
+/// other threads.
+///
+/// This is the equivalent of an `Atomic<Option<&'a T>>`, which isn't possible
+/// since references don't implement `NoUninit`. It is backed by an
+/// `AtomicPtr<T>`, with `None` represented by a null pointer, so it is always
+/// lock-free. Every reference it returns was previously stored into it.
+///
+/// Storing a reference requires it to live for `'a`, so the references which
+/// are loaded are valid for as long as the `AtomicRef` may be used. This makes
+/// `AtomicRef` invariant over `'a`: otherwise an `&AtomicRef<'static, T>`
+/// could be turned into an `&AtomicRef<'short, T>` and used to store a
+/// short-lived reference, which would then be loaded as `&'static T`. Sharing
+/// an `AtomicRef` between threads shares the references in it, so `T` must be
+/// `Sync` for the `AtomicRef` to be `Send` or `Sync`.
+///
+/// Stores are always at least `Release` and loads at least `Acquire`, even if
+/// a weaker ordering is given, so that the value behind a loaded reference is
+/// always visible to the thread which loaded it.
+///
+/// `swap` and the compare-and-swap operations are only available on targets
+/// with atomic read-modify-write operations on pointers.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicRef, Ordering};
+///
+/// struct Config {
+///     verbose: bool,
+/// }
+///
+/// static CONFIG: AtomicRef<'static, Config> = AtomicRef::new(None);
+///
+/// let config = Box::leak(Box::new(Config { verbose: true }));
+/// let published = CONFIG.compare_exchange(
+///     None,
+///     Some(config),
+///     Ordering::Release,
+///     Ordering::Relaxed,
+/// );
+/// assert!(published.is_ok());
+///
+/// let config = CONFIG.load(Ordering::Acquire).unwrap();
+/// assert!(config.verbose);
+/// ```
+pub struct AtomicRef<'a, T> {
+    p: AtomicPtr<T>,
+    marker: PhantomData<Invariant<'a, T>>,
+}
+
+// Makes `AtomicRef` invariant over `'a`, and `Send` and `Sync` only if
+// `&'a T` is.
+type Invariant<'a, T> = (&'a T, fn(&'a T) -> &'a T);
+
+impl<'a, T> AtomicRef<'a, T> {
+    /// Creates a new `AtomicRef`.
+    #[inline]
+    pub const fn new(v: Option<&'a T>) -> AtomicRef<'a, T> {
+        AtomicRef {
+            p: AtomicPtr::new(to_ptr(v)),
+            marker: PhantomData,
+        }
+    }
+
+    /// Consumes the `AtomicRef` and returns the contained reference.
+    #[inline]
+    pub fn into_inner(self) -> Option<&'a T> {
+        unsafe { self.p.into_inner().as_ref() }
+    }
+
+    /// Loads the reference from the `AtomicRef`.
+    ///
+    /// This is at least an `Acquire` load, which synchronizes with the store
+    /// which published the reference, so that writes made to the referenced
+    /// value before it was published are visible.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Option<&'a T> {
+        unsafe { self.p.load(with_acquire(order)).as_ref() }
+    }
+
+    /// Stores a reference into the `AtomicRef`.
+    ///
+    /// This is at least a `Release` store.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Acquire` or `AcqRel`.
+    #[inline]
+    pub fn store(&self, val: Option<&'a T>, order: Ordering) {
+        self.p.store(to_ptr(val), with_release(order))
+    }
+
+    /// Stores a reference into the `AtomicRef`, returning the previous one.
+    ///
+    /// This is at least an `AcqRel` operation.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn swap(&self, val: Option<&'a T>, order: Ordering) -> Option<&'a T> {
+        let order = with_acquire(with_release(order));
+        unsafe { self.p.swap(to_ptr(val), order).as_ref() }
+    }
+
+    /// Stores a reference into the `AtomicRef` if the current reference
+    /// points to the same address as `current`.
+    ///
+    /// The references are compared by address, like `ptr::eq`, not by
+    /// comparing the values they point to. The orderings are at least `AcqRel`
+    /// on success and `Acquire` on failure.
+    ///
+    /// See `Atomic::compare_exchange`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: Option<&'a T>,
+        new: Option<&'a T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<&'a T>, Option<&'a T>> {
+        match self.p.compare_exchange(
+            to_ptr(current),
+            to_ptr(new),
+            with_acquire(with_release(success)),
+            with_acquire(failure),
+        ) {
+            Ok(p) => Ok(unsafe { p.as_ref() }),
+            Err(p) => Err(unsafe { p.as_ref() }),
+        }
+    }
+
+    /// Stores a reference into the `AtomicRef` if the current reference
+    /// points to the same address as `current`, possibly failing spuriously.
+    ///
+    /// The orderings are strengthened like in `compare_exchange`.
+    ///
+    /// See `Atomic::compare_exchange_weak`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: Option<&'a T>,
+        new: Option<&'a T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<&'a T>, Option<&'a T>> {
+        match self.p.compare_exchange_weak(
+            to_ptr(current),
+            to_ptr(new),
+            with_acquire(with_release(success)),
+            with_acquire(failure),
+        ) {
+            Ok(p) => Ok(unsafe { p.as_ref() }),
+            Err(p) => Err(unsafe { p.as_ref() }),
+        }
+    }
+}
+
+impl<'a, T> Default for AtomicRef<'a, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<'a, T> From<Option<&'a T>> for AtomicRef<'a, T> {
+    #[inline]
+    fn from(v: Option<&'a T>) -> Self {
+        Self::new(v)
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for AtomicRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicRef")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicRef;
+    use crate::Ordering::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Config {
+        id: u32,
+        name: &'static str,
+    }
+
+    #[test]
+    fn operations() {
+        let a = Config { id: 1, name: "a" };
+        let b = Config { id: 1, name: "a" };
+        let r = AtomicRef::new(None);
+        assert_eq!(r.swap(Some(&a), SeqCst), None);
+        // `b` is equal to `a` but at a different address.
+        assert_eq!(
+            r.compare_exchange(Some(&b), None, SeqCst, SeqCst),
+            Err(Some(&a))
+        );
+        assert!(core::ptr::eq(r.load(SeqCst).unwrap(), &a));
+        assert_eq!(
+            r.compare_exchange(Some(&a), Some(&b), SeqCst, SeqCst),
+            Ok(Some(&a))
+        );
+        assert!(core::ptr::eq(r.load(SeqCst).unwrap(), &b));
+        assert_eq!(
+            format!("{:?}", r),
+            r#"AtomicRef(Some(Config { id: 1, name: "a" }))"#
+        );
+        r.store(None, SeqCst);
+        assert_eq!(r.into_inner(), None);
+    }
+
+    #[test]
+    fn publish_once() {
+        static CONFIG: AtomicRef<'static, Config> = AtomicRef::new(None);
+        const READERS: usize = if cfg!(miri) { 2 } else { 8 };
+
+        std::thread::scope(|s| {
+            for _ in 0..READERS {
+                s.spawn(|| loop {
+                    if let Some(config) = CONFIG.load(Acquire) {
+                        assert_eq!(
+                            config,
+                            &Config {
+                                id: 7,
+                                name: "live"
+                            }
+                        );
+                        break;
+                    }
+                    std::thread::yield_now();
+                });
+            }
+            s.spawn(|| {
+                let config = std::boxed::Box::leak(std::boxed::Box::new(Config {
+                    id: 7,
+                    name: "live",
+                }));
+                CONFIG.store(Some(config), Release);
+            });
+        });
+    }
+
+    // Even with `Relaxed`, the value behind the reference is visible to the
+    // thread which loads it, which Miri checks for data races.
+    #[test]
+    fn relaxed_publish() {
+        let rounds = if cfg!(miri) { 10 } else { 1000 };
+
+        for i in 0..rounds {
+            let mut config = Config { id: 0, name: "a" };
+            let r: AtomicRef<'_, Config> = AtomicRef::new(None);
+            std::thread::scope(|s| {
+                let (config, r) = (&mut config, &r);
+                s.spawn(move || loop {
+                    if let Some(config) = r.load(Relaxed) {
+                        assert_eq!(config.id, i);
+                        break;
+                    }
+                    std::thread::yield_now();
+                });
+                s.spawn(move || {
+                    config.id = i;
+                    r.store(Some(config), Relaxed);
+                });
+            });
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::cell::Cell;
+use core::fmt;
+use core::hint;
+
+const SPIN_LIMIT: u32 = 6;
+const YIELD_LIMIT: u32 = 10;
+
+/// Exponential backoff for spin loops.
+///
+/// Backing off in a loop which retries an atomic operation reduces contention
+/// and improves overall performance. This is the backoff used internally by
+/// the crate's own retry loops, such as `fetch_update` and the spinlocks of the
+/// fallback implementation.
+///
+/// # Examples
+///
+/// Retrying a `compare_exchange_weak` loop:
+///
+/// ```rust
+/// use atomic::{Atomic, Backoff, Ordering};
+///
+/// fn fetch_double(a: &Atomic<u32>) -> u32 {
+///     let backoff = Backoff::new();
+///     let mut prev = a.load(Ordering::Relaxed);
+///     loop {
+///         match a.compare_exchange_weak(prev, prev * 2, Ordering::AcqRel, Ordering::Relaxed) {
+///             Ok(x) => return x,
+///             Err(x) => prev = x,
+///         }
+///         backoff.spin();
+///     }
+/// }
+/// ```
+///
+/// Waiting for another thread to make progress:
+///
+/// ```rust
+/// use atomic::{Atomic, Backoff, Ordering};
+///
+/// fn spin_wait(ready: &Atomic<bool>) {
+///     let backoff = Backoff::new();
+///     while !ready.load(Ordering::Acquire) {
+///         backoff.snooze();
+///     }
+/// }
+/// ```
+pub struct Backoff {
+    step: Cell<u32>,
+}
+
+impl Backoff {
+    /// Creates a new `Backoff`.
+    #[inline]
+    pub const fn new() -> Backoff {
+        Backoff { step: Cell::new(0) }
+    }
+
+    /// Resets the `Backoff` to its initial state.
+    #[inline]
+    pub fn reset(&self) {
+        self.step.set(0);
+    }
+
+    /// Backs off in a lock-free loop.
+    ///
+    /// This should be used when we retry an operation because another thread
+    /// made progress, for example after a failed `compare_exchange`. The
+    /// processor may yield using `spin_loop`, but the thread never yields to
+    /// the scheduler.
+    #[inline]
+    pub fn spin(&self) {
+        for _ in 0..1 << self.step.get().min(SPIN_LIMIT) {
+            hint::spin_loop();
+        }
+        if self.step.get() <= SPIN_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Backs off in a blocking loop.
+    ///
+    /// This should be used when we wait for another thread to make progress.
+    /// The processor spins for a while and, when the `std` feature is enabled,
+    /// the thread then yields to the scheduler. Without `std` this keeps
+    /// spinning.
+    #[inline]
+    pub fn snooze(&self) {
+        if self.step.get() <= SPIN_LIMIT {
+            for _ in 0..1 << self.step.get() {
+                hint::spin_loop();
+            }
+        } else {
+            #[cfg(feature = "std")]
+            std::thread::yield_now();
+            #[cfg(not(feature = "std"))]
+            for _ in 0..1 << SPIN_LIMIT {
+                hint::spin_loop();
+            }
+        }
+        if self.step.get() <= YIELD_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Returns `true` once backing off has become expensive enough that the
+    /// thread should block instead of spinning, if possible.
+    #[inline]
+    pub fn is_completed(&self) -> bool {
+        self.step.get() > YIELD_LIMIT
+    }
+}
+
+impl Default for Backoff {
+    #[inline]
+    fn default() -> Backoff {
+        Backoff::new()
+    }
+}
+
+impl fmt::Debug for Backoff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Backoff")
+            .field("step", &self.step.get())
+            .field("is_completed", &self.is_completed())
+            .finish()
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+#[cfg(not(atomic_loom))]
+type Counter = crate::Atomic<usize>;
+#[cfg(atomic_loom)]
+type Counter = loom::sync::atomic::AtomicUsize;
+
+/// A reusable barrier which spins until all participants arrive, for
+/// environments without an operating system.
+///
+/// Each call to `wait` blocks until `n` threads have called it, and then
+/// releases all of them. The barrier can then be used for the next round
+/// straight away. Rounds are told apart by a generation counter, so a thread
+/// which is still spinning on a finished round isn't confused by threads
+/// which already arrived at the next one.
+///
+/// Everything which a thread did before calling `wait` happens before
+/// everything which the other threads do after `wait` returns.
+///
+/// Waiting threads spin with `Backoff::snooze`, so with the `std` feature
+/// they eventually yield to the scheduler. Prefer `std::sync::Barrier` when
+/// the participants may wait for a long time and blocking is possible.
+///
+/// This requires the `sync-extras` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::SpinBarrier;
+///
+/// static BOOT: SpinBarrier = SpinBarrier::new(4);
+///
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| {
+///             // Per-core initialization.
+///             BOOT.wait();
+///             // All cores are initialized.
+///         });
+///     }
+/// });
+/// ```
+pub struct SpinBarrier {
+    n: usize,
+    // Number of threads which have arrived in the current round.
+    count: Counter,
+    // Incremented, wrapping around, when a round completes.
+    generation: Counter,
+}
+
+impl SpinBarrier {
+    /// Creates a new barrier for `n` threads.
+    ///
+    /// A barrier for 0 threads behaves like a barrier for 1 thread: `wait`
+    /// returns immediately.
+    #[cfg(not(atomic_loom))]
+    #[inline]
+    pub const fn new(n: usize) -> SpinBarrier {
+        SpinBarrier {
+            n: if n == 0 { 1 } else { n },
+            count: Counter::new(0),
+            generation: Counter::new(0),
+        }
+    }
+
+    /// Creates a new barrier for `n` threads.
+    #[cfg(atomic_loom)]
+    pub fn new(n: usize) -> SpinBarrier {
+        SpinBarrier {
+            n: if n == 0 { 1 } else { n },
+            count: Counter::new(0),
+            generation: Counter::new(0),
+        }
+    }
+
+    /// Spins until `n` threads have called `wait` in this round.
+    ///
+    /// Returns `true` for exactly one thread in each round, the last one to
+    /// arrive, and `false` for the others.
+    pub fn wait(&self) -> bool {
+        // The round can't complete before this thread arrives, so this is
+        // the generation of the round it is joining.
+        let generation = self.generation.load(Ordering::Relaxed);
+        // Arrivals form a release sequence on `count`, which the last thread
+        // acquires before releasing everyone with `generation`.
+        if self.count.fetch_add(1, Ordering::AcqRel) + 1 == self.n {
+            // Nobody touches `count` again until they have seen the new
+            // generation, which is published after the reset.
+            self.count.store(0, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+            return true;
+        }
+        #[cfg(not(atomic_loom))]
+        let backoff = crate::Backoff::new();
+        while self.generation.load(Ordering::Acquire) == generation {
+            #[cfg(not(atomic_loom))]
+            backoff.snooze();
+            #[cfg(atomic_loom)]
+            loom::thread::yield_now();
+        }
+        false
+    }
+
+    /// Returns the number of threads which the barrier waits for.
+    #[inline]
+    pub fn participants(&self) -> usize {
+        self.n
+    }
+}
+
+impl fmt::Debug for SpinBarrier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpinBarrier")
+            .field("participants", &self.n)
+            .field("arrived", &self.count.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(atomic_loom)))]
+mod tests {
+    use super::*;
+    use crate::Atomic;
+    use crate::Ordering::*;
+
+    #[test]
+    fn single() {
+        let b = SpinBarrier::new(0);
+        assert_eq!(b.participants(), 1);
+        assert!(b.wait());
+        assert!(b.wait());
+        assert_eq!(
+            format!("{:?}", SpinBarrier::new(3)),
+            "SpinBarrier { participants: 3, arrived: 0 }"
+        );
+    }
+
+    #[test]
+    fn rounds() {
+        const THREADS: usize = 6;
+        const ROUNDS: usize = if cfg!(miri) { 10 } else { 2_000 };
+
+        // In each round, every thread writes its own slot and then checks
+        // that it sees the slots written by all other threads in that round.
+        let b = SpinBarrier::new(THREADS);
+        let slots: [Atomic<usize>; THREADS] = Atomic::zeroed_array();
+        let leaders = Atomic::<usize>::new(0);
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let (b, slots, leaders) = (&b, &slots, &leaders);
+                s.spawn(move || {
+                    for round in 1..=ROUNDS {
+                        slots[t].store(round, Relaxed);
+                        if b.wait() {
+                            leaders.fetch_add(1, Relaxed);
+                        }
+                        for slot in slots {
+                            assert_eq!(slot.load(Relaxed), round);
+                        }
+                        // Nobody may start the next round before everyone
+                        // has checked this one.
+                        b.wait();
+                    }
+                });
+            }
+        });
+        assert_eq!(leaders.load(SeqCst), ROUNDS);
+    }
+}
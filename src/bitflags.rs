@@ -0,0 +1,266 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use ::bitflags::{Bits, Flags};
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+
+use crate::{Atomic, AtomicInt};
+
+/// An atomic set of flags defined with `bitflags!`.
+///
+/// The flags are stored in an `Atomic` of their bits type, so `AtomicFlags<F>`
+/// is lock-free whenever `Atomic<F::Bits>` is. Every value returned is
+/// truncated with `from_bits_truncate`, so bits which don't correspond to a
+/// defined flag are never exposed, even if they were set through another
+/// view of the same memory.
+///
+/// This requires the `bitflags` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicFlags, Ordering};
+///
+/// bitflags::bitflags! {
+///     #[derive(Copy, Clone, Debug, PartialEq)]
+///     struct Status: u32 {
+///         const READY = 1 << 0;
+///         const BUSY = 1 << 1;
+///     }
+/// }
+///
+/// let status = AtomicFlags::new(Status::READY);
+/// assert_eq!(status.insert(Status::BUSY, Ordering::AcqRel), Status::READY);
+/// assert!(status.contains(Status::READY | Status::BUSY, Ordering::Acquire));
+/// status.remove(Status::READY, Ordering::AcqRel);
+/// assert_eq!(status.load(Ordering::Acquire), Status::BUSY);
+/// ```
+pub struct AtomicFlags<F: Flags> {
+    bits: Atomic<F::Bits>,
+    marker: PhantomData<F>,
+}
+
+impl<F: Flags> AtomicFlags<F> {
+    /// Creates a new `AtomicFlags` with no flags set.
+    #[inline]
+    pub const fn empty() -> AtomicFlags<F> {
+        AtomicFlags {
+            bits: Atomic::new(<F::Bits as Bits>::EMPTY),
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates a new `AtomicFlags`.
+    #[inline]
+    pub fn new(flags: F) -> AtomicFlags<F> {
+        AtomicFlags {
+            bits: Atomic::new(flags.bits()),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<F: Flags> AtomicFlags<F>
+where
+    Atomic<F::Bits>: AtomicInt<Prim = F::Bits>,
+{
+    /// Consumes the `AtomicFlags` and returns the contained flags.
+    #[inline]
+    pub fn into_inner(self) -> F {
+        F::from_bits_truncate(AtomicInt::load(&self.bits, Ordering::Relaxed))
+    }
+
+    /// Loads the flags.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> F {
+        F::from_bits_truncate(AtomicInt::load(&self.bits, order))
+    }
+
+    /// Stores the flags.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, flags: F, order: Ordering) {
+        AtomicInt::store(&self.bits, flags.bits(), order)
+    }
+
+    /// Stores the flags, returning the previous flags.
+    ///
+    /// See `Atomic::swap`.
+    #[inline]
+    pub fn swap(&self, flags: F, order: Ordering) -> F {
+        F::from_bits_truncate(AtomicInt::swap(&self.bits, flags.bits(), order))
+    }
+
+    /// Sets `flags`, returning the previous flags.
+    #[inline]
+    pub fn insert(&self, flags: F, order: Ordering) -> F {
+        F::from_bits_truncate(AtomicInt::fetch_or(&self.bits, flags.bits(), order))
+    }
+
+    /// Clears `flags`, returning the previous flags.
+    #[inline]
+    pub fn remove(&self, flags: F, order: Ordering) -> F {
+        F::from_bits_truncate(AtomicInt::fetch_and(&self.bits, !flags.bits(), order))
+    }
+
+    /// Toggles `flags`, returning the previous flags.
+    #[inline]
+    pub fn toggle(&self, flags: F, order: Ordering) -> F {
+        F::from_bits_truncate(AtomicInt::fetch_xor(&self.bits, flags.bits(), order))
+    }
+
+    /// Returns whether all of `flags` are set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn contains(&self, flags: F, order: Ordering) -> bool {
+        self.load(order).contains(flags)
+    }
+
+    /// Fetches the flags, and applies a function to them that returns an
+    /// optional new value.
+    ///
+    /// See `Atomic::fetch_update`. The function only sees the defined flags.
+    #[inline]
+    pub fn fetch_update<G>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: G,
+    ) -> Result<F, F>
+    where
+        G: FnMut(F) -> Option<F>,
+    {
+        let backoff = crate::Backoff::new();
+        let mut prev = AtomicInt::load(&self.bits, fetch_order);
+        while let Some(next) = f(F::from_bits_truncate(prev)) {
+            match AtomicInt::compare_exchange_weak(
+                &self.bits,
+                prev,
+                next.bits(),
+                set_order,
+                fetch_order,
+            ) {
+                Ok(prev) => return Ok(F::from_bits_truncate(prev)),
+                Err(next_prev) => prev = next_prev,
+            }
+            backoff.spin();
+        }
+        Err(F::from_bits_truncate(prev))
+    }
+}
+
+impl<F: Flags> Default for AtomicFlags<F> {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<F: Flags> From<F> for AtomicFlags<F> {
+    #[inline]
+    fn from(flags: F) -> Self {
+        Self::new(flags)
+    }
+}
+
+impl<F: Flags + fmt::Debug> fmt::Debug for AtomicFlags<F>
+where
+    Atomic<F::Bits>: AtomicInt<Prim = F::Bits>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicFlags")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+
+    ::bitflags::bitflags! {
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct Perms: u16 {
+            const READ = 1 << 0;
+            const WRITE = 1 << 1;
+            const EXEC = 1 << 2;
+            const ADMIN = 1 << 9;
+        }
+    }
+
+    #[test]
+    fn operations() {
+        let a = AtomicFlags::<Perms>::default();
+        assert!(Atomic::<u16>::is_lock_free() == cfg!(target_has_atomic = "16"));
+        assert_eq!(a.insert(Perms::READ | Perms::WRITE, SeqCst), Perms::empty());
+        assert_eq!(
+            a.toggle(Perms::WRITE | Perms::EXEC, SeqCst),
+            Perms::READ | Perms::WRITE
+        );
+        assert_eq!(a.load(SeqCst), Perms::READ | Perms::EXEC);
+        assert!(a.contains(Perms::EXEC, SeqCst));
+        assert!(!a.contains(Perms::EXEC | Perms::ADMIN, SeqCst));
+        assert_eq!(a.remove(Perms::READ, SeqCst), Perms::READ | Perms::EXEC);
+        assert_eq!(
+            a.fetch_update(SeqCst, SeqCst, |p| Some(p | Perms::ADMIN)),
+            Ok(Perms::EXEC)
+        );
+        assert_eq!(a.swap(Perms::READ, SeqCst), Perms::EXEC | Perms::ADMIN);
+        assert_eq!(
+            format!("{:?}", a),
+            format!("AtomicFlags({:?})", Perms::READ)
+        );
+        assert_eq!(a.into_inner(), Perms::READ);
+    }
+
+    #[test]
+    fn undefined_bits() {
+        // Bits which aren't defined flags are never returned.
+        let a = AtomicFlags::new(Perms::from_bits_retain(0xf000 | Perms::READ.bits()));
+        assert_eq!(a.load(SeqCst), Perms::READ);
+        assert_eq!(a.insert(Perms::WRITE, SeqCst), Perms::READ);
+        assert_eq!(
+            a.fetch_update(SeqCst, SeqCst, |p| {
+                assert_eq!(p, Perms::READ | Perms::WRITE);
+                None
+            }),
+            Err(Perms::READ | Perms::WRITE)
+        );
+        assert_eq!(a.into_inner(), Perms::READ | Perms::WRITE);
+    }
+
+    #[test]
+    fn concurrent_disjoint_flags() {
+        const ROUNDS: usize = if cfg!(miri) { 50 } else { 20_000 };
+
+        let a = AtomicFlags::new(Perms::ADMIN);
+        std::thread::scope(|s| {
+            for flag in [Perms::READ, Perms::WRITE, Perms::EXEC] {
+                let a = &a;
+                s.spawn(move || {
+                    for _ in 0..ROUNDS {
+                        // No other thread touches `flag`, so each update must
+                        // see the result of the previous one.
+                        assert!(!a.insert(flag, AcqRel).contains(flag));
+                        assert!(a.remove(flag, AcqRel).contains(flag));
+                    }
+                    a.insert(flag, AcqRel);
+                });
+            }
+        });
+        assert_eq!(a.load(SeqCst), Perms::all());
+    }
+}
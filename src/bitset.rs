@@ -0,0 +1,330 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use crate::Atomic;
+
+const WORD_BITS: usize = usize::BITS as usize;
+
+/// Returns the number of words needed by an `AtomicBitSet` of `bits` bits.
+///
+/// This is the value of the `WORDS` parameter of `AtomicBitSet<bits, WORDS>`.
+#[inline]
+pub const fn bitset_words(bits: usize) -> usize {
+    bits.div_ceil(WORD_BITS)
+}
+
+// Checks at compile time that `WORDS` matches the number of bits.
+struct Words<const N: usize, const WORDS: usize>;
+
+impl<const N: usize, const WORDS: usize> Words<N, WORDS> {
+    const OK: () = if WORDS != bitset_words(N) {
+        core::panic!("`WORDS` must be `bitset_words(N)`")
+    };
+}
+
+/// A fixed-size bitmap of `N` bits, which can be modified concurrently.
+///
+/// The bits are stored in `WORDS` words of type `Atomic<usize>`, which must be
+/// `bitset_words(N)`: stable Rust can't compute the size of the array from `N`
+/// yet. Using any other value fails to compile.
+///
+/// Each operation on a single bit is a single atomic operation on its word.
+/// `find_first_zero_and_set` is linearizable: a bit is only ever granted to
+/// one caller until it is cleared again, which makes this suitable for slot
+/// allocators. Operations on the whole set, such as `count_ones` and `iter`,
+/// read the words one at a time and aren't a snapshot of the whole set.
+///
+/// # Panics
+///
+/// The methods taking a bit index panic if it is out of bounds.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{bitset_words, AtomicBitSet, Ordering};
+///
+/// type Slots = AtomicBitSet<100, { bitset_words(100) }>;
+///
+/// let slots = Slots::new();
+/// let a = slots.find_first_zero_and_set(Ordering::Acquire).unwrap();
+/// let b = slots.find_first_zero_and_set(Ordering::Acquire).unwrap();
+/// assert_eq!((a, b), (0, 1));
+/// slots.clear(a, Ordering::Release);
+/// assert_eq!(slots.find_first_zero_and_set(Ordering::Acquire), Some(0));
+/// assert_eq!(slots.count_ones(Ordering::Relaxed), 2);
+/// ```
+pub struct AtomicBitSet<const N: usize, const WORDS: usize> {
+    words: [Atomic<usize>; WORDS],
+}
+
+impl<const N: usize, const WORDS: usize> AtomicBitSet<N, WORDS> {
+    /// Creates a new `AtomicBitSet` with all bits cleared.
+    #[inline]
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Words::<N, WORDS>::OK;
+        AtomicBitSet {
+            words: Atomic::zeroed_array(),
+        }
+    }
+
+    // The bits of word `w` which are part of the set.
+    #[inline]
+    fn mask(w: usize) -> usize {
+        if w == WORDS - 1 && N % WORD_BITS != 0 {
+            (1 << (N % WORD_BITS)) - 1
+        } else {
+            !0
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn word(&self, i: usize) -> (&Atomic<usize>, usize) {
+        assert!(i < N, "bit index {} out of bounds for {} bits", i, N);
+        (&self.words[i / WORD_BITS], 1 << (i % WORD_BITS))
+    }
+
+    /// Sets bit `i`, returning its previous value.
+    #[inline]
+    #[track_caller]
+    pub fn set(&self, i: usize, order: Ordering) -> bool {
+        let (word, bit) = self.word(i);
+        word.fetch_or(bit, order) & bit != 0
+    }
+
+    /// Clears bit `i`, returning its previous value.
+    #[inline]
+    #[track_caller]
+    pub fn clear(&self, i: usize, order: Ordering) -> bool {
+        let (word, bit) = self.word(i);
+        word.fetch_and(!bit, order) & bit != 0
+    }
+
+    /// Returns the value of bit `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    #[track_caller]
+    pub fn test(&self, i: usize, order: Ordering) -> bool {
+        let (word, bit) = self.word(i);
+        word.load(order) & bit != 0
+    }
+
+    /// Sets the first cleared bit and returns its index, or returns `None` if
+    /// all bits are set.
+    ///
+    /// Each bit is granted to exactly one caller: the bit is set with a
+    /// `fetch_or`, and only the caller which changed it from 0 to 1 gets it.
+    /// `order` is the ordering of that `fetch_or`, usually `Acquire` to pair
+    /// with the `Release` in the `clear` which freed the bit.
+    pub fn find_first_zero_and_set(&self, order: Ordering) -> Option<usize> {
+        for (w, word) in self.words.iter().enumerate() {
+            let mut cur = word.load(Ordering::Relaxed);
+            loop {
+                let free = !cur & Self::mask(w);
+                if free == 0 {
+                    break;
+                }
+                let bit = 1 << free.trailing_zeros();
+                cur = word.fetch_or(bit, order);
+                if cur & bit == 0 {
+                    return Some(w * WORD_BITS + free.trailing_zeros() as usize);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of set bits.
+    ///
+    /// The words are loaded one at a time, so this isn't a snapshot if bits
+    /// are modified concurrently.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    pub fn count_ones(&self, order: Ordering) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.load(order).count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns an iterator over the indices of the set bits, in increasing
+    /// order.
+    ///
+    /// Each word is loaded when the iterator reaches it, so this isn't a
+    /// snapshot if bits are modified concurrently.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn iter(&self, order: Ordering) -> BitSetIter<'_, N, WORDS> {
+        BitSetIter {
+            set: self,
+            order,
+            w: 0,
+            bits: 0,
+        }
+    }
+}
+
+impl<const N: usize, const WORDS: usize> Default for AtomicBitSet<N, WORDS> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const WORDS: usize> fmt::Debug for AtomicBitSet<N, WORDS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter(Ordering::Relaxed)).finish()
+    }
+}
+
+/// An iterator over the set bits of an `AtomicBitSet`, returned by
+/// `AtomicBitSet::iter`.
+pub struct BitSetIter<'a, const N: usize, const WORDS: usize> {
+    set: &'a AtomicBitSet<N, WORDS>,
+    order: Ordering,
+    // The index of the next word to load.
+    w: usize,
+    // The remaining set bits of the previous word.
+    bits: usize,
+}
+
+impl<const N: usize, const WORDS: usize> Iterator for BitSetIter<'_, N, WORDS> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.bits == 0 {
+            if self.w == WORDS {
+                return None;
+            }
+            self.bits = self.set.words[self.w].load(self.order);
+            self.w += 1;
+        }
+        let bit = self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some((self.w - 1) * WORD_BITS + bit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use std::vec::Vec;
+
+    fn fill<const N: usize, const WORDS: usize>(set: &AtomicBitSet<N, WORDS>) {
+        for i in 0..N {
+            assert_eq!(set.find_first_zero_and_set(SeqCst), Some(i));
+        }
+        assert_eq!(set.find_first_zero_and_set(SeqCst), None);
+        assert_eq!(set.count_ones(SeqCst), N);
+        assert!(set.iter(SeqCst).eq(0..N));
+    }
+
+    #[test]
+    fn word_edges() {
+        const W: usize = WORD_BITS;
+
+        let set = AtomicBitSet::<{ 2 * W }, 2>::new();
+        assert!(!set.set(W - 1, SeqCst));
+        assert!(set.set(W - 1, SeqCst));
+        assert!(!set.set(W, SeqCst));
+        assert!(set.test(W - 1, SeqCst) && set.test(W, SeqCst));
+        assert!(!set.test(W + 1, SeqCst));
+        assert!(set.iter(SeqCst).eq([W - 1, W]));
+        assert!(set.clear(W - 1, SeqCst));
+        assert!(!set.clear(W - 1, SeqCst));
+        assert_eq!(set.count_ones(SeqCst), 1);
+        assert_eq!(format!("{:?}", set), format!("{{{}}}", W));
+
+        fill(&AtomicBitSet::<W, 1>::new());
+        fill(&AtomicBitSet::<{ 2 * W }, 2>::new());
+        fill(&AtomicBitSet::<0, 0>::new());
+    }
+
+    #[test]
+    fn partial_word() {
+        // The bits past `N` in the last word are never granted.
+        fill(&AtomicBitSet::<1, 1>::new());
+        fill(&AtomicBitSet::<{ WORD_BITS + 1 }, 2>::new());
+        fill(&AtomicBitSet::<100, { bitset_words(100) }>::new());
+
+        let set = AtomicBitSet::<100, { bitset_words(100) }>::new();
+        set.set(99, SeqCst);
+        assert_eq!(set.iter(SeqCst).collect::<Vec<_>>(), [99]);
+    }
+
+    #[test]
+    #[should_panic(expected = "bit index 100 out of bounds for 100 bits")]
+    fn out_of_bounds() {
+        AtomicBitSet::<100, { bitset_words(100) }>::new().set(100, SeqCst);
+    }
+
+    #[test]
+    fn contended_allocation() {
+        const N: usize = 150;
+        const THREADS: usize = 4;
+        const ROUNDS: usize = if cfg!(miri) { 20 } else { 20_000 };
+
+        let slots = AtomicBitSet::<N, { bitset_words(N) }>::new();
+        // Each slot records whether it is owned, to detect double grants.
+        let owned: [Atomic<bool>; N] = Atomic::zeroed_array();
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    let mut mine = Vec::new();
+                    for i in 0..ROUNDS {
+                        if i % 3 != 2 {
+                            if let Some(slot) = slots.find_first_zero_and_set(Acquire) {
+                                assert!(!owned[slot].swap(true, Relaxed), "slot granted twice");
+                                mine.push(slot);
+                            }
+                        } else if let Some(slot) = mine.pop() {
+                            assert!(owned[slot].swap(false, Relaxed));
+                            slots.clear(slot, Release);
+                        }
+                    }
+                    for slot in mine {
+                        assert!(owned[slot].swap(false, Relaxed));
+                        slots.clear(slot, Release);
+                    }
+                });
+            }
+        });
+        assert_eq!(slots.count_ones(SeqCst), 0);
+
+        // Exhaust the set concurrently: every slot is granted exactly once.
+        let granted: Vec<Vec<usize>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut mine = Vec::new();
+                        while let Some(slot) = slots.find_first_zero_and_set(Acquire) {
+                            mine.push(slot);
+                        }
+                        mine
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut all: Vec<usize> = granted.into_iter().flatten().collect();
+        all.sort_unstable();
+        assert!(all.into_iter().eq(0..N));
+    }
+}
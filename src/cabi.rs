@@ -0,0 +1,254 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! C functions performing the same atomic operations as `Atomic<T>`.
+//!
+//! These allow C code to access objects which are shared with Rust code using
+//! `Atomic<T>`. Objects which aren't lock-free are protected by the same
+//! fallback locks on both sides, so operations from C and Rust are atomic with
+//! respect to each other.
+//!
+//! The functions follow the generic `__atomic_*` functions of GCC's libatomic,
+//! with an additional `align` parameter after the size. Whether an object is
+//! lock-free depends on its alignment as well as its size, and this can't be
+//! deduced from the address: a `[u8; 4]` at an address which happens to be
+//! 4-byte aligned still uses a lock in Rust. `align` must be the alignment of
+//! the Rust type, which is `_Alignof` of the matching C type.
+//!
+//! Memory orders use the values of the `__ATOMIC_*` macros of GCC and Clang.
+//! Orders which are invalid for an operation are treated as `__ATOMIC_SEQ_CST`.
+//!
+//! ```c
+//! void rust_atomic_load(size_t size, size_t align, void *ptr, void *ret, int order);
+//! void rust_atomic_store(size_t size, size_t align, void *ptr, const void *val, int order);
+//! void rust_atomic_exchange(size_t size, size_t align, void *ptr, const void *val,
+//!                           void *ret, int order);
+//! bool rust_atomic_compare_exchange(size_t size, size_t align, void *ptr, void *expected,
+//!                                   const void *desired, int success, int failure);
+//! bool rust_atomic_is_lock_free(size_t size, size_t align);
+//! ```
+//!
+//! This requires the `cabi` feature.
+//!
+//! # Safety
+//!
+//! `ptr` must be valid for reads and writes of `size` bytes and aligned to
+//! `align`, and the object must only be accessed atomically while it is
+//! shared. The other buffers must be valid for `size` bytes, but don't need to
+//! be aligned.
+
+use core::ffi::{c_int, c_void};
+use core::ptr;
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+
+#[cfg(not(feature = "libatomic"))]
+use crate::fallback;
+#[cfg(feature = "libatomic")]
+use crate::libatomic as fallback;
+use crate::ops;
+
+// Types with each of the sizes which can be lock-free, aligned to their size.
+// Operations on them behave exactly like those on any other `Atomic<T>` with
+// the same size and alignment.
+macro_rules! aligned_bytes {
+    ($($name:ident $n:literal)*) => {$(
+        #[derive(Copy, Clone)]
+        #[repr(C, align($n))]
+        struct $name([u8; $n]);
+        unsafe impl NoUninit for $name {}
+    )*};
+}
+aligned_bytes!(Bytes1 1 Bytes2 2 Bytes4 4 Bytes8 8 Bytes16 16);
+
+// Evaluates `$native` with `$t` bound to the aligned type for the given size
+// and alignment, or `$locked` for objects which always use a lock.
+macro_rules! dispatch {
+    ($size:expr, $align:expr, |$t:ident| $native:expr, $locked:expr) => {
+        match ($size, $align >= $size) {
+            (1, true) => {
+                type $t = Bytes1;
+                $native
+            }
+            (2, true) => {
+                type $t = Bytes2;
+                $native
+            }
+            (4, true) => {
+                type $t = Bytes4;
+                $native
+            }
+            (8, true) => {
+                type $t = Bytes8;
+                $native
+            }
+            (16, true) => {
+                type $t = Bytes16;
+                $native
+            }
+            _ => $locked,
+        }
+    };
+}
+
+#[inline]
+fn order(order: c_int) -> Ordering {
+    match order {
+        0 => Ordering::Relaxed,
+        // Consume is treated as acquire, like in C compilers.
+        1 | 2 => Ordering::Acquire,
+        3 => Ordering::Release,
+        4 => Ordering::AcqRel,
+        _ => Ordering::SeqCst,
+    }
+}
+
+#[inline]
+fn load_order(o: c_int) -> Ordering {
+    match order(o) {
+        Ordering::Release | Ordering::AcqRel => Ordering::SeqCst,
+        o => o,
+    }
+}
+
+#[inline]
+fn store_order(o: c_int) -> Ordering {
+    match order(o) {
+        Ordering::Acquire | Ordering::AcqRel => Ordering::SeqCst,
+        o => o,
+    }
+}
+
+/// Atomically loads the object at `ptr` into `ret`.
+///
+/// # Safety
+///
+/// See the [module documentation](self#safety).
+#[no_mangle]
+pub unsafe extern "C" fn rust_atomic_load(
+    size: usize,
+    align: usize,
+    ptr: *mut c_void,
+    ret: *mut c_void,
+    order: c_int,
+) {
+    dispatch!(
+        size,
+        align,
+        |T| ptr::write_unaligned(
+            ret as *mut T,
+            ops::atomic_load(ptr as *mut T, load_order(order))
+        ),
+        fallback::atomic_load_raw(ptr as *mut u8, ret as *mut u8, size)
+    )
+}
+
+/// Atomically stores `val` into the object at `ptr`.
+///
+/// # Safety
+///
+/// See the [module documentation](self#safety).
+#[no_mangle]
+pub unsafe extern "C" fn rust_atomic_store(
+    size: usize,
+    align: usize,
+    ptr: *mut c_void,
+    val: *const c_void,
+    order: c_int,
+) {
+    dispatch!(
+        size,
+        align,
+        |T| ops::atomic_store(
+            ptr as *mut T,
+            ptr::read_unaligned(val as *const T),
+            store_order(order)
+        ),
+        fallback::atomic_store_raw(ptr as *mut u8, val as *const u8, size)
+    )
+}
+
+/// Atomically stores `val` into the object at `ptr`, and writes its previous
+/// value to `ret`.
+///
+/// # Safety
+///
+/// See the [module documentation](self#safety).
+#[no_mangle]
+pub unsafe extern "C" fn rust_atomic_exchange(
+    size: usize,
+    align: usize,
+    ptr: *mut c_void,
+    val: *const c_void,
+    ret: *mut c_void,
+    order: c_int,
+) {
+    dispatch!(
+        size,
+        align,
+        |T| ptr::write_unaligned(
+            ret as *mut T,
+            ops::atomic_swap(
+                ptr as *mut T,
+                ptr::read_unaligned(val as *const T),
+                self::order(order)
+            )
+        ),
+        fallback::atomic_swap_raw(ptr as *mut u8, val as *const u8, ret as *mut u8, size)
+    )
+}
+
+/// Stores `desired` into the object at `ptr` if its bytes are equal to those
+/// of `expected`.
+///
+/// Returns `true` on success. Otherwise the current value is written to
+/// `expected` and `false` is returned.
+///
+/// # Safety
+///
+/// See the [module documentation](self#safety).
+#[no_mangle]
+pub unsafe extern "C" fn rust_atomic_compare_exchange(
+    size: usize,
+    align: usize,
+    ptr: *mut c_void,
+    expected: *mut c_void,
+    desired: *const c_void,
+    success: c_int,
+    failure: c_int,
+) -> bool {
+    dispatch!(
+        size,
+        align,
+        |T| match ops::atomic_compare_exchange(
+            ptr as *mut T,
+            ptr::read_unaligned(expected as *const T),
+            ptr::read_unaligned(desired as *const T),
+            order(success),
+            load_order(failure),
+        ) {
+            Ok(_) => true,
+            Err(current) => {
+                ptr::write_unaligned(expected as *mut T, current);
+                false
+            }
+        },
+        fallback::atomic_compare_exchange_raw(
+            ptr as *mut u8,
+            expected as *mut u8,
+            desired as *const u8,
+            size
+        )
+    )
+}
+
+/// Returns whether objects with the given size and alignment are lock-free.
+#[no_mangle]
+pub extern "C" fn rust_atomic_is_lock_free(size: usize, align: usize) -> bool {
+    dispatch!(size, align, |T| ops::atomic_is_lock_free::<T>(), false)
+}
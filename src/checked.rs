@@ -0,0 +1,290 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::mem::{self, ManuallyDrop};
+use core::sync::atomic::Ordering;
+
+use bytemuck::checked::{CheckedBitPattern, CheckedCastError};
+use bytemuck::NoUninit;
+
+use crate::Atomic;
+
+// Used to reinterpret a value as its bits in a `const fn`. `CheckedBitPattern`
+// guarantees that both have the same layout.
+union Transmute<T: CheckedBitPattern> {
+    value: ManuallyDrop<T>,
+    bits: ManuallyDrop<T::Bits>,
+}
+
+#[inline]
+const fn to_bits<T: CheckedBitPattern>(v: T) -> T::Bits {
+    unsafe {
+        ManuallyDrop::into_inner(
+            Transmute {
+                value: ManuallyDrop::new(v),
+            }
+            .bits,
+        )
+    }
+}
+
+#[inline]
+fn from_bits<T: CheckedBitPattern>(bits: T::Bits) -> Result<T, CheckedCastError> {
+    if T::is_valid_bit_pattern(&bits) {
+        Ok(unsafe { mem::transmute_copy(&bits) })
+    } else {
+        Err(CheckedCastError::InvalidBitPattern)
+    }
+}
+
+#[inline]
+#[track_caller]
+fn expect_valid<T: CheckedBitPattern>(bits: T::Bits) -> T {
+    match from_bits(bits) {
+        Ok(v) => v,
+        Err(_) => panic!("`AtomicChecked` contains an invalid bit pattern"),
+    }
+}
+
+/// An atomic wrapper type for types with invalid bit patterns, which validates
+/// values when they are loaded.
+///
+/// `Atomic<T>` already supports fieldless enums, `char` and the `NonZero`
+/// types: values can only be stored through it as a `T`, so loads always
+/// return a valid value. `AtomicChecked<T>` is for memory which may also be
+/// written by code which doesn't uphold this, such as another process or C
+/// code. It stores the raw `T::Bits`, which can be accessed with `as_bits`,
+/// and checks that they are a valid `T` on every load.
+///
+/// `AtomicChecked<T>` has the same layout as `T`, and is lock-free whenever
+/// `Atomic<T::Bits>` is.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicChecked, Ordering};
+///
+/// #[derive(Copy, Clone, Debug, PartialEq, bytemuck::CheckedBitPattern)]
+/// #[repr(u8)]
+/// enum Phase {
+///     Init,
+///     Run,
+///     Done,
+/// }
+///
+/// static PHASE: AtomicChecked<Phase> = AtomicChecked::new(Phase::Init);
+///
+/// PHASE.store(Phase::Run, Ordering::Release);
+/// assert_eq!(PHASE.load(Ordering::Acquire), Phase::Run);
+///
+/// // Simulate a write from code which doesn't know about `Phase`.
+/// PHASE.as_bits().store(7, Ordering::Release);
+/// assert!(PHASE.load_checked(Ordering::Acquire).is_err());
+/// ```
+#[repr(transparent)]
+pub struct AtomicChecked<T: CheckedBitPattern>(Atomic<T::Bits>);
+
+impl<T: CheckedBitPattern> AtomicChecked<T> {
+    /// Creates a new `AtomicChecked`.
+    #[inline]
+    pub const fn new(v: T) -> AtomicChecked<T> {
+        AtomicChecked(Atomic::new(to_bits(v)))
+    }
+
+    /// Converts a reference to an `Atomic` holding raw bits into a reference
+    /// to an `AtomicChecked`.
+    ///
+    /// This can be combined with `Atomic::slice_from_bytes_mut` to validate
+    /// values in a shared memory mapping.
+    #[inline]
+    pub fn from_bits(bits: &Atomic<T::Bits>) -> &AtomicChecked<T> {
+        unsafe { &*(bits as *const Atomic<T::Bits> as *const AtomicChecked<T>) }
+    }
+
+    /// Returns the underlying `Atomic` holding the raw bits.
+    ///
+    /// Any bit pattern may be stored through it, and is reported by
+    /// `load_checked`.
+    #[inline]
+    pub fn as_bits(&self) -> &Atomic<T::Bits> {
+        &self.0
+    }
+}
+
+impl<T: CheckedBitPattern> AtomicChecked<T>
+where
+    T::Bits: NoUninit,
+{
+    /// Loads a value from the `AtomicChecked`, returning an error if its bits
+    /// aren't a valid `T`.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load_checked(&self, order: Ordering) -> Result<T, CheckedCastError> {
+        from_bits(self.0.load(order))
+    }
+
+    /// Loads a value from the `AtomicChecked`.
+    ///
+    /// See `Atomic::load`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bits aren't a valid `T`.
+    #[inline]
+    #[track_caller]
+    pub fn load(&self, order: Ordering) -> T {
+        expect_valid(self.0.load(order))
+    }
+
+    /// Stores a value into the `AtomicChecked`.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, val: T, order: Ordering) {
+        self.0.store(to_bits(val), order)
+    }
+
+    /// Stores a value into the `AtomicChecked`, returning the old value.
+    ///
+    /// See `Atomic::swap`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the old value isn't a valid `T`. The new value is stored
+    /// regardless.
+    #[inline]
+    #[track_caller]
+    pub fn swap(&self, val: T, order: Ordering) -> T {
+        expect_valid(self.0.swap(to_bits(val), order))
+    }
+
+    /// Stores a value into the `AtomicChecked` if the current value is the
+    /// same as the `current` value.
+    ///
+    /// See `Atomic::compare_exchange`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the comparison fails and the current value isn't a valid `T`.
+    #[inline]
+    #[track_caller]
+    pub fn compare_exchange(
+        &self,
+        current: T,
+        new: T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<T, T> {
+        match self
+            .0
+            .compare_exchange(to_bits(current), to_bits(new), success, failure)
+        {
+            Ok(bits) => Ok(expect_valid(bits)),
+            Err(bits) => Err(expect_valid(bits)),
+        }
+    }
+}
+
+impl<T: CheckedBitPattern + fmt::Debug> fmt::Debug for AtomicChecked<T>
+where
+    T::Bits: NoUninit + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = self.0.load(Ordering::Relaxed);
+        match from_bits::<T>(bits) {
+            Ok(v) => f.debug_tuple("AtomicChecked").field(&v).finish(),
+            Err(_) => f
+                .debug_struct("AtomicChecked")
+                .field("invalid_bits", &bits)
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use bytemuck::CheckedBitPattern;
+    use core::num::NonZeroU32;
+
+    #[derive(Copy, Clone, Debug, PartialEq, CheckedBitPattern)]
+    #[repr(u8)]
+    enum Phase {
+        Init,
+        Run,
+        Done,
+    }
+
+    #[test]
+    fn enum_bits() {
+        let a = AtomicChecked::new(Phase::Init);
+        assert_eq!(a.swap(Phase::Run, SeqCst), Phase::Init);
+        assert_eq!(
+            a.compare_exchange(Phase::Init, Phase::Done, SeqCst, SeqCst),
+            Err(Phase::Run)
+        );
+        assert_eq!(
+            a.compare_exchange(Phase::Run, Phase::Done, SeqCst, SeqCst),
+            Ok(Phase::Run)
+        );
+        assert_eq!(a.as_bits().load(SeqCst), 2);
+
+        a.as_bits().store(3, SeqCst);
+        assert_eq!(
+            a.load_checked(SeqCst),
+            Err(CheckedCastError::InvalidBitPattern)
+        );
+        assert_eq!(format!("{:?}", a), "AtomicChecked { invalid_bits: 3 }");
+        a.as_bits().store(1, SeqCst);
+        assert_eq!(a.load_checked(SeqCst), Ok(Phase::Run));
+        assert_eq!(format!("{:?}", a), "AtomicChecked(Run)");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid bit pattern")]
+    fn invalid_load_panics() {
+        let a = AtomicChecked::new(Phase::Done);
+        a.as_bits().store(0xff, SeqCst);
+        a.load(SeqCst);
+    }
+
+    #[test]
+    fn char_and_nonzero() {
+        let c = AtomicChecked::new('a');
+        c.store('é', SeqCst);
+        assert_eq!(c.load(SeqCst), 'é');
+        c.as_bits().store(0xd800, SeqCst);
+        assert!(c.load_checked(SeqCst).is_err());
+        c.as_bits().store(0x11_0000, SeqCst);
+        assert!(c.load_checked(SeqCst).is_err());
+
+        let n = AtomicChecked::new(NonZeroU32::new(5).unwrap());
+        n.as_bits().store(0, SeqCst);
+        assert!(n.load_checked(SeqCst).is_err());
+    }
+
+    #[test]
+    fn from_bits_ref() {
+        let mut bytes = [0u8, 1, 2, 9];
+        let bits = Atomic::<u8>::from_mut_slice(&mut bytes);
+        let phases: std::vec::Vec<_> = bits
+            .iter()
+            .map(|b| {
+                AtomicChecked::<Phase>::from_bits(b)
+                    .load_checked(Relaxed)
+                    .ok()
+            })
+            .collect();
+        assert_eq!(
+            phases,
+            [Some(Phase::Init), Some(Phase::Run), Some(Phase::Done), None]
+        );
+    }
+}
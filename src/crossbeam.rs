@@ -0,0 +1,83 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Conversions to and from `crossbeam_utils::atomic::AtomicCell`. Only by-value
+// conversions are provided: `AtomicCell<T>` makes no guarantee about its
+// layout, and uses its own lock table for types which aren't lock-free, so
+// viewing one type through a reference to the other would not be atomic.
+
+use crossbeam_utils::atomic::AtomicCell;
+
+use crate::Atomic;
+
+/// Converts an `AtomicCell` into an `Atomic` holding the same value.
+///
+/// The two types differ in how `compare_exchange` decides whether the current
+/// value matches: `AtomicCell` requires `T: Eq` and retries until the stored
+/// value is not equal to `current` according to `==`, while `Atomic` compares
+/// the bytes of the values. For types where equal values can have different
+/// representations, such as floats or types with a custom `PartialEq`, a
+/// `compare_exchange` that succeeds on one type may fail on the other.
+/// `Atomic` also takes explicit memory orderings where `AtomicCell` always uses
+/// `AcqRel`.
+///
+/// This requires the `crossbeam` feature.
+impl<T> From<AtomicCell<T>> for Atomic<T> {
+    #[inline]
+    fn from(v: AtomicCell<T>) -> Self {
+        Atomic::new(v.into_inner())
+    }
+}
+
+/// Converts an `Atomic` into an `AtomicCell` holding the same value.
+///
+/// See the conversion in the other direction for the differences between the
+/// two types.
+///
+/// This requires the `crossbeam` feature.
+impl<T> From<Atomic<T>> for AtomicCell<T> {
+    #[inline]
+    fn from(v: Atomic<T>) -> Self {
+        AtomicCell::new(v.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+
+    // Contains a padding byte after `a`.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[repr(C)]
+    struct Padded {
+        a: u8,
+        b: u16,
+    }
+
+    #[test]
+    fn round_trip() {
+        let cell = AtomicCell::new(0x1234_5678_9abc_def0u64);
+        let a: Atomic<u64> = Atomic::from(cell);
+        assert_eq!(a.fetch_add(1, SeqCst), 0x1234_5678_9abc_def0);
+        let cell: AtomicCell<u64> = a.into();
+        assert_eq!(cell.load(), 0x1234_5678_9abc_def1);
+    }
+
+    #[test]
+    fn round_trip_padded() {
+        let v = Padded { a: 1, b: 0xbeef };
+        let cell: AtomicCell<Padded> = Atomic::new(v).into();
+        assert_eq!(cell.load(), v);
+        let a: Atomic<Padded> = cell.into();
+        assert_eq!(AtomicCell::<Padded>::from(a).into_inner(), v);
+
+        let v = [Padded { a: 3, b: 4 }; 5];
+        let a: Atomic<[Padded; 5]> = Atomic::from(AtomicCell::new(v));
+        assert_eq!(a.into_inner(), v);
+    }
+}
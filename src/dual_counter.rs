@@ -0,0 +1,221 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use crate::Atomic;
+
+const HIGH_SHIFT: u32 = 32;
+
+#[inline]
+const fn join(low: u32, high: u32) -> u64 {
+    (high as u64) << HIGH_SHIFT | low as u64
+}
+
+#[inline]
+const fn split(v: u64) -> (u32, u32) {
+    (v as u32, (v >> HIGH_SHIFT) as u32)
+}
+
+/// Two `u32` counters in a single `Atomic<u64>`, each incremented with a
+/// single `fetch_add`.
+///
+/// The low counter is stored in the low 32 bits and the high counter in the
+/// high 32 bits, so `incr_low(n)` adds `n` and `incr_high(n)` adds
+/// `n << 32`. This is cheaper than a compare-and-swap loop, and `load`
+/// returns both counters as of the same instant, which is useful for
+/// statistics such as the number of enqueued and dequeued items.
+///
+/// # Carries
+///
+/// The counters aren't independent when they overflow: if the low counter
+/// wraps around, the carry increments the high counter by one. A carry out
+/// of the high counter is lost, so the high counter just wraps around.
+/// Both increments return the previous counters, so a carry out of the low
+/// counter can be detected with `prev.0.checked_add(n).is_none()`.
+///
+/// To avoid carries, read the counters with `split_reset` often enough that
+/// neither counter can overflow between two calls. Each increment happens
+/// either before or after the reset, so the totals read this way are exact.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicDualCounter, Ordering};
+///
+/// // Enqueued and dequeued items.
+/// let stats = AtomicDualCounter::new(0, 0);
+/// stats.incr_low(3, Ordering::Relaxed);
+/// stats.incr_high(2, Ordering::Relaxed);
+/// assert_eq!(stats.load(Ordering::Relaxed), (3, 2));
+/// assert_eq!(stats.split_reset(Ordering::Relaxed), (3, 2));
+/// assert_eq!(stats.load(Ordering::Relaxed), (0, 0));
+/// ```
+#[repr(transparent)]
+pub struct AtomicDualCounter(Atomic<u64>);
+
+impl AtomicDualCounter {
+    /// Creates a new `AtomicDualCounter`.
+    #[inline]
+    pub const fn new(low: u32, high: u32) -> AtomicDualCounter {
+        AtomicDualCounter(Atomic::new(join(low, high)))
+    }
+
+    /// Checks if operations on an `AtomicDualCounter` are lock-free on the
+    /// current target.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        Atomic::<u64>::is_lock_free()
+    }
+
+    /// Consumes the `AtomicDualCounter` and returns the counters.
+    #[inline]
+    pub fn into_inner(self) -> (u32, u32) {
+        split(self.0.into_inner())
+    }
+
+    /// Loads both counters, as `(low, high)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> (u32, u32) {
+        split(self.0.load(order))
+    }
+
+    /// Adds `n` to the low counter, returning the previous counters.
+    ///
+    /// If the low counter wraps around, the high counter is incremented by
+    /// one; see the type's documentation.
+    #[inline]
+    pub fn incr_low(&self, n: u32, order: Ordering) -> (u32, u32) {
+        split(self.0.fetch_add(n as u64, order))
+    }
+
+    /// Adds `n` to the high counter, returning the previous counters.
+    ///
+    /// The high counter wraps around on overflow, and the low counter is
+    /// never changed.
+    #[inline]
+    pub fn incr_high(&self, n: u32, order: Ordering) -> (u32, u32) {
+        split(self.0.fetch_add((n as u64) << HIGH_SHIFT, order))
+    }
+
+    /// Resets both counters to zero, returning the previous counters.
+    #[inline]
+    pub fn split_reset(&self, order: Ordering) -> (u32, u32) {
+        split(self.0.swap(0, order))
+    }
+}
+
+impl Default for AtomicDualCounter {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl fmt::Debug for AtomicDualCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicDualCounter")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use core::sync::atomic::AtomicBool;
+
+    #[test]
+    fn near_overflow() {
+        const MAX: u32 = u32::MAX;
+
+        let c = AtomicDualCounter::new(MAX - 1, 7);
+        assert_eq!(c.incr_low(1, SeqCst), (MAX - 1, 7));
+        // The low counter wraps around and carries into the high counter.
+        assert_eq!(c.incr_low(3, SeqCst), (MAX, 7));
+        assert_eq!(c.load(SeqCst), (2, 8));
+
+        let c = AtomicDualCounter::new(5, MAX - 1);
+        assert_eq!(c.incr_high(1, SeqCst), (5, MAX - 1));
+        // The high counter wraps around and the low counter is unchanged.
+        assert_eq!(c.incr_high(2, SeqCst), (5, MAX));
+        assert_eq!(c.load(SeqCst), (5, 1));
+
+        // Both at once: the carry out of the low counter wraps the high one.
+        let c = AtomicDualCounter::new(MAX, MAX);
+        let prev = c.incr_low(1, SeqCst);
+        assert!(prev.0.checked_add(1).is_none());
+        assert_eq!(c.into_inner(), (0, 0));
+    }
+
+    #[test]
+    fn operations() {
+        let c = AtomicDualCounter::default();
+        assert_eq!(
+            AtomicDualCounter::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        c.incr_low(10, SeqCst);
+        c.incr_high(20, SeqCst);
+        assert_eq!(format!("{:?}", c), "AtomicDualCounter((10, 20))");
+        assert_eq!(c.split_reset(SeqCst), (10, 20));
+        assert_eq!(c.incr_high(1, SeqCst), (0, 0));
+        assert_eq!(c.into_inner(), (0, 1));
+    }
+
+    #[test]
+    fn concurrent_split_reset() {
+        const THREADS: u32 = 4;
+        const ROUNDS: u32 = if cfg!(miri) { 50 } else { 100_000 };
+
+        // A reader drains the counters while they are incremented; no
+        // increment is lost or counted twice.
+        let c = AtomicDualCounter::new(0, 0);
+        let done = AtomicBool::new(false);
+        let (mut low, mut high) = std::thread::scope(|s| {
+            let reader = s.spawn(|| {
+                let (mut low, mut high) = (0u64, 0u64);
+                while !done.load(Acquire) {
+                    let (l, h) = c.split_reset(AcqRel);
+                    low += l as u64;
+                    high += h as u64;
+                }
+                (low, high)
+            });
+            let writers: std::vec::Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let c = &c;
+                    s.spawn(move || {
+                        for i in 0..ROUNDS {
+                            if (i + t) % 2 == 0 {
+                                c.incr_low(1, Relaxed);
+                            } else {
+                                c.incr_high(3, Relaxed);
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for w in writers {
+                w.join().unwrap();
+            }
+            done.store(true, Release);
+            reader.join().unwrap()
+        });
+        let (l, h) = c.into_inner();
+        low += l as u64;
+        high += h as u64;
+        assert_eq!(low, (THREADS * ROUNDS / 2) as u64);
+        assert_eq!(high, (THREADS * ROUNDS / 2 * 3) as u64);
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+use core::time::Duration;
+
+use crate::Atomic;
+
+#[inline]
+const fn to_nanos(duration: Duration) -> u64 {
+    let nanos = duration.as_nanos();
+    if nanos > u64::MAX as u128 {
+        u64::MAX
+    } else {
+        nanos as u64
+    }
+}
+
+#[inline]
+const fn from_nanos(nanos: u64) -> Duration {
+    Duration::from_nanos(nanos)
+}
+
+/// A `Duration` which can be shared between threads, such as a timeout which
+/// can be reconfigured at runtime.
+///
+/// A `Duration` is a `u64` of seconds and a `u32` of nanoseconds, which must
+/// be less than one billion. Storing it in an `Atomic<Duration>` would need
+/// 128-bit atomics, and a torn load could break that invariant. Instead, the
+/// duration is stored as a total number of nanoseconds in a single
+/// `Atomic<u64>`, and converted back to a `Duration` when it is loaded.
+///
+/// A `u64` of nanoseconds covers about 584 years. Longer durations are
+/// stored as `Duration::from_nanos(u64::MAX)`, and all arithmetic saturates at
+/// that value and at zero instead of overflowing.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicDuration, Ordering};
+/// use std::time::Duration;
+///
+/// static TIMEOUT: AtomicDuration = AtomicDuration::new(Duration::from_secs(30));
+///
+/// // Back off after a failure, but never wait for more than a minute.
+/// TIMEOUT.fetch_add(Duration::from_secs(45), Ordering::Relaxed);
+/// TIMEOUT.fetch_min(Duration::from_secs(60), Ordering::Relaxed);
+/// assert_eq!(TIMEOUT.load(Ordering::Relaxed), Duration::from_secs(60));
+/// ```
+#[repr(transparent)]
+pub struct AtomicDuration(Atomic<u64>);
+
+impl AtomicDuration {
+    /// Creates a new `AtomicDuration`.
+    #[inline]
+    pub const fn new(duration: Duration) -> AtomicDuration {
+        AtomicDuration(Atomic::new(to_nanos(duration)))
+    }
+
+    /// Returns `true` if operations on values of this type are lock-free.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        Atomic::<u64>::is_lock_free()
+    }
+
+    /// Consumes the `AtomicDuration` and returns the duration.
+    #[inline]
+    pub fn into_inner(self) -> Duration {
+        from_nanos(self.0.into_inner())
+    }
+
+    /// Loads the duration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Duration {
+        from_nanos(self.0.load(order))
+    }
+
+    /// Stores a duration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Acquire` or `AcqRel`.
+    #[inline]
+    pub fn store(&self, duration: Duration, order: Ordering) {
+        self.0.store(to_nanos(duration), order)
+    }
+
+    /// Stores a duration, returning the previous duration.
+    #[inline]
+    pub fn swap(&self, duration: Duration, order: Ordering) -> Duration {
+        from_nanos(self.0.swap(to_nanos(duration), order))
+    }
+
+    /// Adds to the duration, saturating at the largest duration which can
+    /// be stored, and returns the previous duration.
+    #[inline]
+    pub fn fetch_add(&self, duration: Duration, order: Ordering) -> Duration {
+        let val = to_nanos(duration);
+        let prev = self
+            .0
+            .fetch_update(order, Ordering::Relaxed, |x| Some(x.saturating_add(val)));
+        from_nanos(prev.unwrap_or_else(|x| x))
+    }
+
+    /// Subtracts from the duration, saturating at zero, and returns the
+    /// previous duration.
+    #[inline]
+    pub fn fetch_saturating_sub(&self, duration: Duration, order: Ordering) -> Duration {
+        let val = to_nanos(duration);
+        let prev = self
+            .0
+            .fetch_update(order, Ordering::Relaxed, |x| Some(x.saturating_sub(val)));
+        from_nanos(prev.unwrap_or_else(|x| x))
+    }
+
+    /// Stores the minimum of the duration and `duration`, returning the
+    /// previous duration.
+    #[inline]
+    pub fn fetch_min(&self, duration: Duration, order: Ordering) -> Duration {
+        from_nanos(self.0.fetch_min(to_nanos(duration), order))
+    }
+
+    /// Stores the maximum of the duration and `duration`, returning the
+    /// previous duration.
+    #[inline]
+    pub fn fetch_max(&self, duration: Duration, order: Ordering) -> Duration {
+        from_nanos(self.0.fetch_max(to_nanos(duration), order))
+    }
+}
+
+impl Default for AtomicDuration {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Duration::ZERO)
+    }
+}
+
+impl From<Duration> for AtomicDuration {
+    #[inline]
+    fn from(duration: Duration) -> Self {
+        Self::new(duration)
+    }
+}
+
+impl fmt::Debug for AtomicDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicDuration")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+
+    const MAX: Duration = Duration::from_nanos(u64::MAX);
+
+    #[test]
+    fn operations() {
+        let d = AtomicDuration::new(Duration::from_millis(1500));
+        assert_eq!(d.load(SeqCst), Duration::from_millis(1500));
+        assert_eq!(
+            d.fetch_add(Duration::from_nanos(1), SeqCst),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(d.load(SeqCst), Duration::new(1, 500_000_001));
+        d.fetch_saturating_sub(Duration::from_secs(1), SeqCst);
+        assert_eq!(
+            d.fetch_max(Duration::from_secs(2), SeqCst),
+            Duration::new(0, 500_000_001)
+        );
+        assert_eq!(
+            d.fetch_min(Duration::from_secs(1), SeqCst),
+            Duration::from_secs(2)
+        );
+        assert_eq!(d.swap(Duration::ZERO, SeqCst), Duration::from_secs(1));
+        assert_eq!(format!("{:?}", d), "AtomicDuration(0ns)");
+        assert_eq!(AtomicDuration::default().into_inner(), Duration::ZERO);
+    }
+
+    #[test]
+    fn saturation() {
+        let d = AtomicDuration::from(Duration::MAX);
+        assert_eq!(d.load(SeqCst), MAX);
+        d.store(Duration::from_secs(1), SeqCst);
+        d.fetch_add(Duration::MAX, SeqCst);
+        assert_eq!(d.load(SeqCst), MAX);
+        d.fetch_add(Duration::from_secs(1), SeqCst);
+        assert_eq!(d.load(SeqCst), MAX);
+        d.fetch_saturating_sub(Duration::from_secs(1), SeqCst);
+        assert_eq!(d.load(SeqCst), MAX - Duration::from_secs(1));
+        d.store(Duration::from_secs(1), SeqCst);
+        assert_eq!(
+            d.fetch_saturating_sub(Duration::from_secs(2), SeqCst),
+            Duration::from_secs(1)
+        );
+        assert_eq!(d.load(SeqCst), Duration::ZERO);
+        d.fetch_max(Duration::MAX, SeqCst);
+        assert_eq!(d.into_inner(), MAX);
+    }
+
+    #[test]
+    fn concurrent_add_sub() {
+        const THREADS: u64 = 4;
+        let rounds = if cfg!(miri) { 50 } else { 10_000 };
+
+        // Every thread adds and subtracts the same amounts, and the value
+        // never gets close to zero, so the subtractions never saturate.
+        let start = Duration::from_secs(1);
+        let d = AtomicDuration::new(start);
+        std::thread::scope(|s| {
+            for t in 1..=THREADS {
+                let d = &d;
+                s.spawn(move || {
+                    let step = Duration::from_nanos(t * 7);
+                    for _ in 0..rounds {
+                        d.fetch_add(step, Relaxed);
+                        d.fetch_saturating_sub(step, Relaxed);
+                        d.fetch_add(step, Relaxed);
+                    }
+                });
+            }
+        });
+        let added: u64 = (1..=THREADS).map(|t| t * 7 * rounds).sum();
+        assert_eq!(d.load(SeqCst), start + Duration::from_nanos(added));
+    }
+}
@@ -0,0 +1,379 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::convert::TryFrom;
+use core::sync::atomic::Ordering;
+
+use crate::Atomic;
+
+/// The type of the value held by a `DynAtomic`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValueKind {
+    /// `bool`
+    Bool,
+    /// `u8`
+    U8,
+    /// `u16`
+    U16,
+    /// `u32`
+    U32,
+    /// `u64`
+    U64,
+    /// `usize`
+    Usize,
+    /// `i8`
+    I8,
+    /// `i16`
+    I16,
+    /// `i32`
+    I32,
+    /// `i64`
+    I64,
+    /// `isize`
+    Isize,
+    /// `f32`
+    F32,
+    /// `f64`
+    F64,
+}
+
+impl ValueKind {
+    /// Returns `true` for `bool` and the unsigned integer types.
+    #[inline]
+    pub fn is_unsigned(self) -> bool {
+        matches!(
+            self,
+            ValueKind::Bool
+                | ValueKind::U8
+                | ValueKind::U16
+                | ValueKind::U32
+                | ValueKind::U64
+                | ValueKind::Usize
+        )
+    }
+
+    /// Returns `true` for the signed integer types.
+    #[inline]
+    pub fn is_signed(self) -> bool {
+        matches!(
+            self,
+            ValueKind::I8 | ValueKind::I16 | ValueKind::I32 | ValueKind::I64 | ValueKind::Isize
+        )
+    }
+
+    /// Returns `true` for the floating-point types.
+    #[inline]
+    pub fn is_float(self) -> bool {
+        matches!(self, ValueKind::F32 | ValueKind::F64)
+    }
+}
+
+/// A type-erased view of an `Atomic` holding a primitive scalar.
+///
+/// This trait is object-safe, so atomics of different types can be accessed
+/// uniformly through `&dyn DynAtomic`, for example in a registry of metrics.
+/// Values are converted to and from the widest type of their kind: `u64` for
+/// `bool` and unsigned integers, `i64` for signed integers and `f64` for
+/// floating-point numbers.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, DynAtomic, Ordering};
+///
+/// let requests = Atomic::new(3u32);
+/// let latency = Atomic::new(0.25f64);
+/// let metrics: [(&str, &dyn DynAtomic); 2] = [("requests", &requests), ("latency", &latency)];
+///
+/// for (name, metric) in &metrics {
+///     if let Some(v) = metric.load_u64(Ordering::Relaxed) {
+///         println!("{}: {}", name, v);
+///     } else if let Some(v) = metric.load_f64(Ordering::Relaxed) {
+///         println!("{}: {}", name, v);
+///     }
+/// }
+/// ```
+pub trait DynAtomic {
+    /// Returns the type of the value held by the atomic.
+    fn value_kind(&self) -> ValueKind;
+
+    /// Loads the value if it is a `bool` or an unsigned integer.
+    fn load_u64(&self, order: Ordering) -> Option<u64>;
+
+    /// Loads the value if it is a signed integer.
+    fn load_i64(&self, order: Ordering) -> Option<i64>;
+
+    /// Loads the value if it is a floating-point number.
+    fn load_f64(&self, order: Ordering) -> Option<f64>;
+
+    /// Stores a value if the atomic holds a `bool` or an unsigned integer
+    /// which can represent `val`.
+    ///
+    /// Returns `false` without storing anything otherwise.
+    fn store_u64(&self, val: u64, order: Ordering) -> bool;
+
+    /// Stores a value if the atomic holds a signed integer which can represent
+    /// `val`.
+    ///
+    /// Returns `false` without storing anything otherwise.
+    fn store_i64(&self, val: i64, order: Ordering) -> bool;
+
+    /// Stores a value if the atomic holds a floating-point number. The value
+    /// is rounded if the atomic holds an `f32`.
+    ///
+    /// Returns `false` without storing anything otherwise.
+    fn store_f64(&self, val: f64, order: Ordering) -> bool;
+}
+
+impl DynAtomic for Atomic<bool> {
+    #[inline]
+    fn value_kind(&self) -> ValueKind {
+        ValueKind::Bool
+    }
+    #[inline]
+    fn load_u64(&self, order: Ordering) -> Option<u64> {
+        Some(self.load(order) as u64)
+    }
+    #[inline]
+    fn load_i64(&self, _: Ordering) -> Option<i64> {
+        None
+    }
+    #[inline]
+    fn load_f64(&self, _: Ordering) -> Option<f64> {
+        None
+    }
+    #[inline]
+    fn store_u64(&self, val: u64, order: Ordering) -> bool {
+        match val {
+            0 | 1 => {
+                self.store(val == 1, order);
+                true
+            }
+            _ => false,
+        }
+    }
+    #[inline]
+    fn store_i64(&self, _: i64, _: Ordering) -> bool {
+        false
+    }
+    #[inline]
+    fn store_f64(&self, _: f64, _: Ordering) -> bool {
+        false
+    }
+}
+
+macro_rules! dyn_unsigned {
+    ($($t:ident $kind:ident)*) => ($(
+        impl DynAtomic for Atomic<$t> {
+            #[inline]
+            fn value_kind(&self) -> ValueKind {
+                ValueKind::$kind
+            }
+            #[inline]
+            fn load_u64(&self, order: Ordering) -> Option<u64> {
+                u64::try_from(self.load(order)).ok()
+            }
+            #[inline]
+            fn load_i64(&self, _: Ordering) -> Option<i64> {
+                None
+            }
+            #[inline]
+            fn load_f64(&self, _: Ordering) -> Option<f64> {
+                None
+            }
+            #[inline]
+            fn store_u64(&self, val: u64, order: Ordering) -> bool {
+                match $t::try_from(val) {
+                    Ok(val) => {
+                        self.store(val, order);
+                        true
+                    }
+                    Err(_) => false,
+                }
+            }
+            #[inline]
+            fn store_i64(&self, _: i64, _: Ordering) -> bool {
+                false
+            }
+            #[inline]
+            fn store_f64(&self, _: f64, _: Ordering) -> bool {
+                false
+            }
+        }
+    )*);
+}
+
+macro_rules! dyn_signed {
+    ($($t:ident $kind:ident)*) => ($(
+        impl DynAtomic for Atomic<$t> {
+            #[inline]
+            fn value_kind(&self) -> ValueKind {
+                ValueKind::$kind
+            }
+            #[inline]
+            fn load_u64(&self, _: Ordering) -> Option<u64> {
+                None
+            }
+            #[inline]
+            fn load_i64(&self, order: Ordering) -> Option<i64> {
+                i64::try_from(self.load(order)).ok()
+            }
+            #[inline]
+            fn load_f64(&self, _: Ordering) -> Option<f64> {
+                None
+            }
+            #[inline]
+            fn store_u64(&self, _: u64, _: Ordering) -> bool {
+                false
+            }
+            #[inline]
+            fn store_i64(&self, val: i64, order: Ordering) -> bool {
+                match $t::try_from(val) {
+                    Ok(val) => {
+                        self.store(val, order);
+                        true
+                    }
+                    Err(_) => false,
+                }
+            }
+            #[inline]
+            fn store_f64(&self, _: f64, _: Ordering) -> bool {
+                false
+            }
+        }
+    )*);
+}
+
+macro_rules! dyn_float {
+    ($($t:ident $kind:ident)*) => ($(
+        impl DynAtomic for Atomic<$t> {
+            #[inline]
+            fn value_kind(&self) -> ValueKind {
+                ValueKind::$kind
+            }
+            #[inline]
+            fn load_u64(&self, _: Ordering) -> Option<u64> {
+                None
+            }
+            #[inline]
+            fn load_i64(&self, _: Ordering) -> Option<i64> {
+                None
+            }
+            #[inline]
+            fn load_f64(&self, order: Ordering) -> Option<f64> {
+                Some(self.load(order) as f64)
+            }
+            #[inline]
+            fn store_u64(&self, _: u64, _: Ordering) -> bool {
+                false
+            }
+            #[inline]
+            fn store_i64(&self, _: i64, _: Ordering) -> bool {
+                false
+            }
+            #[inline]
+            fn store_f64(&self, val: f64, order: Ordering) -> bool {
+                self.store(val as $t, order);
+                true
+            }
+        }
+    )*);
+}
+
+dyn_unsigned! { u8 U8 u16 U16 u32 U32 u64 U64 usize Usize }
+dyn_signed! { i8 I8 i16 I16 i32 I32 i64 I64 isize Isize }
+dyn_float! { f32 F32 f64 F64 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DynAtomic, ValueKind};
+    use crate::{Atomic, Ordering::*};
+    use std::vec::Vec;
+
+    #[test]
+    fn dyn_registry() {
+        let hits = Atomic::new(10u32);
+        let bytes = Atomic::new(u64::MAX);
+        let delta = Atomic::new(-3i16);
+        let ratio = Atomic::new(0.5f64);
+        let load = Atomic::new(1.5f32);
+        let up = Atomic::new(true);
+        let registry: Vec<&dyn DynAtomic> = vec![&hits, &bytes, &delta, &ratio, &load, &up];
+
+        let kinds: Vec<ValueKind> = registry.iter().map(|a| a.value_kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                ValueKind::U32,
+                ValueKind::U64,
+                ValueKind::I16,
+                ValueKind::F64,
+                ValueKind::F32,
+                ValueKind::Bool
+            ]
+        );
+        let snapshot: Vec<_> = registry
+            .iter()
+            .map(|a| {
+                (
+                    a.load_u64(Relaxed),
+                    a.load_i64(Relaxed),
+                    a.load_f64(Relaxed),
+                )
+            })
+            .collect();
+        assert_eq!(
+            snapshot,
+            [
+                (Some(10), None, None),
+                (Some(u64::MAX), None, None),
+                (None, Some(-3), None),
+                (None, None, Some(0.5)),
+                (None, None, Some(1.5)),
+                (Some(1), None, None),
+            ]
+        );
+        for a in &registry {
+            assert_eq!(a.value_kind().is_unsigned(), a.load_u64(Relaxed).is_some());
+            assert_eq!(a.value_kind().is_signed(), a.load_i64(Relaxed).is_some());
+            assert_eq!(a.value_kind().is_float(), a.load_f64(Relaxed).is_some());
+        }
+    }
+
+    #[test]
+    fn dyn_store() {
+        let a = Atomic::new(0u8);
+        let b = Atomic::new(0i32);
+        let c = Atomic::new(0f32);
+        let d = Atomic::new(false);
+        let (a, b, c, d): (
+            &dyn DynAtomic,
+            &dyn DynAtomic,
+            &dyn DynAtomic,
+            &dyn DynAtomic,
+        ) = (&a, &b, &c, &d);
+
+        assert!(a.store_u64(200, Relaxed));
+        assert!(!a.store_u64(256, Relaxed));
+        assert!(!a.store_i64(1, Relaxed));
+        assert_eq!(a.load_u64(Relaxed), Some(200));
+
+        assert!(b.store_i64(-7, Relaxed));
+        assert!(!b.store_i64(i64::MIN, Relaxed));
+        assert!(!b.store_f64(1.0, Relaxed));
+        assert_eq!(b.load_i64(Relaxed), Some(-7));
+
+        assert!(c.store_f64(2.25, Relaxed));
+        assert!(!c.store_u64(2, Relaxed));
+        assert_eq!(c.load_f64(Relaxed), Some(2.25));
+
+        assert!(d.store_u64(1, Relaxed));
+        assert!(!d.store_u64(2, Relaxed));
+        assert_eq!(d.load_u64(Relaxed), Some(1));
+    }
+}
@@ -6,42 +6,80 @@
 // copied, modified, or distributed except according to those terms.
 
 use core::cmp;
-use core::hint;
+#[cfg(not(atomic_loom))]
+use core::mem::MaybeUninit;
 use core::num::Wrapping;
 use core::ops;
 use core::ptr;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::Ordering;
+#[cfg(not(atomic_loom))]
+use core::sync::atomic::{fence, AtomicUsize};
 
 use bytemuck::NoUninit;
+#[cfg(atomic_loom)]
+use loom::sync::atomic::{fence, AtomicUsize};
+
+#[cfg(not(atomic_loom))]
+use crate::ops::locked_write as write;
+use crate::{Backoff, CachePadded};
 
 // We use an AtomicUsize instead of an AtomicBool because it performs better
 // on architectures that don't have byte-sized atomics.
 //
-// We give each spinlock its own cache line to avoid false sharing.
-#[repr(align(64))]
+// The lock word doubles as a sequence number: it is incremented both when the
+// lock is acquired and when it is released, so it is odd while the lock is
+// held. This allows readers to validate an optimistic read without taking the
+// lock.
 struct SpinLock(AtomicUsize);
 
+#[inline]
+fn spin(backoff: &Backoff) {
+    #[cfg(not(atomic_loom))]
+    backoff.snooze();
+    #[cfg(atomic_loom)]
+    {
+        let _ = backoff;
+        loom::thread::yield_now();
+    }
+}
+
 impl SpinLock {
     fn lock(&self) {
-        while self
-            .0
-            .compare_exchange_weak(0, 1, Ordering::Acquire, Ordering::Relaxed)
-            .is_err()
-        {
-            while self.0.load(Ordering::Relaxed) != 0 {
-                hint::spin_loop();
+        let backoff = Backoff::new();
+        let mut seq = self.0.load(Ordering::Relaxed);
+        loop {
+            if seq & 1 == 0 {
+                match self.0.compare_exchange_weak(
+                    seq,
+                    seq.wrapping_add(1),
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // Optimistic readers which see any of the following
+                        // writes must also see the odd sequence number.
+                        fence(Ordering::Release);
+                        return;
+                    }
+                    Err(x) => seq = x,
+                }
+            } else {
+                spin(&backoff);
+                seq = self.0.load(Ordering::Relaxed);
             }
         }
     }
 
     fn unlock(&self) {
-        self.0.store(0, Ordering::Release);
+        self.0.fetch_add(1, Ordering::Release);
     }
 }
 
 // A big array of spinlocks which we use to guard atomic accesses. A spinlock is
 // chosen based on a hash of the address of the atomic object, which helps to
-// reduce contention compared to a single global lock.
+// reduce contention compared to a single global lock. We give each spinlock its
+// own cache line to avoid false sharing.
+#[cfg(not(atomic_loom))]
 macro_rules! array {
     (@accum (0, $($_es:expr),*) -> ($($body:tt)*))
         => {array!(@as_expr [$($body)*])};
@@ -64,11 +102,21 @@ macro_rules! array {
 
     [$e:expr; $n:tt] => { array!(@accum ($n, $e) -> ()) };
 }
-static SPINLOCKS: [SpinLock; 64] = array![SpinLock(AtomicUsize::new(0)); 64];
+#[cfg(not(atomic_loom))]
+static SPINLOCKS: [CachePadded<SpinLock>; 64] =
+    array![CachePadded::new(SpinLock(AtomicUsize::new(0))); 64];
+
+// loom atomics can't be created in a static, and must be created again for
+// every execution of a model.
+#[cfg(atomic_loom)]
+loom::lazy_static! {
+    static ref SPINLOCKS: [CachePadded<SpinLock>; 64] =
+        core::array::from_fn(|_| CachePadded::new(SpinLock(AtomicUsize::new(0))));
+}
 
 // Spinlock pointer hashing function from compiler-rt
 #[inline]
-fn lock_for_addr(addr: usize) -> &'static SpinLock {
+pub fn lock_index(addr: usize) -> usize {
     // Disregard the lowest 4 bits.  We want all values that may be part of the
     // same memory operation to hash to the same value and therefore use the same
     // lock.
@@ -79,41 +127,248 @@ fn lock_for_addr(addr: usize) -> &'static SpinLock {
     // get collisions from atomic fields in a single object
     hash >>= 16;
     hash ^= low;
-    // Return a pointer to the lock to use
-    &SPINLOCKS[hash & (SPINLOCKS.len() - 1)]
+    // Return the index of the lock to use
+    hash & (SPINLOCKS.len() - 1)
+}
+
+#[inline]
+fn lock_for_addr(addr: usize) -> &'static SpinLock {
+    &SPINLOCKS[lock_index(addr)]
 }
 
+// Panics when the fallback is used with the `debug-detect-fallback` feature,
+// which helps to catch types which have unintentionally stopped being
+// lock-free, for example after adding a field. This compiles to nothing
+// otherwise.
 #[inline]
-fn lock(addr: usize) -> LockGuard {
+fn detect_fallback<T>() {
+    #[cfg(all(feature = "debug-detect-fallback", debug_assertions))]
+    panic!(
+        "`Atomic<{}>` is not lock-free and is using the fallback implementation",
+        core::any::type_name::<T>()
+    );
+}
+
+#[inline]
+fn lock<T>(dst: *mut T) -> LockGuard {
+    detect_fallback::<T>();
+    lock_addr(dst as usize)
+}
+
+#[inline]
+fn lock_addr(addr: usize) -> LockGuard {
+    #[cfg(all(feature = "std", unix, not(atomic_loom)))]
+    atfork::register();
+    #[cfg(all(feature = "std", debug_assertions, not(atomic_loom)))]
+    held::acquire(addr);
     let lock = lock_for_addr(addr);
     lock.lock();
-    LockGuard(lock)
+    LockGuard {
+        lock,
+        #[cfg(all(feature = "std", debug_assertions, not(atomic_loom)))]
+        addr,
+    }
 }
 
-struct LockGuard(&'static SpinLock);
+// Tracks the locks held by the current thread in debug builds. Acquiring a lock
+// which is already held by the same thread would spin forever, so we panic
+// instead. This can happen if two atomics hash to the same lock and the second
+// one is accessed while the first one is locked.
+#[cfg(all(feature = "std", debug_assertions, not(atomic_loom)))]
+mod held {
+    use core::cell::Cell;
+
+    use super::{lock_index, SPINLOCKS};
+
+    std::thread_local! {
+        // Address of the atomic for which each lock is held, or 0.
+        static HELD: Cell<[usize; SPINLOCKS.len()]> = const { Cell::new([0; SPINLOCKS.len()]) };
+    }
+
+    pub fn acquire(addr: usize) {
+        let index = lock_index(addr);
+        let _ = HELD.try_with(|held| {
+            let mut locks = held.get();
+            if locks[index] != 0 {
+                panic!(
+                    "recursive acquisition of fallback lock {} for the atomic at {:#x}, \
+                     which is already held for the atomic at {:#x}",
+                    index, addr, locks[index]
+                );
+            }
+            locks[index] = addr;
+            held.set(locks);
+        });
+    }
+
+    pub fn release(addr: usize) {
+        let _ = HELD.try_with(|held| {
+            let mut locks = held.get();
+            locks[lock_index(addr)] = 0;
+            held.set(locks);
+        });
+    }
+}
+
+// If a process forks while another thread holds one of the locks, the lock is
+// never released in the child. To avoid this we use the same approach as
+// allocators: all locks are acquired before forking and released afterwards in
+// both the parent and the child. This can't deadlock since a thread only ever
+// holds a single lock and never forks while holding it.
+#[cfg(all(feature = "std", unix, not(atomic_loom)))]
+mod atfork {
+    use std::sync::Once;
+
+    use super::SPINLOCKS;
+
+    static REGISTER: Once = Once::new();
+
+    // Registers the fork handlers. This is done before any lock is first
+    // acquired, so a lock can't be held when a fork happens before the
+    // handlers are registered.
+    #[inline]
+    pub fn register() {
+        REGISTER.call_once(|| unsafe {
+            libc::pthread_atfork(Some(prepare), Some(release), Some(release));
+        });
+    }
+
+    extern "C" fn prepare() {
+        for lock in &SPINLOCKS {
+            lock.lock();
+        }
+    }
+
+    extern "C" fn release() {
+        for lock in SPINLOCKS.iter().rev() {
+            lock.unlock();
+        }
+    }
+}
+
+struct LockGuard {
+    lock: &'static SpinLock,
+    #[cfg(all(feature = "std", debug_assertions, not(atomic_loom)))]
+    addr: usize,
+}
 impl Drop for LockGuard {
     #[inline]
     fn drop(&mut self) {
-        self.0.unlock();
+        self.lock.unlock();
+        #[cfg(all(feature = "std", debug_assertions, not(atomic_loom)))]
+        held::release(self.addr);
+    }
+}
+
+#[cfg(atomic_loom)]
+unsafe fn write<T>(dst: *mut T, val: T) {
+    shadow::store(dst, &val);
+    crate::ops::locked_write(dst, val)
+}
+
+// loom can't model racy reads of plain memory, so the loom build keeps a copy
+// of every object written by the fallback as relaxed atomic bytes, which have
+// the same ordering semantics, and optimistic reads use the copy instead.
+#[cfg(atomic_loom)]
+mod shadow {
+    use core::mem::{self, MaybeUninit};
+    use core::slice;
+    use core::sync::atomic::Ordering;
+    use loom::sync::atomic::AtomicU8;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    // This isn't a loom mutex, so it doesn't synchronize the threads of the
+    // model. Creating a loom atomic doesn't switch threads, so the mutex is
+    // never held by a thread which is switched out.
+    loom::lazy_static! {
+        static ref COPIES: Mutex<HashMap<usize, Arc<[AtomicU8]>>> = Mutex::new(HashMap::new());
+    }
+
+    // Returns the copy of the object at `dst`, creating it from the current
+    // value of the object the first time. Writers always get the copy before
+    // modifying the object, so this never reads the object while it is being
+    // written.
+    unsafe fn get<T>(dst: *mut T) -> Arc<[AtomicU8]> {
+        COPIES
+            .lock()
+            .unwrap()
+            .entry(dst as usize)
+            .or_insert_with(|| {
+                let bytes = slice::from_raw_parts(dst as *const u8, mem::size_of::<T>());
+                bytes.iter().map(|&b| AtomicU8::new(b)).collect()
+            })
+            .clone()
+    }
+
+    // The types used with the fallback are `NoUninit`, so all of their bytes
+    // are initialized.
+    pub unsafe fn store<T>(dst: *mut T, val: &T) {
+        let bytes = slice::from_raw_parts(val as *const T as *const u8, mem::size_of::<T>());
+        for (b, &v) in get(dst).iter().zip(bytes) {
+            b.store(v, Ordering::Relaxed);
+        }
+    }
+
+    pub unsafe fn read<T>(dst: *mut T) -> MaybeUninit<T> {
+        let mut val = MaybeUninit::<T>::uninit();
+        let out = val.as_mut_ptr() as *mut u8;
+        for (i, b) in get(dst).iter().enumerate() {
+            *out.add(i) = b.load(Ordering::Relaxed);
+        }
+        val
     }
 }
 
 #[inline]
 pub unsafe fn atomic_load<T>(dst: *mut T) -> T {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     ptr::read(dst)
 }
 
+// Number of optimistic reads attempted by `atomic_load_optimistic` before
+// giving up and taking the lock, which guarantees progress for readers when
+// writers are very busy.
+const OPTIMISTIC_ATTEMPTS: u32 = 16;
+
+#[inline]
+pub unsafe fn atomic_load_optimistic<T: NoUninit>(dst: *mut T) -> T {
+    detect_fallback::<T>();
+    let lock = lock_for_addr(dst as usize);
+    let backoff = Backoff::new();
+    for _ in 0..OPTIMISTIC_ATTEMPTS {
+        let seq = lock.0.load(Ordering::Acquire);
+        if seq & 1 == 0 {
+            // This read may race with a writer holding the lock, in which case
+            // the sequence number will have changed and the value is
+            // discarded. We read into a MaybeUninit since the value may be
+            // torn, and only assume it is initialized after validation.
+            #[cfg(not(atomic_loom))]
+            let val = ptr::read_volatile(dst as *const MaybeUninit<T>);
+            #[cfg(atomic_loom)]
+            let val = shadow::read(dst);
+            fence(Ordering::Acquire);
+            if lock.0.load(Ordering::Relaxed) == seq {
+                return val.assume_init();
+            }
+        }
+        spin(&backoff);
+    }
+    atomic_load(dst)
+}
+
 #[inline]
 pub unsafe fn atomic_store<T>(dst: *mut T, val: T) {
-    let _l = lock(dst as usize);
-    ptr::write(dst, val);
+    let _l = lock(dst);
+    write(dst, val);
 }
 
 #[inline]
 pub unsafe fn atomic_swap<T>(dst: *mut T, val: T) -> T {
-    let _l = lock(dst as usize);
-    ptr::replace(dst, val)
+    let _l = lock(dst);
+    let result = ptr::read(dst);
+    write(dst, val);
+    result
 }
 
 #[inline]
@@ -122,13 +377,13 @@ pub unsafe fn atomic_compare_exchange<T: NoUninit>(
     current: T,
     new: T,
 ) -> Result<T, T> {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
     // compare_exchange compares with memcmp instead of Eq
     let a = bytemuck::bytes_of(&result);
     let b = bytemuck::bytes_of(&current);
     if a == b {
-        ptr::write(dst, new);
+        write(dst, new);
         Ok(result)
     } else {
         Err(result)
@@ -140,9 +395,9 @@ pub unsafe fn atomic_add<T: Copy>(dst: *mut T, val: T) -> T
 where
     Wrapping<T>: ops::Add<Output = Wrapping<T>>,
 {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
-    ptr::write(dst, (Wrapping(result) + Wrapping(val)).0);
+    write(dst, (Wrapping(result) + Wrapping(val)).0);
     result
 }
 
@@ -151,48 +406,188 @@ pub unsafe fn atomic_sub<T: Copy>(dst: *mut T, val: T) -> T
 where
     Wrapping<T>: ops::Sub<Output = Wrapping<T>>,
 {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
-    ptr::write(dst, (Wrapping(result) - Wrapping(val)).0);
+    write(dst, (Wrapping(result) - Wrapping(val)).0);
     result
 }
 
 #[inline]
 pub unsafe fn atomic_and<T: Copy + ops::BitAnd<Output = T>>(dst: *mut T, val: T) -> T {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
-    ptr::write(dst, result & val);
+    write(dst, result & val);
     result
 }
 
 #[inline]
 pub unsafe fn atomic_or<T: Copy + ops::BitOr<Output = T>>(dst: *mut T, val: T) -> T {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
-    ptr::write(dst, result | val);
+    write(dst, result | val);
     result
 }
 
 #[inline]
 pub unsafe fn atomic_xor<T: Copy + ops::BitXor<Output = T>>(dst: *mut T, val: T) -> T {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
-    ptr::write(dst, result ^ val);
+    write(dst, result ^ val);
     result
 }
 
 #[inline]
 pub unsafe fn atomic_min<T: Copy + cmp::Ord>(dst: *mut T, val: T) -> T {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
-    ptr::write(dst, cmp::min(result, val));
+    write(dst, cmp::min(result, val));
     result
 }
 
 #[inline]
 pub unsafe fn atomic_max<T: Copy + cmp::Ord>(dst: *mut T, val: T) -> T {
-    let _l = lock(dst as usize);
+    let _l = lock(dst);
     let result = ptr::read(dst);
-    ptr::write(dst, cmp::max(result, val));
+    write(dst, cmp::max(result, val));
     result
 }
+
+// Versions of the operations above for objects whose size is only known at
+// runtime, used by the C API. These are only used for objects which aren't
+// lock-free at all, so plain copies are fine while holding the lock.
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_load_raw(dst: *mut u8, ret: *mut u8, size: usize) {
+    let _l = lock_addr(dst as usize);
+    ptr::copy_nonoverlapping(dst, ret, size);
+}
+
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_store_raw(dst: *mut u8, val: *const u8, size: usize) {
+    let _l = lock_addr(dst as usize);
+    ptr::copy_nonoverlapping(val, dst, size);
+}
+
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_swap_raw(dst: *mut u8, val: *const u8, ret: *mut u8, size: usize) {
+    let _l = lock_addr(dst as usize);
+    ptr::copy_nonoverlapping(dst, ret, size);
+    ptr::copy_nonoverlapping(val, dst, size);
+}
+
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_compare_exchange_raw(
+    dst: *mut u8,
+    current: *mut u8,
+    new: *const u8,
+    size: usize,
+) -> bool {
+    let _l = lock_addr(dst as usize);
+    let a = core::slice::from_raw_parts(dst, size);
+    let b = core::slice::from_raw_parts(current, size);
+    if a == b {
+        ptr::copy_nonoverlapping(new, dst, size);
+        true
+    } else {
+        ptr::copy_nonoverlapping(dst, current, size);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lock;
+    use crate::{Atomic, Ordering::*};
+    #[cfg(all(feature = "std", unix))]
+    use std::sync::Arc;
+
+    #[test]
+    fn native_load_skips_lock() {
+        // Types with native loads are read without the lock, even on targets
+        // where read-modify-write operations have to use it.
+        let a = Atomic::new(5u32);
+        if Atomic::<u32>::is_lock_free_load() {
+            let _l = lock(a.inner_ptr());
+            assert_eq!(a.load(Relaxed), 5);
+            assert_eq!(a.load_snapshot(), 5);
+        }
+    }
+
+    #[cfg(all(feature = "std", debug_assertions, not(atomic_loom)))]
+    #[test]
+    fn recursive_lock_panics() {
+        // Both atomics are in the same 16 bytes, so they share a lock.
+        #[repr(C, align(16))]
+        struct Pair([Atomic<[u8; 3]>; 2]);
+        let pair = Pair(Atomic::from_array([[0; 3]; 2]));
+        let [a, b] = &pair.0;
+        assert!(a.shares_lock_with(b));
+
+        let _l = lock(a.inner_ptr());
+        let msg = std::panic::catch_unwind(|| drop(lock(b.inner_ptr())))
+            .unwrap_err()
+            .downcast::<std::string::String>()
+            .unwrap();
+        assert!(msg.starts_with("recursive acquisition of fallback lock"));
+        assert!(msg.contains(&format!("{:#x}", a.inner_ptr() as usize)));
+        assert!(msg.contains(&format!("{:#x}", b.inner_ptr() as usize)));
+    }
+
+    #[cfg(all(feature = "std", unix))]
+    #[test]
+    fn fork_under_contention() {
+        #[derive(Copy, Clone, bytemuck::NoUninit)]
+        #[repr(C)]
+        struct Triple(u32, u32, u32);
+
+        // Keep every lock of the table busy from other threads while forking,
+        // so that the fork is very likely to happen while one of them is held.
+        let atomics: Arc<std::vec::Vec<Atomic<Triple>>> =
+            Arc::new((0..64).map(|_| Atomic::new(Triple(0, 0, 0))).collect());
+        // Make sure the fork handlers are registered before forking. A fork
+        // racing with the registration would leave `REGISTER` running forever
+        // in the child, which is not what this test is about.
+        atomics[0].load(Relaxed);
+        let stop = Arc::new(Atomic::new(false));
+        let threads: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let atomics = atomics.clone();
+                let stop = stop.clone();
+                std::thread::spawn(move || {
+                    while !stop.load(Relaxed) {
+                        for a in atomics.iter() {
+                            let Triple(x, y, z) = a.load(Relaxed);
+                            a.store(Triple(x + 1, y, z), Relaxed);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..20 {
+            unsafe {
+                let pid = libc::fork();
+                assert!(pid >= 0);
+                if pid == 0 {
+                    // In the child, every lock must be usable again.
+                    for a in atomics.iter() {
+                        a.swap(Triple(1, 2, 3), Relaxed);
+                    }
+                    libc::_exit(0);
+                }
+                let mut status = 0;
+                assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+                assert!(libc::WIFEXITED(status));
+                assert_eq!(libc::WEXITSTATUS(status), 0);
+            }
+        }
+
+        stop.store(true, Relaxed);
+        for t in threads {
+            t.join().unwrap();
+        }
+    }
+}
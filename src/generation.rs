@@ -0,0 +1,223 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+
+use crate::Atomic;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Generation counters which wrap around, compared as serial numbers.
+///
+/// This is implemented for `u16`, `u32` and `u64`, and is sealed, so it can't
+/// be implemented outside of this crate.
+pub trait Generation: sealed::Sealed + NoUninit + Eq {
+    /// Returns whether `self` is newer than `other`, using the serial number
+    /// arithmetic of RFC 1982.
+    ///
+    /// `self` is newer if it is ahead of `other` by less than half of the
+    /// range of the type, counting modulo `2^N`. Two generations exactly half
+    /// of the range apart are ambiguous, and neither is newer than the other.
+    fn is_newer_than(self, other: Self) -> bool;
+
+    #[doc(hidden)]
+    fn advance(atomic: &Atomic<Self>, order: Ordering) -> Self;
+}
+
+macro_rules! generation {
+    ($($t:ty)*) => ($(
+        impl sealed::Sealed for $t {}
+
+        impl Generation for $t {
+            #[inline]
+            fn is_newer_than(self, other: $t) -> bool {
+                let ahead = self.wrapping_sub(other);
+                ahead != 0 && ahead < 1 << (<$t>::BITS - 1)
+            }
+
+            #[inline]
+            fn advance(atomic: &Atomic<$t>, order: Ordering) -> $t {
+                atomic.fetch_add(1, order).wrapping_add(1)
+            }
+        }
+    )*);
+}
+generation! { u16 u32 u64 }
+
+/// A generation counter which wraps around, like a TCP sequence number.
+///
+/// Comparing generations with `>` gives the wrong answer once the counter
+/// wraps around. `AtomicGeneration` compares them with the serial number
+/// arithmetic of RFC 1982 instead: see `Generation::is_newer_than`. A
+/// generation more than half of the range behind the current one is
+/// considered newer, so the comparison is only meaningful between
+/// generations less than `2^(N-1)` apart.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicGeneration, Ordering};
+///
+/// let gen = AtomicGeneration::<u16>::new(u16::MAX);
+/// assert_eq!(gen.advance(Ordering::AcqRel), 0);
+///
+/// // 1 is newer than 0, even though `u16::MAX` is larger.
+/// assert!(!gen.store_if_newer(u16::MAX, Ordering::AcqRel));
+/// assert!(gen.store_if_newer(1, Ordering::AcqRel));
+/// assert_eq!(gen.load(Ordering::Acquire), 1);
+/// ```
+#[repr(transparent)]
+pub struct AtomicGeneration<T>(Atomic<T>);
+
+impl<T> AtomicGeneration<T> {
+    /// Creates a new `AtomicGeneration`.
+    #[inline]
+    pub const fn new(gen: T) -> AtomicGeneration<T> {
+        AtomicGeneration(Atomic::new(gen))
+    }
+
+    /// Consumes the `AtomicGeneration` and returns the generation.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+impl<T: Generation> AtomicGeneration<T> {
+    /// Loads the current generation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> T {
+        self.0.load(order)
+    }
+
+    /// Increments the generation, wrapping around on overflow, and returns
+    /// the new generation.
+    #[inline]
+    pub fn advance(&self, order: Ordering) -> T {
+        T::advance(&self.0, order)
+    }
+
+    /// Stores `candidate` if it is newer than the current generation.
+    ///
+    /// Returns whether it was stored. A `candidate` which is equal to the
+    /// current generation, or exactly half of the range away from it, isn't
+    /// newer. `order` is the ordering of the successful exchange.
+    pub fn store_if_newer(&self, candidate: T, order: Ordering) -> bool {
+        self.0
+            .fetch_update(order, Ordering::Relaxed, |cur| {
+                candidate.is_newer_than(cur).then_some(candidate)
+            })
+            .is_ok()
+    }
+}
+
+impl<T: Generation + fmt::Debug> fmt::Debug for AtomicGeneration<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicGeneration")
+            .field(&self.0.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn serial_comparison() {
+        // Around the wrap boundary.
+        assert!(0u16.is_newer_than(u16::MAX));
+        assert!(!u16::MAX.is_newer_than(0));
+        assert!(5u32.is_newer_than(u32::MAX - 5));
+        assert!(!7u64.is_newer_than(7));
+
+        // Around the midpoint, which is ambiguous.
+        const HALF: u16 = 1 << 15;
+        assert!((HALF - 1).is_newer_than(0));
+        assert!(!0u16.is_newer_than(HALF - 1));
+        assert!(!HALF.is_newer_than(0));
+        assert!(!0u16.is_newer_than(HALF));
+        assert!(!(HALF + 1).is_newer_than(0));
+        assert!(0u16.is_newer_than(HALF + 1));
+        assert!(!(u32::MAX / 2 + 10).is_newer_than(9));
+        assert!(!9u32.is_newer_than(u32::MAX / 2 + 10));
+        assert!(!(1u64 << 63).is_newer_than(0));
+    }
+
+    #[test]
+    fn operations() {
+        let gen = AtomicGeneration::new(u32::MAX - 1);
+        assert_eq!(gen.advance(SeqCst), u32::MAX);
+        assert_eq!(gen.advance(SeqCst), 0);
+        assert!(!gen.store_if_newer(0, SeqCst));
+        assert!(!gen.store_if_newer(u32::MAX, SeqCst));
+        assert!(gen.store_if_newer(10, SeqCst));
+        assert!(!gen.store_if_newer(10 + (1 << 31), SeqCst));
+        assert!(gen.store_if_newer(9 + (1 << 31), SeqCst));
+        assert_eq!(
+            format!("{:?}", gen),
+            format!("AtomicGeneration({})", 9 + (1u32 << 31))
+        );
+        assert_eq!(gen.into_inner(), 9 + (1 << 31));
+    }
+
+    #[test]
+    fn concurrent_store_if_newer() {
+        const THREADS: u16 = 4;
+        const ROUNDS: u16 = if cfg!(miri) { 50 } else { 2_000 };
+
+        // Each thread publishes increasing generations across the wrap
+        // boundary; the stored generation only ever moves forward. All of the
+        // generations are less than half of the range apart, so a lagging
+        // thread can't store an old generation which looks newer.
+        let start = u16::MAX - 100;
+        let gen = AtomicGeneration::new(start);
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let gen = &gen;
+                s.spawn(move || {
+                    let mut last = start;
+                    for i in 0..ROUNDS {
+                        let candidate = start.wrapping_add(i * THREADS + t + 1);
+                        gen.store_if_newer(candidate, AcqRel);
+                        let cur = gen.load(Acquire);
+                        assert!(!last.is_newer_than(cur), "{} -> {}", last, cur);
+                        last = cur;
+                    }
+                });
+            }
+        });
+        assert_eq!(gen.load(SeqCst), start.wrapping_add(ROUNDS * THREADS));
+
+        // Concurrent advances each return a different generation.
+        let gen = AtomicGeneration::new(u64::MAX - 50);
+        let mut all: Vec<u64> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| s.spawn(|| (0..100).map(|_| gen.advance(AcqRel)).collect::<Vec<_>>()))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), THREADS as usize * 100);
+        assert_eq!(gen.into_inner(), 349);
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// The `glam` feature enables glam's own bytemuck impls, which is all that
+// `Atomic` needs. This checks that the vector types stay usable.
+const _: fn() = || {
+    fn assert_atomicable<T: crate::Atomicable>() {}
+    assert_atomicable::<glam::Vec2>();
+    assert_atomicable::<glam::IVec2>();
+    assert_atomicable::<glam::UVec2>();
+    assert_atomicable::<glam::Vec3>();
+    assert_atomicable::<glam::Vec3A>();
+    assert_atomicable::<glam::Vec4>();
+    assert_atomicable::<glam::IVec4>();
+    assert_atomicable::<glam::Quat>();
+};
+
+#[cfg(test)]
+mod tests {
+    use crate::{Align8, Atomic, Ordering::*};
+    use core::sync::atomic::AtomicBool;
+    use glam::{IVec2, UVec2, Vec2, Vec3A, Vec4};
+
+    #[test]
+    fn lock_free() {
+        // The 8-byte vectors are only aligned to 4 bytes.
+        assert!(!Atomic::<Vec2>::is_lock_free());
+        assert_eq!(
+            Atomic::<Align8<Vec2>>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(
+            Atomic::<Align8<IVec2>>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(
+            Atomic::<Align8<UVec2>>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        if core::mem::align_of::<Vec4>() == 16 {
+            assert_eq!(
+                Atomic::<Vec4>::is_lock_free(),
+                Atomic::<u128>::is_lock_free()
+            );
+        }
+    }
+
+    #[test]
+    fn operations() {
+        let a = Atomic::new(Align8::new(Vec2::new(1.0, 2.0)));
+        assert_eq!(*a.load(SeqCst), Vec2::new(1.0, 2.0));
+        let b = Align8::new(Vec2::new(-3.0, 0.5));
+        assert_eq!(*a.swap(b, SeqCst), Vec2::new(1.0, 2.0));
+
+        // The comparison is bitwise, so -0.0 doesn't match 0.0 and NaN
+        // matches itself.
+        let v = Atomic::new(Vec3A::new(0.0, f32::NAN, 1.0));
+        let zero = Vec3A::new(-0.0, f32::NAN, 1.0);
+        assert!(v
+            .compare_exchange(zero, Vec3A::ONE, SeqCst, SeqCst)
+            .is_err());
+        let current = v.load(SeqCst);
+        assert!(v
+            .compare_exchange(current, Vec3A::ONE, SeqCst, SeqCst)
+            .is_ok());
+        assert_eq!(v.into_inner(), Vec3A::ONE);
+
+        let i = Atomic::new(IVec2::new(1, -1));
+        i.store(IVec2::new(i32::MIN, i32::MAX), SeqCst);
+        assert_eq!(i.load(SeqCst), IVec2::new(i32::MIN, i32::MAX));
+    }
+
+    #[test]
+    fn concurrent_swap() {
+        // All components of every written vector are equal, so a load which
+        // mixed two writes would show up.
+        fn splat(i: u32) -> Vec4 {
+            Vec4::splat(i as f32)
+        }
+
+        let target = Atomic::new(splat(0));
+        let camera = Atomic::new(Align8::new(Vec2::splat(0.0)));
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for t in 0..2 {
+                let (target, camera, done) = (&target, &camera, &done);
+                s.spawn(move || {
+                    let mut i = t;
+                    while !done.load(Relaxed) {
+                        target.swap(splat(i), AcqRel);
+                        camera.swap(Align8::new(Vec2::splat(i as f32)), AcqRel);
+                        i = (i + 2) % 1000;
+                    }
+                });
+            }
+            let rounds = if cfg!(miri) { 50 } else { 50_000 };
+            for _ in 0..rounds {
+                let v = target.load(Acquire);
+                assert_eq!(v, Vec4::splat(v.x), "torn load: {}", v);
+                let c = camera.load(Acquire);
+                assert_eq!(*c, Vec2::splat(c.x), "torn load: {}", *c);
+            }
+            done.store(true, Relaxed);
+        });
+    }
+}
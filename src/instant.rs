@@ -0,0 +1,245 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+use core::time::Duration;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use crate::Atomic;
+
+// All `AtomicInstant`s store the time since this instant, which is captured
+// when the first one is created.
+static ANCHOR: OnceLock<Instant> = OnceLock::new();
+
+#[inline]
+fn anchor() -> Instant {
+    *ANCHOR.get_or_init(Instant::now)
+}
+
+#[inline]
+fn to_nanos(instant: Instant) -> u64 {
+    let nanos = instant.saturating_duration_since(anchor()).as_nanos();
+    nanos.min(u64::MAX as u128) as u64
+}
+
+#[inline]
+fn from_nanos(nanos: u64) -> Instant {
+    anchor() + Duration::from_nanos(nanos)
+}
+
+/// An `Instant` which can be shared between threads, such as the time of the
+/// last heartbeat of a connection.
+///
+/// The instant is stored as the number of nanoseconds since an anchor
+/// `Instant`, which is captured when the first `AtomicInstant` of the process
+/// is created, in a single `Atomic<u64>`. Loads therefore never observe a
+/// mixture of two stores.
+///
+/// Instants are clamped to the range which can be represented:
+///
+/// - An instant before the anchor is stored as the anchor.
+/// - A `u64` of nanoseconds covers about 584 years after the anchor; later
+///   instants are stored as the last representable one.
+///
+/// `Instant` is monotonic, so `store_now` from a single thread never moves
+/// the stored instant backwards. Concurrent `store_now` calls may finish in
+/// a different order than they read the clock though, so use `store_max_now`
+/// if the instant must only move forward.
+///
+/// This requires the `std` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicInstant, Ordering};
+/// use std::time::Duration;
+///
+/// let last_heartbeat = AtomicInstant::now();
+/// last_heartbeat.store_max_now(Ordering::Release);
+/// if last_heartbeat.elapsed(Ordering::Acquire) > Duration::from_secs(30) {
+///     // The connection timed out.
+/// }
+/// ```
+#[repr(transparent)]
+pub struct AtomicInstant(Atomic<u64>);
+
+impl AtomicInstant {
+    /// Creates a new `AtomicInstant`.
+    #[inline]
+    pub fn new(instant: Instant) -> AtomicInstant {
+        AtomicInstant(Atomic::new(to_nanos(instant)))
+    }
+
+    /// Creates a new `AtomicInstant` holding the current time.
+    #[inline]
+    pub fn now() -> AtomicInstant {
+        Self::new(Instant::now())
+    }
+
+    /// Consumes the `AtomicInstant` and returns the instant.
+    #[inline]
+    pub fn into_inner(self) -> Instant {
+        from_nanos(self.0.into_inner())
+    }
+
+    /// Loads the instant.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Instant {
+        from_nanos(self.0.load(order))
+    }
+
+    /// Returns the time elapsed since the stored instant, or zero if it is
+    /// in the future.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn elapsed(&self, order: Ordering) -> Duration {
+        Instant::now().saturating_duration_since(self.load(order))
+    }
+
+    /// Stores an instant.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Acquire` or `AcqRel`.
+    #[inline]
+    pub fn store(&self, instant: Instant, order: Ordering) {
+        self.0.store(to_nanos(instant), order)
+    }
+
+    /// Stores the current time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Acquire` or `AcqRel`.
+    #[inline]
+    pub fn store_now(&self, order: Ordering) {
+        self.store(Instant::now(), order)
+    }
+
+    /// Stores the current time if it is later than the stored instant,
+    /// returning the previous instant.
+    ///
+    /// This is a single `fetch_max`, so the stored instant never moves
+    /// backwards, even with concurrent calls.
+    #[inline]
+    pub fn store_max_now(&self, order: Ordering) -> Instant {
+        from_nanos(self.0.fetch_max(to_nanos(Instant::now()), order))
+    }
+}
+
+impl fmt::Debug for AtomicInstant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicInstant")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use core::sync::atomic::AtomicBool;
+
+    #[test]
+    fn operations() {
+        // Capture the anchor first, so that `start` isn't clamped to it.
+        anchor();
+        let start = Instant::now();
+        let a = AtomicInstant::new(start);
+        assert_eq!(a.load(SeqCst), start);
+        assert!(a.store_max_now(SeqCst) == start);
+        assert!(a.load(SeqCst) >= start);
+
+        // The stored instant never moves backwards with `store_max_now`.
+        let later = start + Duration::from_secs(3600);
+        a.store(later, SeqCst);
+        assert_eq!(a.elapsed(SeqCst), Duration::ZERO);
+        assert_eq!(a.store_max_now(SeqCst), later);
+        assert_eq!(a.into_inner(), later);
+    }
+
+    #[test]
+    fn clamping() {
+        let a = AtomicInstant::now();
+        let anchor = anchor();
+        if let Some(before) = anchor.checked_sub(Duration::from_secs(1)) {
+            a.store(before, SeqCst);
+            assert_eq!(a.load(SeqCst), anchor);
+        }
+        if let Some(far) = anchor.checked_add(Duration::from_secs(1000 * 365 * 24 * 3600)) {
+            a.store(far, SeqCst);
+            assert_eq!(a.load(SeqCst), anchor + Duration::from_nanos(u64::MAX));
+        }
+    }
+
+    #[test]
+    fn concurrent_store_now() {
+        anchor();
+        let start = Instant::now();
+        let a = AtomicInstant::now();
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while !done.load(Relaxed) {
+                        a.store_now(Release);
+                        a.store_max_now(AcqRel);
+                    }
+                });
+            }
+            let rounds = if cfg!(miri) { 50 } else { 10_000 };
+            for _ in 0..rounds {
+                // The loaded instant was stored by one of the writers, so it
+                // is between the start of the test and the current time.
+                let loaded = a.load(Acquire);
+                let elapsed = a.elapsed(Acquire);
+                assert!(loaded >= start && loaded <= Instant::now());
+                assert!(elapsed <= Instant::now() - start);
+            }
+            done.store(true, Relaxed);
+        });
+        assert!(a.load(SeqCst) <= Instant::now());
+    }
+
+    #[test]
+    fn no_tearing() {
+        // The two halves of every stored value are equal, so a torn load
+        // would produce an instant which was never stored.
+        let anchor = anchor();
+        let instant = |i: u64| anchor + Duration::from_nanos(i << 32 | i);
+        let a = AtomicInstant::new(instant(1));
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for t in 0..2u64 {
+                let (a, done) = (&a, &done);
+                s.spawn(move || {
+                    let mut i = t + 1;
+                    while !done.load(Relaxed) {
+                        a.store(instant(i), Relaxed);
+                        i = i % 1000 + 1;
+                    }
+                });
+            }
+            let rounds = if cfg!(miri) { 50 } else { 100_000 };
+            for _ in 0..rounds {
+                let nanos = (a.load(Relaxed) - anchor).as_nanos() as u64;
+                assert_eq!(nanos >> 32, nanos & 0xffff_ffff, "{:#x}", nanos);
+            }
+            done.store(true, Relaxed);
+        });
+    }
+}
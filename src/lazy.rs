@@ -0,0 +1,152 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use crate::{Atomic, Atomicable};
+
+/// A value which is initialized once, on first use.
+///
+/// This is a lightweight alternative to `OnceLock` for small `Copy` values,
+/// such as file descriptors or device ids. It is an `Atomic<T>` along with a
+/// sentinel value which marks it as uninitialized, so it is lock-free
+/// whenever `Atomic<T>` is and doesn't need `std`.
+///
+/// Initialization uses `Atomic::get_or_init_with`: the initializer may run on
+/// several threads at once, but only one result is published and returned to
+/// all of them.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::AtomicLazy;
+///
+/// static FD: AtomicLazy<i32> = AtomicLazy::new(-1);
+///
+/// assert_eq!(FD.get(), None);
+/// assert_eq!(FD.get_or_init(|| 3), 3);
+/// assert_eq!(FD.get_or_init(|| 4), 3);
+/// assert_eq!(FD.get(), Some(3));
+/// ```
+pub struct AtomicLazy<T> {
+    v: Atomic<T>,
+    uninit: T,
+}
+
+impl<T: Copy> AtomicLazy<T> {
+    /// Creates a new, uninitialized `AtomicLazy` which uses `uninit` as the
+    /// sentinel.
+    #[inline]
+    pub const fn new(uninit: T) -> AtomicLazy<T> {
+        AtomicLazy {
+            v: Atomic::new(uninit),
+            uninit,
+        }
+    }
+}
+
+impl<T: Atomicable> AtomicLazy<T> {
+    /// Returns the value, initializing it with `f` if necessary.
+    ///
+    /// See `Atomic::get_or_init_with`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` returns the sentinel.
+    #[inline]
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> T {
+        self.v.get_or_init_with(self.uninit, f)
+    }
+
+    /// Returns the value if it was initialized.
+    #[inline]
+    pub fn get(&self) -> Option<T> {
+        let val = self.v.load(Ordering::Acquire);
+        if bytemuck::bytes_of(&val) == bytemuck::bytes_of(&self.uninit) {
+            None
+        } else {
+            Some(val)
+        }
+    }
+
+    /// Consumes the `AtomicLazy` and returns the value if it was initialized.
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        let val = self.v.into_inner();
+        if bytemuck::bytes_of(&val) == bytemuck::bytes_of(&self.uninit) {
+            None
+        } else {
+            Some(val)
+        }
+    }
+}
+
+impl<T: Atomicable + fmt::Debug> fmt::Debug for AtomicLazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicLazy").field(&self.get()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use core::num::NonZeroU32;
+    use core::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn lazy() {
+        let a = AtomicLazy::new(0u64);
+        assert_eq!(format!("{:?}", a), "AtomicLazy(None)");
+        assert_eq!(a.get_or_init(|| 5), 5);
+        assert_eq!(a.get_or_init(|| 6), 5);
+        assert_eq!(format!("{:?}", a), "AtomicLazy(Some(5))");
+        assert_eq!(a.into_inner(), Some(5));
+        assert_eq!(AtomicLazy::new(0u8).into_inner(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the initializer returned the uninitialized value")]
+    fn lazy_init_sentinel() {
+        AtomicLazy::new(0u32).get_or_init(|| 0);
+    }
+
+    #[test]
+    fn race() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = if cfg!(miri) { 5 } else { 200 };
+
+        for _ in 0..ROUNDS {
+            let lazy = AtomicLazy::new(0usize);
+            let id = Atomic::<Option<NonZeroU32>>::new(None);
+            let calls = AtomicUsize::new(0);
+            let seen: std::vec::Vec<_> = std::thread::scope(|s| {
+                let handles: std::vec::Vec<_> = (1..=THREADS)
+                    .map(|t| {
+                        let (lazy, id, calls) = (&lazy, &id, &calls);
+                        s.spawn(move || {
+                            let a = lazy.get_or_init(|| {
+                                calls.fetch_add(1, Relaxed);
+                                t
+                            });
+                            let b = id.get_or_init_with(None, || NonZeroU32::new(t as u32));
+                            (a, b)
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            // Every thread observed the same winner, whichever ran first.
+            let (a, b) = seen[0];
+            assert!(seen.iter().all(|&x| x == (a, b)));
+            assert_eq!(lazy.get(), Some(a));
+            assert_eq!(id.load(SeqCst), b);
+            assert!((1..=THREADS).contains(&calls.load(SeqCst)));
+        }
+    }
+}
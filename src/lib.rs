@@ -17,7 +17,70 @@
 //!
 //! The `NoUninit` bound is from the [bytemuck] crate, and indicates that a
 //! type has no internal padding bytes. You will need to derive or implement
-//! this trait for all types used with `Atomic<T>`.
+//! this trait for all types used with `Atomic<T>`. The bytemuck traits used
+//! in this crate's bounds are re-exported, and the `derive` feature provides
+//! their derive macros in the `derive` module, so a separate dependency on
+//! bytemuck isn't needed.
+//!
+//! Types with invalid bit patterns, such as `char`, `bool`, the `NonZero`
+//! integers and fieldless enums, may be `NoUninit` as well. Values can only be
+//! stored as a `T`, so loads always return a valid value. `AtomicChecked`
+//! validates values on load for memory which may also be written by other
+//! code.
+//!
+//! `Option`s of the `NonZero` integers are supported too. `None` is
+//! represented by zero, so `Atomic<Option<NonZeroU32>>` is lock-free wherever
+//! `Atomic<u32>` is, and `compare_exchange(None, Some(id), ..)` can be used to
+//! claim a slot.
+//! `AtomicOption<T>` supports other `Option`s of small types, such as
+//! `Option<u32>`, by storing them in a wider integer with a flag bit.
+//!
+//! `Wrapping` and `Saturating` integers support `fetch_add` and `fetch_sub`
+//! with their own overflow behavior: `Atomic<Wrapping<u8>>` wraps around like
+//! `Atomic<u8>`, and `Atomic<Saturating<u8>>` clamps at the bounds.
+//!
+//! Pointers and references aren't `NoUninit`. `Atomic<*mut T>` has its own
+//! operations instead, such as `load_ptr` and `compare_exchange_ptr`.
+//! `AtomicNonNull` and `AtomicOptionNonNull` provide the same operations for
+//! `NonNull<T>` and `Option<NonNull<T>>`, and `AtomicRef` for `Option<&T>`.
+//! They are backed by `AtomicPtr<T>`, so the pointers which are loaded keep
+//! their provenance.
+//! `AtomicStampedPtr` pairs a pointer with a stamp to avoid the ABA problem,
+//! and `AtomicMarkablePtr` with a mark stored in its lowest bit.
+//! `AtomicBox`, which requires the `alloc` feature, owns the `Box` it
+//! contains.
+//!
+//! The `portable_simd` feature, which requires a nightly compiler, enables
+//! bytemuck's support for `core::simd`, so that vectors such as `Simd<u8, 16>`
+//! and `Simd<f32, 4>` can be used with `Atomic`. They are aligned to their
+//! size, so 16-byte vectors are lock-free wherever 128-bit atomics are and
+//! use the fallback otherwise. Only `load`, `store`, `swap` and the
+//! compare-and-swap operations are supported, with bitwise comparisons.
+//!
+//! The `glam` feature enables glam's bytemuck support, so that its vector
+//! types can be used with `Atomic`, for example to publish the latest target
+//! of a camera. The 8-byte vectors such as `Vec2` and `IVec2` are only aligned
+//! to 4 bytes, so they need to be wrapped in `Align8` to be lock-free.
+//! 16-byte aligned vectors such as `Vec4` are lock-free wherever 128-bit
+//! atomics are. `compare_exchange` compares vectors bitwise, like any other
+//! type: a component of `-0.0` doesn't match `0.0`, and a NaN component
+//! matches a NaN with the same bits.
+//!
+//! On x86_64, 16-byte types which are aligned to 16 bytes, such as
+//! `Atomic<u128>` or a pointer paired with a counter, are lock-free when the
+//! crate is built with the `cmpxchg16b` target feature, for example with
+//! `RUSTFLAGS="-C target-feature=+cmpxchg16b"` or `-C target-cpu=native`.
+//! Every operation on them, including loads, is then a `lock cmpxchg16b`
+//! instruction. Without the target feature, the `std` feature detects the
+//! instruction at runtime, and uses the fallback only on the rare CPUs which
+//! lack it. `is_lock_free` is a compile-time guarantee and returns `false` in
+//! that case, while `is_lock_free_runtime` returns the result of the
+//! detection.
+//!
+//! On AArch64, 16-byte types which are aligned to 16 bytes are always
+//! lock-free. Their operations use the `caspal` instruction when the crate is
+//! built with the `lse` target feature, which is enabled by default on Apple
+//! targets, and `ldaxp`/`stlxp` loops otherwise.
 //!
 //! Each method takes an `Ordering` which represents the strength of
 //! the memory barrier for that operation. These orderings are the
@@ -34,51 +97,430 @@
 //! the `const fn` constructors. Atomic statics are often used for lazy global
 //! initialization.
 //!
+//! Types which aren't supported by the native atomic instructions of the
+//! target are implemented using a table of spinlocks. This fallback can be
+//! disabled by turning off the default `fallback` feature, which removes the
+//! lock table from the binary. Using `Atomic<T>` with a type which isn't
+//! lock-free then fails to compile.
+//!
+//! Alternatively, the `debug-detect-fallback` feature makes every use of the
+//! fallback panic in builds with debug assertions, with a message naming the
+//! type. This helps to catch types which unintentionally stopped being
+//! lock-free, for example after a field was added.
+//!
+//! The `libatomic` feature replaces the lock table with calls to the
+//! platform's libatomic, which C compilers use for `__atomic_*` operations on
+//! objects which aren't lock-free. This makes accesses from Rust and C to the
+//! same object atomic with respect to each other. It requires linking against
+//! libatomic.
+//!
+//! Only lock-free atomics are address-free, which means that they can be used
+//! in memory shared between processes. The locks of the fallback are private
+//! to each process, so other atomics must not be shared in this way. See
+//! `Atomic::IS_ADDRESS_FREE`.
+//!
 //! [bytemuck]: https://docs.rs/bytemuck
 
 #![warn(missing_docs)]
 #![warn(rust_2018_idioms)]
 #![no_std]
-#![cfg_attr(feature = "nightly", feature(integer_atomics))]
+#![cfg_attr(feature = "nightly", feature(integer_atomics, cfg_target_has_atomic))]
+#![cfg_attr(feature = "portable_simd", feature(portable_simd))]
 
-#[cfg(any(test, feature = "std"))]
+#[cfg(any(test, feature = "std", atomic_loom))]
 #[macro_use]
 extern crate std;
 
-use core::mem::MaybeUninit;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::num::{Saturating, Wrapping};
 // Re-export some useful definitions from libcore
 pub use core::sync::atomic::{fence, Ordering};
 
 use core::cell::UnsafeCell;
+use core::cmp;
 use core::fmt;
+use core::panic::RefUnwindSafe;
+use core::sync::atomic::AtomicPtr;
 
 #[cfg(feature = "std")]
-use std::panic::RefUnwindSafe;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
 
-use bytemuck::NoUninit;
+#[doc(hidden)]
+pub use bytemuck;
+pub use bytemuck::checked::CheckedCastError;
+pub use bytemuck::{AnyBitPattern, CheckedBitPattern, NoUninit, PodCastError, Zeroable};
 
-#[cfg(feature = "fallback")]
+#[cfg(feature = "abi_stable")]
+mod abi_stable;
+mod aligned;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(any(atomic_asm_128, atomic_cmpxchg16b_dynamic))]
+mod atomic128;
+#[cfg(feature = "alloc")]
+mod atomic_box;
+mod atomic_option;
+mod atomic_ref;
+mod backoff;
+#[cfg(feature = "sync-extras")]
+mod barrier;
+#[cfg(feature = "bitflags")]
+mod bitflags;
+mod bitset;
+#[cfg(feature = "cabi")]
+pub mod cabi;
+mod cache_padded;
+mod checked;
+#[cfg(feature = "crossbeam")]
+mod crossbeam;
+mod dual_counter;
+mod duration;
+mod dyn_atomic;
+#[cfg(all(feature = "fallback", not(feature = "libatomic")))]
 mod fallback;
+mod generation;
+#[cfg(feature = "glam")]
+mod glam;
+#[cfg(feature = "std")]
+mod instant;
+mod lazy;
+#[cfg(feature = "libatomic")]
+mod libatomic;
+mod lock_free;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod min_max;
+#[cfg(feature = "std")]
+mod net;
+mod non_null;
+#[cfg(feature = "num-traits")]
+mod num;
 mod ops;
+mod pair;
+#[cfg(feature = "proptest")]
+mod proptest;
+#[cfg(feature = "radium")]
+mod radium;
+#[cfg(feature = "bevy_reflect")]
+mod reflect;
+#[cfg(feature = "sync-extras")]
+mod seqlock;
+#[cfg(feature = "serde")]
+mod serde;
+mod sharded;
+#[cfg(feature = "portable_simd")]
+mod simd;
+mod slice;
+#[cfg(target_has_atomic = "ptr")]
+mod stack;
+mod stamped;
+mod state;
+#[cfg(target_has_atomic = "ptr")]
+mod tagged;
+mod traits;
+#[cfg(feature = "ufmt")]
+mod ufmt;
+mod versioned;
+#[cfg(feature = "std")]
+mod wait;
+#[cfg(feature = "std")]
+mod wait_async;
+#[cfg(feature = "zerocopy")]
+mod zerocopy;
+#[cfg(feature = "zeroize")]
+mod zeroize;
+
+#[cfg(feature = "bitflags")]
+pub use self::bitflags::AtomicFlags;
+#[cfg(feature = "zerocopy")]
+pub use self::zerocopy::ZeroCopy;
+pub use aligned::{Align1, Align16, Align2, Align4, Align8};
+#[doc(hidden)]
+pub use aligned::{AlignSelector, AlignTo};
+#[cfg(feature = "alloc")]
+pub use atomic_box::AtomicBox;
+pub use atomic_option::{AtomicOption, OptionRepr};
+pub use atomic_ref::AtomicRef;
+pub use backoff::Backoff;
+#[cfg(feature = "sync-extras")]
+pub use barrier::SpinBarrier;
+pub use bitset::{bitset_words, AtomicBitSet, BitSetIter};
+pub use cache_padded::CachePadded;
+pub use checked::AtomicChecked;
+pub use dual_counter::AtomicDualCounter;
+pub use duration::AtomicDuration;
+pub use dyn_atomic::{DynAtomic, ValueKind};
+pub use generation::{AtomicGeneration, Generation};
+#[cfg(feature = "std")]
+pub use instant::AtomicInstant;
+pub use lazy::AtomicLazy;
+#[doc(hidden)]
+pub use lock_free::AssertLockFree;
+pub use lock_free::LockFree;
+pub use min_max::AtomicMinMax;
+#[cfg(feature = "std")]
+pub use net::{AtomicIpv4Addr, AtomicSocketAddrV4};
+pub use non_null::{AtomicNonNull, AtomicOptionNonNull};
+#[cfg(feature = "num-traits")]
+pub use num::{AtomicFloat, AtomicPrimInt};
+pub use pair::AtomicPair;
+#[cfg(feature = "sync-extras")]
+pub use seqlock::{SeqLock, SeqLockWriteGuard};
+pub use sharded::ShardedCounter;
+pub use slice::{copy_from_slice, copy_to_slice};
+#[cfg(target_has_atomic = "ptr")]
+pub use stack::{AtomicStack, Node};
+pub use stamped::AtomicStampedPtr;
+pub use state::AtomicState;
+#[cfg(target_has_atomic = "ptr")]
+pub use tagged::AtomicMarkablePtr;
+pub use traits::{AtomicArithmetic, AtomicInt, AtomicPrimitive, Atomicable};
+pub use versioned::{AtomicVersioned, Version};
+#[cfg(feature = "std")]
+pub use wait_async::WaitFuture;
+
+/// Defines a struct of bit fields packed into a single atomic integer.
+///
+/// The struct wraps an `Atomic` of the integer type, which must be `u8`,
+/// `u16`, `u32` or `u64`, and each field is a range of bits in it. For a field
+/// `len: 4..16` the struct gets:
+///
+/// - `load_len`, which loads the field.
+/// - `store_len`, which replaces the field with a compare-and-swap loop over
+///   the whole word, so concurrent updates of other fields aren't lost.
+/// - `fetch_update_len`, which updates the field with a closure, like
+///   `Atomic::fetch_update`.
+/// - `get_len` and `with_len`, which extract and replace the field in a value
+///   of the whole word.
+///
+/// `load`, `store` and `compare_exchange` access the whole word, which is
+/// useful to take a consistent snapshot of all fields or to update several
+/// fields at once. Values which don't fit in their field cause a panic.
+/// Fields which overlap or don't fit in the integer are compile errors.
+///
+/// This requires the `derive` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::atomic_bitfield;
+/// use atomic::Ordering::{AcqRel, Acquire};
+///
+/// atomic_bitfield! {
+///     /// A packet header.
+///     pub struct Header(u32) {
+///         flags: 0..4,
+///         /// The length of the payload.
+///         pub len: 4..16,
+///         generation: 16..24,
+///     }
+/// }
+///
+/// let header = Header::new(0);
+/// header.store_len(1500, AcqRel);
+/// header.store_flags(0b0101, AcqRel);
+/// assert_eq!(header.fetch_update_generation(AcqRel, Acquire, |g| Some(g + 1)), Ok(0));
+///
+/// let word = header.load(Acquire);
+/// assert_eq!(Header::get_len(word), 1500);
+/// assert_eq!(Header::get_flags(word), 0b0101);
+/// assert_eq!(Header::get_generation(word), 1);
+/// ```
+#[cfg(feature = "derive")]
+pub use atomic_derive::atomic_bitfield;
+
+/// Derive macros for the bytemuck traits required by `Atomic<T>`, and for
+/// generating atomic wrappers of enums and structs.
+///
+/// The generated code refers to bytemuck by name, so types deriving these
+/// without their own dependency on bytemuck need to add
+/// `#[bytemuck(crate = "atomic::bytemuck")]`.
+///
+/// This requires the `derive` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::derive::NoUninit;
+/// use atomic::{Atomic, Ordering};
+///
+/// #[derive(Copy, Clone, PartialEq, Debug, NoUninit)]
+/// #[bytemuck(crate = "atomic::bytemuck")]
+/// #[repr(C)]
+/// struct Point {
+///     x: i16,
+///     y: i16,
+/// }
+///
+/// static CURSOR: Atomic<Point> = Atomic::new(Point { x: 0, y: 0 });
+///
+/// CURSOR.store(Point { x: 3, y: -1 }, Ordering::Relaxed);
+/// assert_eq!(CURSOR.load(Ordering::Relaxed), Point { x: 3, y: -1 });
+/// ```
+///
+/// `Atomicize` is for fieldless enums with an integer repr, such as
+/// `#[repr(u8)]`. It derives `NoUninit` and `CheckedBitPattern`, so these
+/// must not be derived as well, and generates an `Atomic{Name}` wrapper with
+/// the same visibility as the enum. The wrapper stores the discriminant as an
+/// integer and provides `load`, `store`, `swap` and `compare_exchange` which
+/// take and return the enum. Only valid discriminants can be stored through
+/// it, which is checked again on load in builds with debug assertions. The
+/// generated code refers to this crate as `atomic`.
+///
+/// ```rust
+/// use atomic::derive::Atomicize;
+/// use atomic::Ordering;
+///
+/// #[derive(Copy, Clone, PartialEq, Debug, Atomicize)]
+/// #[repr(u8)]
+/// enum Power {
+///     Off = 0,
+///     Standby = 4,
+///     On = 9,
+/// }
+///
+/// static POWER: AtomicPower = AtomicPower::new(Power::Off);
+///
+/// POWER.store(Power::Standby, Ordering::Release);
+/// assert_eq!(POWER.swap(Power::On, Ordering::AcqRel), Power::Standby);
+/// assert_eq!(POWER.load(Ordering::Acquire), Power::On);
+/// ```
+///
+/// `AtomicFields` is for structs with named fields, such as configuration
+/// which is updated at runtime. It generates an `Atomic{Name}` twin with an
+/// `Atomic` for each field, `new`, `snapshot`, `store` and `into_inner`
+/// methods, and a getter and a `set_` setter for each field. `snapshot` and
+/// `store` access the fields one at a time, so they aren't atomic across
+/// fields. Loads use `Acquire` and stores `Release` ordering by default, which
+/// can be changed with `#[atomic(load = Relaxed, store = SeqCst)]` on a field.
+/// Fields with `#[atomic(skip)]` are kept as they are, are cloned by
+/// `snapshot` and aren't changed by `store`.
+///
+/// ```rust
+/// use atomic::derive::AtomicFields;
+///
+/// #[derive(Clone, Debug, PartialEq, AtomicFields)]
+/// struct Limits {
+///     #[atomic(load = Relaxed, store = Relaxed)]
+///     rate: u32,
+///     burst: u32,
+///     #[atomic(skip)]
+///     name: &'static str,
+/// }
+///
+/// let limits = AtomicLimits::new(Limits { rate: 10, burst: 20, name: "api" });
+/// limits.set_rate(15);
+/// assert_eq!(limits.burst(), 20);
+/// assert_eq!(limits.snapshot(), Limits { rate: 15, burst: 20, name: "api" });
+/// ```
+///
+/// `AtomicArithmetic` is for `#[repr(transparent)]` newtypes of a primitive
+/// integer, and implements the `AtomicArithmetic` trait so that their
+/// `Atomic` supports `fetch_add` and the other integer operations. It checks
+/// the repr and the type of the field, but doesn't derive `NoUninit`.
+///
+/// ```rust
+/// use atomic::derive::{AtomicArithmetic, NoUninit};
+/// use atomic::{Atomic, Ordering};
+///
+/// #[derive(Copy, Clone, PartialEq, Debug, NoUninit, AtomicArithmetic)]
+/// #[bytemuck(crate = "atomic::bytemuck")]
+/// #[repr(transparent)]
+/// struct Lsn(u64);
+///
+/// let lsn = Atomic::new(Lsn(100));
+/// assert_eq!(lsn.fetch_add(Lsn(5), Ordering::AcqRel), Lsn(100));
+/// assert_eq!(lsn.fetch_max(Lsn(90), Ordering::AcqRel), Lsn(105));
+/// ```
+#[cfg(feature = "derive")]
+pub mod derive {
+    pub use atomic_derive::{AtomicArithmetic, AtomicFields, Atomicize};
+    pub use bytemuck::{AnyBitPattern, CheckedBitPattern, NoUninit, Zeroable};
+}
 
 /// A generic atomic wrapper type which allows an object to be safely shared
 /// between threads.
+///
+/// # Layout
+///
+/// `Atomic<T>` is guaranteed to have the same size, alignment and bit validity
+/// as `T`. This is part of the stable API: a `T` field of a `#[repr(C)]`
+/// struct can be replaced by an `Atomic<T>` without changing the struct's
+/// layout, for example to match a C11 `_Atomic` field, and pointers can be
+/// cast between `*mut T` and `*mut Atomic<T>`.
 #[repr(transparent)]
 pub struct Atomic<T> {
     // The MaybeUninit is here to work around rust-lang/rust#87341.
     v: UnsafeCell<MaybeUninit<T>>,
 }
 
-// Atomic<T> is only Sync if T is Send
-unsafe impl<T: Copy + Send> Sync for Atomic<T> {}
+// Spot checks of the layout guarantee. UnsafeCell and MaybeUninit are both
+// repr(transparent), so this holds for every T.
+const _: () = {
+    assert!(mem::size_of::<Atomic<u8>>() == mem::size_of::<u8>());
+    assert!(mem::size_of::<Atomic<u64>>() == mem::size_of::<u64>());
+    assert!(mem::align_of::<Atomic<u64>>() == mem::align_of::<u64>());
+    assert!(mem::size_of::<Atomic<[u8; 3]>>() == 3);
+    assert!(mem::align_of::<Atomic<[u8; 3]>>() == 1);
+    assert!(mem::size_of::<Atomic<()>>() == 0);
+};
+
+/// A 128-bit unsigned integer which can be safely shared between threads.
+///
+/// `core::sync::atomic::AtomicU128` is unstable, and only exists on some
+/// targets. This alias has the complete integer API of `Atomic` on every
+/// target: operations use native 128-bit atomic instructions on AArch64, on
+/// x86_64 with the `cmpxchg16b` target feature, and with the `nightly`
+/// feature on other targets which support them, and the fallback lock
+/// otherwise. Code written against it becomes lock-free without changes as
+/// native support becomes available, and `is_lock_free` reports which one is
+/// used on the current target.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicU128, Ordering};
+///
+/// static BYTES: AtomicU128 = AtomicU128::new(0);
+/// BYTES.fetch_add(u64::MAX as u128, Ordering::Relaxed);
+/// BYTES.fetch_add(1, Ordering::Relaxed);
+/// assert_eq!(BYTES.load(Ordering::Relaxed), 1 << 64);
+/// ```
+pub type AtomicU128 = Atomic<u128>;
+
+/// A 128-bit signed integer which can be safely shared between threads.
+///
+/// See `AtomicU128` for when this is lock-free.
+pub type AtomicI128 = Atomic<i128>;
+
+// Atomic<T> is Send and Sync if T is Send, like the std atomics and
+// crossbeam's AtomicCell. Sharing an &Atomic<T> never hands out a &T to other
+// threads: every operation moves values of T in and out by copy, which is
+// effectively sending them to whichever thread performs the next load. T: Sync
+// is therefore not required, but T: Send is, since otherwise something like an
+// Rc could be stored on one thread and loaded on another.
+//
+// Raw pointers are neither Send nor Sync, so an Atomic<*mut T> has to be
+// wrapped in a newtype which asserts that the pointer may be sent to other
+// threads.
+unsafe impl<T: Send> Send for Atomic<T> {}
+unsafe impl<T: Send> Sync for Atomic<T> {}
+
+// An all-zero Atomic<T> is an all-zero T.
+unsafe impl<T: Zeroable> Zeroable for Atomic<T> {}
 
-// Given that atomicity is guaranteed, Atomic<T> is RefUnwindSafe if T is
+// Given that atomicity is guaranteed, Atomic<T> is RefUnwindSafe if T is, in
+// the same way as the std atomic types. UnwindSafe then follows automatically.
 //
 // This is trivially correct for native lock-free atomic types. For those whose
 // atomicity is emulated using a spinlock, it is still correct because the
 // `Atomic` API does not allow doing any panic-inducing operation after writing
 // to the target object.
-#[cfg(feature = "std")]
 impl<T: RefUnwindSafe> RefUnwindSafe for Atomic<T> {}
 
 impl<T: Default> Default for Atomic<T> {
@@ -88,14 +530,201 @@ impl<T: Default> Default for Atomic<T> {
     }
 }
 
+/// Wraps a value in a new `Atomic`, like `Atomic::new`.
+///
+/// The conversion in the other direction can't be expressed as a `From`
+/// implementation because of the orphan rules; use `Atomic::into_inner`
+/// instead. Arrays are converted with `Atomic::from_array`.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+///
+/// struct Limits {
+///     max: Atomic<u32>,
+///     enabled: Atomic<bool>,
+/// }
+///
+/// fn limits(max: impl Into<Atomic<u32>>) -> Limits {
+///     Limits {
+///         max: max.into(),
+///         enabled: true.into(),
+///     }
+/// }
+///
+/// assert_eq!(limits(10).max.load(Ordering::Relaxed), 10);
+/// assert_eq!(limits(Atomic::new(5)).max.into_inner(), 5);
+/// ```
+impl<T> From<T> for Atomic<T> {
+    #[inline]
+    fn from(v: T) -> Self {
+        Self::new(v)
+    }
+}
+
+/// Creates a new, independent `Atomic` holding a snapshot of the current value.
+///
+/// The value is read with a `SeqCst` load. The clone doesn't share any state
+/// with the original: later writes to either one aren't visible through the
+/// other. This is mainly useful for taking point-in-time copies of structs
+/// containing atomic counters.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+///
+/// #[derive(Clone)]
+/// struct Stats {
+///     hits: Atomic<u64>,
+/// }
+///
+/// let live = Stats { hits: Atomic::new(1) };
+/// let snapshot = live.clone();
+/// live.hits.fetch_add(1, Ordering::Relaxed);
+/// assert_eq!(snapshot.hits.load(Ordering::Relaxed), 1);
+/// ```
+impl<T: NoUninit> Clone for Atomic<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::new(self.load(Ordering::SeqCst))
+    }
+}
+
+/// Compares the current values of two `Atomic`s.
+///
+/// Each side is read with a separate `SeqCst` load, so the result only
+/// reflects the values at two slightly different points in time. If either
+/// value is being modified concurrently then the comparison is racy: `a == b`
+/// and `b == c` don't imply `a == c`, and even `a == a` may be `false`.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::Atomic;
+///
+/// let a = Atomic::new(5u32);
+/// assert_eq!(a, Atomic::new(5));
+/// assert!(a == 5 && 5 == a);
+/// assert!(a < 6 && a > 4);
+/// ```
+impl<T: NoUninit + PartialEq> PartialEq for Atomic<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.load(Ordering::SeqCst) == other.load(Ordering::SeqCst)
+    }
+}
+
+impl<T: NoUninit + Eq> Eq for Atomic<T> {}
+
+/// Compares the current value with a plain value, using a `SeqCst` load.
+impl<T: NoUninit + PartialEq> PartialEq<T> for Atomic<T> {
+    #[inline]
+    fn eq(&self, other: &T) -> bool {
+        self.load(Ordering::SeqCst) == *other
+    }
+}
+
+/// Compares the current value with a plain value, using a `SeqCst` load.
+impl<T: NoUninit + PartialOrd> PartialOrd<T> for Atomic<T> {
+    #[inline]
+    fn partial_cmp(&self, other: &T) -> Option<cmp::Ordering> {
+        self.load(Ordering::SeqCst).partial_cmp(other)
+    }
+}
+
+// Allows the plain value to be on the left-hand side of a comparison. This
+// can't be done generically because of the orphan rules.
+macro_rules! atomic_cmp_rev {
+    ($($t:ty)*) => ($(
+        impl PartialEq<Atomic<$t>> for $t {
+            #[inline]
+            fn eq(&self, other: &Atomic<$t>) -> bool {
+                *self == other.load(Ordering::SeqCst)
+            }
+        }
+
+        impl PartialOrd<Atomic<$t>> for $t {
+            #[inline]
+            fn partial_cmp(&self, other: &Atomic<$t>) -> Option<cmp::Ordering> {
+                self.partial_cmp(&other.load(Ordering::SeqCst))
+            }
+        }
+    )*);
+}
+atomic_cmp_rev! { bool u8 u16 u32 u64 usize u128 i8 i16 i32 i64 isize i128 f32 f64 char }
+
+/// Formats the current value as `Atomic(value)`.
+///
+/// The value is read with a `Relaxed` load: formatting is used for logging and
+/// debugging, where imposing a synchronization point would be surprising, and
+/// the value may be stale by the time it is printed anyway. If `T` isn't
+/// lock-free this briefly takes the fallback lock.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::Atomic;
+///
+/// #[derive(Debug)]
+/// struct Stats {
+///     hits: Atomic<u64>,
+///     ratio: Atomic<f32>,
+/// }
+///
+/// let stats = Stats {
+///     hits: Atomic::new(3),
+///     ratio: Atomic::new(0.5),
+/// };
+/// assert_eq!(
+///     format!("{:?}", stats),
+///     "Stats { hits: Atomic(3), ratio: Atomic(0.5) }"
+/// );
+/// ```
 impl<T: NoUninit + fmt::Debug> fmt::Debug for Atomic<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_tuple("Atomic")
-            .field(&self.load(Ordering::SeqCst))
+            .field(&self.load(Ordering::Relaxed))
             .finish()
     }
 }
 
+/// Formats the current value using `T`'s `Display` implementation.
+///
+/// Like `Debug`, this uses a `Relaxed` load, so the output is only a snapshot
+/// of the value at some point during the call and may already be out of date
+/// when it is written.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+///
+/// let requests = Atomic::new(41u64);
+/// requests.fetch_add(1, Ordering::Relaxed);
+/// assert_eq!(format!("requests_total {}", requests), "requests_total 42");
+/// assert_eq!(format!("{:>6.2}", Atomic::new(1.5f64)), "  1.50");
+/// ```
+impl<T: NoUninit + fmt::Display> fmt::Display for Atomic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.load(Ordering::Relaxed).fmt(f)
+    }
+}
+
+macro_rules! atomic_fmt {
+    ($($tr:ident)*) => ($(
+        #[doc = concat!("Formats the current value using `T`'s `", stringify!($tr), "`")]
+        /// implementation, after a `Relaxed` load.
+        impl<T: NoUninit + fmt::$tr> fmt::$tr for Atomic<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::$tr::fmt(&self.load(Ordering::Relaxed), f)
+            }
+        }
+    )*);
+}
+atomic_fmt! { LowerHex UpperHex Binary Octal LowerExp UpperExp }
+
 impl<T> Atomic<T> {
     /// Creates a new `Atomic`.
     #[inline]
@@ -105,23 +734,380 @@ impl<T> Atomic<T> {
         }
     }
 
+    /// Creates a new `Atomic` with all bytes of the value set to zero.
+    ///
+    /// This is the same as `Atomic::default()` for the primitive types, but is
+    /// a `const fn` so it can be used to initialize statics. Use
+    /// `zeroed_array` for arrays.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// static EPOCH: Atomic<[u32; 2]> = Atomic::zeroed();
+    ///
+    /// assert_eq!(EPOCH.load(Ordering::Relaxed), [0, 0]);
+    /// ```
+    #[inline]
+    pub const fn zeroed() -> Atomic<T>
+    where
+        T: Zeroable,
+    {
+        Atomic {
+            v: UnsafeCell::new(MaybeUninit::zeroed()),
+        }
+    }
+
+    /// Creates an array of `Atomic`s with all bytes set to zero.
+    ///
+    /// Unlike `new_array`, this doesn't require `T: Copy` or a value to copy,
+    /// and it is cheap to evaluate at compile time even for large arrays.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// static HISTOGRAM: [Atomic<u64>; 4096] = Atomic::zeroed_array();
+    ///
+    /// HISTOGRAM[17].fetch_add(1, Ordering::Relaxed);
+    /// assert_eq!(HISTOGRAM[17].load(Ordering::Relaxed), 1);
+    /// assert_eq!(HISTOGRAM[18].load(Ordering::Relaxed), 0);
+    /// ```
+    #[inline]
+    pub const fn zeroed_array<const N: usize>() -> [Atomic<T>; N]
+    where
+        T: Zeroable,
+    {
+        // An all-zero Atomic<T> is a valid all-zero T.
+        unsafe { MaybeUninit::<[Atomic<T>; N]>::zeroed().assume_init() }
+    }
+
+    /// Creates an array of `Atomic`s which are all initialized to `v`.
+    ///
+    /// Since `Atomic<T>` isn't `Copy`, `[Atomic::new(v); N]` doesn't compile.
+    /// This function can be used instead, including to initialize statics.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// static SLOTS: [Atomic<u32>; 1024] = Atomic::new_array(0);
+    ///
+    /// SLOTS[7].store(1, Ordering::Relaxed);
+    /// assert_eq!(SLOTS[7].load(Ordering::Relaxed), 1);
+    /// ```
+    #[inline]
+    pub const fn new_array<const N: usize>(v: T) -> [Atomic<T>; N]
+    where
+        T: Copy,
+    {
+        Atomic::from_array([v; N])
+    }
+
+    /// Converts an array of values into an array of `Atomic`s.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let a = Atomic::from_array([1u8, 2, 3]);
+    /// assert_eq!(a[2].load(Ordering::Relaxed), 3);
+    /// ```
+    #[inline]
+    pub const fn from_array<const N: usize>(v: [T; N]) -> [Atomic<T>; N] {
+        // Atomic<T> has the same layout as T, but transmute doesn't work with
+        // dependently-sized types so we go through a union instead.
+        union Transmute<T, const N: usize> {
+            from: ManuallyDrop<[T; N]>,
+            to: ManuallyDrop<[Atomic<T>; N]>,
+        }
+        unsafe {
+            ManuallyDrop::into_inner(
+                Transmute {
+                    from: ManuallyDrop::new(v),
+                }
+                .to,
+            )
+        }
+    }
+
+    /// Whether `Atomic` objects of this type are lock-free.
+    ///
+    /// This is the same as `is_lock_free`, but can be used in constant
+    /// expressions, for example to reject a build in which an atomic would
+    /// silently use a lock.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::Atomic;
+    ///
+    /// #[derive(Copy, Clone, bytemuck::NoUninit)]
+    /// #[repr(C, align(8))]
+    /// struct Pair {
+    ///     a: u32,
+    ///     b: u32,
+    /// }
+    ///
+    /// const _: () = assert!(Atomic::<Pair>::LOCK_FREE);
+    /// ```
+    pub const LOCK_FREE: bool = ops::atomic_is_lock_free::<T>();
+
+    /// Fails to compile if `Atomic` objects of this type are not lock-free.
+    ///
+    /// Evaluating this constant, for example with `let () =
+    /// Atomic::<T>::ASSERT_LOCK_FREE;`, turns an accidental use of the lock-based
+    /// fallback into a compile-time error on the current target. See also
+    /// `new_lock_free`.
+    pub const ASSERT_LOCK_FREE: () = if !Self::LOCK_FREE {
+        // Use the macro from `core` so the error is the same with `std`.
+        core::panic!("`Atomic<T>` is not lock-free for this type on the current target")
+    };
+
+    /// Creates a new `Atomic`, failing to compile if it would not be lock-free
+    /// on the current target.
+    ///
+    /// This is useful in code which must never block, such as real-time
+    /// threads or signal handlers.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::Atomic;
+    ///
+    /// let a = Atomic::new_lock_free(0u32);
+    /// ```
+    ///
+    /// A type which would use a lock is rejected:
+    ///
+    /// ```compile_fail
+    /// use atomic::Atomic;
+    ///
+    /// let a = Atomic::new_lock_free([0u8; 3]);
+    /// ```
+    #[inline]
+    pub const fn new_lock_free(v: T) -> Atomic<T> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ASSERT_LOCK_FREE;
+        Atomic::new(v)
+    }
+
+    /// Whether operations on `Atomic` objects of this type are address-free.
+    ///
+    /// Address-free operations only depend on the memory containing the
+    /// value, so they remain atomic when the same memory is mapped into
+    /// several processes, for example with POSIX shared memory. This is the
+    /// case exactly when the type is lock-free.
+    ///
+    /// Other types use a table of locks which is private to each process, so
+    /// an `Atomic` of such a type **must not** be shared between processes.
+    pub const IS_ADDRESS_FREE: bool = Self::LOCK_FREE;
+
+    const ASSERT_ADDRESS_FREE: () = if !Self::IS_ADDRESS_FREE {
+        core::panic!("`Atomic<T>` is not address-free for this type on the current target")
+    };
+
+    /// Creates a new `Atomic`, failing to compile if its operations would not
+    /// be address-free on the current target.
+    ///
+    /// Use this for atomics placed in memory shared between processes. See
+    /// `IS_ADDRESS_FREE`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// #[repr(C)]
+    /// struct SharedHeader {
+    ///     readers: Atomic<u32>,
+    ///     generation: Atomic<u32>,
+    /// }
+    ///
+    /// let header = SharedHeader {
+    ///     readers: Atomic::new_address_free(0),
+    ///     generation: Atomic::new_address_free(1),
+    /// };
+    /// header.readers.fetch_add(1, Ordering::AcqRel);
+    /// ```
+    ///
+    /// ```compile_fail
+    /// let a = atomic::Atomic::new_address_free([0u8; 3]);
+    /// ```
+    #[inline]
+    pub const fn new_address_free(v: T) -> Atomic<T> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ASSERT_ADDRESS_FREE;
+        Atomic::new(v)
+    }
+
     /// Checks if `Atomic` objects of this type are lock-free.
     ///
     /// If an `Atomic` is not lock-free then it may be implemented using locks
     /// internally, which makes it unsuitable for some situations (such as
     /// communicating with a signal handler).
+    ///
+    /// Zero-sized types are always lock-free: operations on them don't access
+    /// memory at all.
+    ///
+    /// This is a compile-time guarantee. Some types may still use native
+    /// instructions when this returns `false`, if they are detected at
+    /// runtime, such as 16-byte types on x86_64. Use `is_lock_free_runtime`
+    /// to include them.
     #[inline]
     pub const fn is_lock_free() -> bool {
-        ops::atomic_is_lock_free::<T>()
+        Self::LOCK_FREE
+    }
+
+    /// Checks if `Atomic` objects of this type are lock-free on the current
+    /// CPU.
+    ///
+    /// This is the same as `is_lock_free`, except that it also returns `true`
+    /// for types whose native instructions are detected at runtime, such as
+    /// 16-byte types on x86_64 without the `cmpxchg16b` target feature. It
+    /// always agrees with the implementation which is used by the operations
+    /// and reported by `backing`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::Atomic;
+    ///
+    /// assert!(Atomic::<u128>::is_lock_free_runtime() >= Atomic::<u128>::is_lock_free());
+    /// ```
+    #[inline]
+    pub fn is_lock_free_runtime() -> bool {
+        ops::atomic_is_lock_free_runtime::<T>()
+    }
+
+    /// Checks if operations on `Atomic` objects of this type may be used in a
+    /// signal handler.
+    ///
+    /// Operations on lock-free types only use native atomic instructions, so
+    /// they are guaranteed to be async-signal-safe. Other types take a lock,
+    /// which deadlocks if the signal interrupted a thread holding the same
+    /// lock, so they must never be used from a signal handler.
+    ///
+    /// This is equivalent to `is_lock_free`. Use `new_lock_free` to enforce it
+    /// at compile time.
+    #[inline]
+    pub const fn is_async_signal_safe() -> bool {
+        Self::LOCK_FREE
+    }
+
+    /// Checks if loads from `Atomic` objects of this type are lock-free.
+    ///
+    /// Some targets, such as ARMv6-M, support atomic loads and stores but not
+    /// read-modify-write operations. On these targets loads are performed
+    /// natively and never wait for a lock, even though `is_lock_free` returns
+    /// `false`. Stores still take the lock so that they can't interleave with
+    /// a read-modify-write operation performed under the lock.
+    ///
+    /// Detecting such targets requires the `nightly` feature, without it this
+    /// returns the same as `is_lock_free`.
+    #[inline]
+    pub const fn is_lock_free_load() -> bool {
+        ops::atomic_is_lock_free_load::<T>()
+    }
+
+    /// Checks if both loads from and stores to `Atomic` objects of this type
+    /// are lock-free.
+    ///
+    /// On targets with atomic loads and stores but no read-modify-write
+    /// operations, only loads are performed natively, as described in
+    /// `is_lock_free_load`, so this returns the same as `is_lock_free`. A
+    /// queue which only needs lock-free loads should use `is_lock_free_load`
+    /// instead.
+    #[inline]
+    pub const fn is_lock_free_load_store() -> bool {
+        Self::LOCK_FREE
+    }
+
+    /// Checks if read-modify-write operations, such as `swap`,
+    /// `compare_exchange` and the `fetch_*` methods, on `Atomic` objects of
+    /// this type are lock-free.
+    ///
+    /// Read-modify-write operations are the most demanding ones, so this is
+    /// equivalent to `is_lock_free`.
+    #[inline]
+    pub const fn is_lock_free_rmw() -> bool {
+        Self::LOCK_FREE
+    }
+
+    /// Returns which implementation is used for operations on this `Atomic`.
+    ///
+    /// This is purely informational and is mainly useful for debugging
+    /// performance issues, for example to find out which atomics share a lock.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Backing};
+    ///
+    /// let a = Atomic::new([0u8; 3]);
+    /// match a.backing() {
+    ///     Backing::Locked { lock_index } => println!("using lock {}", lock_index),
+    ///     native => println!("using {:?}", native),
+    /// }
+    /// ```
+    #[inline]
+    pub fn backing(&self) -> Backing {
+        ops::atomic_backing::<T>(self.v.get() as usize)
+    }
+
+    /// Checks if this `Atomic` is guarded by the same lock as `other`.
+    ///
+    /// Lock-based atomics are assigned a lock from a fixed-size table based on
+    /// their address, so unrelated atomics may contend with each other. This
+    /// can be used to check that frequently accessed atomics use different
+    /// locks. Returns `false` if either atomic is lock-free.
+    ///
+    /// The assignment of locks is an implementation detail which may change
+    /// between versions, so this should only be used for diagnostics. With the
+    /// `libatomic` feature the assignment is unknown, and this returns `true`
+    /// for any two atomics which aren't lock-free.
+    #[inline]
+    pub fn shares_lock_with<U>(&self, other: &Atomic<U>) -> bool {
+        match (self.backing(), other.backing()) {
+            (Backing::Locked { lock_index: a }, Backing::Locked { lock_index: b }) => a == b,
+            _ => false,
+        }
     }
 }
 
-impl<T: NoUninit> Atomic<T> {
+impl<T> Atomic<T> {
     #[inline]
     fn inner_ptr(&self) -> *mut T {
         self.v.get() as *mut T
     }
 
+    /// Returns a raw pointer to the underlying value.
+    ///
+    /// Accessing the value through this pointer is not atomic. It can be used
+    /// to pass the atomic to C code expecting a pointer to an `_Atomic T`, but
+    /// non-atomic reads and writes through it race with operations on the
+    /// `Atomic` unless they are synchronized by other means.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let a = Atomic::new(5u32);
+    /// let p = a.as_non_atomic_ptr();
+    /// // No other references to `a` exist, so this can't race.
+    /// unsafe { *p += 1 };
+    /// assert_eq!(a.load(Ordering::Relaxed), 6);
+    /// ```
+    #[inline]
+    pub const fn as_non_atomic_ptr(&self) -> *mut T {
+        self.v.get() as *mut T
+    }
+
     /// Returns a mutable reference to the underlying type.
     ///
     /// This is safe because the mutable reference guarantees that no other threads are
@@ -140,6 +1126,48 @@ impl<T: NoUninit> Atomic<T> {
         unsafe { self.v.into_inner().assume_init() }
     }
 
+    /// Converts a mutable reference to a value into a mutable reference to an
+    /// `Atomic`.
+    ///
+    /// This is safe because `Atomic<T>` has the same layout as `T`, and the
+    /// mutable reference guarantees that the value isn't accessed elsewhere
+    /// for as long as the returned reference is alive.
+    #[inline]
+    pub fn from_mut(v: &mut T) -> &mut Atomic<T> {
+        unsafe { &mut *(v as *mut T as *mut Atomic<T>) }
+    }
+
+    /// Converts a mutable slice of values into a mutable slice of `Atomic`s.
+    ///
+    /// This can be used to initialize a buffer with plain writes before
+    /// sharing it. See `from_mut` for why this is safe.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let mut buf = [0u32; 8];
+    /// let shared: &[Atomic<u32>] = Atomic::from_mut_slice(&mut buf);
+    /// shared[2].fetch_add(5, Ordering::Relaxed);
+    /// assert_eq!(buf[2], 5);
+    /// ```
+    #[inline]
+    pub fn from_mut_slice(v: &mut [T]) -> &mut [Atomic<T>] {
+        unsafe { &mut *(v as *mut [T] as *mut [Atomic<T>]) }
+    }
+
+    /// Converts a mutable slice of `Atomic`s into a mutable slice of the
+    /// underlying values.
+    ///
+    /// This is the slice equivalent of `get_mut`.
+    #[inline]
+    pub fn get_mut_slice(v: &mut [Atomic<T>]) -> &mut [T] {
+        unsafe { &mut *(v as *mut [Atomic<T>] as *mut [T]) }
+    }
+}
+
+impl<T> Atomic<T> {
     /// Loads a value from the `Atomic`.
     ///
     /// `load` takes an `Ordering` argument which describes the memory ordering
@@ -149,138 +1177,678 @@ impl<T: NoUninit> Atomic<T> {
     ///
     /// Panics if `order` is `Release` or `AcqRel`.
     #[inline]
-    pub fn load(&self, order: Ordering) -> T {
+    pub fn load(&self, order: Ordering) -> T
+    where
+        T: Atomicable,
+    {
         unsafe { ops::atomic_load(self.inner_ptr(), order) }
     }
 
-    /// Stores a value into the `Atomic`.
+    /// Loads a value from the `Atomic` without blocking writers.
+    ///
+    /// For lock-free types this is the same as `load` with `Acquire` ordering.
+    /// For other types, `load` takes the lock which guards the value, so
+    /// concurrent readers serialize with each other and with writers. This
+    /// function instead reads the value optimistically and validates it using
+    /// a sequence number maintained by the lock, retrying if a writer modified
+    /// the value in the meantime. If writers keep interfering, it eventually
+    /// falls back to taking the lock to guarantee progress.
+    ///
+    /// The load has `Acquire` ordering.
+    #[inline]
+    pub fn load_snapshot(&self) -> T
+    where
+        T: Atomicable,
+    {
+        unsafe { ops::atomic_load_snapshot(self.inner_ptr()) }
+    }
+
+    /// Loads a value from the `Atomic` and copies its bytes into `out`.
+    ///
+    /// `load_bytes` takes an `Ordering` argument which describes the memory
+    /// ordering of this operation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `out` is not the size of `T`, or if `order` is
+    /// `Release` or `AcqRel`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let a = Atomic::new(0x0102_0304u32);
+    /// let mut bytes = [0; 4];
+    /// a.load_bytes(&mut bytes, Ordering::Relaxed);
+    /// assert_eq!(bytes, 0x0102_0304u32.to_ne_bytes());
+    /// ```
+    #[inline]
+    pub fn load_bytes(&self, out: &mut [u8], order: Ordering)
+    where
+        T: Atomicable,
+    {
+        out.copy_from_slice(bytemuck::bytes_of(&self.load(order)));
+    }
+
+    /// Stores a value into the `Atomic`.
+    ///
+    /// `store` takes an `Ordering` argument which describes the memory ordering
+    /// of this operation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Acquire` or `AcqRel`.
+    #[inline]
+    pub fn store(&self, val: T, order: Ordering)
+    where
+        T: Atomicable,
+    {
+        unsafe {
+            ops::atomic_store(self.inner_ptr(), val, order);
+        }
+    }
+
+    /// Stores a value into the `Atomic`, returning the old value.
+    ///
+    /// `swap` takes an `Ordering` argument which describes the memory ordering
+    /// of this operation.
+    #[inline]
+    pub fn swap(&self, val: T, order: Ordering) -> T
+    where
+        T: Atomicable,
+    {
+        unsafe { ops::atomic_swap(self.inner_ptr(), val, order) }
+    }
+
+    /// Stores a value into the `Atomic` if the current value is the same as the
+    /// `current` value.
+    ///
+    /// The return value is a result indicating whether the new value was
+    /// written and containing the previous value. On success this value is
+    /// guaranteed to be equal to `new`.
+    ///
+    /// The values are compared by their bytes rather than with `PartialEq`.
+    /// Types with padding bytes are rejected by the `NoUninit` bound: padding
+    /// isn't preserved when a value is copied, so it can't be canonicalized
+    /// and would make the comparison nondeterministic. Replace the padding
+    /// with explicit fields which are always set to zero instead.
+    ///
+    /// `compare_exchange` takes two `Ordering` arguments to describe the memory
+    /// ordering of this operation. The first describes the required ordering if
+    /// the operation succeeds while the second describes the required ordering
+    /// when the operation fails. The failure ordering can't be `Release` or
+    /// `AcqRel` and must be equivalent or weaker than the success ordering.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: T,
+        new: T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<T, T>
+    where
+        T: Atomicable,
+    {
+        unsafe { ops::atomic_compare_exchange(self.inner_ptr(), current, new, success, failure) }
+    }
+
+    /// Stores a value into the `Atomic` if the current value is the same as the
+    /// `current` value.
+    ///
+    /// Unlike `compare_exchange`, this function is allowed to spuriously fail
+    /// even when the comparison succeeds, which can result in more efficient
+    /// code on some platforms. The return value is a result indicating whether
+    /// the new value was written and containing the previous value.
+    ///
+    /// `compare_exchange` takes two `Ordering` arguments to describe the memory
+    /// ordering of this operation. The first describes the required ordering if
+    /// the operation succeeds while the second describes the required ordering
+    /// when the operation fails. The failure ordering can't be `Release` or
+    /// `AcqRel` and must be equivalent or weaker than the success ordering.
+    /// success ordering.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: T,
+        new: T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<T, T>
+    where
+        T: Atomicable,
+    {
+        unsafe {
+            ops::atomic_compare_exchange_weak(self.inner_ptr(), current, new, success, failure)
+        }
+    }
+
+    /// Fetches the value, and applies a function to it that returns an optional
+    /// new value. Returns a `Result` of `Ok(previous_value)` if the function returned `Some(_)`, else
+    /// `Err(previous_value)`.
+    ///
+    /// Note: This may call the function multiple times if the value has been changed from other threads in
+    /// the meantime, as long as the function returns `Some(_)`, but the function will have been applied
+    /// only once to the stored value.
+    ///
+    /// `fetch_update` takes two [`Ordering`] arguments to describe the memory ordering of this operation.
+    /// The first describes the required ordering for when the operation finally succeeds while the second
+    /// describes the required ordering for loads. These correspond to the success and failure orderings of
+    /// [`compare_exchange`] respectively.
+    ///
+    /// Using [`Acquire`] as success ordering makes the store part
+    /// of this operation [`Relaxed`], and using [`Release`] makes the final successful load
+    /// [`Relaxed`]. The (failed) load ordering can only be [`SeqCst`], [`Acquire`] or [`Relaxed`]
+    /// and must be equivalent to or weaker than the success ordering.
+    ///
+    /// [`compare_exchange`]: #method.compare_exchange
+    /// [`Ordering`]: enum.Ordering.html
+    /// [`Relaxed`]: enum.Ordering.html#variant.Relaxed
+    /// [`Release`]: enum.Ordering.html#variant.Release
+    /// [`Acquire`]: enum.Ordering.html#variant.Acquire
+    /// [`SeqCst`]: enum.Ordering.html#variant.SeqCst
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let x = Atomic::new(7);
+    /// assert_eq!(x.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |_| None), Err(7));
+    /// assert_eq!(x.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| Some(x + 1)), Ok(7));
+    /// assert_eq!(x.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| Some(x + 1)), Ok(8));
+    /// assert_eq!(x.load(Ordering::SeqCst), 9);
+    /// ```
+    #[inline]
+    pub fn fetch_update<F>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: F,
+    ) -> Result<T, T>
+    where
+        T: Atomicable,
+        F: FnMut(T) -> Option<T>,
+    {
+        let backoff = Backoff::new();
+        let mut prev = self.load(fetch_order);
+        while let Some(next) = f(prev) {
+            match self.compare_exchange_weak(prev, next, set_order, fetch_order) {
+                x @ Ok(_) => return x,
+                Err(next_prev) => prev = next_prev,
+            }
+            backoff.spin();
+        }
+        Err(prev)
+    }
+
+    /// Spins until the value of the `Atomic` satisfies `pred`, and returns that
+    /// value.
+    ///
+    /// The value is loaded repeatedly with exponential backoff using
+    /// `spin_loop`. When the `std` feature is enabled, the thread also yields
+    /// to the scheduler once it has been spinning for a while.
+    ///
+    /// This doesn't block the thread, so it is only suitable for short waits or
+    /// for environments without an operating system. See `wait` for a blocking
+    /// alternative.
+    ///
+    /// `wait_until` takes an `Ordering` argument which describes the memory
+    /// ordering of the loads of the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let seq = Atomic::new(3u32);
+    /// assert_eq!(seq.wait_until(Ordering::Acquire, |x| x >= 2), 3);
+    /// ```
+    #[inline]
+    pub fn wait_until<F>(&self, order: Ordering, mut pred: F) -> T
+    where
+        T: Atomicable,
+        F: FnMut(T) -> bool,
+    {
+        let backoff = Backoff::new();
+        loop {
+            let val = self.load(order);
+            if pred(val) {
+                return val;
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Spins until the value of the `Atomic` satisfies `pred`, giving up after
+    /// `max_spins` unsuccessful attempts.
+    ///
+    /// Returns `Ok(value)` with the first value which satisfied `pred`, or
+    /// `Err(value)` with the last value loaded if the limit was reached.
+    ///
+    /// `try_wait_until` takes an `Ordering` argument which describes the memory
+    /// ordering of the loads of the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let flag = Atomic::new(false);
+    /// assert_eq!(flag.try_wait_until(Ordering::Acquire, 10, |x| x), Err(false));
+    /// flag.store(true, Ordering::Release);
+    /// assert_eq!(flag.try_wait_until(Ordering::Acquire, 10, |x| x), Ok(true));
+    /// ```
+    #[inline]
+    pub fn try_wait_until<F>(&self, order: Ordering, max_spins: usize, mut pred: F) -> Result<T, T>
+    where
+        T: Atomicable,
+        F: FnMut(T) -> bool,
+    {
+        let backoff = Backoff::new();
+        let mut val = self.load(order);
+        for _ in 0..max_spins {
+            if pred(val) {
+                return Ok(val);
+            }
+            backoff.snooze();
+            val = self.load(order);
+        }
+        if pred(val) {
+            Ok(val)
+        } else {
+            Err(val)
+        }
+    }
+
+    /// Initializes the `Atomic` with the value returned by `f` if it still
+    /// contains the `uninit` sentinel, and returns the value it contains.
+    ///
+    /// `f` is called by every thread which finds the `Atomic` uninitialized,
+    /// so it may run multiple times concurrently. Only one of the results is
+    /// stored, with a `compare_exchange`, and all threads return that result.
+    /// The other results are discarded. See `AtomicLazy` for a wrapper which
+    /// remembers the sentinel.
+    ///
+    /// The value is loaded with `Acquire` ordering and stored with `Release`
+    /// ordering, so whatever `f` wrote before returning is visible to the
+    /// threads which return its result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` returns `uninit`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::Atomic;
+    /// use core::num::NonZeroU32;
+    ///
+    /// static DEVICE_ID: Atomic<Option<NonZeroU32>> = Atomic::new(None);
+    ///
+    /// let id = DEVICE_ID.get_or_init_with(None, || NonZeroU32::new(7));
+    /// assert_eq!(id, NonZeroU32::new(7));
+    /// assert_eq!(DEVICE_ID.get_or_init_with(None, || unreachable!()), id);
+    /// ```
+    #[inline]
+    pub fn get_or_init_with<F>(&self, uninit: T, f: F) -> T
+    where
+        T: Atomicable,
+        F: FnOnce() -> T,
+    {
+        let val = self.load(Ordering::Acquire);
+        if bytemuck::bytes_of(&val) != bytemuck::bytes_of(&uninit) {
+            return val;
+        }
+        let new = f();
+        assert!(
+            bytemuck::bytes_of(&new) != bytemuck::bytes_of(&uninit),
+            "the initializer returned the uninitialized value"
+        );
+        match self.compare_exchange(uninit, new, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => new,
+            Err(winner) => winner,
+        }
+    }
+}
+
+/// The implementation used for an `Atomic`, as returned by `Atomic::backing`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backing {
+    /// No instructions at all: the type is zero-sized, so there is nothing
+    /// to load or store.
+    ZeroSized,
+    /// Native 8-bit atomic instructions.
+    Native8,
+    /// Native 16-bit atomic instructions.
+    Native16,
+    /// Native 32-bit atomic instructions.
+    Native32,
+    /// Native 64-bit atomic instructions.
+    Native64,
+    /// Native 128-bit atomic instructions.
+    Native128,
+    /// A spinlock from the table used by the fallback implementation.
+    Locked {
+        /// Index of the spinlock in the table. `Atomic`s with the same index
+        /// contend on the same lock.
+        ///
+        /// With the `libatomic` feature the locks belong to libatomic, which
+        /// doesn't expose them, and this is always 0.
+        lock_index: usize,
+    },
+}
+
+/// The reason why `Atomic::wait_timeout` returned.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WaitResult {
+    /// The value was no longer equal to the expected value.
+    Changed,
+    /// The thread was woken up, either by a notification or spuriously, but
+    /// the value is still equal to the expected value.
+    Notified,
+    /// The timeout elapsed while the value was still equal to the expected
+    /// value.
+    TimedOut,
+}
+
+#[cfg(feature = "std")]
+impl WaitResult {
+    /// Returns `true` if the wait returned because the timeout elapsed.
+    #[inline]
+    pub fn timed_out(self) -> bool {
+        self == WaitResult::TimedOut
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: NoUninit> Atomic<T> {
+    /// Blocks the current thread while the value of the `Atomic` is equal to
+    /// `expected`.
+    ///
+    /// The comparison is bitwise, in the same way as `compare_exchange`. If the
+    /// value differs then this function returns immediately, otherwise the
+    /// thread sleeps until it is woken up by `notify_one` or `notify_all`.
+    ///
+    /// This function may also return spuriously, so callers should check the
+    /// value again after it returns.
+    ///
+    /// Where available, this uses the operating system's address-based wait
+    /// primitive (`futex` on Linux, `WaitOnAddress` on Windows, `__ulock_wait`
+    /// on macOS and `memory.atomic.wait32` on WebAssembly).
+    ///
+    /// `wait` takes an `Ordering` argument which describes the memory ordering
+    /// of the loads of the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    /// use std::thread;
+    ///
+    /// let ready = Atomic::new(false);
+    /// thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         ready.store(true, Ordering::Release);
+    ///         ready.notify_all();
+    ///     });
+    ///     while !ready.load(Ordering::Acquire) {
+    ///         ready.wait(false, Ordering::Acquire);
+    ///     }
+    /// });
+    /// ```
+    #[inline]
+    pub fn wait(&self, expected: T, order: Ordering) {
+        unsafe {
+            wait::wait(self.inner_ptr(), expected, order, None);
+        }
+    }
+
+    /// Blocks the current thread while the value of the `Atomic` is equal to
+    /// `expected`, for at most `timeout`.
+    ///
+    /// This behaves like `wait`, but gives up once `timeout` has elapsed. The
+    /// returned `WaitResult` indicates why the function returned, which allows
+    /// callers to distinguish a timeout from a change of the value.
+    ///
+    /// `wait_timeout` takes an `Ordering` argument which describes the memory
+    /// ordering of the loads of the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering, WaitResult};
+    /// use std::time::Duration;
+    ///
+    /// let a = Atomic::new(0u32);
+    /// let result = a.wait_timeout(0, Duration::from_millis(1), Ordering::Acquire);
+    /// assert_eq!(result, WaitResult::TimedOut);
+    /// let result = a.wait_timeout(1, Duration::from_millis(1), Ordering::Acquire);
+    /// assert_eq!(result, WaitResult::Changed);
+    /// ```
+    #[inline]
+    pub fn wait_timeout(&self, expected: T, timeout: Duration, order: Ordering) -> WaitResult {
+        // A timeout too large to represent is treated as infinite.
+        let deadline = Instant::now().checked_add(timeout);
+        unsafe { wait::wait(self.inner_ptr(), expected, order, deadline) }
+    }
+
+    /// Returns a future which resolves once the value of the `Atomic` is no
+    /// longer equal to `expected`.
+    ///
+    /// The comparison is bitwise, in the same way as `compare_exchange`, and
+    /// the future resolves to the new value. Tasks waiting on the future are
+    /// woken up by `notify_one` and `notify_all`, so the value should be
+    /// modified before calling one of these.
+    ///
+    /// The value is loaded with `Acquire` ordering. Dropping the future before
+    /// it completes unregisters the task.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// async fn wait_for_ready(ready: &Atomic<bool>) {
+    ///     while !ready.load(Ordering::Acquire) {
+    ///         ready.wait_async(false).await;
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn wait_async(&self, expected: T) -> WaitFuture<'_, T> {
+        WaitFuture::new(self, expected)
+    }
+
+    /// Wakes up one thread blocked in `wait` on this `Atomic`, as well as one
+    /// task waiting on a future returned by `wait_async`.
+    ///
+    /// The value should be modified before calling this function, otherwise
+    /// the woken thread will simply go back to sleep.
+    ///
+    /// Depending on the platform and the size of `T`, this may wake up more
+    /// than one thread.
+    #[inline]
+    pub fn notify_one(&self) {
+        unsafe { wait::notify(self.inner_ptr(), false) }
+        wait_async::notify(self.inner_ptr() as usize, false);
+    }
+
+    /// Wakes up all threads blocked in `wait` on this `Atomic`, as well as all
+    /// tasks waiting on futures returned by `wait_async`.
+    ///
+    /// The value should be modified before calling this function, otherwise
+    /// the woken threads will simply go back to sleep.
+    #[inline]
+    pub fn notify_all(&self) {
+        unsafe { wait::notify(self.inner_ptr(), true) }
+        wait_async::notify(self.inner_ptr() as usize, true);
+    }
+}
+
+impl<T: NoUninit + AnyBitPattern> Atomic<T> {
+    /// Stores a value made of the bytes in `bytes` into the `Atomic`.
     ///
-    /// `store` takes an `Ordering` argument which describes the memory ordering
-    /// of this operation.
+    /// This requires `T: AnyBitPattern` since any sequence of bytes must be a
+    /// valid value of `T`.
+    ///
+    /// `store_bytes` takes an `Ordering` argument which describes the memory
+    /// ordering of this operation.
     ///
     /// # Panics
     ///
-    /// Panics if `order` is `Acquire` or `AcqRel`.
+    /// Panics if the length of `bytes` is not the size of `T`, or if `order` is
+    /// `Acquire` or `AcqRel`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let a = Atomic::new(0u16);
+    /// a.store_bytes(&7u16.to_ne_bytes(), Ordering::Relaxed);
+    /// assert_eq!(a.load(Ordering::Relaxed), 7);
+    /// ```
     #[inline]
-    pub fn store(&self, val: T, order: Ordering) {
-        unsafe {
-            ops::atomic_store(self.inner_ptr(), val, order);
-        }
+    pub fn store_bytes(&self, bytes: &[u8], order: Ordering) {
+        self.store(bytemuck::pod_read_unaligned(bytes), order);
     }
 
-    /// Stores a value into the `Atomic`, returning the old value.
+    /// Views a mutable byte slice, such as a memory-mapped file, as a slice of
+    /// `Atomic`s.
     ///
-    /// `swap` takes an `Ordering` argument which describes the memory ordering
-    /// of this operation.
+    /// The returned slice can be reborrowed as `&[Atomic<T>]` and shared between
+    /// threads. There is no version taking `&[u8]`: a shared byte slice
+    /// promises that the memory isn't modified, so it can't be soundly
+    /// accessed through atomics.
+    ///
+    /// Memory which is shared with other processes, such as a `MAP_SHARED`
+    /// mapping, must only be accessed through `Atomic`s which are lock-free,
+    /// because the locks of the fallback implementation are private to each
+    /// process. See `Atomic::IS_ADDRESS_FREE`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` isn't suitably aligned for `T`, or if its
+    /// length isn't a multiple of the size of `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// // Stands in for a memory mapping, which is always page-aligned.
+    /// let mut region = vec![0u64; 4];
+    /// let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut region);
+    ///
+    /// let counters: &[Atomic<u64>] = Atomic::slice_from_bytes_mut(bytes).unwrap();
+    /// counters[1].fetch_add(3, Ordering::Relaxed);
+    /// assert_eq!(region[1], 3);
+    /// ```
     #[inline]
-    pub fn swap(&self, val: T, order: Ordering) -> T {
-        unsafe { ops::atomic_swap(self.inner_ptr(), val, order) }
+    pub fn slice_from_bytes_mut(bytes: &mut [u8]) -> Result<&mut [Atomic<T>], PodCastError> {
+        bytemuck::try_cast_slice_mut(bytes).map(Atomic::from_mut_slice)
     }
+}
 
-    /// Stores a value into the `Atomic` if the current value is the same as the
-    /// `current` value.
+impl<const N: usize> Atomic<[u8; N]>
+where
+    [u8; N]: NoUninit,
+{
+    /// Loads the bytes stored in the `Atomic` into `out`.
     ///
-    /// The return value is a result indicating whether the new value was
-    /// written and containing the previous value. On success this value is
-    /// guaranteed to be equal to `new`.
+    /// `load_into` takes an `Ordering` argument which describes the memory
+    /// ordering of this operation.
     ///
-    /// `compare_exchange` takes two `Ordering` arguments to describe the memory
-    /// ordering of this operation. The first describes the required ordering if
-    /// the operation succeeds while the second describes the required ordering
-    /// when the operation fails. The failure ordering can't be `Release` or
-    /// `AcqRel` and must be equivalent or weaker than the success ordering.
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
     #[inline]
-    pub fn compare_exchange(
-        &self,
-        current: T,
-        new: T,
-        success: Ordering,
-        failure: Ordering,
-    ) -> Result<T, T> {
-        unsafe { ops::atomic_compare_exchange(self.inner_ptr(), current, new, success, failure) }
+    pub fn load_into(&self, out: &mut [u8; N], order: Ordering) {
+        *out = self.load(order);
     }
 
-    /// Stores a value into the `Atomic` if the current value is the same as the
-    /// `current` value.
-    ///
-    /// Unlike `compare_exchange`, this function is allowed to spuriously fail
-    /// even when the comparison succeeds, which can result in more efficient
-    /// code on some platforms. The return value is a result indicating whether
-    /// the new value was written and containing the previous value.
+    /// Stores `new` into the `Atomic` if the current bytes are the same as
+    /// `current`.
     ///
-    /// `compare_exchange` takes two `Ordering` arguments to describe the memory
-    /// ordering of this operation. The first describes the required ordering if
-    /// the operation succeeds while the second describes the required ordering
-    /// when the operation fails. The failure ordering can't be `Release` or
-    /// `AcqRel` and must be equivalent or weaker than the success ordering.
-    /// success ordering.
+    /// This is the same as `compare_exchange` but takes the arrays by
+    /// reference.
     #[inline]
-    pub fn compare_exchange_weak(
+    pub fn compare_exchange_bytes(
         &self,
-        current: T,
-        new: T,
+        current: &[u8; N],
+        new: &[u8; N],
         success: Ordering,
         failure: Ordering,
-    ) -> Result<T, T> {
-        unsafe {
-            ops::atomic_compare_exchange_weak(self.inner_ptr(), current, new, success, failure)
-        }
+    ) -> Result<[u8; N], [u8; N]> {
+        self.compare_exchange(*current, *new, success, failure)
     }
 
-    /// Fetches the value, and applies a function to it that returns an optional
-    /// new value. Returns a `Result` of `Ok(previous_value)` if the function returned `Some(_)`, else
-    /// `Err(previous_value)`.
-    ///
-    /// Note: This may call the function multiple times if the value has been changed from other threads in
-    /// the meantime, as long as the function returns `Some(_)`, but the function will have been applied
-    /// only once to the stored value.
+    /// Updates the bytes stored in the `Atomic` in place.
     ///
-    /// `fetch_update` takes two [`Ordering`] arguments to describe the memory ordering of this operation.
-    /// The first describes the required ordering for when the operation finally succeeds while the second
-    /// describes the required ordering for loads. These correspond to the success and failure orderings of
-    /// [`compare_exchange`] respectively.
+    /// `f` is called with a mutable copy of the current bytes and returns
+    /// whether the modified bytes should be stored. This runs a
+    /// `compare_exchange` loop, so `f` may be called multiple times if the
+    /// value is concurrently modified by other threads. Returns
+    /// `Ok(previous_bytes)` if the update was stored, or
+    /// `Err(previous_bytes)` if `f` returned `false`.
     ///
-    /// Using [`Acquire`] as success ordering makes the store part
-    /// of this operation [`Relaxed`], and using [`Release`] makes the final successful load
-    /// [`Relaxed`]. The (failed) load ordering can only be [`SeqCst`], [`Acquire`] or [`Relaxed`]
-    /// and must be equivalent to or weaker than the success ordering.
+    /// The orderings have the same meaning as for `fetch_update`.
     ///
-    /// [`compare_exchange`]: #method.compare_exchange
-    /// [`Ordering`]: enum.Ordering.html
-    /// [`Relaxed`]: enum.Ordering.html#variant.Relaxed
-    /// [`Release`]: enum.Ordering.html#variant.Release
-    /// [`Acquire`]: enum.Ordering.html#variant.Acquire
-    /// [`SeqCst`]: enum.Ordering.html#variant.SeqCst
+    /// `Atomic<[u8; N]>` has an alignment of 1 so it is only lock-free for
+    /// `N == 1`. Other sizes use the fallback implementation unless the array
+    /// is wrapped in a type with a larger alignment.
     ///
     /// # Examples
     ///
     /// ```rust
     /// use atomic::{Atomic, Ordering};
     ///
-    /// let x = Atomic::new(7);
-    /// assert_eq!(x.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |_| None), Err(7));
-    /// assert_eq!(x.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| Some(x + 1)), Ok(7));
-    /// assert_eq!(x.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| Some(x + 1)), Ok(8));
-    /// assert_eq!(x.load(Ordering::SeqCst), 9);
+    /// // version, flags, epoch
+    /// let state = Atomic::new([1u8, 0, 0, 0, 0, 0, 0, 0]);
+    /// let prev = state.map_bytes(Ordering::AcqRel, Ordering::Acquire, |s| {
+    ///     s[1] |= 0x80;
+    ///     true
+    /// });
+    /// assert_eq!(prev, Ok([1, 0, 0, 0, 0, 0, 0, 0]));
+    /// assert_eq!(state.load(Ordering::Relaxed)[1], 0x80);
     /// ```
     #[inline]
-    pub fn fetch_update<F>(
+    pub fn map_bytes<F>(
         &self,
         set_order: Ordering,
         fetch_order: Ordering,
         mut f: F,
-    ) -> Result<T, T>
+    ) -> Result<[u8; N], [u8; N]>
     where
-        F: FnMut(T) -> Option<T>,
+        F: FnMut(&mut [u8; N]) -> bool,
     {
-        let mut prev = self.load(fetch_order);
-        while let Some(next) = f(prev) {
-            match self.compare_exchange_weak(prev, next, set_order, fetch_order) {
-                x @ Ok(_) => return x,
-                Err(next_prev) => prev = next_prev,
+        self.fetch_update(set_order, fetch_order, |prev| {
+            let mut next = prev;
+            if f(&mut next) {
+                Some(next)
+            } else {
+                None
             }
-        }
-        Err(prev)
+        })
     }
 }
 
@@ -319,6 +1887,149 @@ impl Atomic<bool> {
     }
 }
 
+/// Operations on pointers.
+///
+/// Raw pointers aren't `NoUninit`, so the generic operations such as `load`
+/// can't be used with them. These take their place under names with a `_ptr`
+/// suffix, and go directly through an `AtomicPtr<T>`, so the pointers which
+/// are loaded keep their provenance.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+///
+/// let mut value = 5;
+/// let p = Atomic::new(core::ptr::null_mut());
+/// let published = p.compare_exchange_ptr(
+///     core::ptr::null_mut(),
+///     &mut value,
+///     Ordering::Release,
+///     Ordering::Relaxed,
+/// );
+/// assert!(published.is_ok());
+/// assert_eq!(unsafe { *p.load_ptr(Ordering::Acquire) }, 5);
+/// ```
+impl<T> Atomic<*mut T> {
+    /// Loads the pointer from the atomic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load_ptr(&self, order: Ordering) -> *mut T {
+        self.as_atomic_ptr().load(order)
+    }
+
+    /// Stores a pointer into the atomic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Acquire` or `AcqRel`.
+    #[inline]
+    pub fn store_ptr(&self, val: *mut T, order: Ordering) {
+        self.as_atomic_ptr().store(val, order)
+    }
+
+    #[inline]
+    pub(crate) fn as_atomic_ptr(&self) -> &AtomicPtr<T> {
+        // `AtomicPtr<T>` has the same layout as `*mut T`, see `ops.rs`.
+        unsafe { &*(self.as_non_atomic_ptr() as *const AtomicPtr<T>) }
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<T> Atomic<*mut T> {
+    /// Stores a pointer into the atomic, returning the previous pointer.
+    #[inline]
+    pub fn swap_ptr(&self, val: *mut T, order: Ordering) -> *mut T {
+        self.as_atomic_ptr().swap(val, order)
+    }
+
+    /// Stores a pointer into the atomic if the current pointer is the same as
+    /// `current`.
+    ///
+    /// See `Atomic::compare_exchange`.
+    #[inline]
+    pub fn compare_exchange_ptr(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*mut T, *mut T> {
+        self.as_atomic_ptr()
+            .compare_exchange(current, new, success, failure)
+    }
+
+    /// Stores a pointer into the atomic if the current pointer is the same as
+    /// `current`, possibly failing spuriously.
+    ///
+    /// See `Atomic::compare_exchange_weak`.
+    #[inline]
+    pub fn compare_exchange_weak_ptr(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*mut T, *mut T> {
+        self.as_atomic_ptr()
+            .compare_exchange_weak(current, new, success, failure)
+    }
+
+    /// Offsets the pointer by `count` elements of `T`, returning the previous
+    /// pointer.
+    ///
+    /// The offset wraps around like `pointer::wrapping_add`, and the new
+    /// pointer keeps the provenance of the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{Atomic, Ordering};
+    ///
+    /// let mut buf = [0u32; 4];
+    /// let next = Atomic::new(buf.as_mut_ptr());
+    /// let first = next.fetch_ptr_add(3, Ordering::Relaxed);
+    /// assert_eq!(first, buf.as_mut_ptr());
+    /// assert_eq!(next.into_inner(), buf.as_mut_ptr().wrapping_add(3));
+    /// ```
+    #[inline]
+    pub fn fetch_ptr_add(&self, count: usize, order: Ordering) -> *mut T {
+        unsafe { ops::atomic_update_ptr(self.inner_ptr(), order, |p| p.wrapping_add(count)) }
+    }
+
+    /// Offsets the pointer backwards by `count` elements of `T`, returning the
+    /// previous pointer.
+    ///
+    /// The offset wraps around like `pointer::wrapping_sub`, and the new
+    /// pointer keeps the provenance of the previous one.
+    #[inline]
+    pub fn fetch_ptr_sub(&self, count: usize, order: Ordering) -> *mut T {
+        unsafe { ops::atomic_update_ptr(self.inner_ptr(), order, |p| p.wrapping_sub(count)) }
+    }
+
+    /// Offsets the pointer by `val` bytes, returning the previous pointer.
+    ///
+    /// The offset wraps around like `pointer::wrapping_byte_add`, and the new
+    /// pointer keeps the provenance of the previous one.
+    #[inline]
+    pub fn fetch_byte_add(&self, val: usize, order: Ordering) -> *mut T {
+        unsafe { ops::atomic_update_ptr(self.inner_ptr(), order, |p| p.wrapping_byte_add(val)) }
+    }
+
+    /// Offsets the pointer backwards by `val` bytes, returning the previous
+    /// pointer.
+    ///
+    /// The offset wraps around like `pointer::wrapping_byte_sub`, and the new
+    /// pointer keeps the provenance of the previous one.
+    #[inline]
+    pub fn fetch_byte_sub(&self, val: usize, order: Ordering) -> *mut T {
+        unsafe { ops::atomic_update_ptr(self.inner_ptr(), order, |p| p.wrapping_byte_sub(val)) }
+    }
+}
+
 macro_rules! atomic_ops_common {
     ($($t:ty)*) => ($(
         impl Atomic<$t> {
@@ -397,19 +2108,133 @@ macro_rules! atomic_ops_unsigned {
 atomic_ops_signed! { i8 i16 i32 i64 isize i128 }
 atomic_ops_unsigned! { u8 u16 u32 u64 usize u128 }
 
+// `Wrapping<T>` and `Saturating<T>` have the same layout as `T`, so the
+// arithmetic operations can work on the integer directly. `Wrapping<T>` uses
+// the native wrapping operations. There are no native saturating operations,
+// so `Saturating<T>` uses a compare-and-swap loop.
+macro_rules! atomic_ops_overflow {
+    ($($t:ty)*) => ($(
+        impl Atomic<Wrapping<$t>> {
+            /// Add to the current value, wrapping around on overflow, and
+            /// return the previous value.
+            #[inline]
+            pub fn fetch_add(&self, val: Wrapping<$t>, order: Ordering) -> Wrapping<$t> {
+                Wrapping(unsafe { ops::atomic_add(self.inner_ptr() as *mut $t, val.0, order) })
+            }
+
+            /// Subtract from the current value, wrapping around on overflow,
+            /// and return the previous value.
+            #[inline]
+            pub fn fetch_sub(&self, val: Wrapping<$t>, order: Ordering) -> Wrapping<$t> {
+                Wrapping(unsafe { ops::atomic_sub(self.inner_ptr() as *mut $t, val.0, order) })
+            }
+        }
+
+        impl Atomic<Saturating<$t>> {
+            /// Add to the current value, saturating at the numeric bounds, and
+            /// return the previous value.
+            #[inline]
+            pub fn fetch_add(&self, val: Saturating<$t>, order: Ordering) -> Saturating<$t> {
+                let prev = self.fetch_update(order, Ordering::Relaxed, |x| Some(x + val));
+                prev.unwrap_or_else(|x| x)
+            }
+
+            /// Subtract from the current value, saturating at the numeric
+            /// bounds, and return the previous value.
+            #[inline]
+            pub fn fetch_sub(&self, val: Saturating<$t>, order: Ordering) -> Saturating<$t> {
+                let prev = self.fetch_update(order, Ordering::Relaxed, |x| Some(x - val));
+                prev.unwrap_or_else(|x| x)
+            }
+        }
+    )*);
+}
+atomic_ops_overflow! { i8 i16 i32 i64 isize i128 u8 u16 u32 u64 usize u128 }
+
+impl<T: AtomicArithmetic> Atomic<T>
+where
+    Atomic<T::Repr>: AtomicInt<Prim = T::Repr>,
+{
+    #[inline]
+    fn as_repr(&self) -> &Atomic<T::Repr> {
+        // `T` is `#[repr(transparent)]` over `T::Repr`, and so are the
+        // `Atomic`s of both.
+        unsafe { &*(self as *const Self as *const Atomic<T::Repr>) }
+    }
+
+    #[inline]
+    fn from_repr(v: T::Repr) -> T {
+        // Every value of `T::Repr` is a valid `T`.
+        unsafe { mem::transmute_copy(&v) }
+    }
+
+    #[inline]
+    fn into_repr(v: T) -> T::Repr {
+        bytemuck::cast(v)
+    }
+
+    /// Add to the current value, returning the previous value.
+    ///
+    /// This wraps around on overflow, like the underlying integer operation.
+    #[inline]
+    pub fn fetch_add(&self, val: T, order: Ordering) -> T {
+        Self::from_repr(self.as_repr().fetch_add(Self::into_repr(val), order))
+    }
+
+    /// Subtract from the current value, returning the previous value.
+    ///
+    /// This wraps around on overflow, like the underlying integer operation.
+    #[inline]
+    pub fn fetch_sub(&self, val: T, order: Ordering) -> T {
+        Self::from_repr(self.as_repr().fetch_sub(Self::into_repr(val), order))
+    }
+
+    /// Bitwise and with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_and(&self, val: T, order: Ordering) -> T {
+        Self::from_repr(self.as_repr().fetch_and(Self::into_repr(val), order))
+    }
+
+    /// Bitwise or with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_or(&self, val: T, order: Ordering) -> T {
+        Self::from_repr(self.as_repr().fetch_or(Self::into_repr(val), order))
+    }
+
+    /// Bitwise xor with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_xor(&self, val: T, order: Ordering) -> T {
+        Self::from_repr(self.as_repr().fetch_xor(Self::into_repr(val), order))
+    }
+
+    /// Minimum with the current value, comparing the underlying integers.
+    #[inline]
+    pub fn fetch_min(&self, val: T, order: Ordering) -> T {
+        Self::from_repr(self.as_repr().fetch_min(Self::into_repr(val), order))
+    }
+
+    /// Maximum with the current value, comparing the underlying integers.
+    #[inline]
+    pub fn fetch_max(&self, val: T, order: Ordering) -> T {
+        Self::from_repr(self.as_repr().fetch_max(Self::into_repr(val), order))
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::{Atomic, Ordering::*};
-    use bytemuck::NoUninit;
+    use bytemuck::{NoUninit, PodCastError, Zeroable};
     use core::mem;
+    use std::vec::Vec;
 
     #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, NoUninit)]
     #[repr(C)]
     struct Foo(u8, u8);
-    #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, NoUninit)]
+    #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, NoUninit, Zeroable)]
     #[repr(C)]
     struct Bar(u64, u64);
-    #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, NoUninit)]
+    #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, NoUninit, Zeroable)]
     #[repr(C)]
     struct Quux(u32);
 
@@ -432,6 +2257,199 @@ mod tests {
         assert_eq!(a.load(SeqCst), true);
     }
 
+    #[test]
+    fn atomic_char() {
+        let a = Atomic::new('a');
+        assert_eq!(
+            Atomic::<char>::is_lock_free(),
+            cfg!(target_has_atomic = "32"),
+        );
+        assert_eq!(a.swap('é', SeqCst), 'a');
+        assert_eq!(a.compare_exchange('a', 'b', SeqCst, SeqCst), Err('é'));
+        assert_eq!(
+            a.compare_exchange('é', '\u{10ffff}', SeqCst, SeqCst),
+            Ok('é')
+        );
+        assert_eq!(a.load(SeqCst), '\u{10ffff}');
+
+        // Store random valid chars from several threads while others check
+        // that every loaded value is a valid char.
+        static C: Atomic<char> = Atomic::new('\0');
+        std::thread::scope(|s| {
+            for seed in 1..=4u32 {
+                s.spawn(move || {
+                    let mut x = seed.wrapping_mul(0x9e37_79b9);
+                    for _ in 0..10_000 {
+                        x ^= x << 13;
+                        x ^= x >> 17;
+                        x ^= x << 5;
+                        if let Some(c) = char::from_u32(x % 0x11_0000) {
+                            C.store(c, Relaxed);
+                        }
+                    }
+                });
+                s.spawn(|| {
+                    for _ in 0..10_000 {
+                        let c = C.load(Relaxed);
+                        assert_eq!(char::from_u32(c as u32), Some(c));
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn atomic_option_nonzero() {
+        use core::num::{NonZeroU128, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize};
+
+        assert_eq!(
+            Atomic::<Option<NonZeroU8>>::is_lock_free(),
+            cfg!(target_has_atomic = "8")
+        );
+        assert_eq!(
+            Atomic::<Option<NonZeroU16>>::is_lock_free(),
+            cfg!(target_has_atomic = "16")
+        );
+        assert_eq!(
+            Atomic::<Option<NonZeroU32>>::is_lock_free(),
+            cfg!(target_has_atomic = "32")
+        );
+        assert_eq!(
+            Atomic::<Option<NonZeroU64>>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(
+            Atomic::<Option<NonZeroUsize>>::is_lock_free(),
+            Atomic::<usize>::is_lock_free()
+        );
+
+        // Threads claim the slot with their id and release it again. Both the
+        // native path and the fallback, which is used for 128-bit values
+        // without the `nightly` feature, are exercised.
+        fn claim_release<T: bytemuck::PodInOption + PartialEq + core::fmt::Debug + Send + Sync>(
+            ids: [T; 4],
+        ) {
+            let slot = Atomic::<Option<T>>::new(None);
+            let claims = Atomic::new(0u32);
+            std::thread::scope(|s| {
+                for id in ids {
+                    let (slot, claims) = (&slot, &claims);
+                    s.spawn(move || {
+                        for _ in 0..1000 {
+                            if slot
+                                .compare_exchange(None, Some(id), Acquire, Relaxed)
+                                .is_ok()
+                            {
+                                claims.fetch_add(1, Relaxed);
+                                assert_eq!(slot.load(Relaxed), Some(id));
+                                assert_eq!(slot.swap(None, Release), Some(id));
+                            }
+                        }
+                    });
+                }
+            });
+            assert_eq!(slot.load(SeqCst), None);
+            assert!(claims.load(SeqCst) > 0);
+        }
+        let nz32 = |n| NonZeroU32::new(n).unwrap();
+        claim_release([nz32(1), nz32(2), nz32(3), nz32(u32::MAX)]);
+        let nz64 = |n| NonZeroU64::new(n).unwrap();
+        claim_release([nz64(1), nz64(2), nz64(3), nz64(u64::MAX)]);
+        let nz128 = |n| NonZeroU128::new(n).unwrap();
+        claim_release([nz128(1), nz128(2), nz128(3), nz128(u128::MAX)]);
+    }
+
+    // Run with `MIRIFLAGS=-Zmiri-strict-provenance` to check that the pointer
+    // isn't turned into an integer and back.
+    #[cfg(target_has_atomic = "ptr")]
+    #[test]
+    fn atomic_pointer_provenance() {
+        // Raw pointers aren't `Send`, so `Atomic<*mut T>` needs a wrapper to be
+        // shared.
+        struct Slot(Atomic<*mut u64>);
+        unsafe impl Sync for Slot {}
+
+        let slot = Slot(Atomic::new(core::ptr::null_mut()));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let value = std::boxed::Box::into_raw(std::boxed::Box::new(42u64));
+                let published =
+                    slot.0
+                        .compare_exchange_ptr(core::ptr::null_mut(), value, Release, Relaxed);
+                assert!(published.is_ok());
+            });
+            s.spawn(|| {
+                let backoff = crate::Backoff::new();
+                let mut p = slot.0.load_ptr(Acquire);
+                while p.is_null() {
+                    backoff.snooze();
+                    p = slot.0.load_ptr(Acquire);
+                }
+                assert_eq!(unsafe { *p }, 42);
+            });
+        });
+        let p = slot.0.swap_ptr(core::ptr::null_mut(), SeqCst);
+        assert!(slot
+            .0
+            .compare_exchange_weak_ptr(p, p, SeqCst, SeqCst)
+            .is_err());
+        slot.0.store_ptr(p, SeqCst);
+        drop(unsafe { std::boxed::Box::from_raw(slot.0.into_inner()) });
+    }
+
+    #[test]
+    fn atomic_pointer_arithmetic() {
+        const THREADS: usize = 4;
+        const CHUNKS: usize = 64;
+        const CHUNK: usize = 8;
+
+        // Raw pointers aren't `Send`, so `Atomic<*mut T>` needs a wrapper to be
+        // shared.
+        struct Bump(Atomic<*mut u8>);
+        unsafe impl Sync for Bump {}
+
+        let mut buf = std::vec![0u8; THREADS * CHUNKS * CHUNK];
+        let start = buf.as_mut_ptr();
+        let bump = Bump(Atomic::new(start));
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let bump = &bump;
+                s.spawn(move || {
+                    for _ in 0..CHUNKS {
+                        let chunk = bump.0.fetch_byte_add(CHUNK, Relaxed);
+                        let chunk = unsafe { core::slice::from_raw_parts_mut(chunk, CHUNK) };
+                        assert!(chunk.iter().all(|&b| b == 0));
+                        chunk.fill(t as u8 + 1);
+                    }
+                });
+            }
+        });
+
+        // Every chunk was handed out to exactly one thread.
+        let mut per_thread = [0; THREADS];
+        for chunk in buf.chunks(CHUNK) {
+            assert!(chunk.iter().all(|&b| b == chunk[0]));
+            per_thread[chunk[0] as usize - 1] += 1;
+        }
+        assert_eq!(per_thread, [CHUNKS; THREADS]);
+
+        let end = start.wrapping_add(buf.len());
+        assert_eq!(bump.0.fetch_byte_sub(CHUNK, Relaxed), end);
+        let words = Atomic::new(start as *mut u64);
+        assert_eq!(words.fetch_ptr_add(2, Relaxed), start as *mut u64);
+        assert_eq!(
+            words.fetch_ptr_sub(1, Relaxed),
+            start.wrapping_add(16) as *mut u64
+        );
+        assert_eq!(words.into_inner(), start.wrapping_add(8) as *mut u64);
+
+        // Like the std atomics, the offsets wrap around.
+        let p = Atomic::new(core::ptr::null_mut::<u8>());
+        p.fetch_byte_sub(1, Relaxed);
+        assert_eq!(p.fetch_byte_add(2, Relaxed) as usize, usize::MAX);
+        assert_eq!(p.into_inner() as usize, 1);
+    }
+
     #[test]
     fn atomic_i8() {
         let a = Atomic::new(0i8);
@@ -527,7 +2545,10 @@ mod tests {
         let a = Atomic::new(0i128);
         assert_eq!(
             Atomic::<i128>::is_lock_free(),
-            cfg!(feature = "nightly") & cfg!(target_has_atomic = "128")
+            cfg!(any(
+                all(feature = "nightly", target_has_atomic = "128"),
+                atomic_asm_128
+            ))
         );
         assert_eq!(format!("{:?}", a), "Atomic(0)");
         assert_eq!(a.load(SeqCst), 0);
@@ -614,6 +2635,10 @@ mod tests {
             Atomic::<u32>::is_lock_free(),
             cfg!(target_has_atomic = "32")
         );
+        assert_eq!(
+            Atomic::<u32>::IS_ADDRESS_FREE,
+            cfg!(target_has_atomic = "32")
+        );
         assert_eq!(format!("{:?}", a), "Atomic(0)");
         assert_eq!(a.load(SeqCst), 0);
         a.store(1, SeqCst);
@@ -658,7 +2683,10 @@ mod tests {
         let a = Atomic::new(0u128);
         assert_eq!(
             Atomic::<u128>::is_lock_free(),
-            cfg!(feature = "nightly") & cfg!(target_has_atomic = "128")
+            cfg!(any(
+                all(feature = "nightly", target_has_atomic = "128"),
+                atomic_asm_128
+            ))
         );
         assert_eq!(format!("{:?}", a), "Atomic(0)");
         assert_eq!(a.load(SeqCst), 0);
@@ -676,6 +2704,110 @@ mod tests {
         assert_eq!(a.load(SeqCst), 30);
     }
 
+    #[test]
+    fn atomic_128_aliases() {
+        use crate::{AtomicI128, AtomicU128};
+
+        let lock_free = cfg!(any(
+            all(feature = "nightly", target_has_atomic = "128"),
+            atomic_asm_128
+        ));
+        assert_eq!(AtomicU128::is_lock_free(), lock_free);
+        assert_eq!(AtomicI128::is_lock_free(), lock_free);
+
+        // Carries and borrows cross the middle of the value.
+        let u = AtomicU128::new(u64::MAX as u128);
+        assert_eq!(u.fetch_add(1, SeqCst), u64::MAX as u128);
+        assert_eq!(u.fetch_sub(2, SeqCst), 1 << 64);
+        assert_eq!(u.fetch_xor(u128::MAX, SeqCst), u64::MAX as u128 - 1);
+        assert_eq!(u.load(SeqCst), !(u64::MAX as u128 - 1));
+        assert_eq!(u.fetch_add(u128::MAX, SeqCst), !(u64::MAX as u128 - 1));
+        assert_eq!(u.fetch_min(1 << 100, SeqCst), !(u64::MAX as u128));
+        assert_eq!(u.fetch_max(u128::MAX, SeqCst), 1 << 100);
+        assert_eq!(u.into_inner(), u128::MAX);
+
+        let i = AtomicI128::new(i128::MIN);
+        assert_eq!(i.fetch_sub(1, SeqCst), i128::MIN);
+        assert_eq!(i.fetch_max(-1, SeqCst), i128::MAX);
+        assert_eq!(i.fetch_min(i64::MIN as i128 - 1, SeqCst), i128::MAX);
+        assert_eq!(i.fetch_and(!0xff, SeqCst), i64::MIN as i128 - 1);
+        assert_eq!(i.fetch_or(1, SeqCst), i64::MIN as i128 - 256);
+        assert_eq!(i.load(SeqCst), i64::MIN as i128 - 255);
+    }
+
+    #[test]
+    fn atomic_128_concurrent() {
+        const THREADS: u128 = 4;
+        let rounds = if cfg!(miri) { 50 } else { 10_000 };
+
+        // Each step crosses the middle of the value, so a torn update would
+        // lose a carry.
+        let step = u64::MAX as u128 + 1;
+        let a = crate::AtomicU128::new(0);
+        let b = crate::AtomicI128::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                let (a, b) = (&a, &b);
+                s.spawn(move || {
+                    for _ in 0..rounds {
+                        a.fetch_add(step + 1, Relaxed);
+                        b.fetch_sub(step as i128, Relaxed);
+                    }
+                });
+            }
+        });
+        assert_eq!(a.into_inner(), THREADS * rounds * (step + 1));
+        assert_eq!(b.into_inner(), -((THREADS * rounds * step) as i128));
+    }
+
+    #[test]
+    fn atomic_16_bytes_concurrent() {
+        // The two halves always differ by the same amount, so a torn load or
+        // a lost update would break the invariant.
+        #[derive(Copy, Clone, Debug, PartialEq, NoUninit)]
+        #[repr(C, align(16))]
+        struct Pair {
+            lo: u64,
+            hi: u64,
+        }
+        const DELTA: u64 = 0x0123_4567_89ab_cdef;
+        const THREADS: u64 = 4;
+        let rounds = if cfg!(miri) { 20 } else { 5_000 };
+
+        assert_eq!(
+            Atomic::<Pair>::is_lock_free(),
+            cfg!(any(
+                all(feature = "nightly", target_has_atomic = "128"),
+                atomic_asm_128
+            ))
+        );
+        let a = Atomic::new(Pair { lo: 0, hi: DELTA });
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                let a = &a;
+                s.spawn(move || {
+                    for _ in 0..rounds {
+                        let mut cur = a.load(Acquire);
+                        loop {
+                            assert_eq!(cur.hi.wrapping_sub(cur.lo), DELTA);
+                            let new = Pair {
+                                lo: cur.lo + 1,
+                                hi: cur.hi.wrapping_add(1),
+                            };
+                            match a.compare_exchange_weak(cur, new, AcqRel, Acquire) {
+                                Ok(_) => break,
+                                Err(x) => cur = x,
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        let end = a.into_inner();
+        assert_eq!(end.lo, THREADS * rounds);
+        assert_eq!(end.hi, DELTA + THREADS * rounds);
+    }
+
     #[test]
     fn atomic_usize() {
         let a = Atomic::new(0usize);
@@ -695,10 +2827,278 @@ mod tests {
         assert_eq!(a.load(SeqCst), 30);
     }
 
+    #[test]
+    fn atomic_wrapping() {
+        use core::num::Wrapping;
+        let a = Atomic::new(Wrapping(250u8));
+        assert_eq!(a.fetch_add(Wrapping(10), SeqCst), Wrapping(250));
+        assert_eq!(a.load(SeqCst), Wrapping(4));
+        assert_eq!(a.fetch_sub(Wrapping(5), SeqCst), Wrapping(4));
+        assert_eq!(a.load(SeqCst), Wrapping(255));
+
+        let a = Atomic::new(Wrapping(i64::MIN));
+        a.fetch_sub(Wrapping(1), SeqCst);
+        assert_eq!(a.load(SeqCst), Wrapping(i64::MAX));
+    }
+
+    #[test]
+    fn atomic_saturating() {
+        use core::num::Saturating;
+        let a = Atomic::new(Saturating(250u8));
+        assert_eq!(a.fetch_add(Saturating(10), SeqCst), Saturating(250));
+        assert_eq!(a.load(SeqCst), Saturating(255));
+        a.store(Saturating(3), SeqCst);
+        assert_eq!(a.fetch_sub(Saturating(5), SeqCst), Saturating(3));
+        assert_eq!(a.load(SeqCst), Saturating(0));
+
+        let a = Atomic::new(Saturating(-120i8));
+        a.fetch_sub(Saturating(100), SeqCst);
+        assert_eq!(a.load(SeqCst), Saturating(i8::MIN));
+        a.fetch_add(Saturating(i8::MAX), SeqCst);
+        assert_eq!(a.load(SeqCst), Saturating(-1));
+    }
+
+    #[test]
+    fn concurrent_saturating() {
+        let rounds = if cfg!(miri) { 50 } else { 10_000 };
+        let a = Atomic::new(core::num::Saturating(0u16));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..rounds {
+                        a.fetch_add(core::num::Saturating(7), Relaxed);
+                    }
+                });
+            }
+        });
+        // 4 * 7 * 10_000 overflows a u16, which clamps at the maximum.
+        let expected = (4 * 7 * rounds as u32).min(u16::MAX as u32) as u16;
+        assert_eq!(a.load(SeqCst).0, expected);
+    }
+
+    #[test]
+    fn atomic_debug() {
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Pair {
+            a: Atomic<i32>,
+            b: Atomic<Foo>,
+        }
+        let p = Pair {
+            a: Atomic::new(-1),
+            b: Atomic::new(Foo(1, 2)),
+        };
+        assert_eq!(
+            format!("{:?}", p),
+            "Pair { a: Atomic(-1), b: Atomic(Foo(1, 2)) }"
+        );
+        assert_eq!(
+            format!("{:#?}", Atomic::new(Foo(1, 2))),
+            "Atomic(\n    Foo(\n        1,\n        2,\n    ),\n)"
+        );
+        assert_eq!(format!("{:04x?}", Atomic::new(26u8)), "Atomic(001a)");
+    }
+
+    #[test]
+    fn atomic_fmt_radix() {
+        let a = Atomic::new(0xbeefu32);
+        assert_eq!(format!("{:#010x}", a), format!("{:#010x}", 0xbeefu32));
+        assert_eq!(format!("{:#010x}", a), "0x0000beef");
+        assert_eq!(format!("{:X}", a), "BEEF");
+        assert_eq!(format!("{:#b}", Atomic::new(5u8)), "0b101");
+        assert_eq!(format!("{:>6o}", Atomic::new(8i16)), "    10");
+        assert_eq!(format!("{:x}", Atomic::new(-1i8)), "ff");
+        assert_eq!(format!("{:e}", Atomic::new(1500u64)), "1.5e3");
+    }
+
+    #[test]
+    fn atomic_default() {
+        #[derive(Default)]
+        struct Counters {
+            hits: Atomic<usize>,
+            enabled: Atomic<bool>,
+            ratio: Atomic<f64>,
+            last: Atomic<Bar>,
+        }
+        let c = Counters::default();
+        assert_eq!(c.hits.load(SeqCst), 0);
+        assert!(!c.enabled.load(SeqCst));
+        assert_eq!(c.ratio.load(SeqCst), 0.0);
+        assert_eq!(c.last.load(SeqCst), Bar(0, 0));
+
+        static ZERO: Atomic<i64> = Atomic::zeroed();
+        assert_eq!(ZERO.load(SeqCst), 0);
+        static QUUXES: [Atomic<Quux>; 100] = Atomic::zeroed_array();
+        assert!(QUUXES.iter().all(|q| q.load(SeqCst) == Quux(0)));
+        let bars = Atomic::<Bar>::zeroed_array::<3>();
+        assert!(bars.iter().all(|b| b.load(SeqCst) == Bar(0, 0)));
+        assert_eq!(Atomic::<[u16; 3]>::zeroed().into_inner(), [0; 3]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn atomic_clone_snapshot() {
+        let a = Atomic::new(Bar(0, 0));
+        let done = Atomic::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..10000 {
+                    a.store(Bar(i, i), Relaxed);
+                }
+                done.store(true, Release);
+            });
+            while !done.load(Acquire) {
+                let c = a.clone();
+                let v = c.load(Relaxed);
+                assert_eq!(v.0, v.1);
+                a.store(Bar(v.0 + 1, v.0 + 1), Relaxed);
+                assert_eq!(c.load(Relaxed), v);
+            }
+        });
+    }
+
+    #[test]
+    fn atomic_cmp() {
+        let a = Atomic::new(5i32);
+        let b = Atomic::new(5i32);
+        assert_eq!(a, b);
+        assert!(a == 5);
+        assert!(5 == a);
+        assert!(a != 6);
+        assert!(6 != a);
+        assert!(a < 6 && a > 4 && a >= 5 && a <= 5);
+        assert!(4 < a && 6 > a);
+        assert_eq!(Atomic::new(Foo(1, 2)), Foo(1, 2));
+
+        // Comparisons observe the value at the time of the load, so equality
+        // doesn't carry over once one side changes.
+        let c = Atomic::new(5i32);
+        assert!(a == b && b == c);
+        b.store(6, SeqCst);
+        assert!(a == c && a != b && b != c);
+
+        let nan = Atomic::new(f64::NAN);
+        assert!(nan != nan);
+        assert_eq!(nan.partial_cmp(&0.0), None);
+    }
+
+    #[test]
+    fn atomic_unwind_safe() {
+        let a = Atomic::new(1u64);
+        let b = Atomic::new(Foo(1, 2));
+        let r = std::panic::catch_unwind(|| {
+            a.store(2, SeqCst);
+            b.store(Foo(3, 4), SeqCst);
+            if a.load(SeqCst) == 2 {
+                panic!("unwind");
+            }
+        });
+        assert!(r.is_err());
+        assert_eq!(a.load(SeqCst), 2);
+        assert_eq!(b.load(SeqCst), Foo(3, 4));
+
+        fn assert_unwind_safe<T: std::panic::UnwindSafe>(_: T) {}
+        assert_unwind_safe(&a);
+        assert_unwind_safe(a);
+    }
+
+    #[test]
+    fn atomic_layout() {
+        const _: () = assert!(mem::size_of::<Atomic<Bar>>() == mem::size_of::<Bar>());
+        const _: () = assert!(mem::align_of::<Atomic<Bar>>() == mem::align_of::<Bar>());
+        const _: () = assert!(mem::size_of::<Atomic<[u8; 3]>>() == 3);
+        const _: () = assert!(mem::align_of::<Atomic<u16>>() == mem::align_of::<u16>());
+
+        #[derive(Copy, Clone)]
+        #[repr(C, align(16))]
+        struct Aligned(u8);
+        macro_rules! assert_same_layout {
+            ($($t:ty),*) => {$(
+                const _: () = assert!(mem::size_of::<Atomic<$t>>() == mem::size_of::<$t>());
+                const _: () = assert!(mem::align_of::<Atomic<$t>>() == mem::align_of::<$t>());
+                const _: () = assert!(mem::size_of::<[Atomic<$t>; 3]>() == mem::size_of::<[$t; 3]>());
+            )*};
+        }
+        assert_same_layout!(u8, u64, u128, [u8; 3], Aligned, Option<&'static u8>);
+
+        let a = Atomic::new(Bar(1, 2));
+        assert_eq!(a.as_non_atomic_ptr() as usize, &a as *const _ as usize);
+        assert_eq!(unsafe { *a.as_non_atomic_ptr() }, Bar(1, 2));
+
+        let zeroed: [Atomic<u64>; 64] = Zeroable::zeroed();
+        assert!(zeroed.iter().all(|a| a.load(SeqCst) == 0));
+
+        let mut values = [Foo(1, 2), Foo(3, 4), Foo(5, 6)];
+        let atomics = Atomic::from_mut_slice(&mut values);
+        assert_eq!(atomics[1].swap(Foo(7, 8), SeqCst), Foo(3, 4));
+        atomics[2].get_mut().0 = 9;
+        Atomic::get_mut_slice(atomics)[0] = Foo(0, 0);
+        assert_eq!(values, [Foo(0, 0), Foo(7, 8), Foo(9, 6)]);
+
+        let mut x = 5i32;
+        Atomic::from_mut(&mut x).fetch_sub(6, SeqCst);
+        assert_eq!(x, -1);
+    }
+
+    #[test]
+    fn atomic_slice_from_bytes() {
+        let mut buf = [0u64; 5];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut buf);
+        assert_eq!(
+            Atomic::<u64>::slice_from_bytes_mut(&mut bytes[1..9]).unwrap_err(),
+            PodCastError::TargetAlignmentGreaterAndInputNotAligned
+        );
+        assert_eq!(
+            Atomic::<u64>::slice_from_bytes_mut(&mut bytes[..12]).unwrap_err(),
+            PodCastError::OutputSliceWouldHaveSlop
+        );
+        assert!(Atomic::<u64>::slice_from_bytes_mut(&mut bytes[..0])
+            .unwrap()
+            .is_empty());
+
+        let atomics: &[Atomic<u64>] = Atomic::slice_from_bytes_mut(&mut bytes[8..]).unwrap();
+        assert_eq!(atomics.len(), 4);
+        atomics[0].store(7, SeqCst);
+        atomics[3].fetch_add(2, SeqCst);
+        let halves: &[Atomic<u32>] = Atomic::slice_from_bytes_mut(&mut bytes[4..12]).unwrap();
+        assert_eq!(halves.len(), 2);
+        assert_eq!(buf, [0, 7, 0, 0, 2]);
+    }
+
+    #[test]
+    fn atomic_explicit_padding() {
+        // The layout of `(u8, u32)` with the padding made explicit.
+        #[derive(Copy, Clone, Debug, PartialEq, NoUninit)]
+        #[repr(C)]
+        struct Tagged {
+            tag: u8,
+            _pad: [u8; 3],
+            value: u32,
+        }
+        let tagged = |tag, value| Tagged {
+            tag,
+            _pad: [0; 3],
+            value,
+        };
+
+        let a = Atomic::new(tagged(1, 10));
+        assert_eq!(
+            a.compare_exchange(tagged(1, 10), tagged(2, 20), SeqCst, SeqCst),
+            Ok(tagged(1, 10))
+        );
+        assert_eq!(
+            a.compare_exchange(tagged(1, 10), tagged(3, 30), SeqCst, SeqCst),
+            Err(tagged(2, 20))
+        );
+    }
+
     #[test]
     fn atomic_foo() {
         let a = Atomic::default();
         assert_eq!(Atomic::<Foo>::is_lock_free(), false);
+        const _: () = assert!(!Atomic::<Foo>::IS_ADDRESS_FREE);
+        assert!(!Atomic::<Foo>::is_lock_free_load());
+        const _: () = assert!(!Atomic::<Foo>::LOCK_FREE);
         assert_eq!(format!("{:?}", a), "Atomic(Foo(0, 0))");
         assert_eq!(a.load(SeqCst), Foo(0, 0));
         a.store(Foo(1, 1), SeqCst);
@@ -754,4 +3154,618 @@ mod tests {
         );
         assert_eq!(a.load(SeqCst), Quux(3));
     }
+
+    #[test]
+    fn atomic_fetch_update_contended() {
+        let a = Atomic::new(Foo(0, 0));
+        let b = Atomic::new(0u32);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        a.fetch_update(SeqCst, SeqCst, |x| Some(Foo(x.0.wrapping_add(1), x.1)))
+                            .unwrap();
+                        b.fetch_update(SeqCst, SeqCst, |x| Some(x + 1)).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(a.load(SeqCst), Foo(4000u32 as u8, 0));
+        assert_eq!(b.load(SeqCst), 4000);
+    }
+
+    // Compares the time taken by 8 threads incrementing an `Atomic<u64>` with
+    // `fetch_update`, which backs off after a failed `compare_exchange_weak`,
+    // and with the same loop without backoff. This needs at least 8 cores to
+    // be meaningful, so run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn fetch_update_backoff() {
+        use std::time::{Duration, Instant};
+
+        const THREADS: usize = 8;
+        const ROUNDS: u64 = 200_000;
+
+        fn time(update: impl Fn() + Sync) -> Duration {
+            let start = Instant::now();
+            std::thread::scope(|s| {
+                for _ in 0..THREADS {
+                    s.spawn(|| {
+                        for _ in 0..ROUNDS {
+                            update();
+                        }
+                    });
+                }
+            });
+            start.elapsed()
+        }
+
+        let with = Atomic::<u64>::new(0);
+        let with_time = time(|| {
+            with.fetch_update(SeqCst, SeqCst, |x| Some(x + 1)).unwrap();
+        });
+        let without = Atomic::<u64>::new(0);
+        let without_time = time(|| {
+            let mut prev = without.load(SeqCst);
+            while let Err(x) = without.compare_exchange_weak(prev, prev + 1, SeqCst, SeqCst) {
+                prev = x;
+            }
+        });
+        std::println!(
+            "with backoff: {:?}, without backoff: {:?}",
+            with_time,
+            without_time
+        );
+
+        let total = THREADS as u64 * ROUNDS;
+        assert_eq!(with.load(SeqCst), total);
+        assert_eq!(without.load(SeqCst), total);
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if cores >= THREADS {
+            assert!(with_time < without_time);
+        }
+    }
+
+    #[test]
+    fn atomic_load_snapshot() {
+        #[derive(Copy, Clone, Eq, PartialEq, Debug, NoUninit)]
+        #[repr(C)]
+        struct Stats([u64; 8]);
+
+        let a = Atomic::new(Stats([0; 8]));
+        let b = Atomic::new(Bar(0, 0));
+        let c = Atomic::new(7u32);
+        assert_eq!(c.load_snapshot(), 7);
+        let done = Atomic::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=10000 {
+                    a.store(Stats([i; 8]), Relaxed);
+                    b.store(Bar(i, i), Relaxed);
+                }
+                done.store(true, Release);
+            });
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while !done.load(Acquire) {
+                        let x = a.load_snapshot();
+                        assert!(x.0.iter().all(|&v| v == x.0[0]));
+                        let y = b.load_snapshot();
+                        assert_eq!(y.0, y.1);
+                    }
+                });
+            }
+        });
+        assert_eq!(a.load_snapshot(), Stats([10000; 8]));
+        assert_eq!(b.load_snapshot(), Bar(10000, 10000));
+    }
+
+    // Compares the time taken by an increasing number of threads reading a
+    // value which isn't lock-free with `load_snapshot` and `load`. Snapshots
+    // don't write to shared memory, so each reader should take about as long
+    // as a single one, up to the number of cores. Run it with
+    // `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn load_snapshot_scaling() {
+        use std::time::{Duration, Instant};
+
+        const ROUNDS: usize = 1_000_000;
+
+        #[derive(Copy, Clone, NoUninit)]
+        #[repr(C)]
+        struct Stats([u64; 8]);
+
+        fn time(threads: usize, read: impl Fn() + Sync) -> Duration {
+            let start = Instant::now();
+            std::thread::scope(|s| {
+                for _ in 0..threads {
+                    s.spawn(|| {
+                        for _ in 0..ROUNDS {
+                            read();
+                        }
+                    });
+                }
+            });
+            start.elapsed()
+        }
+
+        let a = Atomic::new(Stats([0; 8]));
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let max = cores.clamp(1, 16);
+        let mut threads = 1;
+        let mut times = vec![];
+        while threads <= max {
+            let snapshot = time(threads, || {
+                core::hint::black_box(a.load_snapshot());
+            });
+            let locked = time(threads, || {
+                core::hint::black_box(a.load(Relaxed));
+            });
+            std::println!(
+                "{} readers: snapshot: {:?}, load: {:?}",
+                threads,
+                snapshot,
+                locked
+            );
+            times.push((threads, snapshot, locked));
+            threads *= 2;
+        }
+
+        let (_, single, _) = times[0];
+        let &(threads, snapshot, locked) = times.last().unwrap();
+        if threads >= 4 {
+            assert!(snapshot < single * 2);
+            assert!(snapshot * 2 < locked);
+        }
+    }
+
+    #[test]
+    fn atomic_bytes() {
+        fn round_trip<T: bytemuck::Pod + PartialEq + core::fmt::Debug>(val: T) {
+            let a = Atomic::new(T::zeroed());
+            let mut bytes = vec![0xff; mem::size_of::<T>()];
+            a.store_bytes(bytemuck::bytes_of(&val), SeqCst);
+            a.load_bytes(&mut bytes, SeqCst);
+            assert_eq!(bytes, bytemuck::bytes_of(&val));
+            assert_eq!(a.load(SeqCst), val);
+        }
+        round_trip(0x12u8);
+        round_trip(0x1234u16);
+        round_trip(0x1234_5678u32);
+        round_trip(0x1234_5678_9abc_def0u64);
+        round_trip(u128::MAX - 5);
+        round_trip([1u8, 2, 3]);
+        round_trip([1u64, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn atomic_bytes_wrong_length() {
+        let a = Atomic::new(0u32);
+        a.load_bytes(&mut [0; 3], SeqCst);
+    }
+
+    #[test]
+    fn atomic_copy_slice() {
+        let buf: Vec<Atomic<u8>> = (0..64).map(|_| Atomic::new(0)).collect();
+        let region = &buf[3..40];
+        let done = Atomic::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for k in 0..=255u8 {
+                    crate::copy_from_slice(region, &[k; 37], Relaxed);
+                }
+                done.store(true, Release);
+            });
+            let mut out = [0; 37];
+            while !done.load(Acquire) {
+                crate::copy_to_slice(region, &mut out, Relaxed);
+                // Every aligned word must come from a single store.
+                let head = region.as_ptr().align_offset(mem::size_of::<usize>());
+                for word in out[head..].chunks_exact(mem::size_of::<usize>()) {
+                    assert!(word.iter().all(|&b| b == word[0]));
+                }
+            }
+            crate::copy_to_slice(region, &mut out, Relaxed);
+            assert_eq!(out, [255; 37]);
+        });
+        assert_eq!(buf[2].load(Relaxed), 0);
+        assert_eq!(buf[40].load(Relaxed), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn atomic_copy_slice_wrong_length() {
+        let buf = [Atomic::new(0u8), Atomic::new(0u8)];
+        crate::copy_from_slice(&buf, &[1, 2, 3], Relaxed);
+    }
+
+    fn byte_array<const N: usize>()
+    where
+        [u8; N]: NoUninit,
+    {
+        let a = Atomic::new([0u8; N]);
+        let mut out = [0xff; N];
+        a.load_into(&mut out, SeqCst);
+        assert_eq!(out, [0; N]);
+        assert_eq!(
+            a.compare_exchange_bytes(&[1; N], &[2; N], SeqCst, SeqCst),
+            Err([0; N])
+        );
+        assert_eq!(
+            a.compare_exchange_bytes(&[0; N], &[2; N], SeqCst, SeqCst),
+            Ok([0; N])
+        );
+        assert_eq!(a.map_bytes(SeqCst, SeqCst, |_| false), Err([2; N]));
+        std::thread::scope(|s| {
+            for i in 0..N {
+                let a = &a;
+                s.spawn(move || {
+                    for _ in 0..100 {
+                        a.map_bytes(SeqCst, SeqCst, |b| {
+                            b[i] += 1;
+                            true
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+        a.load_into(&mut out, SeqCst);
+        assert_eq!(out, [102; N]);
+    }
+
+    #[test]
+    fn atomic_byte_array() {
+        assert!(Atomic::<[u8; 1]>::is_lock_free());
+        byte_array::<1>();
+        byte_array::<8>();
+        byte_array::<24>();
+    }
+
+    #[test]
+    fn atomic_array() {
+        static SLOTS: [Atomic<u32>; 1024] = Atomic::new_array(5);
+        static BARS: [Atomic<Bar>; 4] = Atomic::from_array([Bar(1, 2); 4]);
+        std::thread::scope(|s| {
+            for (i, bar) in BARS.iter().enumerate() {
+                s.spawn(move || {
+                    for j in (i..1024).step_by(4) {
+                        SLOTS[j].fetch_add(j as u32, Relaxed);
+                    }
+                    bar.store(Bar(i as u64, i as u64), Relaxed);
+                });
+            }
+        });
+        for (i, slot) in SLOTS.iter().enumerate() {
+            assert_eq!(slot.load(Relaxed), 5 + i as u32);
+        }
+        assert_eq!(BARS[3].load(Relaxed), Bar(3, 3));
+        let a = Atomic::from_array([Foo(1, 2), Foo(3, 4)]);
+        assert_eq!(a[1].load(Relaxed), Foo(3, 4));
+    }
+
+    #[test]
+    fn atomic_static_aligned() {
+        crate::static_atomic! {
+            static A: [u8; 8] = [1; 8];
+            pub(crate) static B: [u8; 4] = [2; 4];
+            #[allow(dead_code)]
+            static C: [Atomic<[u8; 2]>; 3] = [3; 2];
+            static D: Foo = Foo(4, 4);
+        }
+        assert_eq!(mem::align_of_val(&A), 8);
+        assert_eq!(mem::align_of_val(&B), 4);
+        assert_eq!(mem::align_of_val(&C[1]), 2);
+        assert_eq!(mem::align_of_val(&D), 2);
+        assert_eq!(
+            Atomic::<crate::Align8<[u8; 8]>>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(
+            Atomic::<crate::Align4<[u8; 4]>>::is_lock_free(),
+            cfg!(target_has_atomic = "32")
+        );
+        assert_eq!(
+            Atomic::<crate::Align2<Foo>>::is_lock_free(),
+            cfg!(target_has_atomic = "16")
+        );
+        assert_eq!(*A.load(SeqCst), [1; 8]);
+        let old = A.swap(crate::Align8::new([5; 8]), SeqCst);
+        assert_eq!(old.into_inner(), [1; 8]);
+        assert_eq!(*B.load(SeqCst), [2; 4]);
+        assert_eq!(*C[2].load(SeqCst), [3; 2]);
+        assert_eq!(*D.load(SeqCst), Foo(4, 4));
+    }
+
+    #[test]
+    fn atomic_lock_free_load() {
+        fn check<T: NoUninit>() {
+            assert!(!Atomic::<T>::is_lock_free() || Atomic::<T>::is_lock_free_load());
+            assert!(!Atomic::<T>::is_lock_free_load_store() || Atomic::<T>::is_lock_free_load());
+            assert_eq!(
+                Atomic::<T>::is_lock_free_load_store(),
+                Atomic::<T>::is_lock_free()
+            );
+            assert_eq!(Atomic::<T>::is_lock_free_rmw(), Atomic::<T>::is_lock_free());
+        }
+        check::<u8>();
+        check::<u16>();
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+        check::<Foo>();
+        check::<Bar>();
+        check::<Quux>();
+        assert_eq!(
+            Atomic::<u32>::is_lock_free_load(),
+            cfg!(target_has_atomic = "32")
+        );
+        assert!(!Atomic::<[u8; 3]>::is_lock_free_load());
+    }
+
+    #[test]
+    fn atomic_backing() {
+        fn is_locked(b: crate::Backing) -> bool {
+            matches!(b, crate::Backing::Locked { lock_index } if lock_index < 64)
+        }
+        #[derive(Copy, Clone, NoUninit)]
+        #[repr(C)]
+        struct Big(u64, u64, u64);
+
+        if cfg!(target_has_atomic = "8") {
+            assert_eq!(Atomic::new(0u8).backing(), crate::Backing::Native8);
+        }
+        if cfg!(target_has_atomic = "16") {
+            assert_eq!(Atomic::new(0u16).backing(), crate::Backing::Native16);
+        }
+        if cfg!(target_has_atomic = "32") {
+            assert_eq!(Atomic::new(0u32).backing(), crate::Backing::Native32);
+        }
+        if cfg!(target_has_atomic = "64") {
+            assert_eq!(Atomic::new(0u64).backing(), crate::Backing::Native64);
+        }
+        #[cfg(atomic_cmpxchg16b_dynamic)]
+        let native_128 = crate::atomic128::detect();
+        #[cfg(not(atomic_cmpxchg16b_dynamic))]
+        let native_128 = cfg!(any(
+            all(feature = "nightly", target_has_atomic = "128"),
+            atomic_asm_128
+        ));
+        if native_128 {
+            assert_eq!(Atomic::new(0u128).backing(), crate::Backing::Native128);
+        } else {
+            assert!(is_locked(Atomic::new(0u128).backing()));
+        }
+        assert!(is_locked(Atomic::new(Foo(0, 0)).backing()));
+        assert!(is_locked(Atomic::new(Big(0, 0, 0)).backing()));
+    }
+
+    #[test]
+    fn atomic_zst() {
+        #[derive(Copy, Clone, Debug, PartialEq, NoUninit)]
+        #[repr(C)]
+        struct Marker;
+
+        let a = Atomic::new(());
+        assert!(Atomic::<()>::is_lock_free());
+        assert!(Atomic::<()>::is_lock_free_load());
+        assert_eq!(a.backing(), crate::Backing::ZeroSized);
+        a.store((), SeqCst);
+        assert_eq!(a.load(SeqCst), ());
+        assert_eq!(a.swap((), SeqCst), ());
+        assert_eq!(a.compare_exchange((), (), SeqCst, SeqCst), Ok(()));
+        assert_eq!(a.compare_exchange_weak((), (), SeqCst, SeqCst), Ok(()));
+
+        let m = Atomic::new(Marker);
+        assert!(Atomic::<Marker>::is_lock_free());
+        assert_eq!(m.backing(), crate::Backing::ZeroSized);
+        assert!(!m.shares_lock_with(&Atomic::new(Marker)));
+        m.store(Marker, Release);
+        assert_eq!(m.load(Acquire), Marker);
+        assert_eq!(m.swap(Marker, AcqRel), Marker);
+        assert_eq!(
+            m.compare_exchange(Marker, Marker, AcqRel, Acquire),
+            Ok(Marker)
+        );
+        assert_eq!(m.into_inner(), Marker);
+
+        let l = crate::LockFree::new(Marker);
+        l.store(Marker, SeqCst);
+        assert_eq!(l.load(SeqCst), Marker);
+    }
+
+    #[cfg(not(feature = "libatomic"))]
+    #[test]
+    fn atomic_shares_lock_with() {
+        #[repr(C, align(64))]
+        struct Layout {
+            c: [Atomic<Bar>; 3],
+            a: Atomic<Foo>,
+            b: Atomic<Foo>,
+        }
+        let l = Layout {
+            c: Atomic::from_array([Bar(0, 0); 3]),
+            a: Atomic::new(Foo(0, 0)),
+            b: Atomic::new(Foo(0, 0)),
+        };
+
+        // Each element of `c` occupies its own 16-byte block, which hash to
+        // different locks, while `a` and `b` share a block so they collide.
+        assert!(l.a.shares_lock_with(&l.b));
+        assert!(!l.c[0].shares_lock_with(&l.c[1]));
+        assert!(!l.c[1].shares_lock_with(&l.c[2]));
+        assert!(l.c[2].shares_lock_with(&l.c[2]));
+        assert!(!Atomic::new(0u8).shares_lock_with(&Atomic::new(0u8)));
+    }
+
+    #[cfg(all(feature = "std", unix))]
+    #[test]
+    fn atomic_signal_handler() {
+        static COUNTER: Atomic<u64> = Atomic::new_lock_free(0);
+        static HANDLED: Atomic<u64> = Atomic::new_lock_free(0);
+        static STOP: Atomic<bool> = Atomic::new(false);
+
+        extern "C" fn handler(_: libc::c_int) {
+            COUNTER.fetch_add(1, Relaxed);
+            HANDLED.fetch_add(1, Relaxed);
+        }
+
+        assert!(Atomic::<u64>::is_async_signal_safe());
+        assert!(!Atomic::<Bar>::is_async_signal_safe());
+        unsafe {
+            let handler: extern "C" fn(libc::c_int) = handler;
+            libc::signal(libc::SIGUSR1, handler as libc::sighandler_t);
+        }
+
+        // Interrupt this thread as often as possible while it is updating the
+        // same atomic as the handler.
+        let target = unsafe { libc::pthread_self() };
+        let sender = std::thread::spawn(move || {
+            while !STOP.load(Relaxed) {
+                unsafe {
+                    libc::pthread_kill(target, libc::SIGUSR1);
+                }
+            }
+        });
+        const N: u64 = 2_000_000;
+        for _ in 0..N {
+            COUNTER.fetch_add(1, Relaxed);
+        }
+        STOP.store(true, Relaxed);
+        sender.join().unwrap();
+        unsafe {
+            libc::signal(libc::SIGUSR1, libc::SIG_IGN);
+        }
+
+        assert!(HANDLED.load(Relaxed) > 0);
+        assert_eq!(COUNTER.load(Relaxed), N + HANDLED.load(Relaxed));
+    }
+
+    #[test]
+    fn atomic_wait_until() {
+        let data = Atomic::new(0u64);
+        let ready = Atomic::new(Foo(0, 0));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                data.store(42, Relaxed);
+                ready.store(Foo(1, 0), Release);
+            });
+            assert_eq!(ready.wait_until(Acquire, |x| x.0 == 1), Foo(1, 0));
+            assert_eq!(data.load(Relaxed), 42);
+        });
+        assert_eq!(
+            ready.try_wait_until(Acquire, 0, |x| x.1 == 1),
+            Err(Foo(1, 0))
+        );
+        assert_eq!(
+            ready.try_wait_until(Acquire, 0, |x| x.0 == 1),
+            Ok(Foo(1, 0))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    fn ping_pong<T: NoUninit + PartialEq + Send + Sync>(f: impl Fn(u32) -> T + Sync) {
+        let a = Atomic::new(f(0));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..1000 {
+                    let mut v = a.load(Acquire);
+                    while v != f(2 * i + 1) {
+                        a.wait(v, Acquire);
+                        v = a.load(Acquire);
+                    }
+                    a.store(f(2 * i + 2), Release);
+                    a.notify_one();
+                }
+            });
+            for i in 0..1000 {
+                let mut v = a.load(Acquire);
+                while v != f(2 * i) {
+                    a.wait(v, Acquire);
+                    v = a.load(Acquire);
+                }
+                a.store(f(2 * i + 1), Release);
+                a.notify_one();
+            }
+        });
+        assert!(a.load(SeqCst) == f(2000));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn atomic_wait_ping_pong() {
+        ping_pong(|x| x as u8);
+        ping_pong(|x| x as u16);
+        ping_pong(|x| x);
+        ping_pong(|x| x as u64);
+        ping_pong(|x| x as u128);
+        ping_pong(|x| Foo(x as u8, (x >> 8) as u8));
+        ping_pong(|x| Bar(x as u64, 0));
+    }
+
+    #[cfg(feature = "std")]
+    fn wait_timeout<T: NoUninit + PartialEq + Send + Sync>(f: impl Fn(u32) -> T + Sync) {
+        use crate::WaitResult;
+        use std::time::{Duration, Instant};
+
+        let a = Atomic::new(f(0));
+        assert_eq!(
+            a.wait_timeout(f(1), Duration::from_secs(10), Acquire),
+            WaitResult::Changed
+        );
+        let start = Instant::now();
+        assert_eq!(
+            a.wait_timeout(f(0), Duration::from_millis(20), Acquire),
+            WaitResult::TimedOut
+        );
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_secs(5));
+
+        // A change followed by a notification must never be missed.
+        for i in 0..200 {
+            std::thread::scope(|s| {
+                s.spawn(|| {
+                    a.store(f(i + 1), Release);
+                    a.notify_all();
+                });
+                let mut v = a.load(Acquire);
+                while v == f(i) {
+                    let result = a.wait_timeout(v, Duration::from_secs(10), Acquire);
+                    assert!(!result.timed_out());
+                    v = a.load(Acquire);
+                }
+            });
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn atomic_wait_timeout() {
+        wait_timeout(|x| x as u8);
+        wait_timeout(|x| x);
+        wait_timeout(|x| x as u64);
+        wait_timeout(|x| Foo(x as u8, 0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn atomic_wait_notify_all() {
+        for _ in 0..50 {
+            let a = Atomic::new(Foo(0, 0));
+            std::thread::scope(|s| {
+                for _ in 0..8 {
+                    s.spawn(|| {
+                        while a.load(Acquire) == Foo(0, 0) {
+                            a.wait(Foo(0, 0), Acquire);
+                        }
+                    });
+                }
+                a.store(Foo(1, 1), Release);
+                a.notify_all();
+            });
+        }
+    }
 }
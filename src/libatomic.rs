@@ -0,0 +1,216 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Replacement for the fallback implementation which calls the generic
+// functions of the platform's libatomic instead of using our own spinlocks.
+// This is what C compilers emit for `__atomic_*` builtins on objects which
+// aren't lock-free, so both languages use the same locks for shared objects.
+//
+// The functions have the same signatures as those in `fallback.rs`. All
+// operations use `__ATOMIC_SEQ_CST`, matching the fallback which ignores the
+// requested ordering since the lock is sequentially consistent anyway.
+// Read-modify-write operations which libatomic doesn't provide generically are
+// implemented as compare-exchange loops.
+
+use core::cmp;
+use core::ffi::{c_int, c_void};
+use core::mem::{self, MaybeUninit};
+use core::num::Wrapping;
+use core::ops;
+
+#[link(name = "atomic")]
+extern "C" {
+    fn __atomic_load(size: usize, ptr: *mut c_void, ret: *mut c_void, order: c_int);
+    fn __atomic_store(size: usize, ptr: *mut c_void, val: *mut c_void, order: c_int);
+    fn __atomic_exchange(
+        size: usize,
+        ptr: *mut c_void,
+        val: *mut c_void,
+        ret: *mut c_void,
+        order: c_int,
+    );
+    fn __atomic_compare_exchange(
+        size: usize,
+        ptr: *mut c_void,
+        expected: *mut c_void,
+        desired: *mut c_void,
+        success: c_int,
+        failure: c_int,
+    ) -> bool;
+}
+
+const SEQ_CST: c_int = 5;
+
+// libatomic doesn't expose which lock protects an object.
+#[inline]
+pub fn lock_index(_addr: usize) -> usize {
+    0
+}
+
+// See the function with the same name in `fallback.rs`.
+#[inline]
+fn detect_fallback<T>() {
+    #[cfg(all(feature = "debug-detect-fallback", debug_assertions))]
+    panic!(
+        "`Atomic<{}>` is not lock-free and is using the fallback implementation",
+        core::any::type_name::<T>()
+    );
+}
+
+#[inline]
+pub unsafe fn atomic_load<T>(dst: *mut T) -> T {
+    detect_fallback::<T>();
+    let mut ret = MaybeUninit::<T>::uninit();
+    __atomic_load(
+        mem::size_of::<T>(),
+        dst.cast(),
+        ret.as_mut_ptr().cast(),
+        SEQ_CST,
+    );
+    ret.assume_init()
+}
+
+// libatomic has no cheaper way to read a snapshot.
+#[inline]
+pub unsafe fn atomic_load_optimistic<T>(dst: *mut T) -> T {
+    atomic_load(dst)
+}
+
+#[inline]
+pub unsafe fn atomic_store<T>(dst: *mut T, val: T) {
+    detect_fallback::<T>();
+    let mut val = mem::ManuallyDrop::new(val);
+    __atomic_store(
+        mem::size_of::<T>(),
+        dst.cast(),
+        (&mut *val as *mut T).cast(),
+        SEQ_CST,
+    );
+}
+
+#[inline]
+pub unsafe fn atomic_swap<T>(dst: *mut T, val: T) -> T {
+    detect_fallback::<T>();
+    let mut val = mem::ManuallyDrop::new(val);
+    let mut ret = MaybeUninit::<T>::uninit();
+    __atomic_exchange(
+        mem::size_of::<T>(),
+        dst.cast(),
+        (&mut *val as *mut T).cast(),
+        ret.as_mut_ptr().cast(),
+        SEQ_CST,
+    );
+    ret.assume_init()
+}
+
+// Like the fallback, libatomic compares the bytes of the values.
+#[inline]
+pub unsafe fn atomic_compare_exchange<T: Copy>(dst: *mut T, current: T, new: T) -> Result<T, T> {
+    detect_fallback::<T>();
+    let mut current = current;
+    let mut new = new;
+    if __atomic_compare_exchange(
+        mem::size_of::<T>(),
+        dst.cast(),
+        (&mut current as *mut T).cast(),
+        (&mut new as *mut T).cast(),
+        SEQ_CST,
+        SEQ_CST,
+    ) {
+        Ok(current)
+    } else {
+        Err(current)
+    }
+}
+
+#[inline]
+unsafe fn atomic_update<T: Copy>(dst: *mut T, f: impl Fn(T) -> T) -> T {
+    let mut current = atomic_load(dst);
+    loop {
+        match atomic_compare_exchange(dst, current, f(current)) {
+            Ok(prev) => return prev,
+            Err(prev) => current = prev,
+        }
+    }
+}
+
+#[inline]
+pub unsafe fn atomic_add<T: Copy>(dst: *mut T, val: T) -> T
+where
+    Wrapping<T>: ops::Add<Output = Wrapping<T>>,
+{
+    atomic_update(dst, |x| (Wrapping(x) + Wrapping(val)).0)
+}
+
+#[inline]
+pub unsafe fn atomic_sub<T: Copy>(dst: *mut T, val: T) -> T
+where
+    Wrapping<T>: ops::Sub<Output = Wrapping<T>>,
+{
+    atomic_update(dst, |x| (Wrapping(x) - Wrapping(val)).0)
+}
+
+#[inline]
+pub unsafe fn atomic_and<T: Copy + ops::BitAnd<Output = T>>(dst: *mut T, val: T) -> T {
+    atomic_update(dst, |x| x & val)
+}
+
+#[inline]
+pub unsafe fn atomic_or<T: Copy + ops::BitOr<Output = T>>(dst: *mut T, val: T) -> T {
+    atomic_update(dst, |x| x | val)
+}
+
+#[inline]
+pub unsafe fn atomic_xor<T: Copy + ops::BitXor<Output = T>>(dst: *mut T, val: T) -> T {
+    atomic_update(dst, |x| x ^ val)
+}
+
+#[inline]
+pub unsafe fn atomic_min<T: Copy + cmp::Ord>(dst: *mut T, val: T) -> T {
+    atomic_update(dst, |x| cmp::min(x, val))
+}
+
+#[inline]
+pub unsafe fn atomic_max<T: Copy + cmp::Ord>(dst: *mut T, val: T) -> T {
+    atomic_update(dst, |x| cmp::max(x, val))
+}
+
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_load_raw(dst: *mut u8, ret: *mut u8, size: usize) {
+    __atomic_load(size, dst.cast(), ret.cast(), SEQ_CST);
+}
+
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_store_raw(dst: *mut u8, val: *const u8, size: usize) {
+    __atomic_store(size, dst.cast(), val as *mut c_void, SEQ_CST);
+}
+
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_swap_raw(dst: *mut u8, val: *const u8, ret: *mut u8, size: usize) {
+    __atomic_exchange(size, dst.cast(), val as *mut c_void, ret.cast(), SEQ_CST);
+}
+
+#[cfg(feature = "cabi")]
+#[inline]
+pub unsafe fn atomic_compare_exchange_raw(
+    dst: *mut u8,
+    current: *mut u8,
+    new: *const u8,
+    size: usize,
+) -> bool {
+    __atomic_compare_exchange(
+        size,
+        dst.cast(),
+        current.cast(),
+        new as *mut c_void,
+        SEQ_CST,
+        SEQ_CST,
+    )
+}
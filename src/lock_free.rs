@@ -0,0 +1,629 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+
+use crate::{ops, Atomic, Backoff};
+
+/// Used by `static_assert_lock_free!`. The size and alignment are passed as
+/// parameters so that they show up in the error message.
+#[doc(hidden)]
+pub struct AssertLockFree<T, const SIZE: usize, const ALIGN: usize>(PhantomData<T>);
+
+impl<T, const SIZE: usize, const ALIGN: usize> AssertLockFree<T, SIZE, ALIGN> {
+    #[doc(hidden)]
+    pub const OK: () = if !ops::atomic_is_lock_free::<T>() {
+        // Use the macro from `core` so the error is the same with `std`.
+        core::panic!(
+            "`Atomic<T>` is not lock-free on the current target for this type \
+             (the type, size and alignment are the parameters of `AssertLockFree`)"
+        )
+    };
+}
+
+/// Asserts at compile time that `Atomic` objects of the given types are
+/// lock-free on the current target.
+///
+/// This can be used in item position, for example to check that a type shared
+/// with a signal handler or another language never falls back to a lock. On
+/// failure the error names the type along with its size and alignment.
+///
+/// # Examples
+///
+/// ```rust
+/// #[derive(Copy, Clone, bytemuck::NoUninit)]
+/// #[repr(C, align(8))]
+/// struct FfiHeader {
+///     tag: u16,
+///     flags: u16,
+///     len: u32,
+/// }
+///
+/// atomic::static_assert_lock_free!(u32, FfiHeader);
+/// ```
+///
+/// ```compile_fail
+/// #[derive(Copy, Clone, bytemuck::NoUninit)]
+/// #[repr(C)]
+/// struct FfiHeader {
+///     tag: u16,
+///     flags: u16,
+///     len: u32,
+/// }
+///
+/// // Only aligned to 4 bytes, so the 8-byte atomics can't be used.
+/// atomic::static_assert_lock_free!(FfiHeader);
+/// ```
+#[macro_export]
+macro_rules! static_assert_lock_free {
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            const _: () = $crate::AssertLockFree::<
+                $ty,
+                { ::core::mem::size_of::<$ty>() },
+                { ::core::mem::align_of::<$ty>() },
+            >::OK;
+        )+
+    };
+}
+
+/// A native atomic type from `core`, used to implement `LockFree`.
+trait Native {
+    type Int: Copy;
+
+    fn load(&self, order: Ordering) -> Self::Int;
+    fn store(&self, val: Self::Int, order: Ordering);
+    fn swap(&self, val: Self::Int, order: Ordering) -> Self::Int;
+    fn compare_exchange(
+        &self,
+        current: Self::Int,
+        new: Self::Int,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self::Int, Self::Int>;
+    fn compare_exchange_weak(
+        &self,
+        current: Self::Int,
+        new: Self::Int,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self::Int, Self::Int>;
+}
+
+macro_rules! native {
+    ($([$($cfg:tt)*] $atomic:ident $int:ty,)*) => ($(
+        #[cfg($($cfg)*)]
+        impl Native for core::sync::atomic::$atomic {
+            type Int = $int;
+
+            #[inline]
+            fn load(&self, order: Ordering) -> $int {
+                self.load(order)
+            }
+            #[inline]
+            fn store(&self, val: $int, order: Ordering) {
+                self.store(val, order)
+            }
+            #[inline]
+            fn swap(&self, val: $int, order: Ordering) -> $int {
+                self.swap(val, order)
+            }
+            #[inline]
+            fn compare_exchange(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.compare_exchange(current, new, success, failure)
+            }
+            #[inline]
+            fn compare_exchange_weak(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.compare_exchange_weak(current, new, success, failure)
+            }
+        }
+    )*);
+}
+native! {
+    [target_has_atomic = "8"] AtomicU8 u8,
+    [target_has_atomic = "16"] AtomicU16 u16,
+    [target_has_atomic = "32"] AtomicU32 u32,
+    [target_has_atomic = "64"] AtomicU64 u64,
+    [all(feature = "nightly", target_has_atomic = "128")] AtomicU128 u128,
+}
+
+impl Native for ops::AtomicZst {
+    type Int = ();
+
+    #[inline]
+    fn load(&self, order: Ordering) {
+        self.load(order)
+    }
+    #[inline]
+    fn store(&self, val: (), order: Ordering) {
+        self.store(val, order)
+    }
+    #[inline]
+    fn swap(&self, val: (), order: Ordering) {
+        self.swap(val, order)
+    }
+    #[inline]
+    fn compare_exchange(
+        &self,
+        current: (),
+        new: (),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(), ()> {
+        self.compare_exchange(current, new, success, failure)
+    }
+    #[inline]
+    fn compare_exchange_weak(
+        &self,
+        current: (),
+        new: (),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(), ()> {
+        self.compare_exchange_weak(current, new, success, failure)
+    }
+}
+
+#[cfg(atomic_asm_128)]
+impl Native for crate::atomic128::AtomicU128 {
+    type Int = u128;
+
+    #[inline]
+    fn load(&self, order: Ordering) -> u128 {
+        self.load(order)
+    }
+    #[inline]
+    fn store(&self, val: u128, order: Ordering) {
+        self.store(val, order)
+    }
+    #[inline]
+    fn swap(&self, val: u128, order: Ordering) -> u128 {
+        self.swap(val, order)
+    }
+    #[inline]
+    fn compare_exchange(
+        &self,
+        current: u128,
+        new: u128,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u128, u128> {
+        self.compare_exchange(current, new, success, failure)
+    }
+    #[inline]
+    fn compare_exchange_weak(
+        &self,
+        current: u128,
+        new: u128,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u128, u128> {
+        self.compare_exchange_weak(current, new, success, failure)
+    }
+}
+
+unsafe fn load<T, A: Native>(dst: *mut T, order: Ordering) -> T {
+    mem::transmute_copy(&(*(dst as *const A)).load(order))
+}
+
+unsafe fn store<T, A: Native>(dst: *mut T, val: T, order: Ordering) {
+    (*(dst as *const A)).store(mem::transmute_copy(&val), order)
+}
+
+unsafe fn swap<T, A: Native>(dst: *mut T, val: T, order: Ordering) -> T {
+    mem::transmute_copy(&(*(dst as *const A)).swap(mem::transmute_copy(&val), order))
+}
+
+unsafe fn map_result<T, U>(r: Result<T, T>) -> Result<U, U> {
+    match r {
+        Ok(x) => Ok(mem::transmute_copy(&x)),
+        Err(x) => Err(mem::transmute_copy(&x)),
+    }
+}
+
+unsafe fn compare_exchange<T, A: Native>(
+    dst: *mut T,
+    current: T,
+    new: T,
+    success: Ordering,
+    failure: Ordering,
+) -> Result<T, T> {
+    map_result((*(dst as *const A)).compare_exchange(
+        mem::transmute_copy(&current),
+        mem::transmute_copy(&new),
+        success,
+        failure,
+    ))
+}
+
+unsafe fn compare_exchange_weak<T, A: Native>(
+    dst: *mut T,
+    current: T,
+    new: T,
+    success: Ordering,
+    failure: Ordering,
+) -> Result<T, T> {
+    map_result((*(dst as *const A)).compare_exchange_weak(
+        mem::transmute_copy(&current),
+        mem::transmute_copy(&new),
+        success,
+        failure,
+    ))
+}
+
+type CompareExchange<T> = unsafe fn(*mut T, T, T, Ordering, Ordering) -> Result<T, T>;
+
+// The operations for a type, selected at compile time. Calls through these
+// function pointers are resolved during constant evaluation, so unlike the
+// size dispatch of `Atomic`, the other sizes and the fallback are never
+// instantiated, even in debug builds.
+struct VTable<T> {
+    load: unsafe fn(*mut T, Ordering) -> T,
+    store: unsafe fn(*mut T, T, Ordering),
+    swap: unsafe fn(*mut T, T, Ordering) -> T,
+    compare_exchange: CompareExchange<T>,
+    compare_exchange_weak: CompareExchange<T>,
+}
+
+impl<T> VTable<T> {
+    const fn of<A: Native>() -> VTable<T> {
+        VTable {
+            load: load::<T, A>,
+            store: store::<T, A>,
+            swap: swap::<T, A>,
+            compare_exchange: compare_exchange::<T, A>,
+            compare_exchange_weak: compare_exchange_weak::<T, A>,
+        }
+    }
+}
+
+/// An atomic wrapper type which is guaranteed to be lock-free.
+///
+/// This has the same API as `Atomic<T>`, but its operations are implemented
+/// directly with the native atomic type of the matching size, without the
+/// size dispatch and fallback path of `Atomic`. This keeps the generated code
+/// small even in debug builds, which matters on small microcontrollers.
+///
+/// Using a type which is not lock-free on the current target fails to compile.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{LockFree, Ordering};
+///
+/// #[derive(Copy, Clone, Debug, PartialEq, bytemuck::NoUninit)]
+/// #[repr(C, align(4))]
+/// struct Rgba(u8, u8, u8, u8);
+///
+/// static COLOR: LockFree<Rgba> = LockFree::new(Rgba(0, 0, 0, 255));
+/// COLOR.store(Rgba(255, 0, 0, 255), Ordering::Relaxed);
+/// assert_eq!(COLOR.load(Ordering::Relaxed), Rgba(255, 0, 0, 255));
+/// ```
+///
+/// ```compile_fail
+/// let a = atomic::LockFree::new([0u8; 3]);
+/// ```
+#[repr(transparent)]
+pub struct LockFree<T>(Atomic<T>);
+
+impl<T> LockFree<T> {
+    /// Creates a new `LockFree`.
+    #[inline]
+    pub const fn new(v: T) -> LockFree<T> {
+        LockFree(Atomic::new_lock_free(v))
+    }
+
+    const VTABLE: VTable<T> = match mem::size_of::<T>() {
+        0 => VTable::of::<ops::AtomicZst>(),
+        #[cfg(target_has_atomic = "8")]
+        1 => VTable::of::<core::sync::atomic::AtomicU8>(),
+        #[cfg(target_has_atomic = "16")]
+        2 if mem::align_of::<T>() >= 2 => VTable::of::<core::sync::atomic::AtomicU16>(),
+        #[cfg(target_has_atomic = "32")]
+        4 if mem::align_of::<T>() >= 4 => VTable::of::<core::sync::atomic::AtomicU32>(),
+        #[cfg(target_has_atomic = "64")]
+        8 if mem::align_of::<T>() >= 8 => VTable::of::<core::sync::atomic::AtomicU64>(),
+        #[cfg(all(feature = "nightly", target_has_atomic = "128"))]
+        16 if mem::align_of::<T>() >= 16 => VTable::of::<core::sync::atomic::AtomicU128>(),
+        #[cfg(atomic_asm_128)]
+        16 if mem::align_of::<T>() >= 16 => VTable::of::<crate::atomic128::AtomicU128>(),
+        _ => core::panic!("`LockFree<T>` is not lock-free for this type on the current target"),
+    };
+}
+
+impl<T: NoUninit> LockFree<T> {
+    #[inline]
+    fn inner_ptr(&self) -> *mut T {
+        self.0.inner_ptr()
+    }
+
+    /// Returns a mutable reference to the underlying type.
+    ///
+    /// This is safe because the mutable reference guarantees that no other threads are
+    /// concurrently accessing the atomic data.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+
+    /// Consumes the `LockFree` and returns the contained value.
+    ///
+    /// This is safe because passing `self` by value guarantees that no other threads are
+    /// concurrently accessing the atomic data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+
+    /// Loads a value from the `LockFree`.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> T {
+        unsafe { (Self::VTABLE.load)(self.inner_ptr(), order) }
+    }
+
+    /// Stores a value into the `LockFree`.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, val: T, order: Ordering) {
+        unsafe { (Self::VTABLE.store)(self.inner_ptr(), val, order) }
+    }
+
+    /// Stores a value into the `LockFree`, returning the old value.
+    ///
+    /// See `Atomic::swap`.
+    #[inline]
+    pub fn swap(&self, val: T, order: Ordering) -> T {
+        unsafe { (Self::VTABLE.swap)(self.inner_ptr(), val, order) }
+    }
+
+    /// Stores a value into the `LockFree` if the current value is the same as
+    /// the `current` value.
+    ///
+    /// See `Atomic::compare_exchange`.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: T,
+        new: T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<T, T> {
+        unsafe { (Self::VTABLE.compare_exchange)(self.inner_ptr(), current, new, success, failure) }
+    }
+
+    /// Stores a value into the `LockFree` if the current value is the same as
+    /// the `current` value, possibly failing spuriously.
+    ///
+    /// See `Atomic::compare_exchange_weak`.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: T,
+        new: T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<T, T> {
+        unsafe {
+            (Self::VTABLE.compare_exchange_weak)(self.inner_ptr(), current, new, success, failure)
+        }
+    }
+
+    /// Fetches the value, and applies a function to it that returns an optional
+    /// new value.
+    ///
+    /// See `Atomic::fetch_update`.
+    #[inline]
+    pub fn fetch_update<F>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: F,
+    ) -> Result<T, T>
+    where
+        F: FnMut(T) -> Option<T>,
+    {
+        let backoff = Backoff::new();
+        let mut prev = self.load(fetch_order);
+        while let Some(next) = f(prev) {
+            match self.compare_exchange_weak(prev, next, set_order, fetch_order) {
+                x @ Ok(_) => return x,
+                Err(next_prev) => prev = next_prev,
+            }
+            backoff.spin();
+        }
+        Err(prev)
+    }
+}
+
+impl<T: Default> Default for LockFree<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T: NoUninit + fmt::Debug> fmt::Debug for LockFree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LockFree")
+            .field(&self.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl LockFree<bool> {
+    #[inline]
+    fn native(&self) -> &core::sync::atomic::AtomicBool {
+        unsafe { &*(self.inner_ptr() as *const core::sync::atomic::AtomicBool) }
+    }
+
+    /// Logical "and" with a boolean value, returning the previous value.
+    #[inline]
+    pub fn fetch_and(&self, val: bool, order: Ordering) -> bool {
+        self.native().fetch_and(val, order)
+    }
+
+    /// Logical "or" with a boolean value, returning the previous value.
+    #[inline]
+    pub fn fetch_or(&self, val: bool, order: Ordering) -> bool {
+        self.native().fetch_or(val, order)
+    }
+
+    /// Logical "xor" with a boolean value, returning the previous value.
+    #[inline]
+    pub fn fetch_xor(&self, val: bool, order: Ordering) -> bool {
+        self.native().fetch_xor(val, order)
+    }
+}
+
+macro_rules! lock_free_int_ops {
+    ($([$($cfg:tt)*] $t:ty, $atomic:ident;)*) => ($(
+        #[cfg($($cfg)*)]
+        impl LockFree<$t> {
+            #[inline]
+            fn native(&self) -> &core::sync::atomic::$atomic {
+                unsafe { &*(self.inner_ptr() as *const core::sync::atomic::$atomic) }
+            }
+
+            /// Add to the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_add(&self, val: $t, order: Ordering) -> $t {
+                self.native().fetch_add(val, order)
+            }
+
+            /// Subtract from the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_sub(&self, val: $t, order: Ordering) -> $t {
+                self.native().fetch_sub(val, order)
+            }
+
+            /// Bitwise and with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_and(&self, val: $t, order: Ordering) -> $t {
+                self.native().fetch_and(val, order)
+            }
+
+            /// Bitwise or with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_or(&self, val: $t, order: Ordering) -> $t {
+                self.native().fetch_or(val, order)
+            }
+
+            /// Bitwise xor with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_xor(&self, val: $t, order: Ordering) -> $t {
+                self.native().fetch_xor(val, order)
+            }
+
+            /// Minimum with the current value.
+            #[inline]
+            pub fn fetch_min(&self, val: $t, order: Ordering) -> $t {
+                self.native().fetch_min(val, order)
+            }
+
+            /// Maximum with the current value.
+            #[inline]
+            pub fn fetch_max(&self, val: $t, order: Ordering) -> $t {
+                self.native().fetch_max(val, order)
+            }
+        }
+    )*);
+}
+lock_free_int_ops! {
+    [target_has_atomic = "8"] i8, AtomicI8;
+    [target_has_atomic = "8"] u8, AtomicU8;
+    [target_has_atomic = "16"] i16, AtomicI16;
+    [target_has_atomic = "16"] u16, AtomicU16;
+    [target_has_atomic = "32"] i32, AtomicI32;
+    [target_has_atomic = "32"] u32, AtomicU32;
+    [target_has_atomic = "64"] i64, AtomicI64;
+    [target_has_atomic = "64"] u64, AtomicU64;
+    [target_has_atomic = "ptr"] isize, AtomicIsize;
+    [target_has_atomic = "ptr"] usize, AtomicUsize;
+    [all(feature = "nightly", target_has_atomic = "128")] i128, AtomicI128;
+    [all(feature = "nightly", target_has_atomic = "128")] u128, AtomicU128;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LockFree;
+    use crate::Ordering::*;
+    use bytemuck::NoUninit;
+
+    #[derive(Copy, Clone, Debug, PartialEq, NoUninit)]
+    #[repr(C, align(8))]
+    struct Pair(u32, u32);
+
+    #[test]
+    fn lock_free_generic() {
+        let a = LockFree::new(Pair(1, 2));
+        assert_eq!(format!("{:?}", a), "LockFree(Pair(1, 2))");
+        assert_eq!(a.load(SeqCst), Pair(1, 2));
+        a.store(Pair(3, 4), SeqCst);
+        assert_eq!(a.swap(Pair(5, 6), SeqCst), Pair(3, 4));
+        assert_eq!(
+            a.compare_exchange(Pair(0, 0), Pair(7, 8), SeqCst, SeqCst),
+            Err(Pair(5, 6))
+        );
+        assert_eq!(
+            a.compare_exchange(Pair(5, 6), Pair(7, 8), SeqCst, SeqCst),
+            Ok(Pair(5, 6))
+        );
+        assert_eq!(
+            a.fetch_update(SeqCst, SeqCst, |p| Some(Pair(p.1, p.0))),
+            Ok(Pair(7, 8))
+        );
+        assert_eq!(a.into_inner(), Pair(8, 7));
+    }
+
+    #[test]
+    fn lock_free_int() {
+        let a = LockFree::new(0i32);
+        assert_eq!(a.fetch_add(5, SeqCst), 0);
+        assert_eq!(a.fetch_sub(10, SeqCst), 5);
+        assert_eq!(a.fetch_min(-10, SeqCst), -5);
+        assert_eq!(a.fetch_max(3, SeqCst), -10);
+        assert_eq!(a.fetch_and(2, SeqCst), 3);
+        assert_eq!(a.fetch_or(4, SeqCst), 2);
+        assert_eq!(a.fetch_xor(1, SeqCst), 6);
+        assert_eq!(a.load(SeqCst), 7);
+
+        let b = LockFree::new(u8::MAX);
+        assert_eq!(b.fetch_add(1, SeqCst), u8::MAX);
+        assert_eq!(b.fetch_max(9, SeqCst), 0);
+        assert_eq!(b.load(SeqCst), 9);
+    }
+
+    #[test]
+    fn lock_free_bool() {
+        let a = LockFree::<bool>::default();
+        assert!(!a.fetch_or(true, SeqCst));
+        assert!(a.fetch_and(false, SeqCst));
+        assert!(!a.fetch_xor(true, SeqCst));
+        assert!(a.load(SeqCst));
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Metric handles backed by `Atomic`s. metrics implements the handle traits for
+// `Arc<T>` whenever `T` implements them, so a recorder can hand out an
+// `Arc<Atomic<u64>>` directly with `Counter::from_arc`. The orderings match
+// metrics' own implementations for the std atomics.
+
+use ::metrics::{CounterFn, GaugeFn};
+
+use crate::{Atomic, Ordering};
+
+/// Uses the `Atomic` as a counter.
+///
+/// `absolute` only ever raises the value, as required by `CounterFn`.
+///
+/// This requires the `metrics` feature.
+impl CounterFn for Atomic<u64> {
+    #[inline]
+    fn increment(&self, value: u64) {
+        self.fetch_add(value, Ordering::Release);
+    }
+
+    #[inline]
+    fn absolute(&self, value: u64) {
+        self.fetch_max(value, Ordering::AcqRel);
+    }
+}
+
+#[inline]
+fn update(a: &Atomic<f64>, f: impl Fn(f64) -> f64) {
+    let _ = a.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |x| Some(f(x)));
+}
+
+/// Uses the `Atomic` as a gauge.
+///
+/// `increment` and `decrement` are implemented with a `compare_exchange` loop.
+///
+/// This requires the `metrics` feature.
+impl GaugeFn for Atomic<f64> {
+    #[inline]
+    fn increment(&self, value: f64) {
+        update(self, |x| x + value);
+    }
+
+    #[inline]
+    fn decrement(&self, value: f64) {
+        update(self, |x| x - value);
+    }
+
+    #[inline]
+    fn set(&self, value: f64) {
+        self.store(value, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Atomic, Ordering};
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    #[derive(Default)]
+    struct TestRecorder {
+        counters: Mutex<Vec<Arc<Atomic<u64>>>>,
+        gauges: Mutex<Vec<Arc<Atomic<f64>>>>,
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, _: &Key, _: &Metadata<'_>) -> Counter {
+            let c = Arc::new(Atomic::new(0));
+            self.counters.lock().unwrap().push(c.clone());
+            Counter::from_arc(c)
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            let g = Arc::new(Atomic::new(0.0));
+            self.gauges.lock().unwrap().push(g.clone());
+            Gauge::from_arc(g)
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn recorder() {
+        let recorder = TestRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            let c = metrics::counter!("requests");
+            c.increment(3);
+            c.increment(2);
+            c.absolute(4);
+            let g = metrics::gauge!("load");
+            g.set(1.5);
+            g.increment(2.0);
+            g.decrement(0.25);
+        });
+        let counters = recorder.counters.lock().unwrap();
+        assert_eq!(counters[0].load(Ordering::Relaxed), 5);
+        metrics::CounterFn::absolute(&counters[0], 10);
+        assert_eq!(counters[0].load(Ordering::Relaxed), 10);
+        let gauges = recorder.gauges.lock().unwrap();
+        assert_eq!(gauges[0].load(Ordering::Relaxed), 3.25);
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+
+use crate::Atomic;
+
+/// The running minimum and maximum of recorded samples, such as latencies.
+///
+/// The minimum and maximum are two separate atomics, each updated with a
+/// compare-and-swap loop which only writes when the sample is a new minimum
+/// or maximum. Recording a sample which is neither is just two loads.
+///
+/// `T` only needs to be `PartialOrd`, so floats are supported as well. A
+/// sample which doesn't compare with the current value, such as a NaN, is
+/// ignored.
+///
+/// The minimum and maximum start at the values given to `new`, usually the
+/// largest and smallest values of `T`, and `reset` puts them back.
+///
+/// # Resetting
+///
+/// `reset` swaps the minimum and then the maximum, so it isn't atomic with
+/// respect to a concurrent `record`: a sample recorded during a `reset` may
+/// count towards the minimum of one window and the maximum of the next one.
+/// Every sample is still counted in some window, so this doesn't matter for
+/// scrape-and-reset metrics where windows are much longer than a `record`.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicMinMax, Ordering};
+///
+/// static LATENCY_US: AtomicMinMax<u64> = AtomicMinMax::new(u64::MAX, 0);
+///
+/// for sample in [120, 80, 300] {
+///     LATENCY_US.record(sample, Ordering::Relaxed);
+/// }
+/// assert_eq!(LATENCY_US.reset(Ordering::Relaxed), (80, 300));
+/// assert_eq!(LATENCY_US.min(Ordering::Relaxed), u64::MAX);
+/// ```
+pub struct AtomicMinMax<T> {
+    min: Atomic<T>,
+    max: Atomic<T>,
+    initial: (T, T),
+}
+
+impl<T: Copy> AtomicMinMax<T> {
+    /// Creates a new `AtomicMinMax` with the given initial minimum and
+    /// maximum.
+    ///
+    /// These are also the values restored by `reset`. To start without any
+    /// samples, use the largest value of `T` as the minimum and the smallest
+    /// value as the maximum.
+    #[inline]
+    pub const fn new(min: T, max: T) -> AtomicMinMax<T> {
+        AtomicMinMax {
+            min: Atomic::new(min),
+            max: Atomic::new(max),
+            initial: (min, max),
+        }
+    }
+
+    /// Consumes the `AtomicMinMax` and returns the minimum and maximum.
+    #[inline]
+    pub fn into_inner(self) -> (T, T) {
+        (self.min.into_inner(), self.max.into_inner())
+    }
+}
+
+impl<T: NoUninit + PartialOrd> AtomicMinMax<T> {
+    /// Records a sample, updating the minimum and maximum.
+    ///
+    /// `order` is the ordering of the successful updates.
+    #[inline]
+    pub fn record(&self, sample: T, order: Ordering) {
+        let _ = self.min.fetch_update(order, Ordering::Relaxed, |min| {
+            (sample < min).then_some(sample)
+        });
+        let _ = self.max.fetch_update(order, Ordering::Relaxed, |max| {
+            (sample > max).then_some(sample)
+        });
+    }
+
+    /// Returns the minimum.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn min(&self, order: Ordering) -> T {
+        self.min.load(order)
+    }
+
+    /// Returns the maximum.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn max(&self, order: Ordering) -> T {
+        self.max.load(order)
+    }
+
+    /// Restores the initial minimum and maximum, returning the previous ones.
+    ///
+    /// See the type's documentation for the race with a concurrent `record`.
+    #[inline]
+    pub fn reset(&self, order: Ordering) -> (T, T) {
+        let (min, max) = self.initial;
+        (self.min.swap(min, order), self.max.swap(max, order))
+    }
+}
+
+impl<T: NoUninit + PartialOrd + fmt::Debug> fmt::Debug for AtomicMinMax<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicMinMax")
+            .field("min", &self.min(Ordering::Relaxed))
+            .field("max", &self.max(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use std::vec::Vec;
+
+    // A xorshift generator, so that each thread gets its own samples.
+    fn samples(seed: u64, n: usize) -> Vec<u64> {
+        let mut x = seed | 1;
+        (0..n)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x % 1_000_000
+            })
+            .collect()
+    }
+
+    #[test]
+    fn operations() {
+        let m = AtomicMinMax::new(i32::MAX, i32::MIN);
+        m.record(5, SeqCst);
+        assert_eq!((m.min(SeqCst), m.max(SeqCst)), (5, 5));
+        m.record(-3, SeqCst);
+        m.record(2, SeqCst);
+        assert_eq!(format!("{:?}", m), "AtomicMinMax { min: -3, max: 5 }");
+        assert_eq!(m.reset(SeqCst), (-3, 5));
+        assert_eq!(m.reset(SeqCst), (i32::MAX, i32::MIN));
+        m.record(7, SeqCst);
+        assert_eq!(m.into_inner(), (7, 7));
+    }
+
+    #[test]
+    fn floats() {
+        let m = AtomicMinMax::new(f64::INFINITY, f64::NEG_INFINITY);
+        m.record(1.5, SeqCst);
+        m.record(f64::NAN, SeqCst);
+        m.record(-0.5, SeqCst);
+        assert_eq!(m.into_inner(), (-0.5, 1.5));
+    }
+
+    #[test]
+    fn concurrent_record() {
+        const THREADS: u64 = 8;
+        let n = if cfg!(miri) { 50 } else { 10_000 };
+
+        let all: Vec<Vec<u64>> = (0..THREADS).map(|t| samples(t + 1, n)).collect();
+        let m = AtomicMinMax::new(u64::MAX, 0);
+        std::thread::scope(|s| {
+            for samples in &all {
+                let m = &m;
+                s.spawn(move || {
+                    for &sample in samples {
+                        m.record(sample, Relaxed);
+                    }
+                });
+            }
+        });
+        let flat = all.iter().flatten().copied();
+        let expected = (flat.clone().min().unwrap(), flat.max().unwrap());
+        assert_eq!(m.reset(SeqCst), expected);
+    }
+}
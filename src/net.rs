@@ -0,0 +1,322 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use crate::{Atomic, OptionRepr};
+
+#[inline]
+const fn ip_to_bits(ip: Ipv4Addr) -> u32 {
+    u32::from_be_bytes(ip.octets())
+}
+
+#[inline]
+fn ip_from_bits(bits: u32) -> Ipv4Addr {
+    Ipv4Addr::from(bits)
+}
+
+// The address is stored above the port, in the low 48 bits.
+#[inline]
+const fn socket_to_bits(addr: SocketAddrV4) -> u64 {
+    (ip_to_bits(*addr.ip()) as u64) << 16 | addr.port() as u64
+}
+
+#[inline]
+fn socket_from_bits(bits: u64) -> SocketAddrV4 {
+    SocketAddrV4::new(ip_from_bits((bits >> 16) as u32), bits as u16)
+}
+
+// `Option<Ipv4Addr>` and `Option<SocketAddrV4>` are stored with a flag bit
+// above the address, like the integers.
+impl OptionRepr for Ipv4Addr {
+    type Repr = u64;
+    const NONE: u64 = 0;
+
+    #[inline]
+    fn into_repr(self) -> u64 {
+        ip_to_bits(self) as u64 | 1 << 32
+    }
+
+    #[inline]
+    fn from_repr(repr: u64) -> Option<Ipv4Addr> {
+        (repr != 0).then(|| ip_from_bits(repr as u32))
+    }
+}
+
+impl OptionRepr for SocketAddrV4 {
+    type Repr = u64;
+    const NONE: u64 = 0;
+
+    #[inline]
+    fn into_repr(self) -> u64 {
+        socket_to_bits(self) | 1 << 48
+    }
+
+    #[inline]
+    fn from_repr(repr: u64) -> Option<SocketAddrV4> {
+        (repr != 0).then(|| socket_from_bits(repr & ((1 << 48) - 1)))
+    }
+}
+
+macro_rules! atomic_addr {
+    (
+        $(#[$attr:meta])*
+        $name:ident($t:ty, $bits:ty, $to_bits:ident, $from_bits:ident, $default:expr)
+    ) => {
+        $(#[$attr])*
+        #[repr(transparent)]
+        pub struct $name(Atomic<$bits>);
+
+        impl $name {
+            #[doc = concat!("Creates a new `", stringify!($name), "`.")]
+            #[inline]
+            pub const fn new(addr: $t) -> $name {
+                $name(Atomic::new($to_bits(addr)))
+            }
+
+            /// Returns `true` if operations on values of this type are
+            /// lock-free.
+            #[inline]
+            pub const fn is_lock_free() -> bool {
+                Atomic::<$bits>::is_lock_free()
+            }
+
+            #[doc = concat!("Consumes the `", stringify!($name), "` and returns the address.")]
+            #[inline]
+            pub fn into_inner(self) -> $t {
+                $from_bits(self.0.into_inner())
+            }
+
+            /// Loads the address.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `order` is `Release` or `AcqRel`.
+            #[inline]
+            pub fn load(&self, order: Ordering) -> $t {
+                $from_bits(self.0.load(order))
+            }
+
+            /// Stores an address.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `order` is `Acquire` or `AcqRel`.
+            #[inline]
+            pub fn store(&self, addr: $t, order: Ordering) {
+                self.0.store($to_bits(addr), order)
+            }
+
+            /// Stores an address, returning the previous address.
+            #[inline]
+            pub fn swap(&self, addr: $t, order: Ordering) -> $t {
+                $from_bits(self.0.swap($to_bits(addr), order))
+            }
+
+            /// Stores an address if the current address is `current`.
+            ///
+            /// The return value is a result indicating whether the new address
+            /// was written and containing the previous address. See
+            /// `Atomic::compare_exchange`.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: $t,
+                new: $t,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$t, $t> {
+                match self
+                    .0
+                    .compare_exchange($to_bits(current), $to_bits(new), success, failure)
+                {
+                    Ok(bits) => Ok($from_bits(bits)),
+                    Err(bits) => Err($from_bits(bits)),
+                }
+            }
+        }
+
+        impl Default for $name {
+            #[inline]
+            fn default() -> Self {
+                Self::new($default)
+            }
+        }
+
+        impl From<$t> for $name {
+            #[inline]
+            fn from(addr: $t) -> Self {
+                Self::new(addr)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_tuple(stringify!($name))
+                    .field(&self.load(Ordering::Relaxed))
+                    .finish()
+            }
+        }
+    };
+}
+
+atomic_addr! {
+    /// An `Ipv4Addr` which can be shared between threads, such as an upstream
+    /// address which is reloaded at runtime.
+    ///
+    /// The address is stored as a `u32` in a single `Atomic<u32>`, so it is
+    /// lock-free wherever 32-bit atomics are, and loads never observe a
+    /// mixture of two addresses. Use `AtomicOption<Ipv4Addr>` for an address
+    /// which may be absent.
+    ///
+    /// This requires the `std` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{AtomicIpv4Addr, Ordering};
+    /// use std::net::Ipv4Addr;
+    ///
+    /// static UPSTREAM: AtomicIpv4Addr = AtomicIpv4Addr::new(Ipv4Addr::LOCALHOST);
+    ///
+    /// // On reload:
+    /// UPSTREAM.store(Ipv4Addr::new(10, 0, 0, 7), Ordering::Release);
+    /// assert_eq!(UPSTREAM.load(Ordering::Acquire), Ipv4Addr::new(10, 0, 0, 7));
+    /// ```
+    AtomicIpv4Addr(Ipv4Addr, u32, ip_to_bits, ip_from_bits, Ipv4Addr::UNSPECIFIED)
+}
+
+atomic_addr! {
+    /// A `SocketAddrV4` which can be shared between threads.
+    ///
+    /// A `SocketAddrV4` may contain padding, so it can't be stored in an
+    /// `Atomic` directly. The address and the port are packed into the low 48
+    /// bits of a single `Atomic<u64>` instead, so this is lock-free wherever
+    /// 64-bit atomics are, and loads never observe the address of one store
+    /// with the port of another. Use `AtomicOption<SocketAddrV4>` for an
+    /// address which may be absent.
+    ///
+    /// This requires the `std` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use atomic::{AtomicSocketAddrV4, Ordering};
+    /// use std::net::{Ipv4Addr, SocketAddrV4};
+    ///
+    /// let endpoint = AtomicSocketAddrV4::new(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8080));
+    /// let new = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 7), 443);
+    /// assert_eq!(endpoint.swap(new, Ordering::AcqRel).port(), 8080);
+    /// assert_eq!(endpoint.load(Ordering::Acquire), new);
+    /// ```
+    AtomicSocketAddrV4(
+        SocketAddrV4,
+        u64,
+        socket_to_bits,
+        socket_from_bits,
+        SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AtomicOption;
+    use crate::Ordering::*;
+    use core::sync::atomic::AtomicBool;
+
+    #[test]
+    fn ipv4() {
+        let a = AtomicIpv4Addr::default();
+        assert_eq!(a.load(SeqCst), Ipv4Addr::UNSPECIFIED);
+        a.store(Ipv4Addr::new(192, 168, 1, 2), SeqCst);
+        assert_eq!(
+            a.compare_exchange(Ipv4Addr::LOCALHOST, Ipv4Addr::BROADCAST, SeqCst, SeqCst),
+            Err(Ipv4Addr::new(192, 168, 1, 2))
+        );
+        assert_eq!(
+            a.swap(Ipv4Addr::LOCALHOST, SeqCst),
+            Ipv4Addr::new(192, 168, 1, 2)
+        );
+        assert_eq!(format!("{:?}", a), "AtomicIpv4Addr(127.0.0.1)");
+        assert_eq!(a.into_inner(), Ipv4Addr::LOCALHOST);
+        assert_eq!(
+            AtomicIpv4Addr::is_lock_free(),
+            cfg!(target_has_atomic = "32")
+        );
+    }
+
+    #[test]
+    fn socket_v4() {
+        let addr = SocketAddrV4::new(Ipv4Addr::BROADCAST, u16::MAX);
+        let a = AtomicSocketAddrV4::from(addr);
+        assert_eq!(a.load(SeqCst), addr);
+        let other = SocketAddrV4::new(Ipv4Addr::new(1, 2, 3, 4), 0);
+        assert_eq!(a.compare_exchange(addr, other, SeqCst, SeqCst), Ok(addr));
+        assert_eq!(format!("{:?}", a), "AtomicSocketAddrV4(1.2.3.4:0)");
+        assert_eq!(a.into_inner(), other);
+        assert_eq!(
+            AtomicSocketAddrV4::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+    }
+
+    #[test]
+    fn option() {
+        let ip = AtomicOption::<Ipv4Addr>::empty();
+        assert_eq!(ip.load(SeqCst), None);
+        // The unspecified address is distinct from `None`.
+        assert_eq!(ip.insert(Ipv4Addr::UNSPECIFIED, SeqCst), None);
+        assert_eq!(ip.take(SeqCst), Some(Ipv4Addr::UNSPECIFIED));
+
+        let addr = SocketAddrV4::new(Ipv4Addr::BROADCAST, u16::MAX);
+        let socket = AtomicOption::new(Some(addr));
+        assert_eq!(socket.take(SeqCst), Some(addr));
+        assert_eq!(socket.take(SeqCst), None);
+        assert_eq!(
+            AtomicOption::<SocketAddrV4>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+    }
+
+    #[test]
+    fn concurrent_swap() {
+        // Every stored address has all four octets equal to the port, so a
+        // torn load would show up as a mixture.
+        fn addr(i: u8) -> SocketAddrV4 {
+            SocketAddrV4::new(Ipv4Addr::new(i, i, i, i), u16::from(i))
+        }
+
+        let a = AtomicSocketAddrV4::new(addr(0));
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for t in 0..2u8 {
+                let (a, done) = (&a, &done);
+                s.spawn(move || {
+                    let mut i = t;
+                    while !done.load(Relaxed) {
+                        a.store(addr(i), Release);
+                        i = i.wrapping_add(2);
+                    }
+                });
+            }
+            let rounds = if cfg!(miri) { 50 } else { 100_000 };
+            for _ in 0..rounds {
+                let loaded = a.load(Acquire);
+                let [b, c, d, e] = loaded.ip().octets();
+                assert!(
+                    b == c && c == d && d == e && u16::from(b) == loaded.port(),
+                    "torn load: {}",
+                    loaded
+                );
+            }
+            done.store(true, Relaxed);
+        });
+    }
+}
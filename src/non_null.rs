@@ -0,0 +1,435 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[inline]
+const fn to_ptr<T>(v: Option<NonNull<T>>) -> *mut T {
+    match v {
+        Some(p) => p.as_ptr(),
+        None => ptr::null_mut(),
+    }
+}
+
+#[inline]
+pub(crate) fn non_null<T>(p: *mut T) -> NonNull<T> {
+    // The pointer was stored from a `NonNull`.
+    unsafe { NonNull::new_unchecked(p) }
+}
+
+/// An atomic `NonNull<T>`.
+///
+/// This is the equivalent of an `Atomic<NonNull<T>>`, which isn't possible
+/// since pointers don't implement `NoUninit`. It is backed by an
+/// `AtomicPtr<T>` rather than an integer, so the pointers which are loaded
+/// keep the provenance of the pointers which were stored and can be
+/// dereferenced. It is always lock-free.
+///
+/// Comparisons in `compare_exchange` only compare the addresses of the
+/// pointers, not the values they point to. This means that they can't detect
+/// a pointer which was replaced and then stored again (the ABA problem).
+///
+/// `swap` and the compare-and-swap operations are only available on targets
+/// with atomic read-modify-write operations on pointers.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicNonNull, Ordering};
+/// use std::ptr::NonNull;
+///
+/// let mut a = 1;
+/// let mut b = 2;
+/// let p = AtomicNonNull::new(NonNull::from(&mut a));
+/// let old = p.swap(NonNull::from(&mut b), Ordering::AcqRel);
+/// assert_eq!(unsafe { *old.as_ptr() }, 1);
+/// assert_eq!(unsafe { *p.load(Ordering::Acquire).as_ptr() }, 2);
+/// ```
+#[repr(transparent)]
+pub struct AtomicNonNull<T>(AtomicPtr<T>);
+
+impl<T> AtomicNonNull<T> {
+    /// Creates a new `AtomicNonNull`.
+    #[inline]
+    pub const fn new(v: NonNull<T>) -> AtomicNonNull<T> {
+        AtomicNonNull(AtomicPtr::new(v.as_ptr()))
+    }
+
+    /// Returns a mutable reference to the underlying pointer.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut NonNull<T> {
+        // `NonNull<T>` has the same layout as `*mut T`.
+        unsafe { &mut *(self.0.get_mut() as *mut *mut T as *mut NonNull<T>) }
+    }
+
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub(crate) fn as_atomic_ptr(&self) -> &AtomicPtr<T> {
+        &self.0
+    }
+
+    /// Consumes the `AtomicNonNull` and returns the contained pointer.
+    #[inline]
+    pub fn into_inner(self) -> NonNull<T> {
+        non_null(self.0.into_inner())
+    }
+
+    /// Loads the pointer from the `AtomicNonNull`.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> NonNull<T> {
+        non_null(self.0.load(order))
+    }
+
+    /// Stores a pointer into the `AtomicNonNull`.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, val: NonNull<T>, order: Ordering) {
+        self.0.store(val.as_ptr(), order)
+    }
+
+    /// Stores a pointer into the `AtomicNonNull`, returning the previous one.
+    ///
+    /// See `Atomic::swap`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn swap(&self, val: NonNull<T>, order: Ordering) -> NonNull<T> {
+        non_null(self.0.swap(val.as_ptr(), order))
+    }
+
+    /// Stores a pointer into the `AtomicNonNull` if the current pointer has
+    /// the same address as `current`.
+    ///
+    /// See `Atomic::compare_exchange`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: NonNull<T>,
+        new: NonNull<T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<NonNull<T>, NonNull<T>> {
+        self.0
+            .compare_exchange(current.as_ptr(), new.as_ptr(), success, failure)
+            .map(non_null)
+            .map_err(non_null)
+    }
+
+    /// Stores a pointer into the `AtomicNonNull` if the current pointer has
+    /// the same address as `current`, possibly failing spuriously.
+    ///
+    /// See `Atomic::compare_exchange_weak`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: NonNull<T>,
+        new: NonNull<T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<NonNull<T>, NonNull<T>> {
+        self.0
+            .compare_exchange_weak(current.as_ptr(), new.as_ptr(), success, failure)
+            .map(non_null)
+            .map_err(non_null)
+    }
+}
+
+impl<T> From<NonNull<T>> for AtomicNonNull<T> {
+    #[inline]
+    fn from(v: NonNull<T>) -> Self {
+        Self::new(v)
+    }
+}
+
+impl<T> fmt::Debug for AtomicNonNull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicNonNull")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// An atomic `Option<NonNull<T>>`.
+///
+/// This is the equivalent of an `Atomic<Option<NonNull<T>>>`, with `None`
+/// represented by a null pointer. Like `AtomicNonNull`, it is backed by an
+/// `AtomicPtr<T>` so pointer provenance is preserved, and `compare_exchange`
+/// only compares addresses. Its read-modify-write operations have the same
+/// target requirements.
+///
+/// # Examples
+///
+/// A Treiber stack:
+///
+/// ```rust
+/// use atomic::{AtomicOptionNonNull, Ordering};
+/// use std::ptr::NonNull;
+///
+/// struct Node {
+///     value: u32,
+///     next: Option<NonNull<Node>>,
+/// }
+///
+/// let head = AtomicOptionNonNull::<Node>::new(None);
+///
+/// let node = NonNull::from(Box::leak(Box::new(Node { value: 1, next: None })));
+/// let mut next = head.load(Ordering::Relaxed);
+/// loop {
+///     unsafe { (*node.as_ptr()).next = next };
+///     match head.compare_exchange_weak(next, Some(node), Ordering::Release, Ordering::Relaxed) {
+///         Ok(_) => break,
+///         Err(current) => next = current,
+///     }
+/// }
+///
+/// let top = head.swap(None, Ordering::Acquire).unwrap();
+/// let node = unsafe { Box::from_raw(top.as_ptr()) };
+/// assert_eq!(node.value, 1);
+/// ```
+#[repr(transparent)]
+pub struct AtomicOptionNonNull<T>(AtomicPtr<T>);
+
+impl<T> AtomicOptionNonNull<T> {
+    /// Creates a new `AtomicOptionNonNull`.
+    #[inline]
+    pub const fn new(v: Option<NonNull<T>>) -> AtomicOptionNonNull<T> {
+        AtomicOptionNonNull(AtomicPtr::new(to_ptr(v)))
+    }
+
+    /// Returns a mutable reference to the underlying pointer.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Option<NonNull<T>> {
+        // `Option<NonNull<T>>` has the same layout as `*mut T`.
+        unsafe { &mut *(self.0.get_mut() as *mut *mut T as *mut Option<NonNull<T>>) }
+    }
+
+    /// Consumes the `AtomicOptionNonNull` and returns the contained pointer.
+    #[inline]
+    pub fn into_inner(self) -> Option<NonNull<T>> {
+        NonNull::new(self.0.into_inner())
+    }
+
+    /// Loads the pointer from the `AtomicOptionNonNull`.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Option<NonNull<T>> {
+        NonNull::new(self.0.load(order))
+    }
+
+    /// Stores a pointer into the `AtomicOptionNonNull`.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, val: Option<NonNull<T>>, order: Ordering) {
+        self.0.store(to_ptr(val), order)
+    }
+
+    /// Stores a pointer into the `AtomicOptionNonNull`, returning the previous
+    /// one.
+    ///
+    /// See `Atomic::swap`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn swap(&self, val: Option<NonNull<T>>, order: Ordering) -> Option<NonNull<T>> {
+        NonNull::new(self.0.swap(to_ptr(val), order))
+    }
+
+    /// Stores a pointer into the `AtomicOptionNonNull` if the current pointer
+    /// has the same address as `current`.
+    ///
+    /// See `Atomic::compare_exchange`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: Option<NonNull<T>>,
+        new: Option<NonNull<T>>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<NonNull<T>>, Option<NonNull<T>>> {
+        self.0
+            .compare_exchange(to_ptr(current), to_ptr(new), success, failure)
+            .map(NonNull::new)
+            .map_err(NonNull::new)
+    }
+
+    /// Stores a pointer into the `AtomicOptionNonNull` if the current pointer
+    /// has the same address as `current`, possibly failing spuriously.
+    ///
+    /// See `Atomic::compare_exchange_weak`.
+    #[cfg(target_has_atomic = "ptr")]
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: Option<NonNull<T>>,
+        new: Option<NonNull<T>>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<NonNull<T>>, Option<NonNull<T>>> {
+        self.0
+            .compare_exchange_weak(to_ptr(current), to_ptr(new), success, failure)
+            .map(NonNull::new)
+            .map_err(NonNull::new)
+    }
+}
+
+impl<T> Default for AtomicOptionNonNull<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<T> From<Option<NonNull<T>>> for AtomicOptionNonNull<T> {
+    #[inline]
+    fn from(v: Option<NonNull<T>>) -> Self {
+        Self::new(v)
+    }
+}
+
+impl<T> fmt::Debug for AtomicOptionNonNull<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicOptionNonNull")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    #[test]
+    fn non_null() {
+        let mut values = [1, 2];
+        let a = NonNull::from(&mut values[0]);
+        let b = NonNull::from(&mut values[1]);
+        let mut p = AtomicNonNull::new(a);
+        assert_eq!(p.compare_exchange(b, b, SeqCst, SeqCst), Err(a));
+        assert_eq!(p.compare_exchange(a, b, SeqCst, SeqCst), Ok(a));
+        assert_eq!(p.swap(a, SeqCst), b);
+        unsafe { *p.load(SeqCst).as_ptr() += 10 };
+        *p.get_mut() = b;
+        assert_eq!(p.into_inner(), b);
+        assert_eq!(values, [11, 2]);
+    }
+
+    #[test]
+    fn option_non_null() {
+        let mut value = 1;
+        let a = NonNull::from(&mut value);
+        let mut p = AtomicOptionNonNull::default();
+        assert_eq!(p.compare_exchange(Some(a), None, SeqCst, SeqCst), Err(None));
+        assert_eq!(p.compare_exchange(None, Some(a), SeqCst, SeqCst), Ok(None));
+        assert_eq!(p.load(SeqCst), Some(a));
+        assert_eq!(*p.get_mut(), Some(a));
+        p.store(None, SeqCst);
+        assert_eq!(p.into_inner(), None);
+    }
+
+    struct Node {
+        value: usize,
+        next: Option<NonNull<Node>>,
+    }
+
+    struct Stack {
+        head: AtomicOptionNonNull<Node>,
+    }
+
+    unsafe impl Sync for Stack {}
+
+    impl Stack {
+        fn push(&self, value: usize) {
+            let node = NonNull::from(Box::leak(Box::new(Node { value, next: None })));
+            let mut next = self.head.load(Relaxed);
+            loop {
+                unsafe { (*node.as_ptr()).next = next };
+                match self
+                    .head
+                    .compare_exchange_weak(next, Some(node), Release, Relaxed)
+                {
+                    Ok(_) => return,
+                    Err(current) => next = current,
+                }
+            }
+        }
+
+        // The node is returned rather than freed, since other threads may still
+        // be reading its `next` field.
+        fn pop(&self) -> Option<NonNull<Node>> {
+            let mut head = self.head.load(Acquire);
+            loop {
+                let node = head?;
+                let next = unsafe { (*node.as_ptr()).next };
+                match self
+                    .head
+                    .compare_exchange_weak(head, next, Acquire, Acquire)
+                {
+                    Ok(_) => return Some(node),
+                    Err(current) => head = current,
+                }
+            }
+        }
+    }
+
+    struct SendPtr(NonNull<Node>);
+
+    unsafe impl Send for SendPtr {}
+
+    #[test]
+    fn treiber_stack() {
+        const THREADS: usize = 4;
+        const PUSHES: usize = if cfg!(miri) { 10 } else { 1000 };
+
+        let stack = Stack {
+            head: AtomicOptionNonNull::new(None),
+        };
+        let popped: Vec<Vec<SendPtr>> = std::thread::scope(|s| {
+            let threads: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let stack = &stack;
+                    s.spawn(move || {
+                        let mut popped = Vec::new();
+                        for i in 0..PUSHES {
+                            stack.push(t * PUSHES + i);
+                            if i % 2 == 0 {
+                                popped.extend(stack.pop().map(SendPtr));
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+
+        // Free the popped nodes and those left on the stack, and check that
+        // each value which was pushed is found exactly once.
+        let mut seen = std::vec![0; THREADS * PUSHES];
+        for SendPtr(node) in popped.into_iter().flatten() {
+            let node = unsafe { Box::from_raw(node.as_ptr()) };
+            seen[node.value] += 1;
+        }
+        let mut head = stack.head.into_inner();
+        while let Some(node) = head {
+            let node = unsafe { Box::from_raw(node.as_ptr()) };
+            seen[node.value] += 1;
+            head = node.next;
+        }
+        assert!(seen.iter().all(|&n| n == 1));
+    }
+}
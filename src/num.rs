@@ -0,0 +1,205 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+use num_traits::float::FloatCore;
+use num_traits::{PrimInt, WrappingAdd, WrappingSub};
+
+use crate::Atomic;
+
+#[inline]
+fn update<T: NoUninit>(a: &Atomic<T>, order: Ordering, mut f: impl FnMut(T) -> T) -> T {
+    let fetch_order = match order {
+        Ordering::Release => Ordering::Relaxed,
+        Ordering::AcqRel => Ordering::Acquire,
+        order => order,
+    };
+    match a.fetch_update(order, fetch_order, |x| Some(f(x))) {
+        Ok(x) | Err(x) => x,
+    }
+}
+
+/// Arithmetic operations on `Atomic`s of any integer type implementing
+/// `num_traits::PrimInt`.
+///
+/// The primitive integer types already have inherent methods with the same
+/// names, which take precedence and use native instructions where possible.
+/// This trait makes the same operations available to other integer types,
+/// such as newtypes, and to code which is generic over `PrimInt`. They are
+/// implemented with a `compare_exchange` loop.
+///
+/// This requires the `num-traits` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, AtomicPrimInt, Ordering};
+/// use num_traits::{PrimInt, WrappingAdd, WrappingSub};
+///
+/// fn record<T>(max: &Atomic<T>, total: &Atomic<T>, value: T)
+/// where
+///     T: PrimInt + WrappingAdd + WrappingSub + bytemuck::NoUninit,
+/// {
+///     AtomicPrimInt::fetch_max(max, value, Ordering::Relaxed);
+///     AtomicPrimInt::fetch_add(total, value, Ordering::Relaxed);
+/// }
+///
+/// let (max, total) = (Atomic::new(0u16), Atomic::new(0u16));
+/// record(&max, &total, 5);
+/// record(&max, &total, 3);
+/// assert_eq!(max.load(Ordering::Relaxed), 5);
+/// assert_eq!(total.load(Ordering::Relaxed), 8);
+/// ```
+pub trait AtomicPrimInt<T> {
+    /// Wrapping add to the current value, returning the previous value.
+    fn fetch_add(&self, val: T, order: Ordering) -> T;
+
+    /// Wrapping subtract from the current value, returning the previous value.
+    fn fetch_sub(&self, val: T, order: Ordering) -> T;
+
+    /// Minimum with the current value, returning the previous value.
+    fn fetch_min(&self, val: T, order: Ordering) -> T;
+
+    /// Maximum with the current value, returning the previous value.
+    fn fetch_max(&self, val: T, order: Ordering) -> T;
+}
+
+impl<T> AtomicPrimInt<T> for Atomic<T>
+where
+    T: PrimInt + WrappingAdd + WrappingSub + NoUninit,
+{
+    #[inline]
+    fn fetch_add(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x.wrapping_add(&val))
+    }
+
+    #[inline]
+    fn fetch_sub(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x.wrapping_sub(&val))
+    }
+
+    #[inline]
+    fn fetch_min(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x.min(val))
+    }
+
+    #[inline]
+    fn fetch_max(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x.max(val))
+    }
+}
+
+/// Arithmetic operations on `Atomic`s of floating-point types implementing
+/// `num_traits::float::FloatCore`, such as `f32` and `f64`.
+///
+/// These are implemented with a `compare_exchange` loop which compares the
+/// bits of the values, so they also work when the value is NaN.
+///
+/// This requires the `num-traits` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, AtomicFloat, Ordering};
+///
+/// let sum = Atomic::new(0.5f64);
+/// assert_eq!(sum.fetch_add(1.0, Ordering::Relaxed), 0.5);
+/// assert_eq!(sum.fetch_max(0.0, Ordering::Relaxed), 1.5);
+/// assert_eq!(sum.load(Ordering::Relaxed), 1.5);
+/// ```
+pub trait AtomicFloat<T> {
+    /// Add to the current value, returning the previous value.
+    fn fetch_add(&self, val: T, order: Ordering) -> T;
+
+    /// Subtract from the current value, returning the previous value.
+    fn fetch_sub(&self, val: T, order: Ordering) -> T;
+
+    /// Minimum with the current value, returning the previous value.
+    ///
+    /// This follows `FloatCore::min`, so NaN is only stored if both values are
+    /// NaN.
+    fn fetch_min(&self, val: T, order: Ordering) -> T;
+
+    /// Maximum with the current value, returning the previous value.
+    ///
+    /// This follows `FloatCore::max`, so NaN is only stored if both values are
+    /// NaN.
+    fn fetch_max(&self, val: T, order: Ordering) -> T;
+}
+
+impl<T: FloatCore + NoUninit> AtomicFloat<T> for Atomic<T> {
+    #[inline]
+    fn fetch_add(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x + val)
+    }
+
+    #[inline]
+    fn fetch_sub(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x - val)
+    }
+
+    #[inline]
+    fn fetch_min(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x.min(val))
+    }
+
+    #[inline]
+    fn fetch_max(&self, val: T, order: Ordering) -> T {
+        update(self, order, |x| x.max(val))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomicFloat, AtomicPrimInt};
+    use crate::{Atomic, Ordering::*};
+
+    #[test]
+    fn prim_int_generic() {
+        fn check<T>(a: &Atomic<T>, one: T, two: T)
+        where
+            T: num_traits::PrimInt
+                + num_traits::WrappingAdd
+                + num_traits::WrappingSub
+                + bytemuck::NoUninit
+                + core::fmt::Debug,
+        {
+            let zero = T::zero();
+            assert_eq!(AtomicPrimInt::fetch_add(a, two, SeqCst), zero);
+            assert_eq!(AtomicPrimInt::fetch_sub(a, one, SeqCst), two);
+            assert_eq!(AtomicPrimInt::fetch_max(a, two, SeqCst), one);
+            assert_eq!(AtomicPrimInt::fetch_min(a, zero, SeqCst), two);
+            assert_eq!(AtomicPrimInt::fetch_sub(a, one, SeqCst), zero);
+            assert_eq!(a.load(SeqCst), zero.wrapping_sub(&one));
+        }
+        check(&Atomic::new(0u8), 1, 2);
+        check(&Atomic::new(0u128), 1, 2);
+        check(&Atomic::new(0i64), 1, 2);
+
+        let a = Atomic::new(0u32);
+        AtomicPrimInt::fetch_sub(&a, 1, SeqCst);
+        assert_eq!(a.load(SeqCst), u32::MAX);
+    }
+
+    #[test]
+    fn float() {
+        let a = Atomic::new(1.0f32);
+        assert_eq!(a.fetch_add(2.5, SeqCst), 1.0);
+        assert_eq!(a.fetch_sub(0.5, SeqCst), 3.5);
+        assert_eq!(a.fetch_min(-1.0, SeqCst), 3.0);
+        assert_eq!(a.fetch_max(f32::NAN, SeqCst), -1.0);
+        assert_eq!(a.load(SeqCst), -1.0);
+
+        let b = Atomic::new(f64::NAN);
+        assert!(b.fetch_add(1.0, SeqCst).is_nan());
+        assert!(b.load(SeqCst).is_nan());
+        assert!(b.fetch_max(2.0, SeqCst).is_nan());
+        assert_eq!(b.load(SeqCst), 2.0);
+    }
+}
@@ -7,17 +7,29 @@
 
 use bytemuck::NoUninit;
 
-#[cfg(feature = "fallback")]
+#[cfg(all(feature = "fallback", not(feature = "libatomic")))]
 use crate::fallback;
+#[cfg(feature = "libatomic")]
+use crate::libatomic as fallback;
 use core::cmp;
 use core::mem;
 use core::num::Wrapping;
 use core::ops;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::AtomicPtr;
 use core::sync::atomic::Ordering;
 
+use crate::Backing;
+
 macro_rules! match_atomic {
     ($type:ident, $atomic:ident, $impl:expr, $fallback_impl:expr) => {
         match mem::size_of::<$type>() {
+            #[allow(clippy::unit_arg)]
+            0 => {
+                type $atomic = AtomicZst;
+
+                $impl
+            }
             #[cfg(target_has_atomic = "8")]
             1 if mem::align_of::<$type>() >= 1 => {
                 type $atomic = core::sync::atomic::AtomicU8;
@@ -48,10 +60,26 @@ macro_rules! match_atomic {
 
                 $impl
             }
+            #[cfg(atomic_asm_128)]
+            16 if mem::align_of::<$type>() >= 16 => {
+                type $atomic = crate::atomic128::AtomicU128;
+
+                $impl
+            }
+            #[cfg(atomic_cmpxchg16b_dynamic)]
+            16 if mem::align_of::<$type>() >= 16 && crate::atomic128::detect() => {
+                type $atomic = crate::atomic128::AtomicU128;
+
+                $impl
+            }
             #[cfg(feature = "fallback")]
             _ => $fallback_impl,
             #[cfg(not(feature = "fallback"))]
-            _ => panic!("Atomic operations for type `{}` are not available as the `fallback` feature of the `atomic` crate is disabled.", core::any::type_name::<$type>()),
+            _ => {
+                #[allow(clippy::let_unit_value)]
+                let () = RequireLockFree::<$type>::OK;
+                unreachable!()
+            }
         }
     };
 }
@@ -59,6 +87,12 @@ macro_rules! match_atomic {
 macro_rules! match_signed_atomic {
     ($type:ident, $atomic:ident, $impl:expr, $fallback_impl:expr) => {
         match mem::size_of::<$type>() {
+            #[allow(clippy::unit_arg)]
+            0 => {
+                type $atomic = AtomicZst;
+
+                $impl
+            }
             #[cfg(target_has_atomic = "8")]
             1 if mem::align_of::<$type>() >= 1 => {
                 type $atomic = core::sync::atomic::AtomicI8;
@@ -89,10 +123,163 @@ macro_rules! match_signed_atomic {
 
                 $impl
             }
+            #[cfg(atomic_asm_128)]
+            16 if mem::align_of::<$type>() >= 16 => {
+                type $atomic = crate::atomic128::AtomicI128;
+
+                $impl
+            }
+            #[cfg(atomic_cmpxchg16b_dynamic)]
+            16 if mem::align_of::<$type>() >= 16 && crate::atomic128::detect() => {
+                type $atomic = crate::atomic128::AtomicI128;
+
+                $impl
+            }
+            #[cfg(feature = "fallback")]
+            _ => $fallback_impl,
+            #[cfg(not(feature = "fallback"))]
+            _ => {
+                #[allow(clippy::let_unit_value)]
+                let () = RequireLockFree::<$type>::OK;
+                unreachable!()
+            }
+        }
+    };
+}
+
+// Zero-sized types have no bytes to read or write, so every operation on them
+// is trivially atomic. They are dispatched to this type, which does nothing,
+// instead of taking a lock.
+pub(crate) struct AtomicZst;
+
+impl AtomicZst {
+    #[inline]
+    pub(crate) fn load(&self, _: Ordering) {}
+    #[inline]
+    pub(crate) fn store(&self, _: (), _: Ordering) {}
+    #[inline]
+    pub(crate) fn swap(&self, _: (), _: Ordering) {}
+    #[inline]
+    pub(crate) fn compare_exchange(
+        &self,
+        _: (),
+        _: (),
+        _: Ordering,
+        _: Ordering,
+    ) -> Result<(), ()> {
+        Ok(())
+    }
+    #[inline]
+    pub(crate) fn compare_exchange_weak(
+        &self,
+        _: (),
+        _: (),
+        _: Ordering,
+        _: Ordering,
+    ) -> Result<(), ()> {
+        Ok(())
+    }
+    #[inline]
+    fn fetch_add(&self, _: (), _: Ordering) {}
+    #[inline]
+    fn fetch_sub(&self, _: (), _: Ordering) {}
+    #[inline]
+    fn fetch_and(&self, _: (), _: Ordering) {}
+    #[inline]
+    fn fetch_or(&self, _: (), _: Ordering) {}
+    #[inline]
+    fn fetch_xor(&self, _: (), _: Ordering) {}
+    #[inline]
+    fn fetch_min(&self, _: (), _: Ordering) {}
+    #[inline]
+    fn fetch_max(&self, _: (), _: Ordering) {}
+}
+
+// Without the fallback, using a type which isn't lock-free fails to compile.
+// The constants are referenced from every instantiation of the operations, but
+// only fail to evaluate for types which would have needed the fallback.
+#[cfg(not(feature = "fallback"))]
+struct RequireLockFree<T>(core::marker::PhantomData<T>);
+
+#[cfg(not(feature = "fallback"))]
+impl<T> RequireLockFree<T> {
+    const OK: () = if !atomic_is_lock_free::<T>() {
+        core::panic!("`Atomic<T>` is not lock-free for this type and the `fallback` feature of the `atomic` crate is disabled")
+    };
+    const LOAD_OK: () = if !atomic_is_lock_free_load::<T>() {
+        core::panic!("`Atomic<T>` is not lock-free for this type and the `fallback` feature of the `atomic` crate is disabled")
+    };
+}
+
+// Same as `match_atomic!`, but only requires the target to support atomic loads
+// and stores of the given size, not read-modify-write operations.
+//
+// `target_has_atomic_load_store` is unstable, so without the `nightly` feature
+// we conservatively require full atomic support like `match_atomic!`.
+macro_rules! match_atomic_load_store {
+    ($type:ident, $atomic:ident, $impl:expr, $fallback_impl:expr) => {
+        match mem::size_of::<$type>() {
+            #[allow(clippy::unit_arg)]
+            0 => {
+                type $atomic = AtomicZst;
+
+                $impl
+            }
+            #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "8"))]
+            #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "8"))]
+            1 if mem::align_of::<$type>() >= 1 => {
+                type $atomic = core::sync::atomic::AtomicU8;
+
+                $impl
+            }
+            #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "16"))]
+            #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "16"))]
+            2 if mem::align_of::<$type>() >= 2 => {
+                type $atomic = core::sync::atomic::AtomicU16;
+
+                $impl
+            }
+            #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "32"))]
+            #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "32"))]
+            4 if mem::align_of::<$type>() >= 4 => {
+                type $atomic = core::sync::atomic::AtomicU32;
+
+                $impl
+            }
+            #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "64"))]
+            #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "64"))]
+            8 if mem::align_of::<$type>() >= 8 => {
+                type $atomic = core::sync::atomic::AtomicU64;
+
+                $impl
+            }
+            #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "128"))]
+            #[cfg_attr(not(feature = "nightly"), cfg(any()))]
+            16 if mem::align_of::<$type>() >= 16 => {
+                type $atomic = core::sync::atomic::AtomicU128;
+
+                $impl
+            }
+            #[cfg(atomic_asm_128)]
+            16 if mem::align_of::<$type>() >= 16 => {
+                type $atomic = crate::atomic128::AtomicU128;
+
+                $impl
+            }
+            #[cfg(atomic_cmpxchg16b_dynamic)]
+            16 if mem::align_of::<$type>() >= 16 && crate::atomic128::detect() => {
+                type $atomic = crate::atomic128::AtomicU128;
+
+                $impl
+            }
             #[cfg(feature = "fallback")]
             _ => $fallback_impl,
             #[cfg(not(feature = "fallback"))]
-            _ => panic!("Atomic operations for type `{}` are not available as the `fallback` feature of the `atomic` crate is disabled.", core::any::type_name::<$type>()),
+            _ => {
+                #[allow(clippy::let_unit_value)]
+                let () = RequireLockFree::<$type>::LOAD_OK;
+                unreachable!()
+            }
         }
     };
 }
@@ -102,7 +289,8 @@ pub const fn atomic_is_lock_free<T>() -> bool {
     let size = mem::size_of::<T>();
     let align = mem::align_of::<T>();
 
-    (cfg!(target_has_atomic = "8") & (size == 1) & (align >= 1))
+    (size == 0)
+        | (cfg!(target_has_atomic = "8") & (size == 1) & (align >= 1))
         | (cfg!(target_has_atomic = "16") & (size == 2) & (align >= 2))
         | (cfg!(target_has_atomic = "32") & (size == 4) & (align >= 4))
         | (cfg!(target_has_atomic = "64") & (size == 8) & (align >= 8))
@@ -110,11 +298,91 @@ pub const fn atomic_is_lock_free<T>() -> bool {
             & cfg!(target_has_atomic = "128")
             & (size == 16)
             & (align >= 16))
+        | (cfg!(atomic_asm_128) & (size == 16) & (align >= 16))
 }
 
+// Same as `atomic_is_lock_free`, but also includes the types whose native
+// instructions are detected at runtime. This agrees with the size dispatch.
 #[inline]
-pub unsafe fn atomic_load<T: NoUninit>(dst: *mut T, order: Ordering) -> T {
+pub fn atomic_is_lock_free_runtime<T>() -> bool {
+    #[cfg(atomic_cmpxchg16b_dynamic)]
+    if mem::size_of::<T>() == 16 && mem::align_of::<T>() >= 16 {
+        return crate::atomic128::detect();
+    }
+    atomic_is_lock_free::<T>()
+}
+
+#[inline]
+pub const fn atomic_is_lock_free_load<T>() -> bool {
+    let size = mem::size_of::<T>();
+    let align = mem::align_of::<T>();
+
+    // This mirrors the arms of `match_atomic_load_store!`.
+    let supported = match size {
+        0 => true,
+        #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "8"))]
+        #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "8"))]
+        1 => true,
+        #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "16"))]
+        #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "16"))]
+        2 => true,
+        #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "32"))]
+        #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "32"))]
+        4 => true,
+        #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "64"))]
+        #[cfg_attr(not(feature = "nightly"), cfg(target_has_atomic = "64"))]
+        8 => true,
+        #[cfg_attr(feature = "nightly", cfg(target_has_atomic_load_store = "128"))]
+        #[cfg_attr(not(feature = "nightly"), cfg(any()))]
+        16 => true,
+        #[cfg(atomic_asm_128)]
+        16 => true,
+        _ => false,
+    };
+    supported && align >= size
+}
+
+#[inline]
+pub fn atomic_backing<T>(addr: usize) -> Backing {
+    let _ = addr;
     match_atomic!(
+        T,
+        A,
+        match mem::size_of::<A>() {
+            0 => Backing::ZeroSized,
+            1 => Backing::Native8,
+            2 => Backing::Native16,
+            4 => Backing::Native32,
+            8 => Backing::Native64,
+            _ => Backing::Native128,
+        },
+        Backing::Locked {
+            lock_index: fallback::lock_index(addr),
+        }
+    )
+}
+
+// Writes a value while holding one of the fallback locks. Loads of types which
+// only have native loads and stores don't take the lock, so the write must be
+// atomic for them as well.
+#[cfg(all(feature = "fallback", not(feature = "libatomic")))]
+#[inline]
+pub unsafe fn locked_write<T>(dst: *mut T, val: T) {
+    if atomic_is_lock_free_load::<T>() {
+        match_atomic_load_store!(
+            T,
+            A,
+            (*(dst as *const A)).store(mem::transmute_copy(&val), Ordering::SeqCst),
+            unreachable!()
+        )
+    } else {
+        core::ptr::write(dst, val)
+    }
+}
+
+#[inline]
+pub unsafe fn atomic_load<T: NoUninit>(dst: *mut T, order: Ordering) -> T {
+    match_atomic_load_store!(
         T,
         A,
         mem::transmute_copy(&(*(dst as *const A)).load(order)),
@@ -122,6 +390,16 @@ pub unsafe fn atomic_load<T: NoUninit>(dst: *mut T, order: Ordering) -> T {
     )
 }
 
+#[inline]
+pub unsafe fn atomic_load_snapshot<T: NoUninit>(dst: *mut T) -> T {
+    match_atomic_load_store!(
+        T,
+        A,
+        mem::transmute_copy(&(*(dst as *const A)).load(Ordering::Acquire)),
+        fallback::atomic_load_optimistic(dst)
+    )
+}
+
 #[inline]
 pub unsafe fn atomic_store<T: NoUninit>(dst: *mut T, val: T, order: Ordering) {
     match_atomic!(
@@ -299,3 +577,39 @@ pub unsafe fn atomic_umax<T: NoUninit + cmp::Ord>(dst: *mut T, val: T, order: Or
         fallback::atomic_max(dst, val)
     )
 }
+
+// `Atomic<*mut T>` has the layout of `*mut T`, so it can only be accessed as an
+// `AtomicPtr<T>` if their alignments match.
+#[cfg(target_has_atomic = "ptr")]
+const _: () = assert!(mem::align_of::<AtomicPtr<u8>>() == mem::align_of::<*mut u8>());
+
+// Replaces a pointer with `f` applied to it, returning the previous pointer. This
+// goes through `AtomicPtr` so that the provenance of the pointer is preserved.
+#[cfg(target_has_atomic = "ptr")]
+#[inline]
+pub unsafe fn atomic_update_ptr<T>(
+    dst: *mut *mut T,
+    order: Ordering,
+    f: impl Fn(*mut T) -> *mut T,
+) -> *mut T {
+    match atomic_try_update_ptr(dst, order, |p| Some(f(p))) {
+        Ok(p) | Err(p) => p,
+    }
+}
+
+// Same as `atomic_update_ptr`, but leaves the pointer unchanged and returns
+// `Err` if `f` returns `None`.
+#[cfg(target_has_atomic = "ptr")]
+#[inline]
+pub unsafe fn atomic_try_update_ptr<T>(
+    dst: *mut *mut T,
+    order: Ordering,
+    f: impl FnMut(*mut T) -> Option<*mut T>,
+) -> Result<*mut T, *mut T> {
+    let fetch_order = match order {
+        Ordering::Release => Ordering::Relaxed,
+        Ordering::AcqRel => Ordering::Acquire,
+        order => order,
+    };
+    (*(dst as *const AtomicPtr<T>)).fetch_update(order, fetch_order, f)
+}
@@ -0,0 +1,376 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::Ordering;
+
+use bytemuck::{NoUninit, Pod};
+
+use crate::Atomic;
+
+// Checks at compile time that both values fit in the storage type.
+struct Fits<A, B, S>(PhantomData<(A, B, S)>);
+
+impl<A, B, S> Fits<A, B, S> {
+    const OK: () = if mem::size_of::<A>() + mem::size_of::<B>() > mem::size_of::<S>() {
+        core::panic!("`A` and `B` don't fit in the storage type `S`")
+    };
+}
+
+/// Two values packed into a single atomic integer, so that they can be
+/// updated together.
+///
+/// The bytes of `A` and `B` are copied next to each other into an `S`, which
+/// defaults to `u64`, with the remaining bytes set to zero. The values are
+/// never shifted or masked as integers, so the packing doesn't depend on the
+/// endianness of the target. Types whose combined size exceeds the size of
+/// `S` fail to compile.
+///
+/// `AtomicPair<A, B, S>` is lock-free whenever `Atomic<S>` is. Use `u32` as
+/// the storage for pairs which fit in it on targets without 64-bit atomics.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicPair, Ordering};
+///
+/// // A count and a generation, which are always updated together.
+/// let pair = AtomicPair::<u32, u32>::new(0, 0);
+/// let bump = |(count, gen): (u32, u32)| Some((count + 1, gen.wrapping_add(1)));
+/// assert_eq!(pair.fetch_update(Ordering::AcqRel, Ordering::Acquire, bump), Ok((0, 0)));
+/// assert_eq!(pair.load(Ordering::Acquire), (1, 1));
+/// ```
+pub struct AtomicPair<A, B, S = u64> {
+    v: Atomic<S>,
+    marker: PhantomData<(A, B)>,
+}
+
+impl<A: NoUninit, B: NoUninit, S: Pod> AtomicPair<A, B, S> {
+    #[inline]
+    fn pack((a, b): (A, B)) -> S {
+        #[allow(clippy::let_unit_value)]
+        let () = Fits::<A, B, S>::OK;
+        let mut s = S::zeroed();
+        let bytes = bytemuck::bytes_of_mut(&mut s);
+        let (a_bytes, rest) = bytes.split_at_mut(mem::size_of::<A>());
+        a_bytes.copy_from_slice(bytemuck::bytes_of(&a));
+        rest[..mem::size_of::<B>()].copy_from_slice(bytemuck::bytes_of(&b));
+        s
+    }
+
+    #[inline]
+    fn unpack(s: S) -> (A, B) {
+        let bytes = bytemuck::bytes_of(&s);
+        // The storage is only ever written by `pack`, so these are the bytes
+        // of a valid `A` and `B`.
+        unsafe {
+            (
+                ptr::read_unaligned(bytes.as_ptr() as *const A),
+                ptr::read_unaligned(bytes[mem::size_of::<A>()..].as_ptr() as *const B),
+            )
+        }
+    }
+
+    /// Creates a new `AtomicPair`.
+    #[inline]
+    pub fn new(a: A, b: B) -> AtomicPair<A, B, S> {
+        AtomicPair {
+            v: Atomic::new(Self::pack((a, b))),
+            marker: PhantomData,
+        }
+    }
+
+    /// Checks if operations on an `AtomicPair<A, B, S>` are lock-free on the
+    /// current target.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        Atomic::<S>::is_lock_free()
+    }
+
+    /// Consumes the `AtomicPair` and returns the values.
+    #[inline]
+    pub fn into_inner(self) -> (A, B) {
+        Self::unpack(self.v.into_inner())
+    }
+
+    /// Loads both values.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> (A, B) {
+        Self::unpack(self.v.load(order))
+    }
+
+    /// Stores both values.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, val: (A, B), order: Ordering) {
+        self.v.store(Self::pack(val), order)
+    }
+
+    /// Stores both values, returning the previous values.
+    ///
+    /// See `Atomic::swap`.
+    #[inline]
+    pub fn swap(&self, val: (A, B), order: Ordering) -> (A, B) {
+        Self::unpack(self.v.swap(Self::pack(val), order))
+    }
+
+    /// Stores both values if the current values are the same as `current`.
+    ///
+    /// The values are compared by their bytes. See `Atomic::compare_exchange`.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: (A, B),
+        new: (A, B),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(A, B), (A, B)> {
+        match self
+            .v
+            .compare_exchange(Self::pack(current), Self::pack(new), success, failure)
+        {
+            Ok(s) => Ok(Self::unpack(s)),
+            Err(s) => Err(Self::unpack(s)),
+        }
+    }
+
+    /// Stores both values if the current values are the same as `current`,
+    /// possibly failing spuriously.
+    ///
+    /// See `Atomic::compare_exchange_weak`.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: (A, B),
+        new: (A, B),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(A, B), (A, B)> {
+        match self
+            .v
+            .compare_exchange_weak(Self::pack(current), Self::pack(new), success, failure)
+        {
+            Ok(s) => Ok(Self::unpack(s)),
+            Err(s) => Err(Self::unpack(s)),
+        }
+    }
+
+    /// Fetches both values, and applies a function to them that returns
+    /// optional new values.
+    ///
+    /// See `Atomic::fetch_update`.
+    #[inline]
+    pub fn fetch_update<F>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: F,
+    ) -> Result<(A, B), (A, B)>
+    where
+        F: FnMut((A, B)) -> Option<(A, B)>,
+    {
+        match self.v.fetch_update(set_order, fetch_order, |s| {
+            f(Self::unpack(s)).map(Self::pack)
+        }) {
+            Ok(s) => Ok(Self::unpack(s)),
+            Err(s) => Err(Self::unpack(s)),
+        }
+    }
+
+    /// Updates the first value with `f`, leaving the second value unchanged.
+    ///
+    /// This is a compare-and-swap loop over both values, so a concurrent
+    /// update of the second value makes it retry. Returns the previous values.
+    /// See `Atomic::fetch_update`.
+    #[inline]
+    pub fn fetch_update_first<F>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: F,
+    ) -> Result<(A, B), (A, B)>
+    where
+        F: FnMut(A) -> Option<A>,
+    {
+        self.fetch_update(set_order, fetch_order, |(a, b)| f(a).map(|a| (a, b)))
+    }
+
+    /// Updates the second value with `f`, leaving the first value unchanged.
+    ///
+    /// This is a compare-and-swap loop over both values, so a concurrent
+    /// update of the first value makes it retry. Returns the previous values.
+    /// See `Atomic::fetch_update`.
+    #[inline]
+    pub fn fetch_update_second<F>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: F,
+    ) -> Result<(A, B), (A, B)>
+    where
+        F: FnMut(B) -> Option<B>,
+    {
+        self.fetch_update(set_order, fetch_order, |(a, b)| f(b).map(|b| (a, b)))
+    }
+}
+
+impl<A: NoUninit + Default, B: NoUninit + Default, S: Pod> Default for AtomicPair<A, B, S> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(A::default(), B::default())
+    }
+}
+
+impl<A: NoUninit + fmt::Debug, B: NoUninit + fmt::Debug, S: Pod> fmt::Debug
+    for AtomicPair<A, B, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicPair")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use core::num::NonZeroU8;
+
+    #[test]
+    fn operations() {
+        let p = AtomicPair::<u32, u32>::new(1, 2);
+        assert_eq!(
+            AtomicPair::<u32, u32>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(p.swap((3, 4), SeqCst), (1, 2));
+        assert_eq!(
+            p.compare_exchange((3, 5), (0, 0), SeqCst, SeqCst),
+            Err((3, 4))
+        );
+        assert_eq!(
+            p.compare_exchange((3, 4), (5, 6), SeqCst, SeqCst),
+            Ok((3, 4))
+        );
+        assert_eq!(
+            p.fetch_update_first(SeqCst, SeqCst, |a| Some(a * 10)),
+            Ok((5, 6))
+        );
+        assert_eq!(
+            p.fetch_update_second(SeqCst, SeqCst, |_| None),
+            Err((50, 6))
+        );
+        assert_eq!(format!("{:?}", p), "AtomicPair((50, 6))");
+        assert_eq!(p.into_inner(), (50, 6));
+    }
+
+    #[test]
+    fn mixed_types() {
+        // Values of different sizes and alignments, with unused bytes in the
+        // storage, round-trip unchanged.
+        let p = AtomicPair::<NonZeroU8, u16, u32>::new(NonZeroU8::MAX, 0x1234);
+        assert_eq!(
+            AtomicPair::<NonZeroU8, u16, u32>::is_lock_free(),
+            cfg!(target_has_atomic = "32")
+        );
+        assert_eq!(p.load(SeqCst), (NonZeroU8::MAX, 0x1234));
+        p.store((NonZeroU8::MIN, 0xfedc), SeqCst);
+        assert_eq!(
+            p.compare_exchange(
+                (NonZeroU8::MIN, 0xfedc),
+                (NonZeroU8::MAX, 1),
+                SeqCst,
+                SeqCst
+            ),
+            Ok((NonZeroU8::MIN, 0xfedc))
+        );
+        assert_eq!(p.load(SeqCst), (NonZeroU8::MAX, 1));
+
+        let p = AtomicPair::<bool, [u8; 7]>::default();
+        assert_eq!(p.load(SeqCst), (false, [0; 7]));
+    }
+
+    // A bounded queue's head and tail, claimed by producers and consumers.
+    fn bounded_queue<T, S>(capacity: T, items: T)
+    where
+        T: NoUninit
+            + Ord
+            + Send
+            + Sync
+            + fmt::Debug
+            + From<u8>
+            + core::ops::Add<Output = T>
+            + core::ops::Sub<Output = T>,
+        S: Pod + Send,
+    {
+        let one = T::from(1);
+        let pair = AtomicPair::<T, T, S>::new(T::from(0), T::from(0));
+        let check = |(head, tail): (T, T)| {
+            assert!(
+                head <= tail && tail - head <= capacity,
+                "{:?}",
+                (head, tail)
+            );
+        };
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    // Producers advance the tail while the queue isn't full
+                    // and items remain.
+                    loop {
+                        let r = pair.fetch_update(AcqRel, Acquire, |(head, tail)| {
+                            check((head, tail));
+                            (tail < items && tail - head < capacity).then(|| (head, tail + one))
+                        });
+                        if let Err((_, tail)) = r {
+                            if tail == items {
+                                break;
+                            }
+                            std::thread::yield_now();
+                        }
+                    }
+                });
+                s.spawn(|| {
+                    // Consumers advance the head while the queue isn't empty.
+                    loop {
+                        let r = pair.fetch_update(AcqRel, Acquire, |(head, tail)| {
+                            check((head, tail));
+                            (head < tail).then(|| (head + one, tail))
+                        });
+                        if let Err((head, _)) = r {
+                            if head == items {
+                                break;
+                            }
+                            std::thread::yield_now();
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(pair.load(SeqCst), (items, items));
+    }
+
+    #[test]
+    fn bounded_queue_64() {
+        let items = if cfg!(miri) { 100 } else { 20_000 };
+        bounded_queue::<u32, u64>(8, items);
+    }
+
+    #[test]
+    fn bounded_queue_32() {
+        // Fits in a word on 32-bit targets.
+        let items = if cfg!(miri) { 100 } else { 20_000 };
+        bounded_queue::<u16, u32>(8, items);
+    }
+}
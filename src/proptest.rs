@@ -0,0 +1,45 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use ::proptest::arbitrary::{any_with, Arbitrary};
+use ::proptest::strategy::{Map, Strategy};
+use bytemuck::NoUninit;
+
+use crate::Atomic;
+
+/// Generates values using `T`'s strategy, wrapping each in a new `Atomic`.
+///
+/// Shrinking is done on the inner value. `T: NoUninit` is required because
+/// generated values must implement `Debug`.
+///
+/// This requires the `proptest` feature.
+impl<T: Arbitrary + NoUninit> Arbitrary for Atomic<T> {
+    type Parameters = T::Parameters;
+    type Strategy = Map<T::Strategy, fn(T) -> Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        any_with::<T>(args).prop_map(Atomic::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Atomic, Ordering};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn generated(a in any::<Atomic<u32>>(), b in any::<Atomic<[i8; 4]>>(), c in any::<Atomic<bool>>()) {
+            let v = a.load(Ordering::Relaxed);
+            prop_assert_eq!(a.fetch_add(1, Ordering::Relaxed), v);
+            let x = b.load(Ordering::Relaxed);
+            prop_assert_eq!(b.into_inner(), x);
+            let y = c.load(Ordering::Relaxed);
+            prop_assert_eq!(c.fetch_xor(true, Ordering::Relaxed), y);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Implementations of `radium::Radium` for the element types that radium itself
+// supports, so that `Atomic<T>` can be used wherever `AtomicT` or `Cell<T>`
+// would be accepted. All methods forward to the inherent ones.
+
+use core::sync::atomic::{self, Ordering};
+
+use ::radium::Radium;
+
+use crate::Atomic;
+
+// The failure ordering used by the deprecated `compare_and_swap`, matching the
+// standard library.
+#[inline]
+fn failure_ordering(order: Ordering) -> Ordering {
+    match order {
+        Ordering::Release => Ordering::Relaxed,
+        Ordering::AcqRel => Ordering::Acquire,
+        order => order,
+    }
+}
+
+macro_rules! radium_common {
+    ($t:ty) => {
+        type Item = $t;
+
+        #[inline]
+        fn new(value: $t) -> Self {
+            Atomic::new(value)
+        }
+
+        #[inline]
+        fn fence(order: Ordering) {
+            atomic::fence(order);
+        }
+
+        #[inline]
+        fn get_mut(&mut self) -> &mut $t {
+            Atomic::get_mut(self)
+        }
+
+        #[inline]
+        fn into_inner(self) -> $t {
+            Atomic::into_inner(self)
+        }
+
+        #[inline]
+        fn load(&self, order: Ordering) -> $t {
+            Atomic::load(self, order)
+        }
+
+        #[inline]
+        fn store(&self, value: $t, order: Ordering) {
+            Atomic::store(self, value, order);
+        }
+
+        #[inline]
+        fn swap(&self, value: $t, order: Ordering) -> $t {
+            Atomic::swap(self, value, order)
+        }
+
+        #[inline]
+        fn compare_and_swap(&self, current: $t, new: $t, order: Ordering) -> $t {
+            match Atomic::compare_exchange(self, current, new, order, failure_ordering(order)) {
+                Ok(x) | Err(x) => x,
+            }
+        }
+
+        #[inline]
+        fn compare_exchange(
+            &self,
+            current: $t,
+            new: $t,
+            success: Ordering,
+            failure: Ordering,
+        ) -> Result<$t, $t> {
+            Atomic::compare_exchange(self, current, new, success, failure)
+        }
+
+        #[inline]
+        fn compare_exchange_weak(
+            &self,
+            current: $t,
+            new: $t,
+            success: Ordering,
+            failure: Ordering,
+        ) -> Result<$t, $t> {
+            Atomic::compare_exchange_weak(self, current, new, success, failure)
+        }
+
+        #[inline]
+        fn fetch_update<F>(
+            &self,
+            set_order: Ordering,
+            fetch_order: Ordering,
+            f: F,
+        ) -> Result<$t, $t>
+        where
+            F: FnMut($t) -> Option<$t>,
+        {
+            Atomic::fetch_update(self, set_order, fetch_order, f)
+        }
+
+        #[inline]
+        fn fetch_and(&self, value: $t, order: Ordering) -> $t {
+            Atomic::<$t>::fetch_and(self, value, order)
+        }
+
+        // There is no native nand on `Atomic`, so this uses a CAS loop.
+        #[inline]
+        fn fetch_nand(&self, value: $t, order: Ordering) -> $t {
+            match Atomic::fetch_update(self, order, failure_ordering(order), |x| Some(!(x & value)))
+            {
+                Ok(x) | Err(x) => x,
+            }
+        }
+
+        #[inline]
+        fn fetch_or(&self, value: $t, order: Ordering) -> $t {
+            Atomic::<$t>::fetch_or(self, value, order)
+        }
+
+        #[inline]
+        fn fetch_xor(&self, value: $t, order: Ordering) -> $t {
+            Atomic::<$t>::fetch_xor(self, value, order)
+        }
+    };
+}
+
+macro_rules! radium_int {
+    ($($t:ty)*) => ($(
+        impl Radium for Atomic<$t> {
+            radium_common!($t);
+
+            #[inline]
+            fn fetch_add(&self, value: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_add(self, value, order)
+            }
+
+            #[inline]
+            fn fetch_sub(&self, value: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_sub(self, value, order)
+            }
+        }
+    )*);
+}
+
+radium_int! { i8 u8 i16 u16 i32 u32 i64 u64 isize usize }
+
+impl Radium for Atomic<bool> {
+    radium_common!(bool);
+
+    // `bool` doesn't implement `radium::marker::NumericOps`, so these can't
+    // be called.
+    #[doc(hidden)]
+    fn fetch_add(&self, _value: bool, _order: Ordering) -> bool {
+        unreachable!("This method statically cannot be called")
+    }
+
+    #[doc(hidden)]
+    fn fetch_sub(&self, _value: bool, _order: Ordering) -> bool {
+        unreachable!("This method statically cannot be called")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    // Exercises the same operations through both an `Atomic` and a `Cell`,
+    // which must agree.
+    fn exercise<R: Radium<Item = u32>>(r: &R) -> [u32; 8] {
+        r.store(10, Ordering::Relaxed);
+        [
+            r.swap(12, Ordering::SeqCst),
+            r.fetch_add(3, Ordering::SeqCst),
+            r.fetch_sub(1, Ordering::SeqCst),
+            r.fetch_and(0b1100, Ordering::SeqCst),
+            r.fetch_nand(0b0110, Ordering::SeqCst),
+            r.compare_exchange(!0b0100, 7, Ordering::SeqCst, Ordering::SeqCst)
+                .unwrap(),
+            r.compare_exchange_weak(1, 2, Ordering::SeqCst, Ordering::SeqCst)
+                .unwrap_err(),
+            r.load(Ordering::Relaxed),
+        ]
+    }
+
+    #[test]
+    fn radium_matches_cell() {
+        let a = Atomic::new(0u32);
+        let c = Cell::new(0u32);
+        assert_eq!(exercise(&a), exercise(&c));
+
+        let mut a: Atomic<u32> = Radium::new(5);
+        *Radium::get_mut(&mut a) += 1;
+        assert_eq!(Radium::into_inner(a), 6);
+    }
+
+    #[test]
+    fn radium_bool() {
+        let a = <Atomic<bool> as Radium>::new(false);
+        assert!(!Radium::fetch_or(&a, true, Ordering::SeqCst));
+        assert!(Radium::fetch_nand(&a, true, Ordering::SeqCst));
+        assert!(!Radium::fetch_xor(&a, true, Ordering::SeqCst));
+        assert!(Radium::load(&a, Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// `Atomic<T>` is reflected as an opaque value, in the same way as bevy_reflect
+// handles the std atomic types. Reads go through `Clone`, which does a `SeqCst`
+// load, and `apply` replaces the value through `&mut self`.
+
+use bevy_reflect::std_traits::ReflectDefault;
+use bevy_reflect::{impl_reflect_opaque, TypePath};
+use bytemuck::NoUninit;
+use core::fmt::Debug;
+
+use crate::Atomic;
+
+impl_reflect_opaque!((in atomic) Atomic<
+    T: NoUninit + Debug + Default + PartialEq + Send + Sync + TypePath
+>(Debug, Default, PartialEq));
+
+#[cfg(test)]
+mod tests {
+    use crate::{Atomic, Ordering};
+    use bevy_reflect::std_traits::ReflectDefault;
+    use bevy_reflect::{FromReflect, PartialReflect, Reflect, TypePath, TypeRegistry};
+    use std::boxed::Box;
+
+    #[test]
+    fn round_trip() {
+        let a = Atomic::new(1.5f32);
+        let boxed: Box<dyn Reflect> = Box::new(a.clone());
+        assert_eq!(
+            boxed.reflect_type_path(),
+            <Atomic<f32> as TypePath>::type_path()
+        );
+        assert!(boxed.reflect_partial_eq(&a).unwrap());
+        let back = <Atomic<f32> as FromReflect>::from_reflect(boxed.as_partial_reflect()).unwrap();
+        assert_eq!(back.load(Ordering::Relaxed), 1.5);
+
+        let mut target = Atomic::new(0u32);
+        target.apply(&Atomic::new(7u32));
+        assert_eq!(target.load(Ordering::Relaxed), 7);
+        assert!(target.try_apply(&Atomic::new(7i32)).is_err());
+        assert!(Atomic::<u32>::from_reflect(&7u32).is_none());
+    }
+
+    #[test]
+    fn registration() {
+        assert_eq!(
+            <Atomic<u32> as TypePath>::type_path(),
+            "atomic::Atomic<u32>"
+        );
+        let mut registry = TypeRegistry::new();
+        registry.register::<Atomic<u64>>();
+        let default = registry
+            .get_type_data::<ReflectDefault>(core::any::TypeId::of::<Atomic<u64>>())
+            .unwrap()
+            .default();
+        assert!(default.reflect_partial_eq(&Atomic::new(0u64)).unwrap());
+    }
+}
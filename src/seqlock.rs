@@ -0,0 +1,380 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[cfg(not(atomic_loom))]
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+#[cfg(not(atomic_loom))]
+use core::ptr;
+#[cfg(not(atomic_loom))]
+use core::sync::atomic::fence;
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+#[cfg(atomic_loom)]
+use loom::sync::atomic::{fence, AtomicU8};
+
+use crate::Backoff;
+
+#[cfg(not(atomic_loom))]
+type Seq = crate::Atomic<usize>;
+#[cfg(atomic_loom)]
+type Seq = loom::sync::atomic::AtomicUsize;
+
+#[inline]
+fn spin(backoff: &Backoff) {
+    #[cfg(not(atomic_loom))]
+    backoff.snooze();
+    #[cfg(atomic_loom)]
+    {
+        let _ = backoff;
+        loom::thread::yield_now();
+    }
+}
+
+/// A value protected by a sequence lock, for data which is read much more
+/// often than it is written, and too large to be lock-free.
+///
+/// Writers are exclusive: they increment a sequence number before and after
+/// modifying the value, so it is odd while a write is in progress. Readers
+/// never block writers and never write to shared memory. They copy the value
+/// out, and retry if the sequence number was odd or changed during the copy,
+/// which means that the copy may be torn.
+///
+/// # Copy-out semantics
+///
+/// `read` always returns a copy of the value, never a reference, because a
+/// writer may modify the value at any time. A torn copy is only ever held as
+/// raw bytes, and is discarded before it is interpreted as a `T`. This is why
+/// `T` must be `NoUninit`, and therefore `Copy` without pointers or
+/// references: there is nothing which could be dereferenced or dropped in a
+/// copy of a value which is being written.
+///
+/// Readers retry while writers are busy, so they can starve if writes are
+/// continuous. `read` uses `Backoff` between attempts.
+///
+/// This requires the `sync-extras` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::SeqLock;
+///
+/// #[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+/// #[repr(C)]
+/// struct Route {
+///     next_hop: [u8; 4],
+///     metric: u32,
+/// }
+///
+/// static ROUTES: SeqLock<[Route; 16]> = SeqLock::new(
+///     [Route { next_hop: [0; 4], metric: u32::MAX }; 16],
+/// );
+///
+/// // Update a single entry in place.
+/// ROUTES.lock_write()[3] = Route { next_hop: [10, 0, 0, 1], metric: 5 };
+/// assert_eq!(ROUTES.read()[3].metric, 5);
+/// ```
+pub struct SeqLock<T> {
+    seq: Seq,
+    #[cfg(not(atomic_loom))]
+    data: UnsafeCell<T>,
+    // loom can't model racy reads of plain memory, so the value is stored as
+    // relaxed atomic bytes instead, which have the same ordering semantics.
+    #[cfg(atomic_loom)]
+    data: std::boxed::Box<[AtomicU8]>,
+    #[cfg(atomic_loom)]
+    marker: core::marker::PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for SeqLock<T> {}
+// Readers only ever get copies of the value, like sending it.
+unsafe impl<T: Send> Sync for SeqLock<T> {}
+
+impl<T: NoUninit> SeqLock<T> {
+    /// Creates a new `SeqLock`.
+    #[cfg(not(atomic_loom))]
+    #[inline]
+    pub const fn new(val: T) -> SeqLock<T> {
+        SeqLock {
+            seq: Seq::new(0),
+            data: UnsafeCell::new(val),
+        }
+    }
+
+    /// Creates a new `SeqLock`.
+    #[cfg(atomic_loom)]
+    pub fn new(val: T) -> SeqLock<T> {
+        SeqLock {
+            seq: Seq::new(0),
+            data: bytemuck::bytes_of(&val)
+                .iter()
+                .map(|&b| AtomicU8::new(b))
+                .collect(),
+            marker: core::marker::PhantomData,
+        }
+    }
+
+    // Copies the value, which may be torn if a write is in progress.
+    #[cfg(not(atomic_loom))]
+    #[inline]
+    unsafe fn read_data(&self) -> MaybeUninit<T> {
+        ptr::read_volatile(self.data.get() as *const MaybeUninit<T>)
+    }
+
+    #[cfg(atomic_loom)]
+    unsafe fn read_data(&self) -> MaybeUninit<T> {
+        let mut val = MaybeUninit::<T>::uninit();
+        let dst = val.as_mut_ptr() as *mut u8;
+        for (i, b) in self.data.iter().enumerate() {
+            *dst.add(i) = b.load(Ordering::Relaxed);
+        }
+        val
+    }
+
+    // Writes the value. The write lock must be held.
+    #[cfg(not(atomic_loom))]
+    #[inline]
+    unsafe fn write_data(&self, val: T) {
+        ptr::write_volatile(self.data.get(), val)
+    }
+
+    #[cfg(atomic_loom)]
+    unsafe fn write_data(&self, val: T) {
+        for (b, &v) in self.data.iter().zip(bytemuck::bytes_of(&val)) {
+            b.store(v, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns a copy of the value.
+    ///
+    /// This retries until it gets a copy which wasn't modified by a
+    /// concurrent write.
+    pub fn read(&self) -> T {
+        let backoff = Backoff::new();
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 == 0 {
+                // The copy is only assumed to be initialized after the
+                // sequence number shows that no write overlapped it.
+                let val = unsafe { self.read_data() };
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == seq {
+                    return unsafe { val.assume_init() };
+                }
+            }
+            spin(&backoff);
+        }
+    }
+
+    // Takes the write lock, returning the even sequence number from before.
+    fn lock(&self) -> usize {
+        let backoff = Backoff::new();
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq & 1 == 0 {
+                match self.seq.compare_exchange_weak(
+                    seq,
+                    seq.wrapping_add(1),
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // Readers which see any of the following writes must
+                        // also see the odd sequence number.
+                        fence(Ordering::Release);
+                        return seq;
+                    }
+                    Err(x) => seq = x,
+                }
+            } else {
+                spin(&backoff);
+                seq = self.seq.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[inline]
+    fn unlock(&self, seq: usize) {
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Replaces the value.
+    ///
+    /// This waits for concurrent writers, but not for readers.
+    pub fn write(&self, val: T) {
+        let seq = self.lock();
+        unsafe { self.write_data(val) };
+        self.unlock(seq);
+    }
+
+    /// Takes the write lock, returning a guard which allows modifying the
+    /// value in place.
+    ///
+    /// Readers retry until the guard is dropped, so it should only be held
+    /// briefly.
+    pub fn lock_write(&self) -> SeqLockWriteGuard<'_, T> {
+        let seq = self.lock();
+        SeqLockWriteGuard {
+            lock: self,
+            seq,
+            #[cfg(atomic_loom)]
+            value: unsafe { self.read_data().assume_init() },
+        }
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// This doesn't need any synchronization since the `SeqLock` is borrowed
+    /// mutably.
+    #[cfg(not(atomic_loom))]
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes the `SeqLock` and returns the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.read()
+    }
+}
+
+impl<T: NoUninit + Default> Default for SeqLock<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: NoUninit + fmt::Debug> fmt::Debug for SeqLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SeqLock").field(&self.read()).finish()
+    }
+}
+
+/// A guard which holds the write lock of a `SeqLock`, returned by
+/// `SeqLock::lock_write`.
+///
+/// The lock is released when the guard is dropped.
+pub struct SeqLockWriteGuard<'a, T: NoUninit> {
+    lock: &'a SeqLock<T>,
+    seq: usize,
+    #[cfg(atomic_loom)]
+    value: T,
+}
+
+impl<T: NoUninit> Deref for SeqLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        #[cfg(not(atomic_loom))]
+        return unsafe { &*self.lock.data.get() };
+        #[cfg(atomic_loom)]
+        return &self.value;
+    }
+}
+
+impl<T: NoUninit> DerefMut for SeqLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        #[cfg(not(atomic_loom))]
+        return unsafe { &mut *self.lock.data.get() };
+        #[cfg(atomic_loom)]
+        return &mut self.value;
+    }
+}
+
+impl<T: NoUninit> Drop for SeqLockWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(atomic_loom)]
+        unsafe {
+            self.lock.write_data(self.value)
+        };
+        self.lock.unlock(self.seq);
+    }
+}
+
+impl<T: NoUninit + fmt::Debug> fmt::Debug for SeqLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(all(test, not(atomic_loom)))]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicBool;
+
+    #[test]
+    fn operations() {
+        let mut l = SeqLock::new([1u32; 4]);
+        assert_eq!(l.read(), [1; 4]);
+        l.write([2; 4]);
+        {
+            let mut g = l.lock_write();
+            g[0] = 7;
+            assert_eq!(format!("{:?}", g), "[7, 2, 2, 2]");
+        }
+        assert_eq!(l.read(), [7, 2, 2, 2]);
+        l.get_mut()[3] = 9;
+        assert_eq!(format!("{:?}", l), "SeqLock([7, 2, 2, 9])");
+        assert_eq!(l.into_inner(), [7, 2, 2, 9]);
+    }
+
+    #[test]
+    fn concurrent_readers_writers() {
+        const ROUNDS: u64 = if cfg!(miri) { 50 } else { 20_000 };
+
+        // Every value written has all elements equal, so a torn read would
+        // show up as a mixture.
+        let l = SeqLock::new([0u64; 16]);
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let mut last = 0;
+                    while !done.load(Ordering::Relaxed) {
+                        let v = l.read();
+                        assert!(v.iter().all(|&x| x == v[0]), "torn read: {:?}", v);
+                        // The writers only increase the value.
+                        assert!(v[0] >= last);
+                        last = v[0];
+                    }
+                });
+            }
+            let writers: std::vec::Vec<_> = (0..2)
+                .map(|w| {
+                    let l = &l;
+                    s.spawn(move || {
+                        for _ in 0..ROUNDS {
+                            if w == 0 {
+                                let mut g = l.lock_write();
+                                for x in g.iter_mut() {
+                                    *x += 1;
+                                }
+                            } else {
+                                let mut g = l.lock_write();
+                                let next = g[0] + 1;
+                                *g = [next; 16];
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for w in writers {
+                w.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(l.read(), [2 * ROUNDS; 16]);
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use bytemuck::NoUninit;
+
+use crate::{Atomic, Ordering};
+
+/// Serializes the current value as a bare `T`, so a struct containing an
+/// `Atomic<T>` has the same format as one containing a `T`.
+///
+/// The value is loaded with `SeqCst` ordering.
+///
+/// This requires the `serde` feature.
+impl<T: NoUninit + Serialize> Serialize for Atomic<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.load(Ordering::SeqCst).serialize(serializer)
+    }
+}
+
+/// Deserializes a bare `T` and wraps it in a new `Atomic`.
+///
+/// This requires the `serde` feature.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Atomic<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Atomic::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Atomic, Ordering};
+    use serde::{Deserialize, Serialize};
+    use std::string::String;
+
+    #[derive(Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        hits: Atomic<u64>,
+        enabled: Atomic<bool>,
+        ratio: f32,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct PlainConfig {
+        name: String,
+        hits: u64,
+        enabled: bool,
+        ratio: f32,
+    }
+
+    #[test]
+    fn same_format_as_plain() {
+        let config = Config {
+            name: "cache".into(),
+            hits: Atomic::new(3),
+            enabled: Atomic::new(true),
+            ratio: 0.5,
+        };
+        config.hits.fetch_add(1, Ordering::Relaxed);
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            json,
+            r#"{"name":"cache","hits":4,"enabled":true,"ratio":0.5}"#
+        );
+
+        let plain: PlainConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            plain,
+            PlainConfig {
+                name: "cache".into(),
+                hits: 4,
+                enabled: true,
+                ratio: 0.5,
+            }
+        );
+        assert_eq!(serde_json::to_string(&plain).unwrap(), json);
+    }
+
+    #[test]
+    fn round_trip() {
+        let config: Config = serde_json::from_str(
+            r#"{"name":"x","hits":18446744073709551615,"enabled":false,"ratio":2.0}"#,
+        )
+        .unwrap();
+        assert_eq!(config.hits.load(Ordering::Relaxed), u64::MAX);
+        assert!(!config.enabled.load(Ordering::Relaxed));
+        let back: Config = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(back.hits.into_inner(), u64::MAX);
+        assert_eq!(back.name, "x");
+
+        assert!(serde_json::from_str::<Atomic<u8>>("256").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use crate::{Atomic, CachePadded};
+
+// Checks at compile time that there is at least one shard.
+struct NonEmpty<const SHARDS: usize>;
+
+impl<const SHARDS: usize> NonEmpty<SHARDS> {
+    const OK: () = if SHARDS == 0 {
+        core::panic!("a `ShardedCounter` needs at least one shard")
+    };
+}
+
+// A small integer which is different for each thread, used to pick a shard.
+#[cfg(any(test, feature = "std"))]
+#[inline]
+fn thread_index() -> usize {
+    use core::sync::atomic::AtomicUsize;
+
+    // Threads are numbered in the order in which they first use a counter,
+    // so the first `SHARDS` threads never share a shard.
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::thread_local! {
+        static INDEX: usize = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    INDEX.try_with(|&index| index).unwrap_or(0)
+}
+
+// Without thread-locals, use the address of a local variable instead: each
+// thread has its own stack, and the address only changes within a thread
+// when it is called at different stack depths.
+#[cfg(not(any(test, feature = "std")))]
+#[inline]
+fn thread_index() -> usize {
+    let local = 0u8;
+    let page = core::ptr::addr_of!(local) as usize >> 12;
+    page.wrapping_mul(0x9e37_79b9) >> 8
+}
+
+/// A counter which is split into `SHARDS` atomic counters, for counters which
+/// are written much more often than they are read.
+///
+/// Incrementing a single `Atomic<u64>` from many threads makes its cache line
+/// move between cores on every increment. `ShardedCounter` gives each shard
+/// its own cache line with `CachePadded` instead, and each thread increments
+/// the shard selected by its thread index, so threads only contend when they
+/// share a shard. Reading the counter sums all of the shards, which is
+/// slower.
+///
+/// The number of shards defaults to 8, and must be at least 1. With the
+/// `std` feature, each thread is assigned a shard when it first uses a
+/// counter, so up to `SHARDS` threads never share a shard. Without it, the
+/// shard is chosen from the address of the thread's stack.
+///
+/// The counter wraps around on overflow.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Ordering, ShardedCounter};
+///
+/// static REQUESTS: ShardedCounter = ShardedCounter::new();
+///
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| REQUESTS.add(1, Ordering::Relaxed));
+///     }
+/// });
+/// assert_eq!(REQUESTS.sum(Ordering::Relaxed), 4);
+/// ```
+pub struct ShardedCounter<const SHARDS: usize = 8> {
+    shards: [CachePadded<Atomic<u64>>; SHARDS],
+}
+
+impl<const SHARDS: usize> ShardedCounter<SHARDS> {
+    /// Creates a new `ShardedCounter` with a value of zero.
+    #[inline]
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = NonEmpty::<SHARDS>::OK;
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: CachePadded<Atomic<u64>> = CachePadded::new(Atomic::new(0));
+        ShardedCounter {
+            shards: [ZERO; SHARDS],
+        }
+    }
+
+    /// Adds `n` to the counter.
+    ///
+    /// This is a single `fetch_add` on the current thread's shard.
+    #[inline]
+    pub fn add(&self, n: u64, order: Ordering) {
+        self.shards[thread_index() % SHARDS].fetch_add(n, order);
+    }
+
+    /// Returns the sum of all shards.
+    ///
+    /// The shards are loaded one at a time, so this isn't a snapshot if the
+    /// counter is modified concurrently: it includes every addition which
+    /// happened before the call, and possibly some of the concurrent ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    pub fn sum(&self, order: Ordering) -> u64 {
+        self.shards
+            .iter()
+            .fold(0, |sum, shard| sum.wrapping_add(shard.load(order)))
+    }
+}
+
+impl<const SHARDS: usize> Default for ShardedCounter<SHARDS> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SHARDS: usize> fmt::Debug for ShardedCounter<SHARDS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ShardedCounter")
+            .field(&self.sum(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use std::time::{Duration, Instant};
+    use std::vec::Vec;
+
+    #[test]
+    fn sum() {
+        let c = ShardedCounter::<1>::new();
+        c.add(u64::MAX, Relaxed);
+        c.add(2, Relaxed);
+        assert_eq!(c.sum(Relaxed), 1);
+
+        let c = ShardedCounter::<8>::default();
+        c.add(5, Relaxed);
+        c.add(6, Relaxed);
+        assert_eq!(format!("{:?}", c), "ShardedCounter(11)");
+    }
+
+    #[test]
+    fn concurrent_adds() {
+        const THREADS: u64 = 16;
+        const ROUNDS: u64 = if cfg!(miri) { 50 } else { 10_000 };
+
+        let c = ShardedCounter::<8>::new();
+        let indices: Vec<usize> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let c = &c;
+                    s.spawn(move || {
+                        for _ in 0..ROUNDS {
+                            c.add(t, Relaxed);
+                        }
+                        // The thread keeps its shard.
+                        let index = thread_index();
+                        assert_eq!(thread_index(), index);
+                        index
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(c.sum(SeqCst), ROUNDS * (0..THREADS).sum::<u64>());
+
+        // Each thread has its own index, so threads spread over the shards.
+        let mut unique = indices.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), indices.len());
+    }
+
+    // Compares the time taken by 16 threads incrementing a `ShardedCounter`
+    // and a single `Atomic<u64>`. This needs at least 16 cores to be
+    // meaningful, so run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn contention() {
+        const THREADS: usize = 16;
+        const ROUNDS: u64 = 1_000_000;
+
+        fn time(add: impl Fn() + Sync) -> Duration {
+            let start = Instant::now();
+            std::thread::scope(|s| {
+                for _ in 0..THREADS {
+                    s.spawn(|| {
+                        for _ in 0..ROUNDS {
+                            add();
+                        }
+                    });
+                }
+            });
+            start.elapsed()
+        }
+
+        let single = Atomic::<u64>::new(0);
+        let single_time = time(|| {
+            single.fetch_add(1, Relaxed);
+        });
+        let sharded = ShardedCounter::<16>::new();
+        let sharded_time = time(|| sharded.add(1, Relaxed));
+        std::println!("single: {:?}, sharded: {:?}", single_time, sharded_time);
+
+        let total = THREADS as u64 * ROUNDS;
+        assert_eq!(single.load(SeqCst), total);
+        assert_eq!(sharded.sum(SeqCst), total);
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if cores >= THREADS {
+            assert!(sharded_time * 2 < single_time);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// The `portable_simd` feature enables bytemuck's impls for `Simd`, which is
+// all that `Atomic` needs. This checks that the vector types stay usable.
+const _: fn() = || {
+    use core::simd::Simd;
+
+    fn assert_atomicable<T: crate::Atomicable>() {}
+    assert_atomicable::<Simd<u8, 16>>();
+    assert_atomicable::<Simd<u32, 4>>();
+    assert_atomicable::<Simd<f32, 4>>();
+    assert_atomicable::<Simd<u64, 2>>();
+};
+
+#[cfg(test)]
+mod tests {
+    use crate::{Atomic, Ordering::*};
+    use core::mem;
+    use core::simd::Simd;
+    use core::sync::atomic::AtomicBool;
+
+    #[test]
+    fn lock_free() {
+        // SIMD vectors are aligned to their size.
+        assert_eq!(mem::align_of::<Simd<u8, 16>>(), 16);
+        assert_eq!(
+            Atomic::<Simd<u8, 16>>::is_lock_free(),
+            Atomic::<u128>::is_lock_free()
+        );
+        assert_eq!(
+            Atomic::<Simd<f32, 2>>::is_lock_free(),
+            Atomic::<u64>::is_lock_free()
+        );
+    }
+
+    #[test]
+    fn operations() {
+        let a = Atomic::new(Simd::<u8, 16>::splat(1));
+        a.store(Simd::from_array([7; 16]), SeqCst);
+        assert_eq!(a.swap(Simd::splat(2), SeqCst), Simd::splat(7));
+        assert_eq!(
+            a.compare_exchange(Simd::splat(3), Simd::splat(4), SeqCst, SeqCst),
+            Err(Simd::splat(2))
+        );
+        assert_eq!(
+            a.compare_exchange(Simd::splat(2), Simd::splat(4), SeqCst, SeqCst),
+            Ok(Simd::splat(2))
+        );
+        assert_eq!(a.load(SeqCst), Simd::splat(4));
+
+        // The comparison is bitwise.
+        let f = Atomic::new(Simd::<f32, 4>::splat(0.0));
+        let negative_zero = Simd::splat(-0.0);
+        assert!(f
+            .compare_exchange(negative_zero, Simd::splat(1.0), SeqCst, SeqCst)
+            .is_err());
+        assert_eq!(f.into_inner(), Simd::splat(0.0));
+    }
+
+    #[test]
+    fn concurrent_stores() {
+        // All lanes of every stored vector are equal, so a load which mixed
+        // two stores would show up.
+        let a = Atomic::new(Simd::<u32, 4>::splat(0));
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for t in 0..2 {
+                let (a, done) = (&a, &done);
+                s.spawn(move || {
+                    let mut i = t;
+                    while !done.load(Relaxed) {
+                        a.store(Simd::splat(i), Release);
+                        i = i.wrapping_add(2);
+                    }
+                });
+            }
+            let rounds = if cfg!(miri) { 50 } else { 100_000 };
+            for _ in 0..rounds {
+                let v = a.load(Acquire).to_array();
+                assert!(v.iter().all(|&x| x == v[0]), "torn load: {:?}", v);
+            }
+            done.store(true, Relaxed);
+        });
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::Atomic;
+
+const WORD: usize = mem::size_of::<usize>();
+
+// Splits a byte slice into an unaligned head, a number of aligned words and an
+// unaligned tail, returning the length of the head and the number of words.
+#[inline]
+fn split(s: &[Atomic<u8>]) -> (usize, usize) {
+    if !cfg!(target_has_atomic = "ptr") {
+        return (s.len(), 0);
+    }
+    let head = s.as_ptr().align_offset(WORD).min(s.len());
+    (head, (s.len() - head) / WORD)
+}
+
+#[inline]
+unsafe fn word_at(s: &[Atomic<u8>], offset: usize) -> &AtomicUsize {
+    &*(s.as_ptr().add(offset) as *const AtomicUsize)
+}
+
+/// Copies all bytes from `src` into `dst`, writing each element of `dst`
+/// atomically.
+///
+/// The copy is performed using the widest lock-free atomic stores available,
+/// so aligned words of `dst` are written as a whole while the unaligned head
+/// and tail are written byte by byte. A reader using `copy_to_slice` on the
+/// same region will never observe a torn word. There is no ordering between
+/// the individual stores, so the copy should be published to readers using a
+/// separate `Release` store, for example of a sequence number.
+///
+/// # Panics
+///
+/// Panics if the two slices have different lengths, or if `order` is `Acquire`
+/// or `AcqRel`.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+///
+/// let buf: Vec<Atomic<u8>> = (0..16).map(|_| Atomic::new(0)).collect();
+/// atomic::copy_from_slice(&buf[..5], b"hello", Ordering::Relaxed);
+/// assert_eq!(buf[1].load(Ordering::Relaxed), b'e');
+/// ```
+pub fn copy_from_slice(dst: &[Atomic<u8>], src: &[u8], order: Ordering) {
+    assert_eq!(
+        dst.len(),
+        src.len(),
+        "destination and source slices have different lengths"
+    );
+    let (head, words) = split(dst);
+    for (d, &s) in dst[..head].iter().zip(&src[..head]) {
+        d.store(s, order);
+    }
+    for i in 0..words {
+        let offset = head + i * WORD;
+        let mut bytes = [0; WORD];
+        bytes.copy_from_slice(&src[offset..offset + WORD]);
+        unsafe {
+            word_at(dst, offset).store(usize::from_ne_bytes(bytes), order);
+        }
+    }
+    let tail = head + words * WORD;
+    for (d, &s) in dst[tail..].iter().zip(&src[tail..]) {
+        d.store(s, order);
+    }
+}
+
+/// Copies all elements of `src` into `dst`, reading each element of `src`
+/// atomically.
+///
+/// The copy is performed using the widest lock-free atomic loads available,
+/// so aligned words of `src` are read as a whole while the unaligned head and
+/// tail are read byte by byte. See `copy_from_slice` for details.
+///
+/// # Panics
+///
+/// Panics if the two slices have different lengths, or if `order` is `Release`
+/// or `AcqRel`.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+///
+/// let buf: Vec<Atomic<u8>> = b"hello".iter().map(|&b| Atomic::new(b)).collect();
+/// let mut out = [0; 5];
+/// atomic::copy_to_slice(&buf, &mut out, Ordering::Relaxed);
+/// assert_eq!(&out, b"hello");
+/// ```
+pub fn copy_to_slice(src: &[Atomic<u8>], dst: &mut [u8], order: Ordering) {
+    assert_eq!(
+        dst.len(),
+        src.len(),
+        "destination and source slices have different lengths"
+    );
+    let (head, words) = split(src);
+    for (d, s) in dst[..head].iter_mut().zip(&src[..head]) {
+        *d = s.load(order);
+    }
+    for i in 0..words {
+        let offset = head + i * WORD;
+        let word = unsafe { word_at(src, offset).load(order) };
+        dst[offset..offset + WORD].copy_from_slice(&word.to_ne_bytes());
+    }
+    let tail = head + words * WORD;
+    for (d, s) in dst[tail..].iter_mut().zip(&src[tail..]) {
+        *d = s.load(order);
+    }
+}
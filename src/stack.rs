@@ -0,0 +1,279 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::Ordering;
+
+use crate::{Atomic, AtomicStampedPtr};
+
+/// A node which can be linked into an `AtomicStack`.
+///
+/// `Atomic<*mut Self>` isn't `Sync`, since raw pointers aren't, so a node
+/// type has to implement `Send` and `Sync` itself for an `AtomicStack` of it
+/// to be shared between threads.
+///
+/// # Safety
+///
+/// `link` must always return the same field of the node, and nothing other
+/// than `AtomicStack` may modify that field while the node is in a stack. The
+/// stack follows the pointers stored in it without any other checks.
+pub unsafe trait Node: Sized {
+    /// Returns the field which links the node to the next one in the stack.
+    fn link(&self) -> &Atomic<*mut Self>;
+}
+
+/// An intrusive lock-free stack, such as a free list of memory blocks.
+///
+/// The stack doesn't own or allocate its nodes: it links the nodes it is
+/// given through the field returned by `Node::link`, and hands them back by
+/// reference in last-in first-out order. The head is an `AtomicStampedPtr`,
+/// so a `pop` which raced with another thread popping and pushing the same
+/// node again fails and retries instead of corrupting the stack, which is
+/// the ABA problem of the plain Treiber stack. The stack is lock-free when
+/// `AtomicStampedPtr` is, which `is_lock_free_runtime` returns.
+///
+/// # Node lifetime
+///
+/// Nodes are borrowed for `'a`, so they can't be moved or freed while the
+/// stack may still hand them out, whether or not they are in it. A `pop`
+/// which loses a race may read the link of a node which another thread has
+/// already popped, so a node must stay valid even after it is popped, until
+/// the stack itself is gone. Borrowing the nodes for the lifetime of the
+/// stack guarantees this.
+///
+/// `push` is unsafe because a node must not be pushed while it is in a
+/// stack, this one or another one: its link would be overwritten, which
+/// either creates a cycle or links the stacks together.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, AtomicStack, Node};
+///
+/// struct Block {
+///     next: Atomic<*mut Block>,
+///     data: [u8; 64],
+/// }
+///
+/// unsafe impl Node for Block {
+///     fn link(&self) -> &Atomic<*mut Block> {
+///         &self.next
+///     }
+/// }
+///
+/// let blocks: Vec<Block> = (0..4)
+///     .map(|_| Block { next: Atomic::new(std::ptr::null_mut()), data: [0; 64] })
+///     .collect();
+/// let free = AtomicStack::new();
+/// for block in &blocks {
+///     // Each block is pushed only once.
+///     unsafe { free.push(block) };
+/// }
+///
+/// let block = free.pop().unwrap();
+/// assert!(std::ptr::eq(block, &blocks[3]));
+/// // Give the block back once it is no longer used.
+/// unsafe { free.push(block) };
+/// ```
+pub struct AtomicStack<'a, N> {
+    head: AtomicStampedPtr<N>,
+    marker: PhantomData<&'a N>,
+}
+
+// The stack only hands out shared references to its nodes.
+unsafe impl<N: Sync> Send for AtomicStack<'_, N> {}
+unsafe impl<N: Sync> Sync for AtomicStack<'_, N> {}
+
+impl<'a, N: Node> AtomicStack<'a, N> {
+    /// Creates a new empty stack.
+    #[inline]
+    pub const fn new() -> AtomicStack<'a, N> {
+        AtomicStack {
+            head: AtomicStampedPtr::new(ptr::null_mut(), 0),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns `true` if operations on the stack are lock-free.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        AtomicStampedPtr::<N>::is_lock_free()
+    }
+
+    /// Returns `true` if operations on the stack are lock-free on the current
+    /// CPU, including native instructions which are detected at runtime. See
+    /// `Atomic::is_lock_free_runtime`.
+    #[inline]
+    pub fn is_lock_free_runtime() -> bool {
+        AtomicStampedPtr::<N>::is_lock_free_runtime()
+    }
+
+    /// Pushes a node onto the stack.
+    ///
+    /// Everything which the thread did before the push, including writes to
+    /// the node, happens before the `pop` which returns the node.
+    ///
+    /// # Safety
+    ///
+    /// `node` must not be in this stack or in any other `AtomicStack`.
+    pub unsafe fn push(&self, node: &'a N) {
+        let node_ptr = node as *const N as *mut N;
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            node.link().store_ptr(head.0, Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                (node_ptr, head.1.wrapping_add(1)),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Pops the node which was pushed last, or returns `None` if the stack
+    /// is empty.
+    pub fn pop(&self) -> Option<&'a N> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            // Every node in the stack was pushed as a `&'a N`, and it stays
+            // valid for `'a` even if another thread pops it first.
+            let node = unsafe { head.0.as_ref()? };
+            // The link may be overwritten by a concurrent push of this node
+            // after another thread popped it, in which case the stamp has
+            // changed and the exchange fails.
+            let next = node.link().load_ptr(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                (next, head.1.wrapping_add(1)),
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(node),
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Returns `true` if the stack is empty.
+    ///
+    /// This is only a snapshot: other threads may push or pop at any time.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).0.is_null()
+    }
+}
+
+impl<N: Node> Default for AtomicStack<'_, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: Node> fmt::Debug for AtomicStack<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicStack")
+            .field("is_empty", &self.is_empty())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use std::vec::Vec;
+
+    struct Block {
+        next: Atomic<*mut Block>,
+        id: usize,
+        // Written by the thread which popped the block, to check that no
+        // two threads hold it at the same time.
+        owner: Atomic<usize>,
+    }
+
+    unsafe impl Send for Block {}
+    unsafe impl Sync for Block {}
+
+    unsafe impl Node for Block {
+        fn link(&self) -> &Atomic<*mut Block> {
+            &self.next
+        }
+    }
+
+    fn pool(n: usize) -> Vec<Block> {
+        (0..n)
+            .map(|id| Block {
+                next: Atomic::new(ptr::null_mut()),
+                id,
+                owner: Atomic::new(usize::MAX),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lifo() {
+        let blocks = pool(3);
+        let stack = AtomicStack::default();
+        assert!(stack.is_empty());
+        assert!(stack.pop().is_none());
+        assert_eq!(
+            AtomicStack::<Block>::is_lock_free_runtime(),
+            AtomicStampedPtr::<Block>::is_lock_free_runtime()
+        );
+        for block in &blocks {
+            unsafe { stack.push(block) };
+        }
+        assert_eq!(format!("{:?}", stack), "AtomicStack { is_empty: false }");
+        assert_eq!(stack.pop().unwrap().id, 2);
+        let b = stack.pop().unwrap();
+        assert_eq!(b.id, 1);
+        unsafe { stack.push(b) };
+        let ids: Vec<usize> = core::iter::from_fn(|| stack.pop()).map(|b| b.id).collect();
+        assert_eq!(ids, [1, 0]);
+        assert!(stack.is_empty());
+    }
+
+    // Threads repeatedly pop a block and push it back, so that the same
+    // blocks are constantly reused, which is where the ABA problem occurs.
+    #[test]
+    fn concurrent_reuse() {
+        const THREADS: usize = 4;
+        const BLOCKS: usize = 3;
+        let rounds = if cfg!(miri) { 20 } else { 20_000 };
+
+        let blocks = pool(BLOCKS);
+        let stack = AtomicStack::new();
+        for block in &blocks {
+            unsafe { stack.push(block) };
+        }
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let stack = &stack;
+                s.spawn(move || {
+                    for _ in 0..rounds {
+                        if let Some(block) = stack.pop() {
+                            assert_eq!(block.owner.swap(t, Relaxed), usize::MAX);
+                            assert_eq!(block.owner.swap(usize::MAX, Relaxed), t);
+                            unsafe { stack.push(block) };
+                        }
+                    }
+                });
+            }
+        });
+
+        // Every block is in the stack exactly once.
+        let mut ids: Vec<usize> = core::iter::from_fn(|| stack.pop()).map(|b| b.id).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..BLOCKS).collect::<Vec<_>>());
+    }
+}
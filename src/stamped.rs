@@ -0,0 +1,414 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::Ordering;
+
+use bytemuck::NoUninit;
+
+use crate::Atomic;
+
+// A pointer and a stamp, aligned to their combined size so that they can be
+// updated with a single double-width atomic operation. The pointer is stored as
+// a `*mut u8` so that this is `'static` as required by `NoUninit`.
+#[derive(Copy, Clone)]
+#[cfg_attr(target_pointer_width = "16", repr(C, align(4)))]
+#[cfg_attr(target_pointer_width = "32", repr(C, align(8)))]
+#[cfg_attr(target_pointer_width = "64", repr(C, align(16)))]
+struct Stamped {
+    ptr: *mut u8,
+    stamp: usize,
+}
+
+// Both fields have the same size and no padding is needed between them or
+// after them.
+unsafe impl NoUninit for Stamped {}
+
+// Raw pointers aren't `Send`. `AtomicStampedPtr` is `Send` and `Sync` like
+// `AtomicPtr`, and dereferencing the pointers is up to the user.
+unsafe impl Send for Stamped {}
+
+impl Stamped {
+    #[inline]
+    const fn new<T>(ptr: *mut T, stamp: usize) -> Stamped {
+        Stamped {
+            ptr: ptr as *mut u8,
+            stamp,
+        }
+    }
+
+    #[inline]
+    fn get<T>(self) -> (*mut T, usize) {
+        (self.ptr as *mut T, self.stamp)
+    }
+}
+
+/// An atomic pointer with a stamp, which is updated together with the pointer.
+///
+/// Lock-free data structures which reuse nodes suffer from the ABA problem: a
+/// `compare_exchange` on a plain pointer succeeds if the pointer was replaced
+/// and then stored again in the meantime, even though the node it points to
+/// changed. Incrementing the stamp on every update makes such a
+/// `compare_exchange` fail instead.
+///
+/// The pointer and the stamp are updated with a single atomic operation on a
+/// value twice the size of a pointer. On 64-bit targets this requires 128-bit
+/// atomics: the core ones with the `nightly` feature, and otherwise inline
+/// assembly on x86_64 with the `cmpxchg16b` target feature and on
+/// little-endian aarch64, which is also used on stable. On x86_64 without the
+/// `cmpxchg16b` target feature, the instruction is detected at runtime when
+/// `std` is enabled. Where none of these is available, it falls back to a
+/// lock like any other `Atomic`. Use `is_lock_free_runtime` to check which
+/// one is used on the current CPU. Pointers which are loaded keep their
+/// provenance only on the fallback path, since all the 128-bit atomics go
+/// through an integer.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::AtomicStampedPtr;
+/// use atomic::Ordering::{AcqRel, Acquire};
+///
+/// let mut a = 1;
+/// let mut b = 2;
+/// let head = AtomicStampedPtr::new(&mut a as *mut i32, 0);
+///
+/// let (ptr, stamp) = head.load(Acquire);
+/// // Another thread replaces the pointer and restores it.
+/// let b = &mut b as *mut i32;
+/// assert!(head.compare_exchange((ptr, stamp), (b, 1), AcqRel, Acquire).is_ok());
+/// assert!(head.compare_exchange((b, 1), (ptr, 2), AcqRel, Acquire).is_ok());
+/// // The pointer is the same, but the stamp shows that it was changed.
+/// assert!(head.compare_exchange((ptr, stamp), (ptr, 3), AcqRel, Acquire).is_err());
+/// ```
+pub struct AtomicStampedPtr<T> {
+    v: Atomic<Stamped>,
+    marker: PhantomData<*mut T>,
+}
+
+unsafe impl<T> Send for AtomicStampedPtr<T> {}
+unsafe impl<T> Sync for AtomicStampedPtr<T> {}
+
+impl<T> AtomicStampedPtr<T> {
+    /// Creates a new `AtomicStampedPtr`.
+    #[inline]
+    pub const fn new(ptr: *mut T, stamp: usize) -> AtomicStampedPtr<T> {
+        AtomicStampedPtr {
+            v: Atomic::new(Stamped::new(ptr, stamp)),
+            marker: PhantomData,
+        }
+    }
+
+    /// Checks if operations on an `AtomicStampedPtr` are lock-free on the
+    /// current target.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        Atomic::<Stamped>::is_lock_free()
+    }
+
+    /// Checks if operations on an `AtomicStampedPtr` are lock-free on the
+    /// current CPU, including native instructions which are detected at
+    /// runtime. See `Atomic::is_lock_free_runtime`.
+    #[inline]
+    pub fn is_lock_free_runtime() -> bool {
+        Atomic::<Stamped>::is_lock_free_runtime()
+    }
+
+    /// Consumes the `AtomicStampedPtr` and returns the contained pointer and
+    /// stamp.
+    #[inline]
+    pub fn into_inner(self) -> (*mut T, usize) {
+        self.v.into_inner().get()
+    }
+
+    /// Loads the pointer and the stamp.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> (*mut T, usize) {
+        self.v.load(order).get()
+    }
+
+    /// Stores a pointer and a stamp.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, (ptr, stamp): (*mut T, usize), order: Ordering) {
+        self.v.store(Stamped::new(ptr, stamp), order)
+    }
+
+    /// Stores a pointer and a stamp if both the current pointer and stamp are
+    /// the same as `current`.
+    ///
+    /// See `Atomic::compare_exchange`.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: (*mut T, usize),
+        new: (*mut T, usize),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        self.v
+            .compare_exchange(
+                Stamped::new(current.0, current.1),
+                Stamped::new(new.0, new.1),
+                success,
+                failure,
+            )
+            .map(Stamped::get)
+            .map_err(Stamped::get)
+    }
+
+    /// Stores a pointer and a stamp if both the current pointer and stamp are
+    /// the same as `current`, possibly failing spuriously.
+    ///
+    /// See `Atomic::compare_exchange_weak`.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: (*mut T, usize),
+        new: (*mut T, usize),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        self.v
+            .compare_exchange_weak(
+                Stamped::new(current.0, current.1),
+                Stamped::new(new.0, new.1),
+                success,
+                failure,
+            )
+            .map(Stamped::get)
+            .map_err(Stamped::get)
+    }
+
+    /// Increments the stamp, wrapping around on overflow, and returns the
+    /// previous pointer and stamp.
+    ///
+    /// This invalidates the pointer and stamp previously loaded by other
+    /// threads without changing the pointer.
+    #[inline]
+    pub fn fetch_increment_stamp(&self, order: Ordering) -> (*mut T, usize) {
+        let fetch_order = match order {
+            Ordering::Release => Ordering::Relaxed,
+            Ordering::AcqRel => Ordering::Acquire,
+            order => order,
+        };
+        let prev = self.v.fetch_update(order, fetch_order, |v| {
+            Some(Stamped {
+                ptr: v.ptr,
+                stamp: v.stamp.wrapping_add(1),
+            })
+        });
+        match prev {
+            Ok(v) | Err(v) => v.get(),
+        }
+    }
+}
+
+impl<T> Default for AtomicStampedPtr<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(ptr::null_mut(), 0)
+    }
+}
+
+impl<T> fmt::Debug for AtomicStampedPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (ptr, stamp) = self.load(Ordering::Relaxed);
+        f.debug_struct("AtomicStampedPtr")
+            .field("ptr", &ptr)
+            .field("stamp", &stamp)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use core::sync::atomic::AtomicPtr;
+    use std::vec::Vec;
+
+    #[test]
+    fn layout() {
+        assert_eq!(
+            core::mem::size_of::<AtomicStampedPtr<u8>>(),
+            2 * core::mem::size_of::<usize>()
+        );
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(
+            AtomicStampedPtr::<u8>::is_lock_free(),
+            Atomic::<u128>::is_lock_free()
+        );
+    }
+
+    #[test]
+    fn operations() {
+        let mut values = [1, 2];
+        let a = &mut values[0] as *mut i32;
+        let b = &mut values[1] as *mut i32;
+        let p = AtomicStampedPtr::default();
+        assert_eq!(p.load(SeqCst), (ptr::null_mut(), 0));
+        p.store((a, 5), SeqCst);
+        assert_eq!(p.fetch_increment_stamp(SeqCst), (a, 5));
+        assert_eq!(
+            p.compare_exchange((a, 5), (b, 7), SeqCst, SeqCst),
+            Err((a, 6))
+        );
+        assert_eq!(
+            p.compare_exchange((a, 6), (b, 7), SeqCst, SeqCst),
+            Ok((a, 6))
+        );
+        p.store((b, usize::MAX), SeqCst);
+        p.fetch_increment_stamp(SeqCst);
+        assert_eq!(p.into_inner(), (b, 0));
+    }
+
+    // A stack of nodes from a pool which are reused instead of freed, which is
+    // the situation where the ABA problem occurs. `next` is atomic since a
+    // thread may read it from a node which another thread is pushing again.
+    struct Node {
+        next: AtomicPtr<Node>,
+    }
+
+    struct Stack {
+        head: AtomicStampedPtr<Node>,
+    }
+
+    impl Stack {
+        fn push(&self, node: &Node) {
+            let node = node as *const Node as *mut Node;
+            let mut head = self.head.load(Relaxed);
+            loop {
+                unsafe { (*node).next.store(head.0, Relaxed) };
+                match self.head.compare_exchange_weak(
+                    head,
+                    (node, head.1.wrapping_add(1)),
+                    Release,
+                    Relaxed,
+                ) {
+                    Ok(_) => return,
+                    Err(current) => head = current,
+                }
+            }
+        }
+
+        fn pop(&self) -> Option<*mut Node> {
+            let mut head = self.head.load(Acquire);
+            loop {
+                if head.0.is_null() {
+                    return None;
+                }
+                let next = unsafe { (*head.0).next.load(Relaxed) };
+                match self.head.compare_exchange_weak(
+                    head,
+                    (next, head.1.wrapping_add(1)),
+                    Acquire,
+                    Acquire,
+                ) {
+                    Ok(_) => return Some(head.0),
+                    Err(current) => head = current,
+                }
+            }
+        }
+
+        fn len(&self) -> usize {
+            let mut len = 0;
+            let mut node = self.head.load(Acquire).0;
+            while !node.is_null() {
+                len += 1;
+                node = unsafe { (*node).next.load(Relaxed) };
+            }
+            len
+        }
+    }
+
+    fn pool(n: usize) -> Vec<Node> {
+        (0..n)
+            .map(|_| Node {
+                next: AtomicPtr::new(ptr::null_mut()),
+            })
+            .collect()
+    }
+
+    // A thread is about to pop A, having read that its successor is B. Other
+    // threads then pop A and B and push A back. A compare_exchange on the
+    // pointer alone would succeed and make the popped B the new head.
+    #[test]
+    fn aba() {
+        let nodes = pool(3);
+        let stack = Stack {
+            head: AtomicStampedPtr::new(ptr::null_mut(), 0),
+        };
+        for node in nodes.iter().rev() {
+            stack.push(node);
+        }
+        let (a, b) = (
+            &nodes[0] as *const Node as *mut Node,
+            &nodes[1] as *const Node,
+        );
+
+        let head = stack.head.load(Acquire);
+        let next = unsafe { (*head.0).next.load(Relaxed) };
+        assert_eq!((head.0, next as *const Node), (a, b));
+
+        assert_eq!(stack.pop(), Some(a));
+        assert_eq!(stack.pop().map(|p| p as *const Node), Some(b));
+        stack.push(unsafe { &*a });
+
+        let plain = AtomicPtr::new(stack.head.load(Relaxed).0);
+        assert!(plain
+            .compare_exchange(head.0, next, AcqRel, Acquire)
+            .is_ok());
+        assert!(stack
+            .head
+            .compare_exchange(head, (next, head.1 + 1), AcqRel, Acquire)
+            .is_err());
+        assert_eq!(stack.len(), 2);
+    }
+
+    #[test]
+    fn treiber_stack_reuse() {
+        const THREADS: usize = 4;
+        const NODES: usize = 8;
+        const ITERS: usize = if cfg!(miri) { 20 } else { 20_000 };
+
+        let nodes = pool(NODES);
+        let stack = Stack {
+            head: AtomicStampedPtr::new(ptr::null_mut(), 0),
+        };
+        for node in &nodes {
+            stack.push(node);
+        }
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..ITERS {
+                        if let Some(node) = stack.pop() {
+                            stack.push(unsafe { &*node });
+                        }
+                    }
+                });
+            }
+        });
+
+        // No node was lost or linked twice.
+        let mut seen = [false; NODES];
+        let mut node = stack.head.load(Acquire).0;
+        while !node.is_null() {
+            let i = nodes.iter().position(|n| ptr::eq(n, node)).unwrap();
+            assert!(!seen[i]);
+            seen[i] = true;
+            node = unsafe { (*node).next.load(Relaxed) };
+        }
+        assert_eq!(seen, [true; NODES]);
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use crate::{Atomic, Atomicable};
+
+/// An atomic state machine, usually over a fieldless enum.
+///
+/// This codifies the "move from state A to state B, fail otherwise" use of
+/// `compare_exchange`. Only values of type `E` can be stored, so the state is
+/// always a valid `E`; see `AtomicChecked` for memory which is also written by
+/// other code.
+///
+/// Failed transitions return the state which was observed instead, which can
+/// be logged or matched on.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::AtomicState;
+/// use atomic::Ordering::{AcqRel, Acquire};
+///
+/// #[derive(Copy, Clone, Debug, PartialEq, bytemuck::NoUninit)]
+/// #[repr(u8)]
+/// enum Conn {
+///     Idle,
+///     Connecting,
+///     Ready,
+///     Closed,
+/// }
+/// use Conn::*;
+///
+/// let conn = AtomicState::new(Idle);
+/// assert_eq!(conn.transition(Idle, Connecting, AcqRel, Acquire), Ok(Idle));
+/// assert_eq!(conn.transition(Idle, Connecting, AcqRel, Acquire), Err(Connecting));
+///
+/// // Any open connection may be closed.
+/// let close = [(Connecting, Closed), (Ready, Closed)];
+/// assert_eq!(conn.transition_any(&close, Closed, AcqRel, Acquire), Ok(Connecting));
+/// assert_eq!(conn.state(Acquire), Closed);
+/// ```
+#[repr(transparent)]
+pub struct AtomicState<E>(Atomic<E>);
+
+impl<E> AtomicState<E> {
+    /// Creates a new `AtomicState`.
+    #[inline]
+    pub const fn new(state: E) -> AtomicState<E> {
+        AtomicState(Atomic::new(state))
+    }
+
+    /// Consumes the `AtomicState` and returns the state.
+    #[inline]
+    pub fn into_inner(self) -> E {
+        self.0.into_inner()
+    }
+}
+
+impl<E: Atomicable + PartialEq> AtomicState<E> {
+    /// Returns the current state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn state(&self, order: Ordering) -> E {
+        self.0.load(order)
+    }
+
+    /// Moves from the state `from` to the state `to`.
+    ///
+    /// Returns `Ok(from)` if the state was `from`, or the observed state as
+    /// the error otherwise. See `Atomic::compare_exchange` for the orderings.
+    #[inline]
+    pub fn transition(&self, from: E, to: E, success: Ordering, failure: Ordering) -> Result<E, E> {
+        self.0.compare_exchange(from, to, success, failure)
+    }
+
+    /// Moves to the state `to` if `(current state, to)` is one of the
+    /// `allowed` transitions.
+    ///
+    /// Returns the previous state, or the observed state as the error if
+    /// there is no allowed transition from it to `to`. If the state changes
+    /// concurrently, the new state is checked again. See
+    /// `Atomic::compare_exchange` for the orderings.
+    pub fn transition_any(
+        &self,
+        allowed: &[(E, E)],
+        to: E,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<E, E> {
+        let mut cur = self.0.load(failure);
+        loop {
+            if !allowed.iter().any(|&(f, t)| f == cur && t == to) {
+                return Err(cur);
+            }
+            match self.0.compare_exchange_weak(cur, to, success, failure) {
+                Ok(prev) => return Ok(prev),
+                Err(prev) => cur = prev,
+            }
+        }
+    }
+}
+
+impl<E: Atomicable + fmt::Debug> fmt::Debug for AtomicState<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicState")
+            .field(&self.0.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use bytemuck::NoUninit;
+    use core::sync::atomic::AtomicUsize;
+
+    #[derive(Copy, Clone, Debug, PartialEq, NoUninit)]
+    #[repr(u8)]
+    enum Conn {
+        Idle,
+        Connecting,
+        Ready,
+        Closed,
+    }
+    use Conn::*;
+
+    const CLOSE: [(Conn, Conn); 3] = [(Idle, Closed), (Connecting, Closed), (Ready, Closed)];
+
+    #[test]
+    fn lifecycle() {
+        let conn = AtomicState::new(Idle);
+        assert_eq!(format!("{:?}", conn), "AtomicState(Idle)");
+        assert_eq!(
+            conn.transition(Connecting, Ready, SeqCst, SeqCst),
+            Err(Idle)
+        );
+        assert_eq!(conn.transition(Idle, Connecting, SeqCst, SeqCst), Ok(Idle));
+        assert_eq!(
+            conn.transition(Connecting, Ready, SeqCst, SeqCst),
+            Ok(Connecting)
+        );
+        assert_eq!(
+            conn.transition_any(&[(Idle, Connecting)], Connecting, SeqCst, SeqCst),
+            Err(Ready)
+        );
+        // `to` must match the allowed transition as well.
+        assert_eq!(
+            conn.transition_any(&CLOSE, Idle, SeqCst, SeqCst),
+            Err(Ready)
+        );
+        assert_eq!(
+            conn.transition_any(&CLOSE, Closed, SeqCst, SeqCst),
+            Ok(Ready)
+        );
+        assert_eq!(
+            conn.transition_any(&CLOSE, Closed, SeqCst, SeqCst),
+            Err(Closed)
+        );
+        assert_eq!(conn.into_inner(), Closed);
+    }
+
+    #[test]
+    fn concurrent_lifecycle() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = if cfg!(miri) { 5 } else { 500 };
+
+        for _ in 0..ROUNDS {
+            let conn = AtomicState::new(Idle);
+            let (connects, readies, closes) = (
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+            );
+            std::thread::scope(|s| {
+                for t in 0..THREADS {
+                    let (conn, connects, readies, closes) = (&conn, &connects, &readies, &closes);
+                    s.spawn(move || {
+                        if t % 4 == 3 {
+                            if conn.transition_any(&CLOSE, Closed, AcqRel, Acquire).is_ok() {
+                                closes.fetch_add(1, Relaxed);
+                            }
+                            return;
+                        }
+                        match conn.transition(Idle, Connecting, AcqRel, Acquire) {
+                            Ok(_) => {
+                                connects.fetch_add(1, Relaxed);
+                                if conn.transition(Connecting, Ready, AcqRel, Acquire).is_ok() {
+                                    readies.fetch_add(1, Relaxed);
+                                }
+                            }
+                            // Another thread got further, or closed it first.
+                            Err(state) => assert_ne!(state, Idle),
+                        }
+                    });
+                }
+            });
+            // Each transition happened at most once, and the connection was
+            // always closed in the end.
+            assert_eq!(conn.state(SeqCst), Closed);
+            assert_eq!(closes.load(SeqCst), 1);
+            assert!(connects.load(SeqCst) <= 1);
+            assert!(readies.load(SeqCst) <= connects.load(SeqCst));
+        }
+    }
+}
@@ -0,0 +1,628 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Helpers for pointers which carry a tag in the low bits that are always zero
+// because of the alignment of `T`.
+//
+// All address arithmetic is done with `wrapping_byte_add` on the original
+// pointer instead of casting integers back to pointers, so the pointers keep
+// their provenance.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::non_null::non_null;
+use crate::{ops, Atomic, AtomicNonNull};
+
+#[inline]
+const fn tag_mask<T>() -> usize {
+    mem::align_of::<T>() - 1
+}
+
+// Checks at compile time that the tag bits passed as a const parameter fit in
+// the alignment of `T`.
+struct TagBits<T, const BITS: usize>(PhantomData<T>);
+
+impl<T, const BITS: usize> TagBits<T, BITS> {
+    const OK: () = if BITS & !tag_mask::<T>() != 0 {
+        core::panic!("the tag bits don't fit in the alignment of `T`")
+    };
+}
+
+#[inline]
+fn with_addr<T>(p: *mut T, addr: usize) -> *mut T {
+    p.wrapping_byte_add(addr.wrapping_sub(p as usize))
+}
+
+#[inline]
+fn untag<T>(p: *mut T) -> (*mut T, usize) {
+    let addr = p as usize;
+    (
+        with_addr(p, addr & !tag_mask::<T>()),
+        addr & tag_mask::<T>(),
+    )
+}
+
+#[inline]
+#[track_caller]
+fn tag<T>(p: *mut T, tag: usize) -> *mut T {
+    assert!(
+        tag & !tag_mask::<T>() == 0,
+        "the tag doesn't fit in the alignment of `T`"
+    );
+    with_addr(p, (p as usize & !tag_mask::<T>()) | tag)
+}
+
+#[inline]
+fn fetch_or_tag<T, const BITS: usize>(a: &AtomicPtr<T>, order: Ordering) -> *mut T {
+    #[allow(clippy::let_unit_value)]
+    let () = TagBits::<T, BITS>::OK;
+    unsafe { ops::atomic_update_ptr(a.as_ptr(), order, |p| with_addr(p, p as usize | BITS)) }
+}
+
+#[inline]
+fn fetch_clear_tag<T, const BITS: usize>(a: &AtomicPtr<T>, order: Ordering) -> *mut T {
+    #[allow(clippy::let_unit_value)]
+    let () = TagBits::<T, BITS>::OK;
+    unsafe { ops::atomic_update_ptr(a.as_ptr(), order, |p| with_addr(p, p as usize & !BITS)) }
+}
+
+#[inline]
+#[track_caller]
+fn compare_exchange_tagged<T>(
+    a: &AtomicPtr<T>,
+    current: (*mut T, usize),
+    new: (*mut T, usize),
+    success: Ordering,
+    failure: Ordering,
+) -> Result<(*mut T, usize), (*mut T, usize)> {
+    a.compare_exchange(
+        tag(current.0, current.1),
+        tag(new.0, new.1),
+        success,
+        failure,
+    )
+    .map(untag)
+    .map_err(untag)
+}
+
+/// Tagged pointers.
+///
+/// Since `T` is aligned, the low `align_of::<T>().trailing_zeros()` bits of a
+/// pointer to it are always zero and can hold a tag. These methods operate on
+/// the tag and the pointer part separately, while preserving the provenance of
+/// the pointer.
+///
+/// The tag bits of `fetch_or_tag` and `fetch_clear_tag` are a const parameter,
+/// and using bits which don't fit in the alignment of `T` fails to compile.
+/// Tags passed at run time must fit as well, or the method panics.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+///
+/// const MARKED: usize = 1;
+///
+/// let mut node = 0u64;
+/// let link = Atomic::new(&mut node as *mut u64);
+/// link.fetch_or_tag::<MARKED>(Ordering::AcqRel);
+/// let (ptr, tag) = link.load_untagged(Ordering::Acquire);
+/// assert_eq!((ptr, tag), (&mut node as *mut u64, MARKED));
+/// ```
+impl<T> Atomic<*mut T> {
+    /// Sets the tag bits `BITS` of the pointer, returning the previous tagged
+    /// pointer.
+    #[inline]
+    pub fn fetch_or_tag<const BITS: usize>(&self, order: Ordering) -> *mut T {
+        fetch_or_tag::<T, BITS>(self.as_atomic_ptr(), order)
+    }
+
+    /// Clears the tag bits `BITS` of the pointer, returning the previous
+    /// tagged pointer.
+    #[inline]
+    pub fn fetch_clear_tag<const BITS: usize>(&self, order: Ordering) -> *mut T {
+        fetch_clear_tag::<T, BITS>(self.as_atomic_ptr(), order)
+    }
+
+    /// Loads the pointer, returning its pointer part with the tag bits cleared
+    /// and its tag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load_untagged(&self, order: Ordering) -> (*mut T, usize) {
+        untag(self.as_atomic_ptr().load(order))
+    }
+
+    /// Stores `new_ptr` tagged with `new_tag` if the current pointer is
+    /// `current_ptr` tagged with `current_tag`.
+    ///
+    /// Returns the previous pointer part and tag. The tag bits of the pointer
+    /// parts are replaced by the tags.
+    ///
+    /// See `Atomic::compare_exchange`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the tags doesn't fit in the alignment of `T`.
+    #[inline]
+    #[track_caller]
+    pub fn compare_exchange_tagged(
+        &self,
+        current_ptr: *mut T,
+        current_tag: usize,
+        new_ptr: *mut T,
+        new_tag: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        compare_exchange_tagged(
+            self.as_atomic_ptr(),
+            (current_ptr, current_tag),
+            (new_ptr, new_tag),
+            success,
+            failure,
+        )
+    }
+}
+
+/// Tagged pointers.
+///
+/// See the methods with the same names on `Atomic<*mut T>`. The pointer parts
+/// must not be null: a `NonNull` whose address consists only of tag bits
+/// can't be split into a pointer part and a tag.
+impl<T> AtomicNonNull<T> {
+    /// Sets the tag bits `BITS` of the pointer, returning the previous tagged
+    /// pointer.
+    #[inline]
+    pub fn fetch_or_tag<const BITS: usize>(&self, order: Ordering) -> NonNull<T> {
+        non_null(fetch_or_tag::<T, BITS>(self.as_atomic_ptr(), order))
+    }
+
+    /// Clears the tag bits `BITS` of the pointer, returning the previous
+    /// tagged pointer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pointer part is null.
+    #[inline]
+    #[track_caller]
+    pub fn fetch_clear_tag<const BITS: usize>(&self, order: Ordering) -> NonNull<T> {
+        #[allow(clippy::let_unit_value)]
+        let () = TagBits::<T, BITS>::OK;
+        let cleared = unsafe {
+            ops::atomic_try_update_ptr(self.as_atomic_ptr().as_ptr(), order, |p| {
+                Some(with_addr(p, p as usize & !BITS)).filter(|p| !p.is_null())
+            })
+        };
+        match cleared {
+            Ok(prev) => non_null(prev),
+            Err(_) => panic!("the pointer part of an `AtomicNonNull` is null"),
+        }
+    }
+
+    /// Loads the pointer, returning its pointer part with the tag bits cleared
+    /// and its tag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`, or if the pointer part is
+    /// null.
+    #[inline]
+    #[track_caller]
+    pub fn load_untagged(&self, order: Ordering) -> (NonNull<T>, usize) {
+        expect_untagged(untag(self.as_atomic_ptr().load(order)))
+    }
+
+    /// Stores `new_ptr` tagged with `new_tag` if the current pointer is
+    /// `current_ptr` tagged with `current_tag`.
+    ///
+    /// See `Atomic::<*mut T>::compare_exchange_tagged`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the tags doesn't fit in the alignment of `T`, or if
+    /// the comparison fails and the pointer part of the current pointer is
+    /// null.
+    #[inline]
+    #[track_caller]
+    pub fn compare_exchange_tagged(
+        &self,
+        current_ptr: NonNull<T>,
+        current_tag: usize,
+        new_ptr: NonNull<T>,
+        new_tag: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(NonNull<T>, usize), (NonNull<T>, usize)> {
+        compare_exchange_tagged(
+            self.as_atomic_ptr(),
+            (current_ptr.as_ptr(), current_tag),
+            (new_ptr.as_ptr(), new_tag),
+            success,
+            failure,
+        )
+        .map(expect_untagged)
+        .map_err(expect_untagged)
+    }
+}
+
+#[inline]
+#[track_caller]
+fn expect_untagged<T>((p, tag): (*mut T, usize)) -> (NonNull<T>, usize) {
+    match NonNull::new(p) {
+        Some(p) => (p, tag),
+        None => panic!("the pointer part of an `AtomicNonNull` is null"),
+    }
+}
+
+/// An atomic pointer with a mark, which is updated together with the pointer.
+///
+/// The mark is stored in the lowest bit of the pointer, so `T` must have an
+/// alignment of at least 2, which is checked at compile time. Like
+/// `AtomicPtr`, this is lock-free wherever pointers are, and the pointers
+/// which are loaded keep their provenance.
+///
+/// This is typically used for the links of a lock-free linked list, where the
+/// mark on the link to the next node indicates that a node is logically
+/// deleted and no new nodes may be linked after it.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicMarkablePtr, Ordering};
+///
+/// let mut node = 0u32;
+/// let next = AtomicMarkablePtr::new(&mut node as *mut u32, false);
+/// assert!(next.try_mark(&mut node, Ordering::AcqRel));
+/// assert!(!next.try_mark(&mut node, Ordering::AcqRel));
+/// assert_eq!(next.load(Ordering::Acquire), (&mut node as *mut u32, true));
+/// ```
+#[repr(transparent)]
+pub struct AtomicMarkablePtr<T>(AtomicPtr<T>);
+
+impl<T> AtomicMarkablePtr<T> {
+    /// Creates a new `AtomicMarkablePtr`.
+    #[inline]
+    pub fn new(ptr: *mut T, mark: bool) -> AtomicMarkablePtr<T> {
+        #[allow(clippy::let_unit_value)]
+        let () = TagBits::<T, 1>::OK;
+        AtomicMarkablePtr(AtomicPtr::new(tag(ptr, mark as usize)))
+    }
+
+    /// Consumes the `AtomicMarkablePtr` and returns the contained pointer and
+    /// mark.
+    #[inline]
+    pub fn into_inner(self) -> (*mut T, bool) {
+        marked(untag(self.0.into_inner()))
+    }
+
+    /// Loads the pointer and the mark.
+    ///
+    /// See `Atomic::load`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> (*mut T, bool) {
+        marked(untag(self.0.load(order)))
+    }
+
+    /// Stores a pointer and a mark.
+    ///
+    /// See `Atomic::store`.
+    #[inline]
+    pub fn store(&self, (ptr, mark): (*mut T, bool), order: Ordering) {
+        self.0.store(tag(ptr, mark as usize), order)
+    }
+
+    /// Stores a pointer and a mark if both the current pointer and mark are
+    /// the same as `current`.
+    ///
+    /// See `Atomic::compare_exchange`.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: (*mut T, bool),
+        new: (*mut T, bool),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, bool), (*mut T, bool)> {
+        compare_exchange_tagged(
+            &self.0,
+            (current.0, current.1 as usize),
+            (new.0, new.1 as usize),
+            success,
+            failure,
+        )
+        .map(marked)
+        .map_err(marked)
+    }
+
+    /// Sets the mark if the current pointer is `expected` and isn't marked
+    /// yet.
+    ///
+    /// Returns whether the mark was set by this call.
+    #[inline]
+    pub fn try_mark(&self, expected: *mut T, order: Ordering) -> bool {
+        let failure = match order {
+            Ordering::Release => Ordering::Relaxed,
+            Ordering::AcqRel => Ordering::Acquire,
+            order => order,
+        };
+        self.compare_exchange((expected, false), (expected, true), order, failure)
+            .is_ok()
+    }
+}
+
+impl<T> Default for AtomicMarkablePtr<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(ptr::null_mut(), false)
+    }
+}
+
+impl<T> fmt::Debug for AtomicMarkablePtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (ptr, mark) = self.load(Ordering::Relaxed);
+        f.debug_struct("AtomicMarkablePtr")
+            .field("ptr", &ptr)
+            .field("mark", &mark)
+            .finish()
+    }
+}
+
+#[inline]
+fn marked<T>((p, tag): (*mut T, usize)) -> (*mut T, bool) {
+    (p, tag != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ordering::*;
+    use std::vec::Vec;
+
+    const MARKED: usize = 0b01;
+    const LOCKED: usize = 0b10;
+
+    #[test]
+    fn raw_pointer() {
+        let mut values = [1u64, 2];
+        let a = &mut values[0] as *mut u64;
+        let b = &mut values[1] as *mut u64;
+        let p = Atomic::new(a);
+        assert_eq!(p.fetch_or_tag::<MARKED>(SeqCst), a);
+        assert_eq!(
+            p.fetch_or_tag::<LOCKED>(SeqCst),
+            a.wrapping_byte_add(MARKED)
+        );
+        assert_eq!(p.load_untagged(SeqCst), (a, MARKED | LOCKED));
+        assert_eq!(
+            p.compare_exchange_tagged(a, MARKED, b, 0, SeqCst, SeqCst),
+            Err((a, MARKED | LOCKED))
+        );
+        assert_eq!(p.fetch_clear_tag::<MARKED>(SeqCst), a.wrapping_byte_add(3));
+        assert_eq!(
+            p.compare_exchange_tagged(a, LOCKED, b, MARKED, SeqCst, SeqCst),
+            Ok((a, LOCKED))
+        );
+        let (ptr, tag) = p.load_untagged(SeqCst);
+        assert_eq!((unsafe { *ptr }, tag), (2, MARKED));
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit")]
+    fn tag_too_large() {
+        let mut value = 0u16;
+        let p = Atomic::new(&mut value as *mut u16);
+        let _ = p.compare_exchange_tagged(&mut value, 0, &mut value, 2, SeqCst, SeqCst);
+    }
+
+    #[test]
+    fn concurrent_tag_flips() {
+        const FLIPS: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+        let mut value = 0x1234_5678u32;
+        let node = NonNull::from(&mut value);
+        let link = AtomicNonNull::new(node);
+        std::thread::scope(|s| {
+            // Each thread owns one tag bit and flips it, while the pointer
+            // part stays the same and can be dereferenced by readers.
+            let link = &link;
+            s.spawn(move || {
+                for _ in 0..FLIPS {
+                    let prev = link.fetch_or_tag::<MARKED>(AcqRel);
+                    assert_eq!(prev.as_ptr() as usize & MARKED, 0);
+                    link.fetch_clear_tag::<MARKED>(AcqRel);
+                }
+            });
+            s.spawn(move || {
+                for _ in 0..FLIPS {
+                    let prev = link.fetch_or_tag::<LOCKED>(AcqRel);
+                    assert_eq!(prev.as_ptr() as usize & LOCKED, 0);
+                    link.fetch_clear_tag::<LOCKED>(AcqRel);
+                }
+            });
+            s.spawn(move || {
+                for _ in 0..FLIPS {
+                    let (ptr, _) = link.load_untagged(Acquire);
+                    assert_eq!(unsafe { *ptr.as_ptr() }, 0x1234_5678);
+                }
+            });
+        });
+        assert_eq!(link.load_untagged(SeqCst), (node, 0));
+    }
+
+    #[test]
+    fn markable() {
+        let mut values = [1u32, 2];
+        let a = &mut values[0] as *mut u32;
+        let b = &mut values[1] as *mut u32;
+        let p = AtomicMarkablePtr::default();
+        assert_eq!(p.load(SeqCst), (ptr::null_mut(), false));
+        p.store((a, false), SeqCst);
+        assert!(!p.try_mark(b, SeqCst));
+        assert!(p.try_mark(a, SeqCst));
+        assert_eq!(
+            p.compare_exchange((a, false), (b, false), SeqCst, SeqCst),
+            Err((a, true))
+        );
+        assert_eq!(
+            p.compare_exchange((a, true), (b, false), SeqCst, SeqCst),
+            Ok((a, true))
+        );
+        assert_eq!(
+            format!("{:?}", p),
+            format!("AtomicMarkablePtr {{ ptr: {:?}, mark: false }}", b)
+        );
+        let (ptr, mark) = p.into_inner();
+        assert_eq!((unsafe { *ptr }, mark), (2, false));
+    }
+
+    // A Harris linked list. Deleting a node first marks its link to the next
+    // node, which prevents nodes from being inserted after it, and then unlinks
+    // it. Nodes come from a pool which outlives the list, so they aren't freed
+    // while other threads may still be traversing them.
+    struct Node {
+        key: usize,
+        next: AtomicMarkablePtr<Node>,
+    }
+
+    impl Node {
+        fn new(key: usize) -> Node {
+            Node {
+                key,
+                next: AtomicMarkablePtr::default(),
+            }
+        }
+    }
+
+    struct List {
+        head: Node,
+    }
+
+    unsafe impl Sync for List {}
+
+    impl List {
+        // Returns the last node with a key less than `key` and the node after
+        // it, unlinking any deleted nodes in between.
+        fn search(&self, key: usize) -> (&Node, *mut Node) {
+            'retry: loop {
+                let mut prev = &self.head;
+                let mut curr = prev.next.load(Acquire).0;
+                loop {
+                    let node = match unsafe { curr.as_ref() } {
+                        Some(node) => node,
+                        None => return (prev, curr),
+                    };
+                    let (succ, deleted) = node.next.load(Acquire);
+                    if deleted {
+                        let unlinked = prev.next.compare_exchange(
+                            (curr, false),
+                            (succ, false),
+                            AcqRel,
+                            Acquire,
+                        );
+                        if unlinked.is_err() {
+                            continue 'retry;
+                        }
+                        curr = succ;
+                    } else if node.key >= key {
+                        return (prev, curr);
+                    } else {
+                        prev = node;
+                        curr = succ;
+                    }
+                }
+            }
+        }
+
+        fn insert(&self, node: &Node) -> bool {
+            let new = node as *const Node as *mut Node;
+            loop {
+                let (prev, curr) = self.search(node.key);
+                if unsafe { curr.as_ref() }.is_some_and(|c| c.key == node.key) {
+                    return false;
+                }
+                node.next.store((curr, false), Relaxed);
+                if prev
+                    .next
+                    .compare_exchange((curr, false), (new, false), Release, Relaxed)
+                    .is_ok()
+                {
+                    return true;
+                }
+            }
+        }
+
+        fn delete(&self, key: usize) -> bool {
+            loop {
+                let (prev, curr) = self.search(key);
+                let node = match unsafe { curr.as_ref() } {
+                    Some(node) if node.key == key => node,
+                    _ => return false,
+                };
+                let (succ, _) = node.next.load(Acquire);
+                if !node.next.try_mark(succ, AcqRel) {
+                    continue;
+                }
+                // If this fails, a later search unlinks the node.
+                let _ = prev
+                    .next
+                    .compare_exchange((curr, false), (succ, false), AcqRel, Relaxed);
+                return true;
+            }
+        }
+
+        fn keys(&self) -> Vec<usize> {
+            let mut keys = Vec::new();
+            let mut curr = self.head.next.load(Acquire).0;
+            while let Some(node) = unsafe { curr.as_ref() } {
+                let (next, deleted) = node.next.load(Acquire);
+                if !deleted {
+                    keys.push(node.key);
+                }
+                curr = next;
+            }
+            keys
+        }
+    }
+
+    #[test]
+    fn harris_list() {
+        const THREADS: usize = 4;
+        const KEYS: usize = if cfg!(miri) { 8 } else { 256 };
+
+        let pool: Vec<Node> = (0..THREADS * KEYS).map(Node::new).collect();
+        let list = List { head: Node::new(0) };
+        std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let (list, pool) = (&list, &pool);
+                s.spawn(move || {
+                    // Interleave the keys of the threads, and delete the even
+                    // keys of the next thread, which may not be inserted yet.
+                    for i in 0..KEYS {
+                        assert!(list.insert(&pool[i * THREADS + t]));
+                        let other = i * THREADS + (t + 1) % THREADS;
+                        if other % 2 == 0 {
+                            while !list.delete(other) {
+                                std::thread::yield_now();
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        let odd: Vec<usize> = (0..THREADS * KEYS).filter(|k| k % 2 == 1).collect();
+        assert_eq!(list.keys(), odd);
+        assert!(!list.insert(&Node::new(1)));
+        assert!(!list.delete(0));
+    }
+}
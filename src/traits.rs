@@ -0,0 +1,454 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::hash::Hash;
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::Ordering;
+
+use bytemuck::{NoUninit, Pod};
+
+use crate::Atomic;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Types which can be used with the operations of `Atomic<T>`.
+///
+/// This is implemented for every type implementing `bytemuck::NoUninit` and
+/// can't be implemented otherwise. It only exists so that using an `Atomic`
+/// with an unsuitable type produces an error message explaining what is
+/// needed, instead of one about bytemuck's internal traits.
+#[cfg_attr(
+    atomic_diagnostic_namespace,
+    diagnostic::on_unimplemented(
+        message = "`{Self}` can't be used with `Atomic` because it doesn't implement `NoUninit`",
+        label = "`{Self}` doesn't implement `NoUninit`",
+        note = "derive `bytemuck::NoUninit` for `{Self}`, which requires a `#[repr(C)]`, \
+                `#[repr(transparent)]` or primitive `#[repr]`",
+        note = "the type must not contain any padding bytes: add explicit fields \
+                to fill any gaps, or reorder the fields so that none are needed",
+        note = "enums must be fieldless and have an explicit primitive `#[repr]`, \
+                such as `#[repr(u8)]`"
+    )
+)]
+pub trait Atomicable: NoUninit {}
+
+impl<T: NoUninit> Atomicable for T {}
+
+/// Integer operations on `Atomic`s, which allows writing code which is generic
+/// over the width of an atomic integer.
+///
+/// This trait is implemented for `Atomic<T>` for all primitive integer types
+/// and is sealed, so it can't be implemented outside of this crate.
+///
+/// # Examples
+///
+/// A ring buffer index which is generic over the width of its counter:
+///
+/// ```rust
+/// use atomic::{Atomic, AtomicInt, Ordering};
+///
+/// struct RingIndex<C: AtomicInt> {
+///     head: C,
+///     capacity: u64,
+/// }
+///
+/// impl<C: AtomicInt> RingIndex<C>
+/// where
+///     C::Prim: Into<u64> + From<u8>,
+/// {
+///     fn new(capacity: u64) -> Self {
+///         RingIndex {
+///             head: C::new(0u8.into()),
+///             capacity,
+///         }
+///     }
+///
+///     /// Claims the next slot, returning its position in the buffer.
+///     fn next_slot(&self) -> u64 {
+///         let head = self.head.fetch_add(1u8.into(), Ordering::Relaxed);
+///         head.into() % self.capacity
+///     }
+/// }
+///
+/// let small: RingIndex<Atomic<u8>> = RingIndex::new(4);
+/// let large: RingIndex<Atomic<u64>> = RingIndex::new(4);
+/// for i in 0..6 {
+///     assert_eq!(small.next_slot(), i % 4);
+///     assert_eq!(large.next_slot(), i % 4);
+/// }
+/// ```
+pub trait AtomicInt: sealed::Sealed + Sized + Send + Sync {
+    /// The primitive integer type held by the atomic.
+    type Prim: Copy + Eq + Ord + Hash + fmt::Debug + fmt::Display + Default + Pod + Send;
+
+    /// Creates a new atomic integer.
+    fn new(v: Self::Prim) -> Self;
+
+    /// Loads a value from the atomic integer. See `Atomic::load`.
+    fn load(&self, order: Ordering) -> Self::Prim;
+
+    /// Stores a value into the atomic integer. See `Atomic::store`.
+    fn store(&self, val: Self::Prim, order: Ordering);
+
+    /// Stores a value into the atomic integer, returning the old value. See
+    /// `Atomic::swap`.
+    fn swap(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+
+    /// Stores a value into the atomic integer if the current value is the
+    /// same as the `current` value. See `Atomic::compare_exchange`.
+    fn compare_exchange(
+        &self,
+        current: Self::Prim,
+        new: Self::Prim,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self::Prim, Self::Prim>;
+
+    /// Stores a value into the atomic integer if the current value is the
+    /// same as the `current` value, possibly failing spuriously. See
+    /// `Atomic::compare_exchange_weak`.
+    fn compare_exchange_weak(
+        &self,
+        current: Self::Prim,
+        new: Self::Prim,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self::Prim, Self::Prim>;
+
+    /// Add to the current value, returning the previous value.
+    fn fetch_add(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+
+    /// Subtract from the current value, returning the previous value.
+    fn fetch_sub(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+
+    /// Bitwise and with the current value, returning the previous value.
+    fn fetch_and(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+
+    /// Bitwise or with the current value, returning the previous value.
+    fn fetch_or(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+
+    /// Bitwise xor with the current value, returning the previous value.
+    fn fetch_xor(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+
+    /// Minimum with the current value.
+    fn fetch_min(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+
+    /// Maximum with the current value.
+    fn fetch_max(&self, val: Self::Prim, order: Ordering) -> Self::Prim;
+}
+
+macro_rules! atomic_int {
+    ($($t:ty)*) => ($(
+        impl sealed::Sealed for Atomic<$t> {}
+
+        impl AtomicInt for Atomic<$t> {
+            type Prim = $t;
+
+            #[inline]
+            fn new(v: $t) -> Self {
+                Atomic::<$t>::new(v)
+            }
+            #[inline]
+            fn load(&self, order: Ordering) -> $t {
+                Atomic::<$t>::load(self, order)
+            }
+            #[inline]
+            fn store(&self, val: $t, order: Ordering) {
+                Atomic::<$t>::store(self, val, order)
+            }
+            #[inline]
+            fn swap(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::swap(self, val, order)
+            }
+            #[inline]
+            fn compare_exchange(
+                &self,
+                current: $t,
+                new: $t,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$t, $t> {
+                Atomic::<$t>::compare_exchange(self, current, new, success, failure)
+            }
+            #[inline]
+            fn compare_exchange_weak(
+                &self,
+                current: $t,
+                new: $t,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$t, $t> {
+                Atomic::<$t>::compare_exchange_weak(self, current, new, success, failure)
+            }
+            #[inline]
+            fn fetch_add(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_add(self, val, order)
+            }
+            #[inline]
+            fn fetch_sub(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_sub(self, val, order)
+            }
+            #[inline]
+            fn fetch_and(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_and(self, val, order)
+            }
+            #[inline]
+            fn fetch_or(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_or(self, val, order)
+            }
+            #[inline]
+            fn fetch_xor(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_xor(self, val, order)
+            }
+            #[inline]
+            fn fetch_min(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_min(self, val, order)
+            }
+            #[inline]
+            fn fetch_max(&self, val: $t, order: Ordering) -> $t {
+                Atomic::<$t>::fetch_max(self, val, order)
+            }
+        }
+    )*);
+}
+atomic_int! { u8 u16 u32 u64 usize u128 i8 i16 i32 i64 isize i128 }
+
+/// Integer newtypes, such as `struct Lsn(u64)`, whose `Atomic` supports the
+/// integer operations of the wrapped integer.
+///
+/// `Atomic<T>` of such a type has `fetch_add`, `fetch_sub`, `fetch_and`,
+/// `fetch_or`, `fetch_xor`, `fetch_min` and `fetch_max`, which take and
+/// return `T` and operate on the underlying integer with the same native
+/// operations as `Atomic<T::Repr>`. They wrap around on overflow like the
+/// integer operations, whatever the newtype's own arithmetic does.
+///
+/// With the `derive` feature, this can be derived for a
+/// `#[repr(transparent)]` struct with a single field of a primitive integer
+/// type. `NoUninit` must be derived or implemented as well.
+///
+/// # Safety
+///
+/// `Self` must be `#[repr(transparent)]` over `Repr`, and every value of
+/// `Repr` must be a valid value of `Self`, since the result of the operations
+/// is converted back to `Self`.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, AtomicArithmetic, NoUninit, Ordering};
+///
+/// #[derive(Copy, Clone, Debug, PartialEq)]
+/// #[repr(transparent)]
+/// struct Lsn(u64);
+///
+/// unsafe impl NoUninit for Lsn {}
+/// unsafe impl AtomicArithmetic for Lsn {
+///     type Repr = u64;
+/// }
+///
+/// static NEXT_LSN: Atomic<Lsn> = Atomic::new(Lsn(1));
+///
+/// assert_eq!(NEXT_LSN.fetch_add(Lsn(10), Ordering::Relaxed), Lsn(1));
+/// assert_eq!(NEXT_LSN.load(Ordering::Relaxed), Lsn(11));
+/// ```
+pub unsafe trait AtomicArithmetic: NoUninit {
+    /// The primitive integer type which `Self` wraps.
+    type Repr: Pod;
+}
+
+/// `Atomic`s which have the same layout as one of the atomic types of
+/// `core::sync::atomic`.
+///
+/// This is implemented for `Atomic<T>` of the primitive integer types and
+/// `bool`, when the target supports atomics of that size. References can be
+/// converted in both directions without copying, and operations through either
+/// view are atomic with respect to each other. The owned types can also be
+/// converted into each other with `From`.
+///
+/// This trait is sealed, so it can't be implemented outside of this crate.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, AtomicPrimitive, Ordering};
+/// use std::sync::atomic::AtomicU32;
+///
+/// fn bump(counter: &AtomicU32) {
+///     counter.fetch_add(1, Ordering::Relaxed);
+/// }
+///
+/// let a = Atomic::new(1u32);
+/// bump(a.as_std());
+/// assert_eq!(a.load(Ordering::Relaxed), 2);
+/// ```
+pub trait AtomicPrimitive: sealed::Sealed {
+    /// The corresponding type from `core::sync::atomic`.
+    type Std;
+
+    /// Returns a reference to the `core::sync::atomic` type with the same
+    /// layout.
+    ///
+    /// This fails to compile on targets where the alignment of the primitive
+    /// type is smaller than the alignment of the atomic type, such as `u64`
+    /// on 32-bit x86.
+    fn as_std(&self) -> &Self::Std;
+
+    /// Converts a reference to the `core::sync::atomic` type into a reference
+    /// to this type.
+    fn from_std(v: &Self::Std) -> &Self;
+}
+
+// Used to assert that `Atomic<T>` has the same layout as a std atomic type.
+// Targets without atomic read-modify-write operations have no such types.
+#[cfg_attr(not(target_has_atomic = "8"), allow(dead_code))]
+struct SameLayout<A, B>(PhantomData<(A, B)>);
+
+#[cfg_attr(not(target_has_atomic = "8"), allow(dead_code))]
+impl<A, B> SameLayout<A, B> {
+    const OK: () = assert!(
+        mem::size_of::<A>() == mem::size_of::<B>() && mem::align_of::<A>() == mem::align_of::<B>(),
+        "the atomic types have a different layout on this target"
+    );
+}
+
+macro_rules! atomic_primitive {
+    ($($cfg:meta, $t:ty, $std:ident;)*) => ($(
+        #[cfg($cfg)]
+        impl AtomicPrimitive for Atomic<$t> {
+            type Std = core::sync::atomic::$std;
+
+            #[inline]
+            fn as_std(&self) -> &Self::Std {
+                #[allow(clippy::let_unit_value)]
+                let () = SameLayout::<Self, Self::Std>::OK;
+                unsafe { &*(self as *const Self as *const Self::Std) }
+            }
+
+            #[inline]
+            fn from_std(v: &Self::Std) -> &Self {
+                // The std type has the same size, and at least the alignment,
+                // of `Atomic<T>`.
+                unsafe { &*(v as *const Self::Std as *const Self) }
+            }
+        }
+
+        #[cfg($cfg)]
+        impl From<core::sync::atomic::$std> for Atomic<$t> {
+            #[inline]
+            fn from(v: core::sync::atomic::$std) -> Self {
+                Atomic::new(v.into_inner())
+            }
+        }
+
+        #[cfg($cfg)]
+        impl From<Atomic<$t>> for core::sync::atomic::$std {
+            #[inline]
+            fn from(v: Atomic<$t>) -> Self {
+                core::sync::atomic::$std::new(v.into_inner())
+            }
+        }
+    )*);
+}
+impl sealed::Sealed for Atomic<bool> {}
+atomic_primitive! {
+    target_has_atomic = "8", bool, AtomicBool;
+    target_has_atomic = "8", u8, AtomicU8;
+    target_has_atomic = "8", i8, AtomicI8;
+    target_has_atomic = "16", u16, AtomicU16;
+    target_has_atomic = "16", i16, AtomicI16;
+    target_has_atomic = "32", u32, AtomicU32;
+    target_has_atomic = "32", i32, AtomicI32;
+    target_has_atomic = "64", u64, AtomicU64;
+    target_has_atomic = "64", i64, AtomicI64;
+    target_has_atomic = "ptr", usize, AtomicUsize;
+    target_has_atomic = "ptr", isize, AtomicIsize;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomicInt, AtomicPrimitive};
+    use crate::{Atomic, Ordering::*};
+    use core::sync::atomic::{AtomicBool, AtomicI16, AtomicU64, AtomicUsize};
+
+    fn exercise<C: AtomicInt>(zero: C::Prim, one: C::Prim, two: C::Prim, three: C::Prim) {
+        let a = C::new(zero);
+        assert_eq!(a.fetch_add(two, SeqCst), zero);
+        assert_eq!(a.fetch_sub(one, SeqCst), two);
+        assert_eq!(a.fetch_max(two, SeqCst), one);
+        assert_eq!(a.fetch_min(one, SeqCst), two);
+        assert_eq!(a.fetch_or(two, SeqCst), one);
+        assert_eq!(a.fetch_and(two, SeqCst), three);
+        assert_eq!(a.fetch_xor(two, SeqCst), two);
+        assert_eq!(a.swap(one, SeqCst), zero);
+        assert_eq!(a.compare_exchange(zero, two, SeqCst, SeqCst), Err(one));
+        assert_eq!(a.compare_exchange(one, two, SeqCst, SeqCst), Ok(one));
+        a.store(zero, SeqCst);
+        assert_eq!(a.load(SeqCst), zero);
+    }
+
+    #[test]
+    fn atomic_int_generic() {
+        exercise::<Atomic<u8>>(0, 1, 2, 3);
+        exercise::<Atomic<u64>>(0, 1, 2, 3);
+        exercise::<Atomic<i16>>(0, 1, 2, 3);
+        exercise::<Atomic<u128>>(0, 1, 2, 3);
+        exercise::<Atomic<isize>>(0, 1, 2, 3);
+    }
+
+    #[test]
+    fn atomic_primitive_views() {
+        let a = Atomic::new(5u64);
+        let s: &AtomicU64 = a.as_std();
+        assert_eq!(s as *const AtomicU64 as usize, &a as *const _ as usize);
+        s.fetch_add(1, SeqCst);
+        assert_eq!(a.load(SeqCst), 6);
+        a.fetch_sub(2, SeqCst);
+        assert_eq!(s.load(SeqCst), 4);
+        assert!(core::ptr::eq(Atomic::from_std(s), &a));
+
+        let std = AtomicI16::new(-1);
+        let b: &Atomic<i16> = Atomic::from_std(&std);
+        assert_eq!(b.fetch_min(-5, SeqCst), -1);
+        assert_eq!(std.load(SeqCst), -5);
+        assert!(core::ptr::eq(b.as_std(), &std));
+
+        let flag = AtomicBool::new(false);
+        Atomic::<bool>::from_std(&flag).store(true, SeqCst);
+        assert!(flag.load(SeqCst));
+    }
+
+    #[test]
+    fn atomic_primitive_from() {
+        let a: Atomic<usize> = AtomicUsize::new(7).into();
+        assert_eq!(a.load(SeqCst), 7);
+        let s: AtomicUsize = a.into();
+        assert_eq!(s.load(SeqCst), 7);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn atomic_primitive_interleaved() {
+        let a = Atomic::new(0usize);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..1000 {
+                    a.fetch_add(1, SeqCst);
+                }
+            });
+            scope.spawn(|| {
+                for _ in 0..1000 {
+                    a.as_std().fetch_add(1, SeqCst);
+                }
+            });
+        });
+        assert_eq!(AtomicUsize::from(a).into_inner(), 2000);
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// These only forward to the implementations of `T`, so formatting the integer
+// and bool atomics doesn't use `core::fmt` at all.
+
+use ::ufmt::{uDebug, uDisplay, uWrite, Formatter};
+use bytemuck::NoUninit;
+
+use crate::{Atomic, Ordering};
+
+/// Formats the current value like the `Debug` implementation, as
+/// `Atomic(value)`, after a `Relaxed` load.
+///
+/// This requires the `ufmt` feature.
+impl<T: NoUninit + uDebug> uDebug for Atomic<T> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        f.debug_tuple("Atomic")?
+            .field(&self.load(Ordering::Relaxed))?
+            .finish()
+    }
+}
+
+/// Formats the current value using `T`'s `uDisplay` implementation, after a
+/// `Relaxed` load.
+///
+/// This requires the `ufmt` feature.
+impl<T: NoUninit + uDisplay> uDisplay for Atomic<T> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        uDisplay::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Atomic;
+    use core::convert::Infallible;
+    use std::string::String;
+    use ufmt::{uWrite, uwrite};
+
+    struct Buf(String);
+
+    impl uWrite for Buf {
+        type Error = Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+            self.0.push_str(s);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn formatting() {
+        let mut buf = Buf(String::new());
+        uwrite!(buf, "{:?} {}", Atomic::new(-5i32), Atomic::new(42u64)).unwrap();
+        uwrite!(buf, " {:?} {}", Atomic::new(true), Atomic::new(false)).unwrap();
+        assert_eq!(buf.0, "Atomic(-5) 42 Atomic(true) false");
+
+        // The same as the `core::fmt` implementations.
+        let a = Atomic::new(u8::MAX);
+        let mut buf = Buf(String::new());
+        uwrite!(buf, "{:?}", a).unwrap();
+        assert_eq!(buf.0, format!("{:?}", a));
+    }
+}
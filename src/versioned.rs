@@ -0,0 +1,297 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use bytemuck::{NoUninit, Pod};
+
+use crate::AtomicPair;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The version counters of an `AtomicVersioned`.
+///
+/// This is implemented for `u32`, stored with the value in a `u64`, and for
+/// `u64`, stored with the value in a `u128`. It is sealed, so it can't be
+/// implemented outside of this crate.
+pub trait Version: sealed::Sealed + NoUninit {
+    /// The integer holding both the value and the version.
+    type Storage: Pod;
+
+    #[doc(hidden)]
+    const ZERO: Self;
+
+    #[doc(hidden)]
+    fn wrapping_next(self) -> Self;
+
+    #[doc(hidden)]
+    fn to_u64(self) -> u64;
+}
+
+macro_rules! version {
+    ($($t:ty => $storage:ty;)*) => ($(
+        impl sealed::Sealed for $t {}
+
+        impl Version for $t {
+            type Storage = $storage;
+            const ZERO: $t = 0;
+
+            #[inline]
+            fn wrapping_next(self) -> $t {
+                self.wrapping_add(1)
+            }
+
+            #[inline]
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+        }
+    )*);
+}
+version! {
+    u32 => u64;
+    u64 => u128;
+}
+
+/// A small value paired with a version which is incremented on every write.
+///
+/// This supports optimistic concurrency: a reader remembers the version it
+/// loaded, and a writer only succeeds if the version hasn't changed since.
+/// Both are stored in a single `AtomicPair`, so they are always loaded and
+/// updated together.
+///
+/// With the default `u32` version, `T` must fit in 32 bits and the pair is
+/// stored in a `u64`. With a `u64` version, `T` may be up to 64 bits and the
+/// pair is stored in a `u128`, which is only lock-free on targets with
+/// 128-bit atomics. Larger types fail to compile.
+///
+/// # Wrap-around
+///
+/// The version wraps around to zero after `2^32` writes with a `u32` version,
+/// or `2^64` writes with a `u64` version. A reader which holds on to a
+/// version across exactly that many writes can't tell that the value
+/// changed. Use a `u64` version if that many writes may happen while a
+/// version is held.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{AtomicVersioned, Ordering};
+///
+/// let config = AtomicVersioned::<u16>::new(80);
+/// let (port, version) = config.load(Ordering::Acquire);
+/// assert_eq!((port, version), (80, 0));
+///
+/// // Another writer gets there first.
+/// assert_eq!(config.store_bump(8080, Ordering::AcqRel), 1);
+/// assert_eq!(
+///     config.compare_exchange_version(version, port + 1, Ordering::AcqRel, Ordering::Acquire),
+///     Err((8080, 1))
+/// );
+/// ```
+pub struct AtomicVersioned<T, V: Version = u32> {
+    v: AtomicPair<T, V, V::Storage>,
+}
+
+impl<T: NoUninit, V: Version> AtomicVersioned<T, V> {
+    /// Creates a new `AtomicVersioned` with version 0.
+    #[inline]
+    pub fn new(val: T) -> AtomicVersioned<T, V> {
+        AtomicVersioned {
+            v: AtomicPair::new(val, V::ZERO),
+        }
+    }
+
+    /// Checks if operations on an `AtomicVersioned<T, V>` are lock-free on
+    /// the current target.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        AtomicPair::<T, V, V::Storage>::is_lock_free()
+    }
+
+    /// Consumes the `AtomicVersioned` and returns the value and its version.
+    #[inline]
+    pub fn into_inner(self) -> (T, u64) {
+        let (val, version) = self.v.into_inner();
+        (val, version.to_u64())
+    }
+
+    /// Loads the value and its version.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> (T, u64) {
+        let (val, version) = self.v.load(order);
+        (val, version.to_u64())
+    }
+
+    /// Stores a value and increments the version, returning the new version.
+    ///
+    /// This is a compare-and-swap loop, with `order` as the ordering of the
+    /// successful exchange.
+    #[inline]
+    pub fn store_bump(&self, val: T, order: Ordering) -> u64 {
+        let prev = self
+            .v
+            .fetch_update(order, Ordering::Relaxed, |(_, version)| {
+                Some((val, version.wrapping_next()))
+            });
+        match prev {
+            Ok((_, version)) | Err((_, version)) => version.wrapping_next().to_u64(),
+        }
+    }
+
+    /// Stores a value and increments the version if the version is still
+    /// `expected_version`.
+    ///
+    /// Returns the new version, or the current value and version as the error
+    /// if the version was different. See `Atomic::compare_exchange` for the
+    /// orderings.
+    pub fn compare_exchange_version(
+        &self,
+        expected_version: u64,
+        new: T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u64, (T, u64)> {
+        let r = self.v.fetch_update(success, failure, |(_, version)| {
+            (version.to_u64() == expected_version).then(|| (new, version.wrapping_next()))
+        });
+        match r {
+            Ok((_, version)) => Ok(version.wrapping_next().to_u64()),
+            Err((val, version)) => Err((val, version.to_u64())),
+        }
+    }
+}
+
+impl<T: NoUninit + Default, V: Version> Default for AtomicVersioned<T, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: NoUninit + fmt::Debug, V: Version> fmt::Debug for AtomicVersioned<T, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicVersioned")
+            .field(&self.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Atomic;
+    use crate::Ordering::*;
+    use std::collections::HashMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn operations() {
+        let a = AtomicVersioned::<u32>::default();
+        assert_eq!(
+            AtomicVersioned::<u32>::is_lock_free(),
+            cfg!(target_has_atomic = "64")
+        );
+        assert_eq!(a.store_bump(5, SeqCst), 1);
+        assert_eq!(
+            a.compare_exchange_version(0, 6, SeqCst, SeqCst),
+            Err((5, 1))
+        );
+        assert_eq!(a.compare_exchange_version(1, 6, SeqCst, SeqCst), Ok(2));
+        assert_eq!(format!("{:?}", a), "AtomicVersioned((6, 2))");
+        assert_eq!(a.into_inner(), (6, 2));
+
+        let a = AtomicVersioned::<u64, u64>::new(u64::MAX);
+        assert_eq!(
+            AtomicVersioned::<u64, u64>::is_lock_free(),
+            Atomic::<u128>::is_lock_free()
+        );
+        assert_eq!(a.store_bump(1, SeqCst), 1);
+        assert_eq!(a.load(SeqCst), (1, 1));
+    }
+
+    #[test]
+    fn wrap_around() {
+        let a = AtomicVersioned::<u16> {
+            v: AtomicPair::new(1, u32::MAX - 1),
+        };
+        assert_eq!(a.store_bump(2, SeqCst), u32::MAX as u64);
+        assert_eq!(a.store_bump(3, SeqCst), 0);
+        // The version is back to the one from `new`, so a reader which held
+        // on to it can't tell that the value changed.
+        assert_eq!(a.compare_exchange_version(0, 4, SeqCst, SeqCst), Ok(1));
+
+        let a = AtomicVersioned::<u32, u64> {
+            v: AtomicPair::new(1, u64::MAX),
+        };
+        assert_eq!(a.store_bump(2, SeqCst), 0);
+        assert_eq!(a.into_inner(), (2, 0));
+    }
+
+    #[test]
+    fn concurrent_versions() {
+        const THREADS: u32 = 4;
+        const ROUNDS: u32 = if cfg!(miri) { 50 } else { 10_000 };
+
+        let a = AtomicVersioned::<u32>::new(u32::MAX);
+        let (written, seen) = std::thread::scope(|s| {
+            let reader = s.spawn(|| {
+                let mut seen = HashMap::new();
+                for _ in 0..THREADS * ROUNDS {
+                    let (val, version) = a.load(Acquire);
+                    let prev = seen.insert(version, val);
+                    assert!(prev.is_none() || prev == Some(val), "{:?}", (version, val));
+                }
+                seen
+            });
+            let writers: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let a = &a;
+                    s.spawn(move || {
+                        let mut written = Vec::new();
+                        for i in 0..ROUNDS {
+                            let val = t * ROUNDS + i;
+                            if i % 2 == 0 {
+                                written.push((a.store_bump(val, AcqRel), val));
+                            } else {
+                                let (_, version) = a.load(Acquire);
+                                if let Ok(version) =
+                                    a.compare_exchange_version(version, val, AcqRel, Acquire)
+                                {
+                                    written.push((version, val));
+                                }
+                            }
+                        }
+                        written
+                    })
+                })
+                .collect();
+            let written: Vec<_> = writers
+                .into_iter()
+                .flat_map(|w| w.join().unwrap())
+                .collect();
+            (written, reader.join().unwrap())
+        });
+
+        // Every write got its own version, and the reader only saw values
+        // with the version they were written with.
+        let written: HashMap<u64, u32> = written.iter().copied().collect();
+        assert_eq!(written.len() as u64, a.load(SeqCst).1);
+        for (version, val) in seen {
+            if version != 0 {
+                assert_eq!(written[&version], val);
+            }
+        }
+    }
+}
@@ -0,0 +1,486 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::sync::atomic::Ordering;
+use core::time::Duration;
+use std::time::Instant;
+
+use bytemuck::NoUninit;
+
+use crate::{ops, Backoff, WaitResult};
+
+// Number of times we poll the value before blocking the thread. Most waits are
+// short so this avoids the cost of a system call or of the wait queue.
+const SPIN_LIMIT: u32 = 6;
+
+// Spinlock pointer hashing function from compiler-rt, see fallback.rs.
+#[inline]
+pub fn hash_addr(addr: usize, len: usize) -> usize {
+    let mut hash = addr >> 4;
+    let low = hash & (len - 1);
+    hash >>= 16;
+    hash ^= low;
+    hash & (len - 1)
+}
+
+// Time left until `deadline`, or `None` if there is no deadline.
+#[inline]
+fn remaining(deadline: Option<Instant>) -> Option<Duration> {
+    deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+#[inline]
+pub unsafe fn wait<T: NoUninit>(
+    dst: *mut T,
+    expected: T,
+    order: Ordering,
+    deadline: Option<Instant>,
+) -> WaitResult {
+    let should_wait =
+        || bytemuck::bytes_of(&ops::atomic_load(dst, order)) == bytemuck::bytes_of(&expected);
+
+    let backoff = Backoff::new();
+    for _ in 0..SPIN_LIMIT {
+        if !should_wait() {
+            return WaitResult::Changed;
+        }
+        backoff.spin();
+    }
+
+    imp::wait(dst, expected, should_wait, deadline);
+
+    if !should_wait() {
+        WaitResult::Changed
+    } else if remaining(deadline) == Some(Duration::from_secs(0)) {
+        WaitResult::TimedOut
+    } else {
+        WaitResult::Notified
+    }
+}
+
+#[inline]
+pub unsafe fn notify<T>(dst: *mut T, all: bool) {
+    imp::notify(dst, all);
+}
+
+// Platforms with a futex-like primitive. Values which are exactly 32 bits and
+// lock-free are waited on directly. Everything else waits on a 32-bit sequence
+// number in a bucket selected from the address, which is bumped by every
+// notification, and re-checks the value after waking up.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    windows,
+    target_os = "macos",
+    target_os = "ios",
+    all(target_arch = "wasm32", target_feature = "atomics"),
+))]
+mod imp {
+    use core::mem;
+    use core::sync::atomic::{fence, AtomicU32, Ordering};
+    use std::time::Instant;
+
+    use bytemuck::NoUninit;
+
+    use super::{hash_addr, remaining};
+    use crate::{ops, CachePadded};
+
+    struct Bucket {
+        // Number of threads currently blocked on an address hashing to this
+        // bucket, which allows notifications to skip the system call.
+        waiters: AtomicU32,
+        seq: AtomicU32,
+    }
+
+    impl Bucket {
+        const fn new() -> Bucket {
+            Bucket {
+                waiters: AtomicU32::new(0),
+                seq: AtomicU32::new(0),
+            }
+        }
+    }
+
+    // We give each bucket its own cache line to avoid false sharing.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_BUCKET: CachePadded<Bucket> = CachePadded::new(Bucket::new());
+    static BUCKETS: [CachePadded<Bucket>; 64] = [EMPTY_BUCKET; 64];
+
+    #[inline]
+    fn bucket_for_addr(addr: usize) -> &'static Bucket {
+        &BUCKETS[hash_addr(addr, BUCKETS.len())]
+    }
+
+    #[inline]
+    fn is_direct<T>() -> bool {
+        mem::size_of::<T>() == 4 && ops::atomic_is_lock_free::<T>()
+    }
+
+    pub unsafe fn wait<T: NoUninit>(
+        dst: *mut T,
+        expected: T,
+        should_wait: impl Fn() -> bool,
+        deadline: Option<Instant>,
+    ) {
+        let bucket = bucket_for_addr(dst as usize);
+
+        // This fence pairs with the one in `notify`: either the notifier sees
+        // our registration or we see the new value.
+        bucket.waiters.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+        if is_direct::<T>() {
+            if should_wait() {
+                super::futex::wait(
+                    &*(dst as *const AtomicU32),
+                    mem::transmute_copy(&expected),
+                    remaining(deadline),
+                );
+            }
+        } else {
+            let seq = bucket.seq.load(Ordering::Acquire);
+            if should_wait() {
+                super::futex::wait(&bucket.seq, seq, remaining(deadline));
+            }
+        }
+        bucket.waiters.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub unsafe fn notify<T>(dst: *mut T, all: bool) {
+        let bucket = bucket_for_addr(dst as usize);
+        if is_direct::<T>() {
+            fence(Ordering::SeqCst);
+            if bucket.waiters.load(Ordering::Relaxed) == 0 {
+                return;
+            }
+            let word = &*(dst as *const AtomicU32);
+            if all {
+                super::futex::wake_all(word);
+            } else {
+                super::futex::wake_one(word);
+            }
+        } else {
+            bucket.seq.fetch_add(1, Ordering::Release);
+            fence(Ordering::SeqCst);
+            if bucket.waiters.load(Ordering::Relaxed) == 0 {
+                return;
+            }
+            // The sequence number is shared with other addresses so we have to
+            // wake everyone to be sure that the right thread is woken up.
+            super::futex::wake_all(&bucket.seq);
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod futex {
+    use core::convert::TryInto;
+    use core::ptr;
+    use core::sync::atomic::AtomicU32;
+    use core::time::Duration;
+
+    #[inline]
+    pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        // Timeouts which don't fit in a timespec are treated as infinite.
+        let ts = timeout.and_then(|timeout| {
+            Some(libc::timespec {
+                tv_sec: timeout.as_secs().try_into().ok()?,
+                tv_nsec: timeout.subsec_nanos() as _,
+            })
+        });
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                word as *const AtomicU32,
+                libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                expected,
+                ts.as_ref()
+                    .map_or(ptr::null(), |ts| ts as *const libc::timespec),
+            );
+        }
+    }
+
+    #[inline]
+    pub fn wake_one(word: &AtomicU32) {
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                word as *const AtomicU32,
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                1i32,
+            );
+        }
+    }
+
+    #[inline]
+    pub fn wake_all(word: &AtomicU32) {
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                word as *const AtomicU32,
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                i32::MAX,
+            );
+        }
+    }
+}
+
+#[cfg(windows)]
+mod futex {
+    use core::ffi::c_void;
+    use core::sync::atomic::AtomicU32;
+    use core::time::Duration;
+
+    const INFINITE: u32 = 0xffff_ffff;
+
+    #[link(name = "synchronization")]
+    extern "system" {
+        fn WaitOnAddress(
+            address: *const c_void,
+            compare_address: *const c_void,
+            address_size: usize,
+            milliseconds: u32,
+        ) -> i32;
+        fn WakeByAddressSingle(address: *const c_void);
+        fn WakeByAddressAll(address: *const c_void);
+    }
+
+    #[inline]
+    pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        // Round up to the next millisecond so that we never wake up early.
+        let ms = timeout.map_or(INFINITE, |timeout| {
+            let ms = timeout.as_nanos().saturating_add(999_999) / 1_000_000;
+            ms.min(u128::from(INFINITE - 1)) as u32
+        });
+        unsafe {
+            WaitOnAddress(
+                word as *const AtomicU32 as *const c_void,
+                &expected as *const u32 as *const c_void,
+                4,
+                ms,
+            );
+        }
+    }
+
+    #[inline]
+    pub fn wake_one(word: &AtomicU32) {
+        unsafe { WakeByAddressSingle(word as *const AtomicU32 as *const c_void) }
+    }
+
+    #[inline]
+    pub fn wake_all(word: &AtomicU32) {
+        unsafe { WakeByAddressAll(word as *const AtomicU32 as *const c_void) }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod futex {
+    use core::ffi::{c_int, c_void};
+    use core::sync::atomic::AtomicU32;
+    use core::time::Duration;
+
+    const UL_COMPARE_AND_WAIT: u32 = 1;
+    const ULF_WAKE_ALL: u32 = 0x0000_0100;
+    const ULF_NO_ERRNO: u32 = 0x0100_0000;
+
+    extern "C" {
+        fn __ulock_wait(operation: u32, addr: *mut c_void, value: u64, timeout_us: u32) -> c_int;
+        fn __ulock_wake(operation: u32, addr: *mut c_void, wake_value: u64) -> c_int;
+    }
+
+    #[inline]
+    pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        // A timeout of 0 means no timeout, so round up to at least 1us.
+        let us = timeout.map_or(0, |timeout| {
+            let us = timeout.as_nanos().saturating_add(999) / 1000;
+            us.clamp(1, u128::from(u32::MAX)) as u32
+        });
+        unsafe {
+            __ulock_wait(
+                UL_COMPARE_AND_WAIT | ULF_NO_ERRNO,
+                word as *const AtomicU32 as *mut c_void,
+                expected as u64,
+                us,
+            );
+        }
+    }
+
+    #[inline]
+    pub fn wake_one(word: &AtomicU32) {
+        unsafe {
+            __ulock_wake(
+                UL_COMPARE_AND_WAIT | ULF_NO_ERRNO,
+                word as *const AtomicU32 as *mut c_void,
+                0,
+            );
+        }
+    }
+
+    #[inline]
+    pub fn wake_all(word: &AtomicU32) {
+        unsafe {
+            __ulock_wake(
+                UL_COMPARE_AND_WAIT | ULF_WAKE_ALL | ULF_NO_ERRNO,
+                word as *const AtomicU32 as *mut c_void,
+                0,
+            );
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+mod futex {
+    use core::arch::wasm32;
+    use core::sync::atomic::AtomicU32;
+    use core::time::Duration;
+
+    #[inline]
+    pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+        let ns = timeout.map_or(-1, |timeout| {
+            timeout.as_nanos().min(i64::MAX as u128) as i64
+        });
+        unsafe {
+            wasm32::memory_atomic_wait32(word as *const AtomicU32 as *mut i32, expected as i32, ns);
+        }
+    }
+
+    #[inline]
+    pub fn wake_one(word: &AtomicU32) {
+        unsafe {
+            wasm32::memory_atomic_notify(word as *const AtomicU32 as *mut i32, 1);
+        }
+    }
+
+    #[inline]
+    pub fn wake_all(word: &AtomicU32) {
+        unsafe {
+            wasm32::memory_atomic_notify(word as *const AtomicU32 as *mut i32, u32::MAX);
+        }
+    }
+}
+
+// Generic implementation for other platforms: waiting threads are parked in a
+// queue selected from the address and unparked by the notifier.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    windows,
+    target_os = "macos",
+    target_os = "ios",
+    all(target_arch = "wasm32", target_feature = "atomics"),
+)))]
+mod imp {
+    use core::ptr;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::time::Duration;
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+    use std::thread::{self, Thread};
+    use std::time::Instant;
+    use std::vec::Vec;
+
+    use super::{hash_addr, remaining};
+    use crate::CachePadded;
+
+    // A thread parked in `wait`. The `notified` flag lives on the waiting
+    // thread's stack and is only accessed while holding the bucket lock, which
+    // guarantees that it is still alive.
+    struct Waiter {
+        addr: usize,
+        thread: Thread,
+        notified: *const AtomicBool,
+    }
+
+    unsafe impl Send for Waiter {}
+
+    struct Bucket {
+        waiters: Mutex<Vec<Waiter>>,
+    }
+
+    impl Bucket {
+        const fn new() -> Bucket {
+            Bucket {
+                waiters: Mutex::new(Vec::new()),
+            }
+        }
+
+        #[inline]
+        fn lock(&self) -> MutexGuard<'_, Vec<Waiter>> {
+            // No user code runs while the lock is held, so poisoning is harmless.
+            self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    // We give each bucket its own cache line to avoid false sharing.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_BUCKET: CachePadded<Bucket> = CachePadded::new(Bucket::new());
+    static BUCKETS: [CachePadded<Bucket>; 64] = [EMPTY_BUCKET; 64];
+
+    #[inline]
+    fn bucket_for_addr(addr: usize) -> &'static Bucket {
+        &BUCKETS[hash_addr(addr, BUCKETS.len())]
+    }
+
+    pub unsafe fn wait<T>(
+        dst: *mut T,
+        _expected: T,
+        should_wait: impl Fn() -> bool,
+        deadline: Option<Instant>,
+    ) {
+        let addr = dst as usize;
+        let bucket = bucket_for_addr(addr);
+        let notified = AtomicBool::new(false);
+        {
+            // `should_wait` is re-checked while holding the queue lock, so a
+            // notification which follows a change of the value can't be missed.
+            let mut waiters = bucket.lock();
+            if !should_wait() {
+                return;
+            }
+            waiters.push(Waiter {
+                addr,
+                thread: thread::current(),
+                notified: &notified,
+            });
+        }
+
+        // Park until a notifier has removed us from the queue. `park` may
+        // return spuriously so we check the flag under the lock each time.
+        loop {
+            match remaining(deadline) {
+                None => thread::park(),
+                Some(timeout) if timeout > Duration::from_secs(0) => thread::park_timeout(timeout),
+                Some(_) => {}
+            }
+            let mut waiters = bucket.lock();
+            if notified.load(Ordering::Relaxed) {
+                return;
+            }
+            if remaining(deadline) == Some(Duration::from_secs(0)) {
+                // We timed out, so remove ourselves from the queue.
+                waiters.retain(|waiter| !ptr::eq(waiter.notified, &notified));
+                return;
+            }
+        }
+    }
+
+    pub unsafe fn notify<T>(dst: *mut T, all: bool) {
+        let addr = dst as usize;
+        let mut waiters = bucket_for_addr(addr).lock();
+        let mut i = 0;
+        while i < waiters.len() {
+            if waiters[i].addr == addr {
+                let waiter = waiters.remove(i);
+                (*waiter.notified).store(true, Ordering::Relaxed);
+                waiter.thread.unpark();
+                if !all {
+                    return;
+                }
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
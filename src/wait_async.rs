@@ -0,0 +1,365 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::vec::Vec;
+
+use bytemuck::NoUninit;
+
+use crate::{Atomic, CachePadded};
+
+// A task waiting for the value at `addr` to change. `key` uniquely identifies
+// the `WaitFuture` which registered it.
+struct Entry {
+    addr: usize,
+    key: usize,
+    waker: Waker,
+}
+
+struct Bucket {
+    // Number of entries in the list, which allows notifications to skip taking
+    // the lock when nobody is waiting.
+    len: AtomicUsize,
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl Bucket {
+    const fn new() -> Bucket {
+        Bucket {
+            len: AtomicUsize::new(0),
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Vec<Entry>> {
+        // No user code runs while the lock is held, so poisoning is harmless.
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Removes the entry for `key`, returning whether it was still registered.
+    fn remove(&self, entries: &mut Vec<Entry>, key: usize) -> bool {
+        match entries.iter().position(|entry| entry.key == key) {
+            Some(i) => {
+                entries.swap_remove(i);
+                self.len.store(entries.len(), Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Removes the entries for `addr` and returns their wakers. The wakers
+    // should be woken after releasing the lock since they may run user code.
+    fn take_wakers(&self, entries: &mut Vec<Entry>, addr: usize, all: bool) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        let mut i = 0;
+        while i < entries.len() {
+            if entries[i].addr == addr {
+                wakers.push(entries.swap_remove(i).waker);
+                if !all {
+                    break;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        self.len.store(entries.len(), Ordering::Relaxed);
+        wakers
+    }
+}
+
+// A table of waker lists, selected using a hash of the address of the atomic
+// object, similar to the wait queues used by the blocking `wait`.
+// We give each bucket its own cache line to avoid false sharing.
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_BUCKET: CachePadded<Bucket> = CachePadded::new(Bucket::new());
+static BUCKETS: [CachePadded<Bucket>; 64] = [EMPTY_BUCKET; 64];
+
+static NEXT_KEY: AtomicUsize = AtomicUsize::new(0);
+
+#[inline]
+fn bucket_for_addr(addr: usize) -> &'static Bucket {
+    &BUCKETS[crate::wait::hash_addr(addr, BUCKETS.len())]
+}
+
+#[inline]
+pub fn notify(addr: usize, all: bool) {
+    let bucket = bucket_for_addr(addr);
+
+    // This fence pairs with the one in `WaitFuture::poll`: either we see the
+    // registered entry or the task sees the new value.
+    fence(Ordering::SeqCst);
+    if bucket.len.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let wakers = bucket.take_wakers(&mut bucket.lock(), addr, all);
+    wakers.into_iter().for_each(Waker::wake);
+}
+
+/// Future returned by `Atomic::wait_async`.
+///
+/// Resolves to the new value once the value of the `Atomic` is no longer equal
+/// to the expected value.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitFuture<'a, T: NoUninit> {
+    atomic: &'a Atomic<T>,
+    expected: T,
+    // Key of our entry in the waker list, if we have registered one.
+    key: Option<usize>,
+}
+
+impl<'a, T: NoUninit> WaitFuture<'a, T> {
+    #[inline]
+    pub(crate) fn new(atomic: &'a Atomic<T>, expected: T) -> Self {
+        WaitFuture {
+            atomic,
+            expected,
+            key: None,
+        }
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        self.atomic.inner_ptr() as usize
+    }
+
+    #[inline]
+    fn changed(&self) -> Option<T> {
+        let val = self.atomic.load(Ordering::Acquire);
+        if bytemuck::bytes_of(&val) == bytemuck::bytes_of(&self.expected) {
+            None
+        } else {
+            Some(val)
+        }
+    }
+}
+
+impl<T: NoUninit> Unpin for WaitFuture<'_, T> {}
+
+impl<T: NoUninit> Future for WaitFuture<'_, T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let this = &mut *self;
+        if this.key.is_none() {
+            if let Some(val) = this.changed() {
+                return Poll::Ready(val);
+            }
+        }
+
+        let addr = this.addr();
+        let bucket = bucket_for_addr(addr);
+        let mut entries = bucket.lock();
+        let key = *this
+            .key
+            .get_or_insert_with(|| NEXT_KEY.fetch_add(1, Ordering::Relaxed));
+        match entries.iter_mut().find(|entry| entry.key == key) {
+            Some(entry) => {
+                if !entry.waker.will_wake(cx.waker()) {
+                    entry.waker = cx.waker().clone();
+                }
+            }
+            None => {
+                entries.push(Entry {
+                    addr,
+                    key,
+                    waker: cx.waker().clone(),
+                });
+                bucket.len.store(entries.len(), Ordering::Relaxed);
+            }
+        }
+
+        // Check the value again now that we are registered, so that a change
+        // which happened since the last check isn't missed.
+        fence(Ordering::SeqCst);
+        match this.changed() {
+            Some(val) => {
+                bucket.remove(&mut entries, key);
+                this.key = None;
+                Poll::Ready(val)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T: NoUninit> Drop for WaitFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            let addr = self.addr();
+            let bucket = bucket_for_addr(addr);
+            let mut entries = bucket.lock();
+
+            // If our entry is gone then we were woken by a notification which
+            // we will never act on, so pass it on to another waiting task.
+            if !bucket.remove(&mut entries, key) {
+                let wakers = bucket.take_wakers(&mut entries, addr, false);
+                drop(entries);
+                wakers.into_iter().for_each(Waker::wake);
+            }
+        }
+    }
+}
+
+impl<T: NoUninit + fmt::Debug> fmt::Debug for WaitFuture<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitFuture")
+            .field("atomic", self.atomic)
+            .field("expected", &self.expected)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket_for_addr, WaitFuture};
+    use crate::{Atomic, Ordering::*};
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::{Context, Poll, Waker};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn registered<T: bytemuck::NoUninit>(a: &Atomic<T>) -> usize {
+        let addr = a.inner_ptr() as usize;
+        bucket_for_addr(addr)
+            .lock()
+            .iter()
+            .filter(|entry| entry.addr == addr)
+            .count()
+    }
+
+    #[test]
+    fn wake_on_notify() {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let a = Atomic::new(0u64);
+        let mut fut = a.wait_async(0);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Pending);
+        assert_eq!(registered(&a), 1);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+        a.store(5, Release);
+        a.notify_one();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(5));
+        drop(fut);
+        assert_eq!(registered(&a), 0);
+    }
+
+    #[test]
+    fn change_without_notify() {
+        let waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+        let mut cx = Context::from_waker(&waker);
+
+        // The value is re-checked on every poll, even without a notification.
+        let a = Atomic::new(1u8);
+        let mut fut = a.wait_async(1);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Pending);
+        a.store(2, Release);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(2));
+        assert_eq!(registered(&a), 0);
+
+        let mut fut = a.wait_async(1);
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(2));
+    }
+
+    #[test]
+    fn cancellation() {
+        let waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+        let mut cx = Context::from_waker(&waker);
+
+        let a = Atomic::new([0u8; 3]);
+        let mut futs: std::vec::Vec<WaitFuture<'_, [u8; 3]>> =
+            (0..10).map(|_| a.wait_async([0; 3])).collect();
+        for fut in &mut futs {
+            assert_eq!(Pin::new(fut).poll(&mut cx), Poll::Pending);
+        }
+        assert_eq!(registered(&a), 10);
+        futs.truncate(4);
+        assert_eq!(registered(&a), 4);
+        drop(futs);
+        assert_eq!(registered(&a), 0);
+    }
+
+    #[test]
+    fn cancelled_notification_is_forwarded() {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let a = Atomic::new(0u32);
+        let mut fut1 = a.wait_async(0);
+        let mut fut2 = a.wait_async(0);
+        assert_eq!(Pin::new(&mut fut1).poll(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut fut2).poll(&mut cx), Poll::Pending);
+        a.store(1, Release);
+        a.notify_one();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        drop(fut1);
+        drop(fut2);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        assert_eq!(registered(&a), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn tokio_ping_pong() {
+        let a = Arc::new(Atomic::new(0u16));
+        let b = a.clone();
+        let task = tokio::spawn(async move {
+            for i in 0..500u16 {
+                let mut v = b.load(Acquire);
+                while v != 2 * i + 1 {
+                    v = b.wait_async(v).await;
+                }
+                b.store(v + 1, Release);
+                b.notify_all();
+            }
+        });
+        for i in 0..500u16 {
+            let mut v = a.load(Acquire);
+            while v != 2 * i {
+                v = a.wait_async(v).await;
+            }
+            a.store(v + 1, Release);
+            a.notify_all();
+        }
+        task.await.unwrap();
+        assert_eq!(a.load(SeqCst), 1000);
+    }
+
+    #[tokio::test]
+    async fn tokio_notify_from_thread() {
+        let a = Arc::new(Atomic::new(false));
+        let b = a.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            b.store(true, Release);
+            b.notify_all();
+        });
+        assert!(a.wait_async(false).await);
+        thread.join().unwrap();
+    }
+}
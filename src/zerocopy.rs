@@ -0,0 +1,129 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+
+use ::zerocopy::{FromBytes, Immutable, IntoBytes};
+use bytemuck::{AnyBitPattern, NoUninit, Zeroable};
+
+/// A wrapper which allows types implementing zerocopy's traits to be used in
+/// an `Atomic`.
+///
+/// `Atomic<T>` requires `T: bytemuck::NoUninit`, and Rust's coherence rules
+/// don't allow a second blanket implementation of its bounds for types
+/// implementing `zerocopy::IntoBytes`. Wrapping the value in `ZeroCopy` bridges
+/// the two: `ZeroCopy<T>` implements `NoUninit` whenever `T` implements
+/// `IntoBytes + Immutable`, and `AnyBitPattern` whenever `T` also implements
+/// `FromBytes`, which enables `Atomic::store_bytes`.
+///
+/// `ZeroCopy<T>` has the same layout as `T`, so wrapping has no cost and
+/// doesn't change whether the `Atomic` is lock-free.
+///
+/// This requires the `zerocopy` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering, ZeroCopy};
+/// use zerocopy::{Immutable, IntoBytes};
+///
+/// #[derive(Copy, Clone, PartialEq, Debug, IntoBytes, Immutable)]
+/// #[repr(C)]
+/// struct Range {
+///     start: u16,
+///     end: u16,
+/// }
+///
+/// let r = Atomic::new(ZeroCopy(Range { start: 0, end: 8 }));
+/// r.store(ZeroCopy(Range { start: 8, end: 16 }), Ordering::Relaxed);
+/// assert_eq!(r.load(Ordering::Relaxed).0, Range { start: 8, end: 16 });
+/// ```
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZeroCopy<T>(pub T);
+
+impl<T> ZeroCopy<T> {
+    /// Returns the wrapped value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for ZeroCopy<T> {
+    #[inline]
+    fn from(v: T) -> Self {
+        ZeroCopy(v)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ZeroCopy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// IntoBytes guarantees that there are no padding bytes and Immutable that there
+// is no interior mutability, which are exactly the requirements of NoUninit.
+unsafe impl<T: IntoBytes + Immutable + Copy + 'static> NoUninit for ZeroCopy<T> {}
+
+// FromBytes guarantees that every bit pattern, including all zeros, is valid.
+unsafe impl<T: FromBytes> Zeroable for ZeroCopy<T> {}
+unsafe impl<T: FromBytes + Immutable + Copy + 'static> AnyBitPattern for ZeroCopy<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::ZeroCopy;
+    use crate::{Atomic, Atomicable, Ordering};
+    use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+    #[derive(Copy, Clone, PartialEq, Debug, bytemuck::NoUninit)]
+    #[repr(C)]
+    struct BytemuckPair(u16, u16);
+
+    #[derive(Copy, Clone, PartialEq, Debug, IntoBytes, FromBytes, Immutable)]
+    #[repr(C)]
+    struct ZerocopyPair(u16, u16);
+
+    // Generic code over Atomic works the same with either kind of type.
+    fn swap_in<T: Atomicable + PartialEq + core::fmt::Debug>(a: &Atomic<T>, old: T, new: T) {
+        assert_eq!(
+            a.compare_exchange(old, new, Ordering::SeqCst, Ordering::SeqCst),
+            Ok(old)
+        );
+        assert_eq!(
+            a.compare_exchange(old, new, Ordering::SeqCst, Ordering::SeqCst),
+            Err(new)
+        );
+        assert_eq!(a.load(Ordering::SeqCst), new);
+    }
+
+    #[test]
+    fn both_ecosystems() {
+        let a = Atomic::new(BytemuckPair(1, 2));
+        swap_in(&a, BytemuckPair(1, 2), BytemuckPair(3, 4));
+
+        let z = Atomic::new(ZeroCopy(ZerocopyPair(1, 2)));
+        swap_in(
+            &z,
+            ZeroCopy(ZerocopyPair(1, 2)),
+            ZeroCopy(ZerocopyPair(3, 4)),
+        );
+        assert_eq!(
+            Atomic::<ZeroCopy<ZerocopyPair>>::is_lock_free(),
+            Atomic::<BytemuckPair>::is_lock_free()
+        );
+
+        // Byte-level operations are available when the type is FromBytes.
+        z.store_bytes(ZerocopyPair(5, 6).as_bytes(), Ordering::SeqCst);
+        assert_eq!(z.load(Ordering::SeqCst).0, ZerocopyPair(5, 6));
+
+        let odd = Atomic::new(ZeroCopy([1u8, 2, 3]));
+        swap_in(&odd, ZeroCopy([1, 2, 3]), ZeroCopy([4, 5, 6]));
+        assert_eq!(Atomic::<ZeroCopy<[u8; 3]>>::zeroed().into_inner().0, [0; 3]);
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2016 Amanieu d'Antras
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use ::zeroize::Zeroize;
+use bytemuck::Zeroable;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::Atomic;
+
+/// Overwrites the value with zeroes using a volatile write, followed by a
+/// compiler fence so that the write isn't optimized away.
+///
+/// Since this takes `&mut self`, no other thread can access the value
+/// concurrently and the fallback lock doesn't need to be taken. To scrub an
+/// `Atomic` when it is dropped, wrap it in `zeroize::Zeroizing`, which still
+/// allows the `Atomic` to be shared through a reference.
+///
+/// This requires the `zeroize` feature.
+///
+/// # Examples
+///
+/// ```rust
+/// use atomic::{Atomic, Ordering};
+/// use zeroize::Zeroizing;
+///
+/// let key = Zeroizing::new(Atomic::new([0x5au8; 16]));
+/// key.store([0xa5; 16], Ordering::Release);
+/// // `key` is zeroed when it goes out of scope.
+/// ```
+impl<T: Zeroable> Zeroize for Atomic<T> {
+    fn zeroize(&mut self) {
+        unsafe { ptr::write_volatile(self.get_mut(), T::zeroed()) };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::zeroize::Zeroizing;
+    use core::mem::{self, ManuallyDrop};
+
+    fn bytes<T>(a: &Atomic<T>) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(a.as_non_atomic_ptr() as *const u8, mem::size_of::<T>())
+        }
+    }
+
+    #[test]
+    fn zeroize() {
+        let mut a = Atomic::new([0xffu8; 16]);
+        a.zeroize();
+        assert_eq!(bytes(&a), [0; 16]);
+
+        // Not lock-free.
+        let mut a = Atomic::new([0xffu8; 3]);
+        a.zeroize();
+        assert_eq!(bytes(&a), [0; 3]);
+        let mut a = Atomic::new([u64::MAX; 3]);
+        a.zeroize();
+        assert_eq!(bytes(&a), [0; 24]);
+    }
+
+    #[test]
+    fn zeroizing_on_drop() {
+        let mut key = ManuallyDrop::new(Zeroizing::new(Atomic::new([0x5au64; 3])));
+        key.store([0xa5; 3], Ordering::Relaxed);
+        unsafe { ManuallyDrop::drop(&mut key) };
+        // `Atomic` has no drop glue, so its storage is still readable.
+        assert_eq!(bytes(&key), [0; 24]);
+    }
+}
@@ -0,0 +1,96 @@
+// Checks that `AtomicBox` neither leaks nor double-frees under a swap storm,
+// using a global allocator which counts live allocations of `Payload`. They are
+// recognized by their unusual alignment, since threads and the test harness
+// allocate concurrently as well. This is a separate test binary so that the
+// allocator doesn't affect other tests.
+#![cfg(feature = "alloc")]
+
+use atomic::{AtomicBox, Ordering::*};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicIsize, AtomicUsize};
+use std::sync::Mutex;
+
+struct Counting;
+
+static LIVE: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.align() == PAYLOAD_ALIGN {
+            LIVE.fetch_add(1, Relaxed);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if layout.align() == PAYLOAD_ALIGN {
+            LIVE.fetch_sub(1, Relaxed);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// The tests in this file count the same allocations, so they must not run
+// concurrently.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const PAYLOAD_ALIGN: usize = 128;
+
+#[repr(align(128))]
+struct Payload {
+    id: usize,
+}
+
+#[test]
+fn swap_storm() {
+    const THREADS: usize = 4;
+    const SWAPS: usize = if cfg!(miri) { 50 } else { 20_000 };
+
+    let _serial = SERIAL.lock().unwrap();
+    let slot = AtomicBox::new(None);
+    let taken = AtomicUsize::new(0);
+    let before = LIVE.load(SeqCst);
+    std::thread::scope(|s| {
+        for t in 0..THREADS {
+            let (slot, taken) = (&slot, &taken);
+            s.spawn(move || {
+                for i in 0..SWAPS {
+                    let new = Box::new(Payload { id: t * SWAPS + i });
+                    match i % 4 {
+                        0 => slot.store(new),
+                        1 => drop(slot.swap(Some(new))),
+                        2 => {
+                            if let Some(old) = slot.swap(Some(new)) {
+                                assert!(old.id < THREADS * SWAPS);
+                                taken.fetch_add(1, Relaxed);
+                            }
+                        }
+                        _ => {
+                            drop(new);
+                            drop(slot.take());
+                        }
+                    }
+                }
+            });
+        }
+    });
+    assert!(taken.load(SeqCst) > 0);
+    // Only the value left in the slot is still allocated.
+    let left = slot.is_some(SeqCst) as isize;
+    assert_eq!(LIVE.load(SeqCst) - before, left);
+    drop(slot);
+    assert_eq!(LIVE.load(SeqCst), before);
+}
+
+#[test]
+fn drop_frees_value() {
+    let _serial = SERIAL.lock().unwrap();
+    let before = LIVE.load(SeqCst);
+    let slot = AtomicBox::from(Box::new(Payload { id: 0 }));
+    assert_eq!(LIVE.load(SeqCst), before + 1);
+    drop(slot);
+    assert_eq!(LIVE.load(SeqCst), before);
+}
@@ -0,0 +1,92 @@
+// Uses `atomic_bitfield!` as a downstream crate would.
+#![cfg(feature = "derive")]
+
+use atomic::atomic_bitfield;
+use atomic::Ordering::*;
+
+atomic_bitfield! {
+    /// Flags, a 12-bit length and an 8-bit generation.
+    pub struct Header(u32) {
+        pub flags: 0..4,
+        pub len: 4..16,
+        pub generation: 16..24,
+    }
+}
+
+atomic_bitfield! {
+    struct Wide(u64) {
+        all: 0..64,
+    }
+}
+
+#[test]
+fn fields() {
+    let h = Header::new(0xff00_0000);
+    h.store_len(0xabc, SeqCst);
+    h.store_flags(0x5, SeqCst);
+    assert_eq!(h.load_len(SeqCst), 0xabc);
+    assert_eq!(h.load_flags(SeqCst), 0x5);
+    assert_eq!(h.load_generation(SeqCst), 0);
+    assert_eq!(
+        h.fetch_update_generation(SeqCst, SeqCst, |g| Some(g + 7)),
+        Ok(0)
+    );
+    assert_eq!(h.fetch_update_generation(SeqCst, SeqCst, |_| None), Err(7));
+    // The bits outside of the fields are preserved.
+    assert_eq!(h.load(SeqCst), 0xff07_abc5);
+
+    let word = Header::with_len(Header::with_flags(0, 1), 2);
+    assert_eq!(
+        h.compare_exchange(0xff07_abc5, word, SeqCst, SeqCst),
+        Ok(0xff07_abc5)
+    );
+    assert_eq!(Header::get_flags(word), 1);
+    assert_eq!(Header::get_len(word), 2);
+    assert_eq!(h.into_inner(), 0x21);
+
+    let w = Wide::new(0);
+    w.store_all(u64::MAX, SeqCst);
+    assert_eq!(w.load_all(SeqCst), u64::MAX);
+}
+
+#[test]
+#[should_panic(expected = "the value doesn't fit in the bit field")]
+fn value_too_large() {
+    Header::new(0).store_flags(0x10, SeqCst);
+}
+
+#[test]
+fn concurrent_fields() {
+    const UPDATES: u32 = if cfg!(miri) { 50 } else { 20_000 };
+
+    let h = Header::new(0);
+    std::thread::scope(|s| {
+        // Each thread owns one field, so no update may be lost.
+        s.spawn(|| {
+            for _ in 0..UPDATES {
+                h.fetch_update_flags(AcqRel, Acquire, |f| Some((f + 1) % 16))
+                    .unwrap();
+            }
+        });
+        s.spawn(|| {
+            for _ in 0..UPDATES {
+                h.fetch_update_len(AcqRel, Acquire, |l| Some((l + 1) % 4096))
+                    .unwrap();
+            }
+        });
+        s.spawn(|| {
+            for i in 0..UPDATES {
+                h.store_generation(i % 256, Release);
+            }
+        });
+        s.spawn(|| {
+            for _ in 0..UPDATES {
+                // Bits outside of the fields are never set.
+                assert_eq!(h.load(Acquire) >> 24, 0);
+            }
+        });
+    });
+    assert_eq!(h.load_flags(SeqCst), UPDATES % 16);
+    assert_eq!(h.load_len(SeqCst), UPDATES % 4096);
+    assert_eq!(h.load_generation(SeqCst), (UPDATES - 1) % 256);
+}
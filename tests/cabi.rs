@@ -0,0 +1,91 @@
+// Shares an object which isn't lock-free between Rust code and C code using
+// the functions exported by the `cabi` feature. The C code is built into a
+// shared library which is loaded at runtime and calls back into this
+// executable.
+#![cfg(all(feature = "cabi", target_os = "linux"))]
+
+use atomic::cabi::rust_atomic_is_lock_free;
+use atomic::{Atomic, Ordering::*};
+use std::ffi::c_uint;
+use std::thread;
+
+mod common;
+
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::NoUninit)]
+#[repr(C)]
+struct Counters {
+    a: u64,
+    b: u64,
+    c: u64,
+    d: u64,
+}
+
+type IncrementFn = unsafe extern "C" fn(*mut Counters, c_uint) -> c_uint;
+
+static SHARED: Atomic<Counters> = Atomic::new(Counters {
+    a: 0,
+    b: 0,
+    c: 0,
+    d: 0,
+});
+
+const ITERS: u32 = 20_000;
+
+fn load_c_increment() -> IncrementFn {
+    let f = common::load_c_function("cabi/shared.c", "c_increment", &[]);
+    unsafe { std::mem::transmute::<*mut libc::c_void, IncrementFn>(f) }
+}
+
+#[test]
+fn lock_free() {
+    assert!(!rust_atomic_is_lock_free(32, 8));
+    assert!(!rust_atomic_is_lock_free(4, 1));
+    assert_eq!(
+        rust_atomic_is_lock_free(8, 8),
+        Atomic::<u64>::is_lock_free()
+    );
+    assert_eq!(rust_atomic_is_lock_free(1, 1), Atomic::<u8>::is_lock_free());
+}
+
+#[test]
+fn shared_with_c() {
+    let c_increment = load_c_increment();
+
+    let mut threads = Vec::new();
+    for _ in 0..2 {
+        threads.push(thread::spawn(move || unsafe {
+            c_increment(SHARED.as_non_atomic_ptr(), ITERS)
+        }));
+        threads.push(thread::spawn(|| {
+            let mut torn = 0;
+            for _ in 0..ITERS {
+                let _ = SHARED.fetch_update(SeqCst, Relaxed, |v| {
+                    if v.a != v.b || v.a != v.c || v.a != v.d {
+                        torn += 1;
+                    }
+                    Some(Counters {
+                        a: v.a + 1,
+                        b: v.b + 1,
+                        c: v.c + 1,
+                        d: v.d + 1,
+                    })
+                });
+            }
+            torn
+        }));
+    }
+    for t in threads {
+        assert_eq!(t.join().unwrap(), 0);
+    }
+
+    let n = 4 * u64::from(ITERS);
+    assert_eq!(
+        SHARED.load(SeqCst),
+        Counters {
+            a: n,
+            b: n,
+            c: n,
+            d: n
+        }
+    );
+}
@@ -0,0 +1,39 @@
+// C side of tests/cabi.rs, accessing an object shared with Rust through the
+// functions exported by the `cabi` feature.
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+void rust_atomic_load(size_t size, size_t align, void *ptr, void *ret, int order);
+bool rust_atomic_compare_exchange(size_t size, size_t align, void *ptr, void *expected,
+                                  const void *desired, int success, int failure);
+
+// Too large to be lock-free. All fields are always updated together, so they
+// are equal unless an update was torn.
+struct counters {
+    uint64_t a, b, c, d;
+};
+
+// Increments all fields of `shared` `iters` times, and returns the number of
+// loads which observed fields that differ from each other.
+unsigned c_increment(struct counters *shared, unsigned iters)
+{
+    unsigned torn = 0;
+    for (unsigned i = 0; i < iters; i++) {
+        struct counters cur, next;
+        rust_atomic_load(sizeof(cur), _Alignof(struct counters), shared, &cur,
+                         __ATOMIC_RELAXED);
+        do {
+            if (cur.a != cur.b || cur.a != cur.c || cur.a != cur.d)
+                torn++;
+            next.a = cur.a + 1;
+            next.b = cur.b + 1;
+            next.c = cur.c + 1;
+            next.d = cur.d + 1;
+        } while (!rust_atomic_compare_exchange(sizeof(cur), _Alignof(struct counters), shared,
+                                               &cur, &next, __ATOMIC_SEQ_CST,
+                                               __ATOMIC_RELAXED));
+    }
+    return torn;
+}
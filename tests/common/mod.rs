@@ -0,0 +1,39 @@
+// Helpers shared by the tests which exercise C code.
+
+use std::ffi::CString;
+use std::path::Path;
+
+// Builds `tests/<source>` into a shared library and returns the address of
+// `function` in it. `link` lists additional libraries to link against.
+pub fn load_c_function(source: &str, function: &str, link: &[&str]) -> *mut libc::c_void {
+    let target = env!("ATOMIC_TEST_TARGET");
+    let name = Path::new(source).file_stem().unwrap().to_str().unwrap();
+    let lib = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("lib{}.so", name));
+    let mut cmd = cc::Build::new()
+        .cargo_metadata(false)
+        .target(target)
+        .host(target)
+        .opt_level(2)
+        .pic(true)
+        .get_compiler()
+        .to_command();
+    cmd.arg("-shared").arg("-o").arg(&lib).arg(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(source),
+    );
+    for l in link {
+        cmd.arg(format!("-l{}", l));
+    }
+    assert!(cmd.status().unwrap().success());
+
+    let lib = CString::new(lib.to_str().unwrap()).unwrap();
+    let function = CString::new(function).unwrap();
+    unsafe {
+        let handle = libc::dlopen(lib.as_ptr(), libc::RTLD_NOW);
+        assert!(!handle.is_null(), "failed to load the C library");
+        let f = libc::dlsym(handle, function.as_ptr());
+        assert!(!f.is_null());
+        f
+    }
+}
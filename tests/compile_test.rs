@@ -0,0 +1,14 @@
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*-fail.rs");
+    #[cfg(target_arch = "x86_64")]
+    t.pass("tests/ui/*-pass.rs");
+    #[cfg(feature = "derive")]
+    t.compile_fail("tests/ui/derive/*-fail.rs");
+    #[cfg(not(feature = "fallback"))]
+    {
+        t.compile_fail("tests/ui/no_fallback/*-fail.rs");
+        t.pass("tests/ui/no_fallback/*-pass.rs");
+    }
+}
@@ -0,0 +1,203 @@
+// Uses `#[derive(Atomicize)]` as a downstream crate would.
+#![cfg(feature = "derive")]
+
+use atomic::derive::{AtomicArithmetic, AtomicFields, Atomicize, NoUninit};
+use atomic::Atomic;
+use atomic::Ordering::*;
+use bytemuck::checked::CheckedBitPattern;
+use core::num::NonZeroU16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Atomicize)]
+#[repr(u16)]
+pub enum Sparse {
+    Low = 1,
+    Mid = 300,
+    High = 0xfff0,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Atomicize)]
+#[repr(i8)]
+enum Signed {
+    Neg = -2,
+    Zero,
+    Pos = 5,
+}
+
+#[test]
+fn non_contiguous_discriminants() {
+    let a = AtomicSparse::new(Sparse::Low);
+    assert_eq!(a.load(SeqCst), Sparse::Low);
+    a.store(Sparse::High, SeqCst);
+    assert_eq!(a.swap(Sparse::Mid, SeqCst), Sparse::High);
+    assert_eq!(
+        a.compare_exchange(Sparse::Low, Sparse::High, SeqCst, SeqCst),
+        Err(Sparse::Mid)
+    );
+    assert_eq!(
+        a.compare_exchange(Sparse::Mid, Sparse::Low, SeqCst, SeqCst),
+        Ok(Sparse::Mid)
+    );
+    assert_eq!(a.into_inner(), Sparse::Low);
+    assert_eq!(core::mem::size_of::<AtomicSparse>(), 2);
+
+    let valid: Vec<u16> = (0..=u16::MAX)
+        .filter(Sparse::is_valid_bit_pattern)
+        .collect();
+    assert_eq!(valid, [1, 300, 0xfff0]);
+}
+
+#[test]
+fn signed_discriminants() {
+    let a = AtomicSigned::from(Signed::Zero);
+    assert_eq!(a.swap(Signed::Neg, SeqCst), Signed::Zero);
+    assert_eq!(a.load(SeqCst), Signed::Neg);
+    let valid: Vec<i8> = (i8::MIN..=i8::MAX)
+        .filter(Signed::is_valid_bit_pattern)
+        .collect();
+    assert_eq!(valid, [-2, -1, 5]);
+}
+
+#[test]
+fn atomic_enum() {
+    // The derived `NoUninit` impl also allows `Atomic<Sparse>`.
+    let a = Atomic::new(Sparse::High);
+    assert_eq!(a.load(SeqCst), Sparse::High);
+}
+
+#[derive(Clone, Debug, PartialEq, AtomicFields)]
+pub struct Config {
+    pub rate: u32,
+    #[atomic(load = Relaxed, store = SeqCst)]
+    pub burst: u64,
+    pub mode: Option<NonZeroU16>,
+    #[atomic(skip)]
+    pub name: String,
+}
+
+#[test]
+fn atomic_fields() {
+    let config = AtomicConfig::new(Config {
+        rate: 1,
+        burst: 2,
+        mode: None,
+        name: "api".into(),
+    });
+    config.set_rate(10);
+    assert_eq!(config.rate(), 10);
+    assert_eq!(config.burst.swap(20, SeqCst), 2);
+    config.store(Config {
+        rate: 30,
+        burst: 40,
+        mode: NonZeroU16::new(5),
+        name: "ignored".into(),
+    });
+    let expected = Config {
+        rate: 30,
+        burst: 40,
+        mode: NonZeroU16::new(5),
+        name: "api".into(),
+    };
+    assert_eq!(config.snapshot(), expected);
+    assert_eq!(config.into_inner(), expected);
+}
+
+#[test]
+fn atomic_fields_concurrent() {
+    const THREADS: u32 = 4;
+    const STORES: u32 = if cfg!(miri) { 20 } else { 2_000 };
+
+    fn config(t: u32, i: u32) -> Config {
+        let v = t * STORES + i;
+        Config {
+            rate: v,
+            burst: u64::from(v) << 32,
+            mode: NonZeroU16::new((v % 1000 + 1) as u16),
+            name: String::new(),
+        }
+    }
+
+    let shared = AtomicConfig::new(config(0, 0));
+    std::thread::scope(|s| {
+        for t in 0..THREADS {
+            let shared = &shared;
+            s.spawn(move || {
+                for i in 0..STORES {
+                    if i % 2 == 0 {
+                        shared.store(config(t, i));
+                    } else {
+                        let c = config(t, i);
+                        shared.set_rate(c.rate);
+                        shared.set_mode(c.mode);
+                    }
+                }
+            });
+            s.spawn(move || {
+                for _ in 0..STORES {
+                    // The fields may come from different stores, but every
+                    // field must hold a value which some thread wrote.
+                    let snap = shared.snapshot();
+                    assert!(snap.rate < THREADS * STORES);
+                    assert_eq!(snap.burst as u32, 0);
+                    assert!((snap.burst >> 32) < u64::from(THREADS * STORES));
+                    assert!(snap.mode.unwrap().get() <= 1000);
+                }
+            });
+        }
+    });
+    let last = shared.snapshot();
+    assert!(last.rate < THREADS * STORES);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, NoUninit, AtomicArithmetic)]
+#[repr(transparent)]
+struct Lsn(u64);
+
+#[derive(Copy, Clone, Debug, PartialEq, NoUninit, AtomicArithmetic)]
+#[repr(transparent)]
+struct Offset {
+    delta: i32,
+}
+
+#[test]
+fn atomic_arithmetic() {
+    assert!(Atomic::<Lsn>::is_lock_free());
+    let lsn = Atomic::new(Lsn(10));
+    assert_eq!(lsn.fetch_add(Lsn(5), SeqCst), Lsn(10));
+    assert_eq!(lsn.fetch_sub(Lsn(3), SeqCst), Lsn(15));
+    assert_eq!(lsn.fetch_max(Lsn(100), SeqCst), Lsn(12));
+    assert_eq!(lsn.fetch_min(Lsn(50), SeqCst), Lsn(100));
+    assert_eq!(lsn.fetch_and(Lsn(0x30), SeqCst), Lsn(50));
+    assert_eq!(lsn.fetch_or(Lsn(1), SeqCst), Lsn(0x30));
+    assert_eq!(lsn.fetch_xor(Lsn(0x31), SeqCst), Lsn(0x31));
+    assert_eq!(lsn.load(SeqCst), Lsn(0));
+    // The operations wrap around like the integer ones.
+    assert_eq!(lsn.fetch_sub(Lsn(1), SeqCst), Lsn(0));
+    assert_eq!(lsn.load(SeqCst), Lsn(u64::MAX));
+
+    // Signed integers are compared as signed.
+    let offset = Atomic::new(Offset { delta: -5 });
+    assert_eq!(offset.fetch_max(Offset { delta: -7 }, SeqCst).delta, -5);
+    assert_eq!(offset.fetch_min(Offset { delta: -7 }, SeqCst).delta, -5);
+    assert_eq!(offset.load(SeqCst).delta, -7);
+}
+
+#[test]
+fn atomic_arithmetic_concurrent() {
+    const THREADS: u64 = 4;
+    const ADDS: u64 = if cfg!(miri) { 50 } else { 10_000 };
+
+    let next = Atomic::new(Lsn(0));
+    let highest = Atomic::new(Lsn(0));
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ADDS {
+                    let lsn = next.fetch_add(Lsn(1), Relaxed);
+                    highest.fetch_max(lsn, Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(next.load(SeqCst), Lsn(THREADS * ADDS));
+    assert_eq!(highest.load(SeqCst), Lsn(THREADS * ADDS - 1));
+}
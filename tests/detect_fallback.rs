@@ -0,0 +1,54 @@
+// These tests only run with `--features debug-detect-fallback` in a debug
+// build, for example:
+//
+//     cargo test --features debug-detect-fallback --test detect_fallback
+#![cfg(all(feature = "debug-detect-fallback", debug_assertions))]
+
+use atomic::{Atomic, Ordering};
+
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Triple {
+    a: u32,
+    b: u32,
+    c: u32,
+}
+
+#[test]
+#[should_panic(expected = "is not lock-free and is using the fallback implementation")]
+fn fallback_panics() {
+    let a = Atomic::new(Triple { a: 0, b: 0, c: 0 });
+    a.load(Ordering::Relaxed);
+}
+
+#[test]
+#[should_panic(expected = "Triple")]
+fn fallback_names_type() {
+    let a = Atomic::new(Triple { a: 0, b: 0, c: 0 });
+    let _ = a.load_snapshot();
+}
+
+#[test]
+fn lock_free_does_not_panic() {
+    let a = Atomic::new(0u64);
+    a.store(1, Ordering::Relaxed);
+    assert_eq!(a.fetch_add(1, Ordering::Relaxed), 1);
+    assert_eq!(a.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn zero_sized_does_not_panic() {
+    #[derive(Copy, Clone, Debug, PartialEq, bytemuck::NoUninit)]
+    #[repr(C)]
+    struct Marker;
+
+    let a = Atomic::new(Marker);
+    a.store(Marker, Ordering::Relaxed);
+    assert_eq!(a.swap(Marker, Ordering::Relaxed), Marker);
+    assert_eq!(
+        a.compare_exchange(Marker, Marker, Ordering::Relaxed, Ordering::Relaxed),
+        Ok(Marker)
+    );
+    assert_eq!(a.load_snapshot(), Marker);
+    assert_eq!(Atomic::new(()).load(Ordering::Relaxed), ());
+}
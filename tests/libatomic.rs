@@ -0,0 +1,63 @@
+// Shares an object which isn't lock-free between Rust code using the
+// `libatomic` feature and C code using the compiler's atomic builtins. Both
+// sides must use the locks of libatomic for the updates to be atomic.
+#![cfg(all(feature = "libatomic", target_os = "linux"))]
+
+use atomic::{Atomic, Ordering::*};
+use std::ffi::c_uint;
+use std::thread;
+
+mod common;
+
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::NoUninit)]
+#[repr(C)]
+struct Triple {
+    a: u64,
+    b: u64,
+    c: u64,
+}
+
+type IncrementFn = unsafe extern "C" fn(*mut Triple, c_uint) -> c_uint;
+
+static SHARED: Atomic<Triple> = Atomic::new(Triple { a: 0, b: 0, c: 0 });
+
+const ITERS: u32 = 20_000;
+
+#[test]
+fn shared_with_c() {
+    assert!(!Atomic::<Triple>::is_lock_free());
+    let f = common::load_c_function("libatomic/shared.c", "c_increment", &["atomic"]);
+    let c_increment = unsafe { std::mem::transmute::<*mut libc::c_void, IncrementFn>(f) };
+
+    let mut threads = Vec::new();
+    for _ in 0..2 {
+        threads.push(thread::spawn(move || unsafe {
+            c_increment(SHARED.as_non_atomic_ptr(), ITERS)
+        }));
+        threads.push(thread::spawn(|| {
+            let mut torn = 0;
+            for _ in 0..ITERS {
+                let v = SHARED.load(Relaxed);
+                if v.a != v.b || v.a != v.c {
+                    torn += 1;
+                }
+                SHARED
+                    .fetch_update(SeqCst, Relaxed, |v| {
+                        Some(Triple {
+                            a: v.a + 1,
+                            b: v.b + 1,
+                            c: v.c + 1,
+                        })
+                    })
+                    .unwrap();
+            }
+            torn
+        }));
+    }
+    for t in threads {
+        assert_eq!(t.join().unwrap(), 0);
+    }
+
+    let n = 4 * u64::from(ITERS);
+    assert_eq!(SHARED.load(SeqCst), Triple { a: n, b: n, c: n });
+}
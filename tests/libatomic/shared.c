@@ -0,0 +1,32 @@
+// C side of tests/libatomic.rs, accessing an object shared with Rust through
+// the compiler's atomic builtins, which call libatomic for objects of this
+// size.
+
+#include <stdbool.h>
+#include <stdint.h>
+
+// Too large to be lock-free. All fields are always updated together, so they
+// are equal unless an update was torn.
+struct triple {
+    uint64_t a, b, c;
+};
+
+// Increments all fields of `shared` `iters` times, and returns the number of
+// loads which observed fields that differ from each other.
+unsigned c_increment(struct triple *shared, unsigned iters)
+{
+    unsigned torn = 0;
+    for (unsigned i = 0; i < iters; i++) {
+        struct triple cur, next;
+        __atomic_load(shared, &cur, __ATOMIC_RELAXED);
+        do {
+            if (cur.a != cur.b || cur.a != cur.c)
+                torn++;
+            next.a = cur.a + 1;
+            next.b = cur.b + 1;
+            next.c = cur.c + 1;
+        } while (!__atomic_compare_exchange(shared, &cur, &next, false, __ATOMIC_SEQ_CST,
+                                            __ATOMIC_RELAXED));
+    }
+    return torn;
+}
@@ -0,0 +1,107 @@
+// Model checks of the `sync-extras` types with loom. Run with:
+//
+//     RUSTFLAGS="--cfg atomic_loom" cargo test --release --features sync-extras --test loom
+#![cfg(all(atomic_loom, feature = "sync-extras"))]
+
+use atomic::{Atomic, Ordering, SeqLock, SpinBarrier};
+use loom::cell::UnsafeCell;
+use loom::sync::Arc;
+use loom::thread;
+
+#[test]
+fn barrier_publishes_writes() {
+    loom::model(|| {
+        let barrier = Arc::new(SpinBarrier::new(2));
+        let data = Arc::new(UnsafeCell::new(0));
+        let other = {
+            let (barrier, data) = (barrier.clone(), data.clone());
+            thread::spawn(move || {
+                data.with_mut(|d| unsafe { *d = 1 });
+                barrier.wait()
+            })
+        };
+        let leader = barrier.wait();
+        // loom reports a data race if the write isn't visible here.
+        assert_eq!(data.with(|d| unsafe { *d }), 1);
+        assert!(leader != other.join().unwrap());
+    });
+}
+
+#[test]
+fn barrier_reuse() {
+    loom::model(|| {
+        // A thread which is still leaving the first round must not be
+        // confused by the other thread arriving at the second one.
+        let barrier = Arc::new(SpinBarrier::new(2));
+        let round = Arc::new(UnsafeCell::new(0));
+        let other = {
+            let (barrier, round) = (barrier.clone(), round.clone());
+            thread::spawn(move || {
+                barrier.wait();
+                barrier.wait();
+                round.with_mut(|r| unsafe { *r = 2 });
+                barrier.wait();
+            })
+        };
+        barrier.wait();
+        assert_eq!(round.with(|r| unsafe { *r }), 0);
+        barrier.wait();
+        barrier.wait();
+        assert_eq!(round.with(|r| unsafe { *r }), 2);
+        other.join().unwrap();
+    });
+}
+
+#[test]
+fn seqlock_no_torn_reads() {
+    loom::model(|| {
+        // Both bytes of every value written are equal.
+        let lock = Arc::new(SeqLock::new([0u8; 2]));
+        let writer = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                lock.write([1; 2]);
+                lock.write([2; 2]);
+            })
+        };
+        let v = lock.read();
+        assert_eq!(v[0], v[1]);
+        writer.join().unwrap();
+        assert_eq!(lock.read(), [2; 2]);
+    });
+}
+
+#[test]
+fn seqlock_exclusive_writers() {
+    loom::model(|| {
+        let lock = Arc::new(SeqLock::new(0u8));
+        let other = {
+            let lock = lock.clone();
+            thread::spawn(move || *lock.lock_write() += 1)
+        };
+        *lock.lock_write() += 1;
+        other.join().unwrap();
+        assert_eq!(lock.read(), 2);
+    });
+}
+
+#[test]
+fn load_snapshot_no_torn_reads() {
+    loom::model(|| {
+        // Both bytes of every value stored are equal. The type isn't
+        // lock-free, so this uses the optimistic reads of the fallback.
+        let a = Arc::new(Atomic::new([0u8; 2]));
+        assert!(!Atomic::<[u8; 2]>::is_lock_free());
+        let writer = {
+            let a = a.clone();
+            thread::spawn(move || {
+                a.store([1; 2], Ordering::Relaxed);
+                a.store([2; 2], Ordering::Relaxed);
+            })
+        };
+        let v = a.load_snapshot();
+        assert_eq!(v[0], v[1]);
+        writer.join().unwrap();
+        assert_eq!(a.load_snapshot(), [2; 2]);
+    });
+}
@@ -0,0 +1,201 @@
+// Uses `AtomicPrimInt` with an integer newtype, as a downstream crate would.
+#![cfg(feature = "num-traits")]
+
+use atomic::{Atomic, AtomicPrimInt, Ordering::*};
+use core::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Not, Rem, Shl, Shr, Sub};
+use num_traits::{
+    Bounded, CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, Num, NumCast, One, PrimInt,
+    Saturating, ToPrimitive, WrappingAdd, WrappingSub, Zero,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, bytemuck::NoUninit)]
+#[repr(transparent)]
+struct Ticks(u32);
+
+macro_rules! binop {
+    ($($tr:ident $f:ident)*) => ($(
+        impl $tr for Ticks {
+            type Output = Ticks;
+            fn $f(self, rhs: Ticks) -> Ticks {
+                Ticks(self.0.$f(rhs.0))
+            }
+        }
+    )*);
+}
+binop! { Add add Sub sub Mul mul Div div Rem rem BitAnd bitand BitOr bitor BitXor bitxor }
+
+macro_rules! checked {
+    ($($tr:ident $f:ident)*) => ($(
+        impl $tr for Ticks {
+            fn $f(&self, rhs: &Ticks) -> Option<Ticks> {
+                self.0.$f(rhs.0).map(Ticks)
+            }
+        }
+    )*);
+}
+checked! { CheckedAdd checked_add CheckedSub checked_sub CheckedMul checked_mul CheckedDiv checked_div }
+
+impl WrappingAdd for Ticks {
+    fn wrapping_add(&self, rhs: &Ticks) -> Ticks {
+        Ticks(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl WrappingSub for Ticks {
+    fn wrapping_sub(&self, rhs: &Ticks) -> Ticks {
+        Ticks(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Not for Ticks {
+    type Output = Ticks;
+    fn not(self) -> Ticks {
+        Ticks(!self.0)
+    }
+}
+
+impl Shl<usize> for Ticks {
+    type Output = Ticks;
+    fn shl(self, rhs: usize) -> Ticks {
+        Ticks(self.0 << rhs)
+    }
+}
+
+impl Shr<usize> for Ticks {
+    type Output = Ticks;
+    fn shr(self, rhs: usize) -> Ticks {
+        Ticks(self.0 >> rhs)
+    }
+}
+
+impl Zero for Ticks {
+    fn zero() -> Ticks {
+        Ticks(0)
+    }
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl One for Ticks {
+    fn one() -> Ticks {
+        Ticks(1)
+    }
+}
+
+impl Num for Ticks {
+    type FromStrRadixErr = core::num::ParseIntError;
+    fn from_str_radix(s: &str, radix: u32) -> Result<Ticks, Self::FromStrRadixErr> {
+        u32::from_str_radix(s, radix).map(Ticks)
+    }
+}
+
+impl Bounded for Ticks {
+    fn min_value() -> Ticks {
+        Ticks(u32::MIN)
+    }
+    fn max_value() -> Ticks {
+        Ticks(u32::MAX)
+    }
+}
+
+impl Saturating for Ticks {
+    fn saturating_add(self, rhs: Ticks) -> Ticks {
+        Ticks(self.0.saturating_add(rhs.0))
+    }
+    fn saturating_sub(self, rhs: Ticks) -> Ticks {
+        Ticks(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl ToPrimitive for Ticks {
+    fn to_i64(&self) -> Option<i64> {
+        self.0.to_i64()
+    }
+    fn to_u64(&self) -> Option<u64> {
+        self.0.to_u64()
+    }
+}
+
+impl NumCast for Ticks {
+    fn from<T: ToPrimitive>(n: T) -> Option<Ticks> {
+        n.to_u32().map(Ticks)
+    }
+}
+
+impl PrimInt for Ticks {
+    fn count_ones(self) -> u32 {
+        self.0.count_ones()
+    }
+    fn count_zeros(self) -> u32 {
+        self.0.count_zeros()
+    }
+    fn leading_zeros(self) -> u32 {
+        self.0.leading_zeros()
+    }
+    fn trailing_zeros(self) -> u32 {
+        self.0.trailing_zeros()
+    }
+    fn rotate_left(self, n: u32) -> Ticks {
+        Ticks(self.0.rotate_left(n))
+    }
+    fn rotate_right(self, n: u32) -> Ticks {
+        Ticks(self.0.rotate_right(n))
+    }
+    fn signed_shl(self, n: u32) -> Ticks {
+        Ticks(self.0.signed_shl(n))
+    }
+    fn signed_shr(self, n: u32) -> Ticks {
+        Ticks(self.0.signed_shr(n))
+    }
+    fn unsigned_shl(self, n: u32) -> Ticks {
+        Ticks(self.0.unsigned_shl(n))
+    }
+    fn unsigned_shr(self, n: u32) -> Ticks {
+        Ticks(self.0.unsigned_shr(n))
+    }
+    fn swap_bytes(self) -> Ticks {
+        Ticks(self.0.swap_bytes())
+    }
+    fn from_be(x: Ticks) -> Ticks {
+        Ticks(u32::from_be(x.0))
+    }
+    fn from_le(x: Ticks) -> Ticks {
+        Ticks(u32::from_le(x.0))
+    }
+    fn to_be(self) -> Ticks {
+        Ticks(self.0.to_be())
+    }
+    fn to_le(self) -> Ticks {
+        Ticks(self.0.to_le())
+    }
+    fn pow(self, exp: u32) -> Ticks {
+        Ticks(self.0.pow(exp))
+    }
+}
+
+#[test]
+fn newtype_fetch_ops() {
+    let a = Atomic::new(Ticks(10));
+    assert_eq!(a.fetch_add(Ticks(5), SeqCst), Ticks(10));
+    assert_eq!(a.fetch_sub(Ticks(20), SeqCst), Ticks(15));
+    assert_eq!(a.load(SeqCst), Ticks(u32::MAX - 4));
+    assert_eq!(a.fetch_min(Ticks(7), SeqCst), Ticks(u32::MAX - 4));
+    assert_eq!(a.fetch_max(Ticks(3), SeqCst), Ticks(7));
+    assert_eq!(a.load(SeqCst), Ticks(7));
+}
+
+#[test]
+fn newtype_contended() {
+    let a = Atomic::new(Ticks(0));
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..1000 {
+                    a.fetch_add(Ticks(1), Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(a.load(SeqCst), Ticks(4000));
+}
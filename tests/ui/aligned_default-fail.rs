@@ -0,0 +1,5 @@
+// `Default` must check the size like `new`, otherwise the wrapper would
+// contain uninitialized padding bytes.
+fn main() {
+    let _ = atomic::Align8::<u8>::default();
+}
@@ -0,0 +1,14 @@
+use atomic::{AtomicRef, Ordering};
+
+// If `AtomicRef` were covariant, a short-lived reference could be stored into
+// an `AtomicRef<'static, _>` and loaded back as `&'static u32`.
+fn shorten<'a>(r: &'a AtomicRef<'static, u32>) -> &'a AtomicRef<'a, u32> {
+    r
+}
+
+static GLOBAL: AtomicRef<'static, u32> = AtomicRef::new(None);
+
+fn main() {
+    let local = 5;
+    shorten(&GLOBAL).store(Some(&local), Ordering::Relaxed);
+}
@@ -0,0 +1,6 @@
+use atomic::AtomicBitSet;
+
+fn main() {
+    // 100 bits need at least two words.
+    let _ = AtomicBitSet::<100, 1>::new();
+}
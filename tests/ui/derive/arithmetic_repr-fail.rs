@@ -0,0 +1,14 @@
+use atomic::derive::AtomicArithmetic;
+
+#[derive(Clone, Copy, AtomicArithmetic)]
+struct NotTransparent(u64);
+
+#[derive(Clone, Copy, AtomicArithmetic)]
+#[repr(transparent)]
+struct NotInteger(f64);
+
+#[derive(Clone, Copy, AtomicArithmetic)]
+#[repr(C)]
+struct TwoFields(u32, u32);
+
+fn main() {}
@@ -0,0 +1,10 @@
+use atomic::atomic_bitfield;
+
+atomic_bitfield! {
+    struct Header(u32) {
+        flags: 0..4,
+        len: 3..16,
+    }
+}
+
+fn main() {}
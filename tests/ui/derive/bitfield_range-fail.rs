@@ -0,0 +1,16 @@
+use atomic::atomic_bitfield;
+
+atomic_bitfield! {
+    struct Small(u8) {
+        low: 0..4,
+        high: 4..9,
+    }
+}
+
+atomic_bitfield! {
+    struct Empty(u32) {
+        none: 4..4,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use atomic::{Atomic, Ordering};
+
+#[derive(Copy, Clone)]
+enum State {
+    Idle,
+    Busy(core::num::NonZeroU32),
+}
+
+fn main() {
+    let a = Atomic::new(State::Idle);
+    a.store(State::Idle, Ordering::Relaxed);
+}
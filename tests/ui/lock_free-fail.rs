@@ -0,0 +1,13 @@
+use atomic::LockFree;
+
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Triple {
+    a: u32,
+    b: u32,
+    c: u32,
+}
+
+fn main() {
+    let _ = LockFree::new(Triple { a: 0, b: 0, c: 0 });
+}
@@ -0,0 +1,7 @@
+use atomic::AtomicMarkablePtr;
+
+fn main() {
+    // `u8` has no alignment bits to store the mark in.
+    let mut value = 0u8;
+    let _ = AtomicMarkablePtr::new(&mut value as *mut u8, false);
+}
@@ -0,0 +1,13 @@
+use atomic::Atomic;
+
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Large {
+    a: u64,
+    b: u64,
+    c: u64,
+}
+
+fn main() {
+    let _ = Atomic::new_lock_free(Large { a: 0, b: 0, c: 0 });
+}
@@ -0,0 +1,9 @@
+use atomic::{Atomic, Ordering};
+
+static COUNTER: Atomic<u64> = Atomic::new_lock_free(0);
+
+fn main() {
+    let () = Atomic::<u64>::ASSERT_LOCK_FREE;
+    COUNTER.store(1, Ordering::Relaxed);
+    assert_eq!(COUNTER.load(Ordering::Relaxed), 1);
+}
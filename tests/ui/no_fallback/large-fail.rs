@@ -0,0 +1,14 @@
+use atomic::{Atomic, Ordering};
+
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Triple {
+    a: u32,
+    b: u32,
+    c: u32,
+}
+
+fn main() {
+    let a = Atomic::new(Triple { a: 0, b: 0, c: 0 });
+    a.store(Triple { a: 1, b: 2, c: 3 }, Ordering::Relaxed);
+}
@@ -0,0 +1,8 @@
+use atomic::{Atomic, Ordering};
+
+fn main() {
+    let a = Atomic::new(0u32);
+    a.store(1, Ordering::Relaxed);
+    assert_eq!(a.fetch_add(1, Ordering::Relaxed), 1);
+    assert_eq!(a.load(Ordering::Relaxed), 2);
+}
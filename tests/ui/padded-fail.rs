@@ -0,0 +1,13 @@
+use atomic::{Atomic, Ordering};
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct Header {
+    tag: u8,
+    len: u32,
+}
+
+fn main() {
+    let a = Atomic::new(Header { tag: 0, len: 0 });
+    a.load(Ordering::Relaxed);
+}
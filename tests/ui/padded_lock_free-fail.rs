@@ -0,0 +1,21 @@
+use atomic::{Atomic, Ordering};
+
+// Padded, but small enough to use native atomics on 64-bit targets. The bound
+// must not depend on whether the fallback would have been used.
+#[derive(Copy, Clone)]
+#[repr(C, align(8))]
+struct Slot {
+    index: u32,
+    flags: u16,
+}
+
+fn main() {
+    let a = Atomic::new(Slot { index: 0, flags: 0 });
+    a.store(Slot { index: 1, flags: 0 }, Ordering::Relaxed);
+    let _ = a.compare_exchange(
+        Slot { index: 1, flags: 0 },
+        Slot { index: 2, flags: 0 },
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    );
+}
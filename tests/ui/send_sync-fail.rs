@@ -0,0 +1,11 @@
+use atomic::Atomic;
+use std::rc::Rc;
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+fn main() {
+    assert_send::<Atomic<Rc<u32>>>();
+    assert_sync::<Atomic<Rc<u32>>>();
+    assert_sync::<Atomic<*mut ()>>();
+}
@@ -0,0 +1,30 @@
+use atomic::{Atomic, Ordering};
+use std::ptr;
+
+struct Node {
+    value: u32,
+}
+
+// The pointer is only dereferenced while the node is known to be alive.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+struct NodePtr(*mut Node);
+unsafe impl Send for NodePtr {}
+// Pointers have no padding bytes.
+unsafe impl bytemuck::NoUninit for NodePtr {}
+
+static HEAD: Atomic<NodePtr> = Atomic::new(NodePtr(ptr::null_mut()));
+
+fn main() {
+    let node = NodePtr(Box::into_raw(Box::new(Node { value: 7 })));
+    std::thread::spawn(move || HEAD.store(node, Ordering::Release))
+        .join()
+        .unwrap();
+
+    let local = Atomic::new(NodePtr(ptr::null_mut()));
+    std::thread::scope(|s| {
+        s.spawn(|| local.store(HEAD.load(Ordering::Acquire), Ordering::Relaxed));
+    });
+    let node = unsafe { Box::from_raw(local.into_inner().0) };
+    assert_eq!(node.value, 7);
+}
@@ -0,0 +1,11 @@
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct FfiHeader {
+    tag: u16,
+    flags: u16,
+    len: u32,
+}
+
+atomic::static_assert_lock_free!(u8, FfiHeader);
+
+fn main() {}
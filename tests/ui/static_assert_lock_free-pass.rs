@@ -0,0 +1,13 @@
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C, align(8))]
+struct FfiHeader {
+    tag: u16,
+    flags: u16,
+    len: u32,
+}
+
+atomic::static_assert_lock_free!(u8, u16, u32, u64, FfiHeader);
+
+fn main() {
+    atomic::static_assert_lock_free!(usize);
+}
@@ -0,0 +1,8 @@
+use atomic::{Atomic, Ordering};
+
+fn main() {
+    // `u16` is only 2-byte aligned, so only the lowest bit can hold a tag.
+    let mut value = 0u16;
+    let link = Atomic::new(&mut value as *mut u16);
+    link.fetch_or_tag::<0b10>(Ordering::Relaxed);
+}